@@ -0,0 +1,153 @@
+use bitpattern::bitmatch;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// A synthetic decoder with 100 arms, generated with and without a table.
+macro_rules! decoder {
+    ($name:ident, $($options:tt)*) => {
+        #[bitmatch($($options)*)]
+        fn $name(insn: u32) -> u32 {
+            match insn {
+                "0000000 bbbbb aaaaa 000 ddddd 0110011" => 0 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 000 ddddd 0010011" => 1 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 000 ddddd 0000011" => 2 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 000 ddddd 0100011" => 3 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 000 ddddd 1100011" => 4 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 001 ddddd 0110011" => 5 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 001 ddddd 0010011" => 6 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 001 ddddd 0000011" => 7 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 001 ddddd 0100011" => 8 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 001 ddddd 1100011" => 9 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 010 ddddd 0110011" => 10 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 010 ddddd 0010011" => 11 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 010 ddddd 0000011" => 12 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 010 ddddd 0100011" => 13 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 010 ddddd 1100011" => 14 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 011 ddddd 0110011" => 15 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 011 ddddd 0010011" => 16 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 011 ddddd 0000011" => 17 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 011 ddddd 0100011" => 18 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 011 ddddd 1100011" => 19 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 100 ddddd 0110011" => 20 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 100 ddddd 0010011" => 21 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 100 ddddd 0000011" => 22 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 100 ddddd 0100011" => 23 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 100 ddddd 1100011" => 24 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 101 ddddd 0110011" => 25 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 101 ddddd 0010011" => 26 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 101 ddddd 0000011" => 27 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 101 ddddd 0100011" => 28 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 101 ddddd 1100011" => 29 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 110 ddddd 0110011" => 30 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 110 ddddd 0010011" => 31 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 110 ddddd 0000011" => 32 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 110 ddddd 0100011" => 33 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 110 ddddd 1100011" => 34 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 111 ddddd 0110011" => 35 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 111 ddddd 0010011" => 36 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 111 ddddd 0000011" => 37 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 111 ddddd 0100011" => 38 ^ d ^ a ^ b,
+                "0000000 bbbbb aaaaa 111 ddddd 1100011" => 39 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 000 ddddd 0110011" => 40 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 000 ddddd 0010011" => 41 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 000 ddddd 0000011" => 42 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 000 ddddd 0100011" => 43 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 000 ddddd 1100011" => 44 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 001 ddddd 0110011" => 45 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 001 ddddd 0010011" => 46 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 001 ddddd 0000011" => 47 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 001 ddddd 0100011" => 48 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 001 ddddd 1100011" => 49 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 010 ddddd 0110011" => 50 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 010 ddddd 0010011" => 51 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 010 ddddd 0000011" => 52 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 010 ddddd 0100011" => 53 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 010 ddddd 1100011" => 54 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 011 ddddd 0110011" => 55 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 011 ddddd 0010011" => 56 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 011 ddddd 0000011" => 57 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 011 ddddd 0100011" => 58 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 011 ddddd 1100011" => 59 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 100 ddddd 0110011" => 60 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 100 ddddd 0010011" => 61 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 100 ddddd 0000011" => 62 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 100 ddddd 0100011" => 63 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 100 ddddd 1100011" => 64 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 101 ddddd 0110011" => 65 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 101 ddddd 0010011" => 66 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 101 ddddd 0000011" => 67 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 101 ddddd 0100011" => 68 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 101 ddddd 1100011" => 69 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 110 ddddd 0110011" => 70 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 110 ddddd 0010011" => 71 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 110 ddddd 0000011" => 72 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 110 ddddd 0100011" => 73 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 110 ddddd 1100011" => 74 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 111 ddddd 0110011" => 75 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 111 ddddd 0010011" => 76 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 111 ddddd 0000011" => 77 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 111 ddddd 0100011" => 78 ^ d ^ a ^ b,
+                "0000001 bbbbb aaaaa 111 ddddd 1100011" => 79 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 000 ddddd 0110011" => 80 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 000 ddddd 0010011" => 81 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 000 ddddd 0000011" => 82 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 000 ddddd 0100011" => 83 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 000 ddddd 1100011" => 84 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 001 ddddd 0110011" => 85 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 001 ddddd 0010011" => 86 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 001 ddddd 0000011" => 87 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 001 ddddd 0100011" => 88 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 001 ddddd 1100011" => 89 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 010 ddddd 0110011" => 90 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 010 ddddd 0010011" => 91 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 010 ddddd 0000011" => 92 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 010 ddddd 0100011" => 93 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 010 ddddd 1100011" => 94 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 011 ddddd 0110011" => 95 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 011 ddddd 0010011" => 96 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 011 ddddd 0000011" => 97 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 011 ddddd 0100011" => 98 ^ d ^ a ^ b,
+                "0000010 bbbbb aaaaa 011 ddddd 1100011" => 99 ^ d ^ a ^ b,
+                _ => !0,
+            }
+        }
+    };
+}
+
+decoder!(decode_linear,);
+decoder!(decode_table, table_bits = 8);
+
+fn insns() -> Vec<u32> {
+    // xorshift, so every run decodes the same instructions.
+    let mut x = 0x1234_5678u32;
+    (0..1024)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            x
+        })
+        .collect()
+}
+
+fn bench(c: &mut Criterion) {
+    let insns = insns();
+    c.bench_function("linear", |b| {
+        b.iter(|| {
+            insns
+                .iter()
+                .map(|x| decode_linear(black_box(*x)))
+                .sum::<u32>()
+        })
+    });
+    c.bench_function("table", |b| {
+        b.iter(|| {
+            insns
+                .iter()
+                .map(|x| decode_table(black_box(*x)))
+                .sum::<u32>()
+        })
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+use crate::consts::{self, MaskInput};
+use crate::pattern::{ones, Bit, Pattern, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Ident, Result, Token, Visibility};
+
+/// Continuous run of free bits.
+struct Run {
+    /// Bit position of the least significant bit of the run.
+    pos: u32,
+    width: u32,
+    /// Position of the run in the value of all free bits.
+    offset: u32,
+}
+
+/// Runs of the letters and '?' bits from the least significant one.
+///
+/// The value of all free bits holds them in the same order without the '0'
+/// and '1' bits, so the first run has offset 0.
+fn free_runs(pattern: &Pattern) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut offset = 0;
+    let mut prev_free = false;
+    for (pos, bit) in pattern.bits.iter().rev().enumerate() {
+        let free = !matches!(bit, Bit::Zero | Bit::One);
+        if free {
+            match runs.last_mut() {
+                Some(run) if prev_free => run.width += 1,
+                _ => runs.push(Run {
+                    pos: pos as u32,
+                    width: 1,
+                    offset,
+                }),
+            }
+            offset += 1;
+        }
+        prev_free = free;
+    }
+    runs
+}
+
+/// Number of free bits.
+fn free_width(runs: &[Run]) -> u32 {
+    runs.iter().map(|x| x.width).sum()
+}
+
+/// Value of the pattern with the free bits taken from `free`.
+fn gen_deposit(pattern: &Pattern, ty: Ty, runs: &[Run], free: &Ident) -> TokenStream {
+    let bit_pattern = ty.lit(pattern.value());
+    let runs = runs.iter().map(|run| {
+        let offset = ty.lit(run.offset as u128);
+        let mask = ty.lit(ones(run.width));
+        let pos = ty.lit(run.pos as u128);
+        quote! { (((#free >> #offset) & #mask) << #pos) }
+    });
+    quote! { (#bit_pattern #( | #runs )*) }
+}
+
+/// Value of all free bits of `bits`, the inverse of `gen_deposit`.
+fn gen_compress(ty: Ty, runs: &[Run], bits: &Ident) -> TokenStream {
+    let runs = runs.iter().map(|run| {
+        let pos = ty.lit(run.pos as u128);
+        let mask = ty.lit(ones(run.width));
+        let offset = ty.lit(run.offset as u128);
+        quote! { (((#bits >> #pos) & #mask) << #offset) }
+    });
+    quote! { (0 #( | #runs )*) }
+}
+
+fn feature_error(input: &MaskInput, name: &str, feature: &str) -> Error {
+    Error::new(
+        input.pattern.span(),
+        format!(
+            "`{}` requires the \"{}\" feature of bitpattern",
+            name, feature
+        ),
+    )
+}
+
+/// `proptest` strategy of the values of the pattern.
+///
+/// The value of all free bits is drawn from a range, so proptest shrinks it
+/// toward all free bits being 0.
+pub(crate) fn gen_strategy(input: MaskInput) -> Result<TokenStream> {
+    if !cfg!(feature = "proptest") {
+        return Err(feature_error(&input, "bitstrategy!", "proptest"));
+    }
+    let (pattern, ty) = consts::parse_pattern(&input.pattern, input.ty.as_ref())?;
+    let runs = free_runs(&pattern);
+    let x = ty.ident();
+    let last = ty.lit(ones(free_width(&runs)));
+    let free = Ident::new("free", Span::mixed_site());
+    let deposit = gen_deposit(&pattern, ty, &runs, &free);
+    let arg = if runs.is_empty() {
+        quote! { _ }
+    } else {
+        quote! { #free }
+    };
+    Ok(quote! {
+        ::proptest::strategy::Strategy::prop_map(0..=#last, |#arg: #x| #deposit)
+    })
+}
+
+/// Input of `bitarbitrary!`: `vis Name, "...", ty`.
+pub(crate) struct ArbitraryInput {
+    vis: Visibility,
+    name: Ident,
+    mask: MaskInput,
+}
+
+impl Parse for ArbitraryInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let mask = input.parse()?;
+        Ok(ArbitraryInput { vis, name, mask })
+    }
+}
+
+/// Newtype of the values of the pattern implementing `quickcheck::Arbitrary`.
+///
+/// Shrinking shrinks the value of all free bits, keeping the '0' and '1' bits.
+pub(crate) fn gen_arbitrary(input: ArbitraryInput) -> Result<TokenStream> {
+    if !cfg!(feature = "quickcheck") {
+        return Err(feature_error(&input.mask, "bitarbitrary!", "quickcheck"));
+    }
+    let lit = &input.mask.pattern;
+    let (pattern, ty) = consts::parse_pattern(lit, input.mask.ty.as_ref())?;
+    let vis = &input.vis;
+    let name = &input.name;
+    let x = ty.ident();
+    let runs = free_runs(&pattern);
+
+    let free = Ident::new("free", Span::mixed_site());
+    let bits = Ident::new("bits", Span::mixed_site());
+    let deposit = gen_deposit(&pattern, ty, &runs, &free);
+    let compress = gen_compress(ty, &runs, &bits);
+    let width = free_width(&runs);
+    let arbitrary = if width == 0 {
+        quote! { let _ = g; }
+    } else if width as usize == ty.bits() {
+        quote! { let #free = <#x as ::quickcheck::Arbitrary>::arbitrary(g); }
+    } else {
+        let last = ty.lit(ones(width));
+        quote! { let #free = <#x as ::quickcheck::Arbitrary>::arbitrary(g) & #last; }
+    };
+    let shrink = if width == 0 {
+        quote! { ::std::boxed::Box::new(::core::iter::empty()) }
+    } else {
+        quote! {
+            let #bits = self.0;
+            let #free: #x = #compress;
+            ::std::boxed::Box::new(
+                ::quickcheck::Arbitrary::shrink(&#free).map(|#free| Self(#deposit)),
+            )
+        }
+    };
+    let doc = format!("Value of `\"{}\"`.", lit.value());
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #vis struct #name(pub #x);
+
+        impl ::quickcheck::Arbitrary for #name {
+            fn arbitrary(g: &mut ::quickcheck::Gen) -> Self {
+                #arbitrary
+                Self(#deposit)
+            }
+
+            fn shrink(&self) -> ::std::boxed::Box<dyn ::core::iter::Iterator<Item = Self>> {
+                #shrink
+            }
+        }
+    })
+}
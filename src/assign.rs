@@ -0,0 +1,166 @@
+use crate::pattern::Pattern;
+use crate::{BitpatternInput, Value};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Error, Expr, Ident, Result, Token};
+
+/// Input of `bitmatch_into!`: the options, pattern and value of
+/// `bitpattern!`, then the places assigned the fields, either one for each
+/// run in order, or as `place = letter`.
+pub(crate) struct IntoInput {
+    head: BitpatternInput,
+    places: Vec<Expr>,
+}
+
+impl Parse for IntoInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let head = BitpatternInput::parse_head(input)?;
+        let mut places = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            places.push(input.parse()?);
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
+        Ok(IntoInput { head, places })
+    }
+}
+
+/// Letter named by the right side of `place = letter`.
+fn letter(expr: &Expr) -> Option<&Ident> {
+    match expr {
+        Expr::Path(path) if path.qself.is_none() => path.path.get_ident(),
+        _ => None,
+    }
+}
+
+/// Index of the run of each place, in order.
+fn runs(pattern: &Pattern, value: &Value, span: Span, places: &[Expr]) -> Result<Vec<usize>> {
+    let named = places
+        .iter()
+        .filter(|x| matches!(x, Expr::Assign(_)))
+        .count();
+    if named == 0 {
+        if places.len() != pattern.fields.len() {
+            return Err(Error::new(
+                span,
+                format!(
+                    "expected a place for each of the {} fields, found {}",
+                    pattern.fields.len(),
+                    places.len()
+                ),
+            ));
+        }
+        return Ok((0..places.len()).collect());
+    }
+    if named != places.len() {
+        return Err(Error::new(
+            span,
+            "expected a place for each field in order, or `place = letter` for each",
+        ));
+    }
+
+    let mut indices: Vec<usize> = Vec::new();
+    for place in places {
+        let right = match place {
+            Expr::Assign(x) => &*x.right,
+            _ => unreachable!(),
+        };
+        let letter = match letter(right) {
+            Some(x) => x,
+            None => return Err(Error::new(right.span(), "expected a letter of the pattern")),
+        };
+        let name = letter.to_string();
+        let runs: Vec<_> = pattern
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| name.chars().eq([x.name]))
+            .collect();
+        let index = match runs.as_slice() {
+            [(index, _)] => *index,
+            [] => {
+                return Err(crate::unknown_letter(
+                    value,
+                    letter.span(),
+                    &name,
+                    format!("'{}' isn't a letter of the pattern", name),
+                ));
+            }
+            _ => {
+                return Err(Error::new(
+                    letter.span(),
+                    format!(
+                        "'{}' has {} runs in the pattern, but a named place needs one",
+                        name,
+                        runs.len()
+                    ),
+                ));
+            }
+        };
+        if indices.contains(&index) {
+            return Err(Error::new(
+                letter.span(),
+                format!("'{}' is assigned twice", name),
+            ));
+        }
+        indices.push(index);
+    }
+    Ok(indices)
+}
+
+/// Assigns the fields to the places if the value matches, leaving them
+/// untouched otherwise, and evaluates to whether it matched.
+///
+/// The fields are extracted like `bitpattern!`, so each place must have the
+/// type of its field.
+pub(crate) fn gen_code(input: IntoInput) -> Result<TokenStream> {
+    let span = input.head.pattern.span();
+    let value = match &input.head.value {
+        Some(Value { tuple: true, .. }) => {
+            return Err(Error::new(
+                span,
+                "`tuple` can't be used with `bitmatch_into!`",
+            ));
+        }
+        Some(Value { rest: true, .. }) => {
+            return Err(Error::new(
+                span,
+                "`rest` can't be used with `bitmatch_into!`",
+            ));
+        }
+        Some(Value { boolean: true, .. }) => {
+            return Err(Error::new(
+                span,
+                "`bool` can't be used with `bitmatch_into!`",
+            ));
+        }
+        Some(x) => x,
+        None => {
+            return Err(Error::new(span, "`bitmatch_into!` needs a value to match"));
+        }
+    };
+    let pattern = crate::parse_pattern(&input.head)?;
+    let indices = runs(&pattern, value, span, &input.places)?;
+
+    let values: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let places = input.places.iter().map(|place| match place {
+        Expr::Assign(x) => &*x.left,
+        x => x,
+    });
+    let assigned = indices.iter().map(|index| &values[*index]);
+    let code = TokenStream::from(crate::gen_pattern(input.head));
+    Ok(quote! {
+        match #code {
+            ::core::option::Option::Some((#(#values),*)) => {
+                #(#places = #assigned;)*
+                true
+            }
+            ::core::option::Option::None => false,
+        }
+    })
+}
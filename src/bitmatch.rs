@@ -0,0 +1,1285 @@
+use crate::cover::{self, Cube};
+use crate::fragment::{self, Fragment};
+use crate::pattern::{gen_fits, gen_word, ones, parse_lits, parse_low, Pattern, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use std::collections::HashMap;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::spanned::Spanned;
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    Arm, Attribute, Block, Error, Expr, ExprIf, ExprLet, ExprMatch, ExprWhile, FnArg, Ident,
+    ImplItemFn, Item, ItemFn, Lit, LitInt, LitStr, Local, Meta, Pat, PatIdent, Result, Signature,
+    Stmt, Token,
+};
+
+/// Options given by `#[bitmatch(...)]`.
+#[derive(Clone, Default)]
+struct Options {
+    /// Skip the exhaustiveness check.
+    non_exhaustive: bool,
+    /// Warn about partially overlapping arms.
+    warn_overlap: bool,
+    /// Dispatch the arms by a lookup on this many bits.
+    table_bits: Option<u32>,
+    /// Fragments given by `define(name = "...")`.
+    fragments: Vec<Fragment>,
+    /// Match only the low bits of a value wider than the pattern.
+    low: bool,
+}
+
+impl Options {
+    fn parse(&mut self, tokens: TokenStream) -> Result<()> {
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("non_exhaustive") {
+                self.non_exhaustive = true;
+                Ok(())
+            } else if meta.path.is_ident("warn_overlap") {
+                self.warn_overlap = true;
+                Ok(())
+            } else if meta.path.is_ident("low") {
+                self.low = true;
+                Ok(())
+            } else if meta.path.is_ident("table_bits") {
+                let lit: LitInt = meta.value()?.parse()?;
+                let bits: u32 = lit.base10_parse()?;
+                if !(1..=16).contains(&bits) {
+                    return Err(Error::new(
+                        lit.span(),
+                        "table_bits must be between 1 and 16",
+                    ));
+                }
+                self.table_bits = Some(bits);
+                Ok(())
+            } else if meta.path.is_ident("define") {
+                meta.parse_nested_meta(|meta| {
+                    let name = match meta.path.get_ident() {
+                        Some(x) => x.to_string(),
+                        None => return Err(meta.error("expected a fragment name")),
+                    };
+                    let pattern = parse_lits(meta.value()?)?;
+                    self.fragments.retain(|x| x.name != name);
+                    self.fragments.push(Fragment {
+                        name,
+                        pattern: pattern.value(),
+                    });
+                    Ok(())
+                })
+            } else {
+                Err(meta.error("unsupported bitmatch option"))
+            }
+        });
+        parser.parse2(tokens)
+    }
+}
+
+pub(crate) fn gen_code(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let mut options = Options::default();
+    options.parse(attr)?;
+
+    let mut item: Item = syn::parse2(item)?;
+    let mut visitor = Visitor { options };
+    visitor.visit_item_mut(&mut item);
+    Ok(quote! { #item })
+}
+
+struct Visitor {
+    options: Options,
+}
+
+impl VisitMut for Visitor {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit_mut::visit_expr_mut(self, expr);
+
+        let tokens = match expr {
+            Expr::Match(x) if x.arms.iter().any(|arm| contains_str(&arm.pat)) => self
+                .match_options(&mut x.attrs)
+                .and_then(|options| rewrite_match(x, &options)),
+            Expr::If(x) if let_str(&x.cond).is_some() => rewrite_if(x, &self.options),
+            Expr::While(x) if let_str(&x.cond).is_some() => rewrite_while(x, &self.options),
+            _ => return,
+        };
+        // Errors are wrapped in a block, which is valid after `else` as well.
+        *expr = Expr::Verbatim(tokens.unwrap_or_else(|e| {
+            let error = e.to_compile_error();
+            quote! { { #error } }
+        }));
+    }
+
+    fn visit_item_fn_mut(&mut self, item: &mut ItemFn) {
+        visit_mut::visit_item_fn_mut(self, item);
+        rewrite_params(&mut item.sig, &mut item.block, &self.options);
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, item: &mut ImplItemFn) {
+        visit_mut::visit_impl_item_fn_mut(self, item);
+        rewrite_params(&mut item.sig, &mut item.block, &self.options);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        visit_mut::visit_block_mut(self, block);
+
+        // A `let` with a string pattern expands to several statements in the
+        // enclosing block, so its bindings stay in scope.
+        let mut stmts = Vec::new();
+        for stmt in block.stmts.drain(..) {
+            match stmt {
+                Stmt::Local(local) if str_lit(&local.pat).is_some() => {
+                    let tokens =
+                        rewrite_let(&local, &self.options).unwrap_or_else(|e| e.to_compile_error());
+                    let expanded: Block = syn::parse_quote! { { #tokens } };
+                    stmts.extend(expanded.stmts);
+                }
+                x => stmts.push(x),
+            }
+        }
+        block.stmts = stmts;
+    }
+}
+
+impl Visitor {
+    /// Removes `#[bitmatch(...)]` from `attrs` and applies it to the item options.
+    fn match_options(&self, attrs: &mut Vec<Attribute>) -> Result<Options> {
+        let mut options = self.options.clone();
+        let mut ret = Ok(());
+        attrs.retain(|attr| {
+            if !attr.path().is_ident("bitmatch") {
+                return true;
+            }
+            if let Meta::List(list) = &attr.meta {
+                let parsed = options.parse(list.tokens.clone());
+                if ret.is_ok() {
+                    ret = parsed;
+                }
+            }
+            false
+        });
+        ret.map(|_| options)
+    }
+}
+
+fn str_lit(pat: &Pat) -> Option<&LitStr> {
+    match pat {
+        Pat::Lit(x) => match &x.lit {
+            Lit::Str(x) => Some(x),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the pattern strings if `pat` consists of string literals only.
+fn str_patterns(pat: &Pat) -> Option<Vec<&LitStr>> {
+    match pat {
+        Pat::Lit(x) => match &x.lit {
+            Lit::Str(x) => Some(vec![x]),
+            _ => None,
+        },
+        Pat::Paren(x) => str_patterns(&x.pat),
+        Pat::Or(x) => {
+            let mut ret = Vec::new();
+            for case in &x.cases {
+                ret.append(&mut str_patterns(case)?);
+            }
+            Some(ret)
+        }
+        _ => None,
+    }
+}
+
+fn contains_str(pat: &Pat) -> bool {
+    match pat {
+        Pat::Lit(x) => matches!(x.lit, Lit::Str(_)),
+        Pat::Paren(x) => contains_str(&x.pat),
+        Pat::Or(x) => x.cases.iter().any(contains_str),
+        Pat::Ident(x) => x.subpat.as_ref().is_some_and(|(_, x)| contains_str(x)),
+        Pat::Tuple(x) => x.elems.iter().any(contains_str),
+        _ => false,
+    }
+}
+
+/// Returns true if `arm` matches any value.
+fn is_catch_all(arm: &Arm) -> bool {
+    if arm.guard.is_some() {
+        return false;
+    }
+    match &arm.pat {
+        Pat::Wild(_) => true,
+        Pat::Ident(x) => x.subpat.is_none(),
+        _ => false,
+    }
+}
+
+/// An alternative of a string literal arm.
+struct Alt {
+    pattern: Pattern,
+    span: Span,
+}
+
+/// A string literal arm.
+struct BitArm<'a> {
+    alts: Vec<Alt>,
+    /// `x` of `x @ "..."`, bound to the scrutinee.
+    binding: Option<PatIdent>,
+    arm: &'a Arm,
+}
+
+enum Entry<'a> {
+    Bits(BitArm<'a>),
+    Rust(&'a Arm),
+}
+
+/// Value of `lit` with the pattern fragments expanded.
+fn expand(lit: &LitStr, options: &Options) -> Result<String> {
+    fragment::expand(&lit.value(), &options.fragments).map_err(|e| Error::new(lit.span(), e))
+}
+
+/// Parses a pattern whose letters are bound as variables.
+fn parse_lit(lit: &LitStr, options: &Options) -> Result<Pattern> {
+    let pattern = Pattern::parse(&expand(lit, options)?);
+    for letter in pattern.letters() {
+        if syn::parse_str::<Ident>(&letter.to_string()).is_err() {
+            return Err(Error::new(
+                lit.span(),
+                format!("'{}' can't be used as a field name", letter),
+            ));
+        }
+    }
+    Ok(pattern)
+}
+
+fn parse_arm<'a>(arm: &'a Arm, options: &Options) -> Result<Option<BitArm<'a>>> {
+    if !contains_str(&arm.pat) {
+        return Ok(None);
+    }
+    let (binding, pat) = match &arm.pat {
+        Pat::Ident(x) => {
+            let binding = PatIdent {
+                subpat: None,
+                ..x.clone()
+            };
+            (Some(binding), &*x.subpat.as_ref().unwrap().1)
+        }
+        x => (None, x),
+    };
+    let lits = match str_patterns(pat) {
+        Some(x) => x,
+        None => {
+            return Err(Error::new(
+                arm.pat.span(),
+                "string patterns can't be combined with other patterns",
+            ));
+        }
+    };
+
+    let mut alts: Vec<Alt> = Vec::new();
+    for lit in lits {
+        let pattern = parse_lit(lit, options)?;
+
+        // The arm body must typecheck for every alternative.
+        if let Some(first) = alts.first() {
+            let mut expected = first.pattern.letters();
+            let mut actual = pattern.letters();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            if expected != actual {
+                return Err(Error::new(
+                    lit.span(),
+                    "all alternatives must have the same fields",
+                ));
+            }
+            for letter in actual {
+                let expected = first.pattern.width(letter);
+                let actual = pattern.width(letter);
+                if expected != actual {
+                    return Err(Error::new(
+                        lit.span(),
+                        format!(
+                            "field '{}' must have the same width in all alternatives: expected {}, found {}",
+                            letter, expected, actual
+                        ),
+                    ));
+                }
+            }
+        }
+
+        alts.push(Alt {
+            pattern,
+            span: lit.span(),
+        });
+    }
+
+    Ok(Some(BitArm { alts, binding, arm }))
+}
+
+/// Checks that the alternatives have the same length as the earlier ones.
+fn check_len(len: &mut Option<usize>, alts: &[Alt]) -> Result<()> {
+    for alt in alts {
+        match *len {
+            None => *len = Some(alt.pattern.len()),
+            Some(len) if len != alt.pattern.len() => {
+                return Err(Error::new(
+                    alt.span,
+                    format!(
+                        "pattern length mismatch: expected {}, found {}",
+                        len,
+                        alt.pattern.len()
+                    ),
+                ));
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn rewrite_match(expr: &ExprMatch, options: &Options) -> Result<TokenStream> {
+    let (arms, widths) = join_tuples(&expr.arms, options)?;
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut len = None;
+    for arm in &arms {
+        match parse_arm(arm, options)? {
+            Some(x) => {
+                check_len(&mut len, &x.alts)?;
+                entries.push(Entry::Bits(x));
+            }
+            None => entries.push(Entry::Rust(arm)),
+        }
+    }
+
+    let first = entries.iter().find_map(|x| match x {
+        Entry::Bits(x) => x.alts.first(),
+        _ => None,
+    });
+    let first = first.unwrap();
+    let ty = first.pattern.len_ty(first.span)?;
+
+    let len = first.pattern.len();
+    let warnings = check_reachable(&entries, len, options)?;
+    let fallback = match entries.last() {
+        Some(Entry::Bits(_)) => Some(check_exhaustive(expr, &entries, len, options)?),
+        _ if options.table_bits.is_some() => Some(check_exhaustive(expr, &entries, len, options)?),
+        _ => None,
+    };
+
+    let value = Ident::new("value", Span::mixed_site());
+    let bits = Ident::new("bits", Span::mixed_site());
+    let binding = crate::gen_binding(&value, &ty.ident(), &expr.expr);
+    let (bits_expr, fits) = gen_bits(&value, &widths, ty, len, options);
+    // The arms only match if the value has no bits set above the pattern.
+    let (fits_binding, fits) = match fits {
+        Some(x) => {
+            let fits = Ident::new("fits", Span::mixed_site());
+            (Some(quote! { let #fits = #x; }), Some(quote! { #fits && }))
+        }
+        None => (None, None),
+    };
+    let fits = fits.as_ref();
+
+    if let (Some(table_bits), Some(fallback)) = (options.table_bits, &fallback) {
+        let table = gen_table(&entries, len, table_bits, &value, &bits, fits, fallback);
+        return Ok(quote! {
+            {
+                #binding
+                let #bits = #bits_expr;
+                #fits_binding
+                #(#warnings)*
+                #table
+            }
+        });
+    }
+
+    // Guards can't be checked before the bindings they use, so the arms are
+    // matched against a tuple of the value and its bits.
+    let arms = entries.iter().map(|entry| match entry {
+        Entry::Bits(x) => gen_arm(x, ty, &bits, fits),
+        Entry::Rust(x) => {
+            let mut arm = (*x).clone();
+            let pat = &arm.pat;
+            arm.pat = syn::parse_quote! { (#pat, _) };
+            arm.comma = Some(Default::default());
+            quote! { #arm }
+        }
+    });
+    let fallback = fallback.map(|x| quote! { _ => #x, });
+
+    Ok(quote! {
+        {
+            #binding
+            let #bits = #bits_expr;
+            #fits_binding
+            #(#warnings)*
+            match (#value, #bits) {
+                #(#arms)*
+                #fallback
+            }
+        }
+    })
+}
+
+/// Removes `#[bitmatch("...")]` from the parameters of a function, binding
+/// the letters at the top of its body.
+fn rewrite_params(sig: &mut Signature, block: &mut Block, options: &Options) {
+    let mut stmts = Vec::new();
+    for input in &mut sig.inputs {
+        if let FnArg::Typed(x) = input {
+            let mut lits = Vec::new();
+            x.attrs.retain(|attr| {
+                if attr.path().is_ident("bitmatch") {
+                    lits.push(attr.parse_args::<LitStr>());
+                    false
+                } else {
+                    true
+                }
+            });
+            for lit in lits {
+                let tokens = lit
+                    .and_then(|lit| rewrite_param(&x.pat, &lit, options))
+                    .unwrap_or_else(|e| e.to_compile_error());
+                let expanded: Block = syn::parse_quote! { { #tokens } };
+                stmts.extend(expanded.stmts);
+            }
+        }
+    }
+    stmts.append(&mut block.stmts);
+    block.stmts = stmts;
+}
+
+/// Statements binding the letters of `lit` from the parameter `pat`.
+fn rewrite_param(pat: &Pat, lit: &LitStr, options: &Options) -> Result<TokenStream> {
+    let name = match pat {
+        Pat::Ident(x) if x.subpat.is_none() => &x.ident,
+        x => {
+            return Err(Error::new(
+                x.span(),
+                "parameters with a string pattern must be bound to a name",
+            ));
+        }
+    };
+    let pattern = parse_lit(lit, options)?;
+    let ty = pattern.len_ty(lit.span())?;
+    // Parameters are irrefutable, like `let` without `else`. The bindings are
+    // still generated, so uses of them don't raise more errors.
+    let error = if pattern.mask() != 0 {
+        Some(
+            Error::new(
+                lit.span(),
+                "refutable pattern in parameter: patterns with fixed bits can't be used",
+            )
+            .to_compile_error(),
+        )
+    } else {
+        None
+    };
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let x = ty.ident();
+    let bindings = pattern.letters().into_iter().map(|letter| {
+        let name = Ident::new(&letter.to_string(), lit.span());
+        let extract = pattern.extract(letter, ty, &bits);
+        quote! { let #name: #x = #extract; }
+    });
+    // Bits above the pattern are dropped, since there's no way to reject them.
+    let (word, _) = gen_bits(name, &[], ty, pattern.len(), options);
+    Ok(quote! {
+        #error
+        let #bits = #word;
+        #(#bindings)*
+    })
+}
+
+/// Returns `cond` if it is a `let` with a string pattern.
+fn let_str(cond: &Expr) -> Option<&ExprLet> {
+    match cond {
+        Expr::Let(x) if contains_str(&x.pat) => Some(x),
+        _ => None,
+    }
+}
+
+/// Parses the pattern of `if let` or `while let` as an arm running `body`.
+fn let_arm(cond: &ExprLet, body: &Block) -> Arm {
+    let pat = &cond.pat;
+    syn::parse_quote! { #pat => #body }
+}
+
+/// Statements evaluating the scrutinee of `if let` or `while let`, the
+/// success condition and the bindings.
+fn gen_let(
+    cond: &ExprLet,
+    body: &Block,
+    options: &Options,
+) -> Result<(TokenStream, TokenStream, TokenStream)> {
+    let value = Ident::new("value", Span::mixed_site());
+    let bits = Ident::new("bits", Span::mixed_site());
+    let arm = let_arm(cond, body);
+    let arm = parse_arm(&arm, options)?.unwrap();
+    check_len(&mut None, &arm.alts)?;
+    let ty = arm.alts[0].pattern.len_ty(arm.alts[0].span)?;
+    let conds = gen_conds(&arm, ty, &bits);
+    let bindings = gen_bindings(&arm, &conds, ty, &bits);
+    let binding = arm.binding.as_ref().map(|x| quote! { let #x = #value; });
+
+    let len = arm.alts[0].pattern.len();
+    let (word, fits) = gen_bits(&value, &[], ty, len, options);
+    let fits = fits.map(|x| quote! { #x && });
+    let value_binding = crate::gen_binding(&value, &ty.ident(), &cond.expr);
+    Ok((
+        quote! {
+            #value_binding
+            let #bits = #word;
+        },
+        quote! { (#fits (#(#conds)||*)) },
+        quote! { #binding #bindings },
+    ))
+}
+
+/// Expands `if let "..." = x { ... }`, keeping any `else` branch.
+fn rewrite_if(expr: &ExprIf, options: &Options) -> Result<TokenStream> {
+    let cond = let_str(&expr.cond).unwrap();
+    let (stmts, cond_bits, bindings) = gen_let(cond, &expr.then_branch, options)?;
+
+    let then = &expr.then_branch;
+    let else_branch = expr.else_branch.as_ref().map(|(_, x)| quote! { else #x });
+    Ok(quote! {
+        {
+            #stmts
+            if #cond_bits {
+                #bindings
+                #then
+            } #else_branch
+        }
+    })
+}
+
+/// Expands `while let "..." = x { ... }`, evaluating `x` on every iteration.
+fn rewrite_while(expr: &ExprWhile, options: &Options) -> Result<TokenStream> {
+    let cond = let_str(&expr.cond).unwrap();
+    let (stmts, cond_bits, bindings) = gen_let(cond, &expr.body, options)?;
+
+    let label = &expr.label;
+    let body = &expr.body;
+    Ok(quote! {
+        #label loop {
+            #stmts
+            if !#cond_bits {
+                break;
+            }
+            #bindings
+            #body
+        }
+    })
+}
+
+/// Expands `let "..." = x [else { ... }];` into statements binding each letter.
+fn rewrite_let(local: &Local, options: &Options) -> Result<TokenStream> {
+    let lit = str_lit(&local.pat).unwrap();
+    let pattern = parse_lit(lit, options)?;
+    let ty = pattern.len_ty(lit.span())?;
+    let init = match &local.init {
+        Some(x) => x,
+        None => {
+            return Err(Error::new(
+                local.span(),
+                "`let` with a string pattern needs a value",
+            ));
+        }
+    };
+
+    let value = Ident::new("value", Span::mixed_site());
+    let bits = Ident::new("bits", Span::mixed_site());
+    let x = ty.ident();
+
+    let (word, fits) = gen_bits(&value, &[], ty, pattern.len(), options);
+    let mut conds: Vec<_> = fits.iter().cloned().collect();
+    if pattern.mask() != 0 {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        conds.push(quote! { #bits & #bit_mask == #bit_pattern });
+    }
+
+    // `let true = ... else` keeps the compiler's check that `else` diverges.
+    let check = match &init.diverge {
+        Some(_) if conds.is_empty() => None,
+        Some((_, diverge)) => Some(quote! { let true = (#(#conds)&&*) else #diverge; }),
+        // The bindings are still generated, so uses of them don't raise more
+        // errors.
+        None if pattern.mask() != 0 => Some(
+            Error::new(
+                lit.span(),
+                "refutable pattern in `let`: patterns with fixed bits need an `else` branch",
+            )
+            .to_compile_error(),
+        ),
+        // Without `else`, bits above the pattern are dropped like
+        // `bitextract!`.
+        None => None,
+    };
+
+    let attrs = &local.attrs;
+    let bindings = pattern.letters().into_iter().map(|letter| {
+        let name = Ident::new(&letter.to_string(), lit.span());
+        let extract = pattern.extract(letter, ty, &bits);
+        quote! {
+            #(#attrs)*
+            let #name: #x = #extract;
+        }
+    });
+
+    let binding = crate::gen_binding(&value, &x, &init.expr);
+    Ok(quote! {
+        #binding
+        let #bits = #word;
+        #check
+        #(#bindings)*
+    })
+}
+
+/// Input of `bitswitch!`: `[low,] value { arms }`.
+pub(crate) struct SwitchInput {
+    low: bool,
+    expr: Expr,
+    arms: Vec<Arm>,
+}
+
+impl Parse for SwitchInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let low = parse_low(input)?;
+        let expr = Expr::parse_without_eager_brace(input)?;
+        let content;
+        syn::braced!(content in input);
+        let mut arms = Vec::new();
+        while !content.is_empty() {
+            arms.push(content.call(Arm::parse)?);
+        }
+        Ok(SwitchInput { low, expr, arms })
+    }
+}
+
+/// Expands `bitswitch!` like a `match` in `#[bitmatch]`.
+///
+/// Without a catch-all arm, the arms are wrapped in `Some`, and `None` is
+/// returned if no arm matches.
+pub(crate) fn gen_bitswitch(input: SwitchInput) -> Result<TokenStream> {
+    let SwitchInput {
+        low,
+        expr,
+        mut arms,
+    } = input;
+    if !arms.iter().any(is_catch_all) {
+        for arm in &mut arms {
+            let body = &arm.body;
+            *arm.body = syn::parse_quote! { ::core::option::Option::Some(#body) };
+            arm.comma = Some(Default::default());
+        }
+        arms.push(syn::parse_quote! { _ => ::core::option::Option::None, });
+    }
+    let mut expr = Expr::Match(ExprMatch {
+        attrs: Vec::new(),
+        match_token: Default::default(),
+        expr: Box::new(expr),
+        brace_token: Default::default(),
+        arms,
+    });
+    let mut visitor = Visitor {
+        options: Options {
+            low,
+            ..Options::default()
+        },
+    };
+    visitor.visit_expr_mut(&mut expr);
+    Ok(quote! { #expr })
+}
+
+/// Input of `bitlet!`: `[low,] "..." = value` with an optional `, else
+/// diverge`.
+pub(crate) struct LetInput {
+    low: bool,
+    pattern: LitStr,
+    value: Expr,
+    diverge: Option<Expr>,
+}
+
+impl Parse for LetInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let low = parse_low(input)?;
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        let mut diverge = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            input.parse::<Token![else]>()?;
+            diverge = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(LetInput {
+            low,
+            pattern,
+            value,
+            diverge,
+        })
+    }
+}
+
+/// Expands `bitlet!` like `let "..." = value else { diverge };`.
+pub(crate) fn gen_bitlet(input: LetInput) -> Result<TokenStream> {
+    let LetInput {
+        low,
+        pattern,
+        value,
+        diverge,
+    } = input;
+    let stmt: Stmt = match diverge {
+        Some(x) => syn::parse_quote! { let #pattern = #value else { #x }; },
+        None => syn::parse_quote! { let #pattern = #value; },
+    };
+    match stmt {
+        Stmt::Local(local) => rewrite_let(
+            &local,
+            &Options {
+                low,
+                ..Options::default()
+            },
+        ),
+        _ => unreachable!(),
+    }
+}
+
+/// Joins tuples of string patterns like `("aaaa", "bbbb")` into a single
+/// pattern, returning the arms and the width of each tuple element.
+///
+/// `_` elements match any value of the element width. The widths are empty
+/// if there are no tuple patterns.
+fn join_tuples(arms: &[Arm], options: &Options) -> Result<(Vec<Arm>, Vec<usize>)> {
+    let mut widths: Vec<Option<usize>> = Vec::new();
+    for arm in arms {
+        tuple_widths(&arm.pat, &mut widths, options)?;
+    }
+    let widths: Vec<usize> = widths.into_iter().map(|x| x.unwrap_or(0)).collect();
+    if widths.is_empty() {
+        return Ok((arms.to_vec(), widths));
+    }
+
+    let arms = arms
+        .iter()
+        .map(|arm| {
+            let mut arm = arm.clone();
+            join_tuple(&mut arm.pat, &widths);
+            arm
+        })
+        .collect();
+    Ok((arms, widths))
+}
+
+/// Records the width of each element of tuples of string patterns in `pat`.
+fn tuple_widths(pat: &Pat, widths: &mut Vec<Option<usize>>, options: &Options) -> Result<()> {
+    match pat {
+        Pat::Tuple(x) if x.elems.iter().any(contains_str) => {
+            if widths.is_empty() {
+                widths.resize(x.elems.len(), None);
+            } else if widths.len() != x.elems.len() {
+                return Err(Error::new(
+                    x.span(),
+                    format!(
+                        "tuple length mismatch: expected {}, found {}",
+                        widths.len(),
+                        x.elems.len()
+                    ),
+                ));
+            }
+            for (elem, width) in x.elems.iter().zip(widths.iter_mut()) {
+                match elem {
+                    Pat::Wild(_) => (),
+                    elem => {
+                        let lit = match str_lit(elem) {
+                            Some(x) => x,
+                            None => {
+                                return Err(Error::new(
+                                    elem.span(),
+                                    "tuple elements must be string patterns or `_`",
+                                ));
+                            }
+                        };
+                        let len = Pattern::parse(&expand(lit, options)?).len();
+                        match *width {
+                            Some(width) if width != len => {
+                                return Err(Error::new(
+                                    lit.span(),
+                                    format!(
+                                        "pattern length mismatch: expected {}, found {}",
+                                        width, len
+                                    ),
+                                ));
+                            }
+                            _ => *width = Some(len),
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Pat::Paren(x) => tuple_widths(&x.pat, widths, options),
+        Pat::Or(x) => x
+            .cases
+            .iter()
+            .try_for_each(|x| tuple_widths(x, widths, options)),
+        Pat::Ident(x) => match &x.subpat {
+            Some((_, x)) => tuple_widths(x, widths, options),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Replaces tuples of string patterns in `pat` by the joined pattern.
+fn join_tuple(pat: &mut Pat, widths: &[usize]) {
+    match pat {
+        Pat::Tuple(x) if x.elems.iter().any(contains_str) => {
+            let mut joined = Vec::new();
+            let mut span = None;
+            for (elem, width) in x.elems.iter().zip(widths) {
+                match str_lit(elem) {
+                    Some(lit) => {
+                        span.get_or_insert(lit.span());
+                        joined.push(lit.value());
+                    }
+                    None => joined.push("?".repeat(*width)),
+                }
+            }
+            let lit = LitStr::new(&joined.join(" "), span.unwrap());
+            *pat = syn::parse_quote! { #lit };
+        }
+        Pat::Paren(x) => join_tuple(&mut x.pat, widths),
+        Pat::Or(x) => x.cases.iter_mut().for_each(|x| join_tuple(x, widths)),
+        Pat::Ident(x) => {
+            if let Some((_, x)) = &mut x.subpat {
+                join_tuple(x, widths);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Expression of the bits of `value`, joining the elements of a tuple with
+/// the first element as the most significant bits, and the condition that
+/// neither it nor an element has bits set above its pattern, unless `low`
+/// is given.
+fn gen_bits(
+    value: &Ident,
+    widths: &[usize],
+    ty: Ty,
+    len: usize,
+    options: &Options,
+) -> (TokenStream, Option<TokenStream>) {
+    if widths.is_empty() {
+        let fits = if options.low {
+            None
+        } else {
+            Some(gen_fits(value, len as u32))
+        };
+        return (gen_word(value, ty), fits);
+    }
+
+    let elem = Ident::new("elem", Span::mixed_site());
+    let mut shift: usize = widths.iter().sum();
+    let mut fits = Vec::new();
+    let mut elems = Vec::new();
+    for (i, width) in widths.iter().enumerate() {
+        if *width == 0 {
+            continue;
+        }
+        shift -= width;
+        let index = syn::Index::from(i);
+        if !options.low {
+            let fit = gen_fits(&elem, *width as u32);
+            fits.push(quote! { { let #elem = #value.#index; #fit } });
+        }
+        let word = gen_word(&elem, ty);
+        let mask = ty.lit(ones(*width as u32));
+        let shift = ty.lit(shift as u128);
+        elems.push(quote! { (({ let #elem = #value.#index; #word } & #mask) << #shift) });
+    }
+    let fits = if fits.is_empty() {
+        None
+    } else {
+        Some(quote! { (#(#fits)&&*) })
+    };
+    (quote! { (#(#elems)|*) }, fits)
+}
+
+/// Checks that every arm can match, returning warnings about overlapping arms.
+///
+/// Guarded arms may not match, so they don't shadow later arms.
+fn check_reachable(entries: &[Entry], len: usize, options: &Options) -> Result<Vec<TokenStream>> {
+    let mut warnings = Vec::new();
+    let mut cubes: Vec<Cube> = Vec::new();
+    let mut catch_all = false;
+    for entry in entries {
+        let arm = match entry {
+            Entry::Bits(x) => x,
+            Entry::Rust(x) => {
+                catch_all |= is_catch_all(x);
+                continue;
+            }
+        };
+        for alt in &arm.alts {
+            let cube = Cube::from_pattern(&alt.pattern);
+
+            let shadowed =
+                catch_all || cover::remaining(cube, &cubes).is_some_and(|x| x.is_empty());
+            if shadowed {
+                return Err(Error::new(alt.span, "unreachable pattern"));
+            }
+
+            if options.warn_overlap {
+                // Overlap with a later, more general arm is fine.
+                let overlap = cubes
+                    .iter()
+                    .find(|x| !cube.contains(x) && x.intersects(&cube));
+                if let Some(x) = overlap {
+                    let example = x.intersection(&cube).unwrap().example(len);
+                    let note = format!(
+                        "this pattern partially overlaps an earlier arm, e.g. \"{}\" matches both",
+                        example
+                    );
+                    warnings.push(gen_warning(&note, alt.span));
+                }
+            }
+
+            if arm.arm.guard.is_none() {
+                cubes.push(cube);
+            }
+        }
+    }
+    Ok(warnings)
+}
+
+/// Statement raising a deprecation warning with `note` at `span`.
+///
+/// Each warning declares its struct in a block of its own, so that several
+/// don't clash.
+fn gen_warning(note: &str, span: Span) -> TokenStream {
+    let name = Ident::new("overlapping_bit_pattern", span);
+    quote_spanned! { span =>
+        {
+            #[deprecated(note = #note)]
+            #[allow(non_camel_case_types)]
+            struct #name;
+            let _ = #name;
+        }
+    }
+}
+
+/// Checks that the arms cover every value, returning the unreachable fallback.
+///
+/// Guarded arms may not match, so they don't cover anything. Without `low`,
+/// values with bits set above the patterns aren't covered either, so only a
+/// `_` arm makes the match exhaustive.
+fn check_exhaustive(
+    expr: &ExprMatch,
+    entries: &[Entry],
+    len: usize,
+    options: &Options,
+) -> Result<TokenStream> {
+    let fallback = quote! { unreachable!("no bit pattern matched") };
+    if options.non_exhaustive {
+        // The check is skipped, so nothing proves the fallback unreachable
+        // without an arm matching the rest.
+        let catch_all = entries
+            .iter()
+            .any(|x| matches!(x, Entry::Rust(x) if is_catch_all(x)));
+        if !catch_all {
+            return Err(Error::new(
+                expr.match_token.span,
+                "`#[bitmatch(non_exhaustive)]` needs a `_` arm for the values the patterns don't cover",
+            ));
+        }
+        return Ok(fallback);
+    }
+
+    let mut cubes = Vec::new();
+    for entry in entries {
+        match entry {
+            Entry::Bits(x) if x.arm.guard.is_none() => {
+                cubes.extend(x.alts.iter().map(|x| Cube::from_pattern(&x.pattern)))
+            }
+            Entry::Bits(_) => (),
+            Entry::Rust(x) => {
+                if is_catch_all(x) {
+                    return Ok(fallback);
+                }
+            }
+        }
+    }
+
+    let span = expr.match_token.span;
+    match cover::uncovered(&cubes) {
+        Some(x) if x.is_empty() && (options.low || len >= 128) => Ok(fallback),
+        Some(x) if x.is_empty() => Err(Error::new(
+            span,
+            "non-exhaustive patterns: values with bits set above the pattern not covered, \
+             add a `_` arm or use `#[bitmatch(low)]`",
+        )),
+        Some(x) => Err(Error::new(
+            span,
+            format!(
+                "non-exhaustive patterns: \"{}\" not covered",
+                x[0].example(len)
+            ),
+        )),
+        None => Err(Error::new(
+            span,
+            "non-exhaustive patterns: too complex to check, add a `_` arm",
+        )),
+    }
+}
+
+/// Conditions matching each alternative of `arm`.
+fn gen_conds(arm: &BitArm, ty: Ty, bits: &Ident) -> Vec<TokenStream> {
+    arm.alts
+        .iter()
+        .map(|alt| {
+            if alt.pattern.mask() == 0 {
+                return quote! { true };
+            }
+            let bit_mask = ty.lit(alt.pattern.mask());
+            let bit_pattern = ty.lit(alt.pattern.value());
+            quote! { (#bits & #bit_mask == #bit_pattern) }
+        })
+        .collect()
+}
+
+fn gen_arm(arm: &BitArm, ty: Ty, bits: &Ident, fits: Option<&TokenStream>) -> TokenStream {
+    let conds = gen_conds(arm, ty, bits);
+    let bindings = gen_bindings(arm, &conds, ty, bits);
+    let body = &arm.arm.body;
+    let guard = arm.arm.guard.as_ref().map(|(_, guard)| {
+        quote! { && { #bindings #guard } }
+    });
+
+    let binding = match &arm.binding {
+        Some(x) => quote! { #x },
+        None => quote! { _ },
+    };
+
+    quote! {
+        (#binding, #bits) if #fits (#(#conds)||*) #guard => {
+            #bindings
+            #body
+        }
+    }
+}
+
+/// `let` statements binding each letter, extracted by the matched alternative.
+fn gen_bindings(arm: &BitArm, conds: &[TokenStream], ty: Ty, bits: &Ident) -> TokenStream {
+    let x = ty.ident();
+    let first = &arm.alts[0];
+    let bindings = first.pattern.letters().into_iter().map(|letter| {
+        let name = Ident::new(&letter.to_string(), first.span);
+        let mut value = arm.alts.last().unwrap().pattern.extract(letter, ty, bits);
+        for (alt, cond) in arm.alts.iter().zip(conds).rev().skip(1) {
+            let extract = alt.pattern.extract(letter, ty, bits);
+            value = quote! { if #cond { #extract } else { #value } };
+        }
+        quote! {
+            #[allow(unused_variables)]
+            let #name: #x = #value;
+        }
+    });
+    quote! { #(#bindings)* }
+}
+
+/// Total number of candidate arms over all keys of bits `lo..lo + width`.
+fn table_cost(entries: &[Entry], lo: u32, width: u32) -> u128 {
+    let span = ones(width) << lo;
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::Bits(x) => x
+                .alts
+                .iter()
+                .map(|alt| 1 << (width - (alt.pattern.mask() & span).count_ones()))
+                .sum(),
+            Entry::Rust(_) => 1 << width,
+        })
+        .sum()
+}
+
+/// Indices of the entries that may match when bits `lo..lo + width` equal
+/// `key`, in order, stopping at the first one matching all such values.
+///
+/// Unless `low` is given, the string patterns don't match values with bits
+/// set above them, so only the `_` arm stops the candidates.
+fn candidates(entries: &[Entry], lo: u32, width: u32, key: u128, low: bool) -> Vec<usize> {
+    let bucket = Cube {
+        mask: ones(width) << lo,
+        value: key << lo,
+    };
+    let mut ret = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let (matches, covers) = match entry {
+            Entry::Bits(x) => {
+                let cubes = x.alts.iter().map(|alt| Cube::from_pattern(&alt.pattern));
+                let covers =
+                    low && x.arm.guard.is_none() && cubes.clone().any(|x| x.contains(&bucket));
+                (cubes.clone().any(|x| x.intersects(&bucket)), covers)
+            }
+            Entry::Rust(x) => (true, is_catch_all(x)),
+        };
+        if matches {
+            ret.push(i);
+        }
+        if covers {
+            break;
+        }
+    }
+    ret
+}
+
+/// Dispatches the arms on the bits that discriminate them best.
+///
+/// The first match selects the index of the matching arm among the arms that
+/// can match the looked up bits, keeping the order of the arms, and the
+/// second one runs it.
+fn gen_table(
+    entries: &[Entry],
+    len: usize,
+    table_bits: u32,
+    value: &Ident,
+    bits: &Ident,
+    fits: Option<&TokenStream>,
+    fallback: &TokenStream,
+) -> TokenStream {
+    let ty = Ty::from_len(len).unwrap();
+    let width = table_bits.min(len as u32);
+    let lo = (0..=len as u32 - width)
+        .min_by_key(|lo| table_cost(entries, *lo, width))
+        .unwrap();
+
+    // Keys with the same candidates share a table arm.
+    let mut groups: Vec<(Vec<usize>, Vec<u128>)> = Vec::new();
+    let mut group_index: HashMap<Vec<usize>, usize> = HashMap::new();
+    for key in 0..1 << width {
+        let candidates = candidates(entries, lo, width, key, fits.is_none());
+        match group_index.get(&candidates) {
+            Some(i) => groups[*i].1.push(key),
+            None => {
+                group_index.insert(candidates.clone(), groups.len());
+                groups.push((candidates, vec![key]));
+            }
+        }
+    }
+    // The most common candidates go last, matching the remaining keys.
+    let largest = (0..groups.len())
+        .max_by_key(|i| groups[*i].1.len())
+        .unwrap();
+    let last = groups.remove(largest);
+    groups.push(last);
+
+    let index = Ident::new("index", Span::mixed_site());
+    let none = entries.len();
+    let tests = groups.iter().enumerate().map(|(i, (candidates, keys))| {
+        let keys = if i == groups.len() - 1 {
+            quote! { _ }
+        } else {
+            let ranges = key_ranges(keys).into_iter().map(|(start, end)| {
+                let (start_lit, end_lit) = (ty.lit(start), ty.lit(end));
+                if start == end {
+                    quote! { #start_lit }
+                } else {
+                    quote! { #start_lit..=#end_lit }
+                }
+            });
+            quote! { #(#ranges)|* }
+        };
+        let arms = candidates.iter().map(|i| match &entries[*i] {
+            Entry::Bits(x) => gen_test_arm(x, *i, ty, value, bits, fits),
+            Entry::Rust(x) => {
+                let pat = &x.pat;
+                let guard = x.guard.as_ref().map(|(_, guard)| quote! { if #guard });
+                quote! { (#pat, _) #guard => #i, }
+            }
+        });
+        quote! {
+            #keys => match (#value, #bits) {
+                #(#arms)*
+                _ => #none,
+            },
+        }
+    });
+
+    let arms = entries.iter().enumerate().map(|(i, entry)| match entry {
+        Entry::Bits(x) => {
+            let conds = gen_conds(x, ty, bits);
+            let bindings = gen_bindings(x, &conds, ty, bits);
+            let binding = x.binding.as_ref().map(|x| quote! { let #x = #value; });
+            let body = &x.arm.body;
+            quote! {
+                #i => {
+                    #binding
+                    #bindings
+                    #body
+                }
+            }
+        }
+        // The guard has been checked already.
+        Entry::Rust(x) => {
+            let pat = &x.pat;
+            let body = &x.body;
+            quote! {
+                #i => match (#value, #bits) {
+                    (#pat, _) => #body,
+                    #[allow(unreachable_patterns)]
+                    _ => unreachable!(),
+                },
+            }
+        }
+    });
+
+    let mask = ty.lit(ones(width));
+    let lo = ty.lit(lo as u128);
+    quote! {
+        #[allow(unused_variables)]
+        let #index: usize = match (#bits >> #lo) & #mask {
+            #(#tests)*
+        };
+        match #index {
+            #(#arms)*
+            _ => #fallback,
+        }
+    }
+}
+
+/// Sorted `keys` as inclusive ranges of consecutive keys.
+fn key_ranges(keys: &[u128]) -> Vec<(u128, u128)> {
+    let mut ret: Vec<(u128, u128)> = Vec::new();
+    for key in keys {
+        match ret.last_mut() {
+            Some((_, end)) if *end + 1 == *key => *end = *key,
+            _ => ret.push((*key, *key)),
+        }
+    }
+    ret
+}
+
+/// Arm of the first table match giving `index` if `arm` matches.
+fn gen_test_arm(
+    arm: &BitArm,
+    index: usize,
+    ty: Ty,
+    value: &Ident,
+    bits: &Ident,
+    fits: Option<&TokenStream>,
+) -> TokenStream {
+    let conds = gen_conds(arm, ty, bits);
+    let guard = arm.arm.guard.as_ref().map(|(_, guard)| {
+        let bindings = gen_bindings(arm, &conds, ty, bits);
+        let binding = arm.binding.as_ref().map(|x| {
+            let name = &x.ident;
+            quote! { let #name = #value; }
+        });
+        quote! { && { #binding #bindings #guard } }
+    });
+    quote! {
+        (_, #bits) if #fits (#(#conds)||*) #guard => #index,
+    }
+}
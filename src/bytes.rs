@@ -0,0 +1,154 @@
+use crate::pattern::{ones, Field, Pattern, Ty};
+use crate::Value;
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::{Error, Ident, Result};
+
+/// Bits of a field held by one byte.
+struct Part {
+    /// Index of the byte in the slice.
+    index: usize,
+    /// Right shift moving the bits to the bottom of the byte.
+    shift: u32,
+    width: u32,
+    /// Left shift moving the bits to their place in the field.
+    offset: u32,
+}
+
+/// Parts of `field` from the most significant one.
+///
+/// Pattern bit 0 is the most significant bit of byte 0.
+fn parts(field: &Field, len: usize) -> Vec<Part> {
+    let start = len - field.pos as usize - field.width as usize;
+    let end = len - field.pos as usize;
+    (start / 8..end.div_ceil(8))
+        .map(|index| {
+            let lo = start.max(index * 8);
+            let hi = end.min(index * 8 + 8);
+            Part {
+                index,
+                shift: (index * 8 + 8 - hi) as u32,
+                width: (hi - lo) as u32,
+                offset: (end - hi) as u32,
+            }
+        })
+        .collect()
+}
+
+/// Value of the bits of `part`, as `ty`.
+fn gen_part(part: &Part, ty: Ty, bytes: &Ident) -> TokenStream {
+    let index = part.index;
+    let mut value = quote! { #bytes[#index] };
+    if part.shift != 0 {
+        let shift = Literal::u32_unsuffixed(part.shift);
+        value = quote! { (#value >> #shift) };
+    }
+    if part.width != 8 {
+        let mask = Ty::U8.lit(ones(part.width));
+        value = quote! { (#value & #mask) };
+    }
+    if ty != Ty::U8 {
+        let x = ty.ident();
+        value = quote! { (#value as #x) };
+    }
+    if part.offset != 0 {
+        let offset = Literal::u32_unsuffixed(part.offset);
+        value = quote! { (#value << #offset) };
+    }
+    value
+}
+
+/// Matches a pattern against a byte slice.
+///
+/// Byte 0 of the slice holds the most significant 8 bits of the pattern, and
+/// the slice may be longer than the pattern. The bits are compared in 64-bit
+/// chunks, and the bytes after the last whole chunk one by one. Each field is
+/// assembled from the bytes it spans, so it can cross chunks. Fields have the
+/// type selected by the pattern length, or `u128` beyond 128 bits, converted
+/// like the fields of integers.
+pub(crate) fn gen_code(pattern: &Pattern, span: Span, value: &Value) -> Result<TokenStream> {
+    let len = pattern.len();
+    if len == 0 || !len.is_multiple_of(8) {
+        return Err(Error::new(
+            span,
+            format!(
+                "patterns matching a byte slice need a multiple of 8 bits, found {}",
+                len
+            ),
+        ));
+    }
+    if let Some(field) = pattern.fields.iter().find(|x| x.width > 128) {
+        return Err(Error::new(
+            span,
+            format!(
+                "field '{}' has {} bits, more than the 128 of `u128`",
+                field.name, field.width
+            ),
+        ));
+    }
+    let ty = pattern.ty().unwrap_or(Ty::U128);
+
+    let bytes = Ident::new("bytes", Span::mixed_site());
+    let mut checks = Vec::new();
+    for (index, chunk) in pattern.bits.chunks(64).enumerate() {
+        let chunk = Pattern {
+            bits: chunk.to_vec(),
+            fields: Vec::new(),
+        };
+        let start = index * 8;
+        if chunk.len() == 64 {
+            if chunk.mask() != 0 {
+                let indices = start..start + 8;
+                let mask = Ty::U64.lit(chunk.mask());
+                let value = Ty::U64.lit(chunk.value());
+                checks.push(quote! {
+                    u64::from_be_bytes([#(#bytes[#indices]),*]) & #mask == #value
+                });
+            }
+            continue;
+        }
+        for (offset, byte) in chunk.bits.chunks(8).enumerate() {
+            let byte = Pattern {
+                bits: byte.to_vec(),
+                fields: Vec::new(),
+            };
+            if byte.mask() != 0 {
+                let index = start + offset;
+                let mask = Ty::U8.lit(byte.mask());
+                let value = Ty::U8.lit(byte.value());
+                checks.push(quote! { #bytes[#index] & #mask == #value });
+            }
+        }
+    }
+    let x = ty.ident();
+    let fields = pattern.fields.iter().map(|field| {
+        let parts = parts(field, len);
+        let parts = parts.iter().map(|part| gen_part(part, ty, &bytes));
+        crate::gen_field(field, quote! { (#(#parts)|*) }, &x, &|v| ty.lit(v), value)
+    });
+    let found = quote! { ::core::option::Option::Some((#(#fields),*)) };
+    let found = if checks.is_empty() {
+        found
+    } else {
+        quote! {
+            if #(#checks)&&* {
+                #found
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    let n = len / 8;
+    let value = &value.expr;
+    Ok(quote! {
+        {
+            let #bytes: &[u8] = #value;
+            if #bytes.len() < #n {
+                ::core::option::Option::None
+            } else {
+                #found
+            }
+        }
+    })
+}
@@ -0,0 +1,546 @@
+use crate::debug::radix;
+use crate::pattern::{
+    gen_fits, gen_word, letter_span, ones, parse_lits, parse_low, Bit, LowInput, Pattern, Ty,
+};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Expr, Ident, Item, LitStr, Result, Token};
+
+/// Assertion that the '0' and '1' bits of the pattern match the value, and
+/// unless `low`, that it has no bits set above the pattern.
+///
+/// On failure, the panic message shows the value in binary under the pattern,
+/// a line marking the mismatched bits, and the values of the letters. It's
+/// written by `core::fmt` without allocating.
+pub(crate) fn gen_assert(input: LowInput, debug: bool) -> Result<TokenStream> {
+    let LowInput { low, input } = input;
+    let (pattern, ty) = input.parse_pattern()?;
+    let x = ty.ident();
+    let len = pattern.len();
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    let lit = &input.pattern;
+
+    let value = Ident::new("value", Span::mixed_site());
+    let bits = Ident::new("bits", Span::mixed_site());
+    let binding = crate::gen_binding(&value, &x, &input.value);
+    let high = if low {
+        None
+    } else {
+        let fits = gen_fits(&value, len as u32);
+        Some(quote! {
+            if !#fits {
+                ::core::panic!(
+                    "assertion failed: bits set above the pattern\n pattern: {}",
+                    #lit
+                );
+            }
+        })
+    };
+    let word = gen_word(&value, ty);
+    let mut fmt = String::from(
+        "assertion failed: bit pattern mismatch\n pattern: {}\n  actual: {}\n          {}",
+    );
+    let letters = pattern.letters();
+    if !letters.is_empty() {
+        let fields: Vec<String> = letters
+            .iter()
+            .map(|x| format!("{} = {}", x, radix(pattern.width(*x))))
+            .collect();
+        fmt.push_str("\n  fields: ");
+        fmt.push_str(&fields.join(", "));
+    }
+    let values = letters.iter().map(|x| pattern.extract(*x, ty, &bits));
+
+    let mismatch = quote! {
+        if #bits & #bit_mask != #bit_pattern {
+            // Bits of a value laid out like the pattern, or if marking, a '^'
+            // for each set bit.
+            struct Line(#x, bool);
+
+            impl ::core::fmt::Display for Line {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    let mut pos = #len;
+                    let mut rest = self.0;
+                    for c in #lit.chars() {
+                        // Marks end at the last set bit.
+                        if self.1 && rest == 0 {
+                            break;
+                        }
+                        if c == '_' || c.is_whitespace() {
+                            ::core::fmt::Write::write_char(f, c)?;
+                            continue;
+                        }
+                        pos -= 1;
+                        let set = (self.0 >> pos) & 1 != 0;
+                        rest &= !(1 << pos);
+                        let c = match (self.1, set) {
+                            (false, false) => '0',
+                            (false, true) => '1',
+                            (true, false) => ' ',
+                            (true, true) => '^',
+                        };
+                        ::core::fmt::Write::write_char(f, c)?;
+                    }
+                    ::core::result::Result::Ok(())
+                }
+            }
+
+            ::core::panic!(
+                #fmt,
+                #lit,
+                Line(#bits, false),
+                Line((#bits ^ #bit_pattern) & #bit_mask, true)
+                #(, #values)*
+            );
+        }
+    };
+    let check = if pattern.mask() == 0 {
+        quote! { #binding #high let _ = #word; }
+    } else {
+        quote! { #binding #high let #bits = #word; #mismatch }
+    };
+
+    if debug {
+        Ok(quote! {
+            if ::core::cfg!(debug_assertions) {
+                #check
+            }
+        })
+    } else {
+        Ok(quote! { { #check } })
+    }
+}
+
+/// Input of `biteq!`: `[fixed,] [low,] "...", x, y`, with the options in
+/// any order.
+pub(crate) struct EqInput {
+    /// Compare only the '0' and '1' bits.
+    fixed: bool,
+    /// Given as `low, "..."`, comparing the low bits of wider values.
+    low: bool,
+    pattern: LitStr,
+    lhs: Expr,
+    rhs: Expr,
+}
+
+impl Parse for EqInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut fixed = false;
+        let mut low = false;
+        while input.peek(Ident) && input.peek2(Token![,]) {
+            let ident: Ident = input.parse()?;
+            let option = if ident == "fixed" {
+                &mut fixed
+            } else if ident == "low" {
+                &mut low
+            } else {
+                return Err(Error::new(ident.span(), "expected `fixed` or `low`"));
+            };
+            if *option {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` is given twice", ident),
+                ));
+            }
+            *option = true;
+            input.parse::<Token![,]>()?;
+        }
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let lhs = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let rhs = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(EqInput {
+            fixed,
+            low,
+            pattern,
+            lhs,
+            rhs,
+        })
+    }
+}
+
+/// Comparison of two values on the bits of the pattern other than '?', or
+/// on its '0' and '1' bits if `fixed`. Unless `low`, values with bits set
+/// above the pattern are never equal.
+pub(crate) fn gen_eq(input: EqInput) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+    let mask = if input.fixed {
+        pattern.mask()
+    } else {
+        ones(pattern.len() as u32) & !pattern.any_mask()
+    };
+
+    let x = ty.ident();
+    let lhs = Ident::new("lhs", Span::mixed_site());
+    let rhs = Ident::new("rhs", Span::mixed_site());
+    let lhs_binding = crate::gen_binding(&lhs, &x, &input.lhs);
+    let rhs_binding = crate::gen_binding(&rhs, &x, &input.rhs);
+    let mut checks = Vec::new();
+    if !input.low {
+        let len = pattern.len() as u32;
+        checks.push(gen_fits(&lhs, len));
+        checks.push(gen_fits(&rhs, len));
+    }
+    let lhs_word = gen_word(&lhs, ty);
+    let rhs_word = gen_word(&rhs, ty);
+    if mask != 0 {
+        let mask = ty.lit(mask);
+        checks.push(quote! { (#lhs_word ^ #rhs_word) & #mask == 0 });
+    }
+    if checks.is_empty() {
+        return Ok(quote! {
+            {
+                #lhs_binding
+                #rhs_binding
+                let _ = #lhs_word;
+                let _ = #rhs_word;
+                true
+            }
+        });
+    }
+    Ok(quote! {
+        {
+            #lhs_binding
+            #rhs_binding
+            #(#checks)&&*
+        }
+    })
+}
+
+/// Input of `bitmatch_any!` and `bitmatch_all!`: `[low,] x, ["...", ...]`.
+pub(crate) struct AnyInput {
+    /// Given as `low, x`, matching the low bits of a wider value.
+    low: bool,
+    value: Expr,
+    patterns: Vec<LitStr>,
+}
+
+impl Parse for AnyInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // A value named `low` is followed by the patterns.
+        let low = !input.peek3(syn::token::Bracket) && parse_low(input)?;
+        let value = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let content;
+        let bracket = syn::bracketed!(content in input);
+        let patterns: Vec<LitStr> = content
+            .parse_terminated(parse_lits, Token![,])?
+            .into_iter()
+            .collect();
+        input.parse::<Option<Token![,]>>()?;
+        if patterns.is_empty() {
+            return Err(Error::new(bracket.span.join(), "expected a pattern"));
+        }
+        Ok(AnyInput {
+            low,
+            value,
+            patterns,
+        })
+    }
+}
+
+impl AnyInput {
+    /// Parses the patterns, which must have the same length.
+    fn parse_patterns(&self) -> Result<(Vec<Pattern>, Ty)> {
+        let mut patterns = Vec::new();
+        for lit in &self.patterns {
+            let (pattern, _) = Pattern::parse_lit(lit)?;
+            if let Some(first) = patterns.first() {
+                let first: &Pattern = first;
+                if pattern.len() != first.len() {
+                    return Err(Error::new(
+                        lit.span(),
+                        format!(
+                            "pattern has {} bits, but the first pattern has {}",
+                            pattern.len(),
+                            first.len()
+                        ),
+                    ));
+                }
+            }
+            patterns.push(pattern);
+        }
+        let ty = patterns[0].ty().unwrap();
+        Ok((patterns, ty))
+    }
+
+    /// Binding of the value, the condition that it has no bits set above the
+    /// patterns unless `low`, and its bits as the type `ty`.
+    fn gen_value(&self, len: usize, ty: Ty) -> (TokenStream, Option<TokenStream>, TokenStream) {
+        let value = Ident::new("value", Span::mixed_site());
+        let binding = crate::gen_binding(&value, &ty.ident(), &self.value);
+        let fits = if self.low {
+            None
+        } else {
+            Some(gen_fits(&value, len as u32))
+        };
+        (binding, fits, gen_word(&value, ty))
+    }
+}
+
+/// Index of the first pattern whose '0' and '1' bits match, tried in order.
+/// Unless `low`, a value with bits set above the patterns matches none.
+pub(crate) fn gen_any(input: AnyInput) -> Result<TokenStream> {
+    let (patterns, ty) = input.parse_patterns()?;
+    let (binding, fits, word) = input.gen_value(patterns[0].len(), ty);
+    let bits = Ident::new("bits", Span::mixed_site());
+    let mut arms = Vec::new();
+    let mut rest = quote! { ::core::option::Option::None };
+    for (i, pattern) in patterns.iter().enumerate() {
+        // A pattern without '0' and '1' bits matches anything after it.
+        if pattern.mask() == 0 {
+            rest = quote! { ::core::option::Option::Some(#i) };
+            break;
+        }
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        arms.push(quote! {
+            if #bits & #bit_mask == #bit_pattern {
+                ::core::option::Option::Some(#i)
+            }
+        });
+    }
+    let high = fits.map(|fits| {
+        quote! {
+            if !#fits {
+                ::core::option::Option::None
+            } else
+        }
+    });
+    let body = if arms.is_empty() {
+        quote! { let _ = #bits; #high { #rest } }
+    } else {
+        quote! { #high #(#arms)else* else { #rest } }
+    };
+    Ok(quote! {
+        {
+            #binding
+            let #bits = #word;
+            #body
+        }
+    })
+}
+
+/// `u32` with bit `i` set if the '0' and '1' bits of pattern `i` match.
+/// Unless `low`, a value with bits set above the patterns matches none.
+pub(crate) fn gen_all(input: AnyInput) -> Result<TokenStream> {
+    if input.patterns.len() > 32 {
+        return Err(Error::new(
+            input.patterns[32].span(),
+            format!(
+                "{} patterns don't fit in the `u32` of `bitmatch_all!`",
+                input.patterns.len()
+            ),
+        ));
+    }
+    let (patterns, ty) = input.parse_patterns()?;
+    let (binding, fits, word) = input.gen_value(patterns[0].len(), ty);
+    let bits = Ident::new("bits", Span::mixed_site());
+    let terms = patterns.iter().enumerate().map(|(i, pattern)| {
+        let matched = if pattern.mask() == 0 {
+            quote! { 1u32 }
+        } else {
+            let bit_mask = ty.lit(pattern.mask());
+            let bit_pattern = ty.lit(pattern.value());
+            quote! { ((#bits & #bit_mask == #bit_pattern) as u32) }
+        };
+        if i == 0 {
+            matched
+        } else {
+            let i = i as u32;
+            quote! { (#matched << #i) }
+        }
+    });
+    let unused = if patterns.iter().all(|x| x.mask() == 0) {
+        Some(quote! { let _ = #bits; })
+    } else {
+        None
+    };
+    let terms = quote! { #(#terms)|* };
+    let body = match fits {
+        Some(fits) => quote! { if #fits { #terms } else { 0 } },
+        None => terms,
+    };
+    Ok(quote! {
+        {
+            #binding
+            let #bits = #word;
+            #unused
+            #body
+        }
+    })
+}
+
+/// Input of `bitoverlap!` and `bitintersect!`: `"...", "..."`.
+pub(crate) struct PairInput {
+    lhs: LitStr,
+    rhs: LitStr,
+}
+
+impl Parse for PairInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lhs = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let rhs = parse_lits(input)?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(PairInput { lhs, rhs })
+    }
+}
+
+impl PairInput {
+    /// Parses both patterns, which must have the same length.
+    fn parse_patterns(&self) -> Result<(Pattern, Pattern)> {
+        let mut patterns = Vec::new();
+        for lit in [&self.lhs, &self.rhs] {
+            let (pattern, _) = Pattern::parse_lit(lit)?;
+            patterns.push(pattern);
+        }
+        let rhs = patterns.pop().unwrap();
+        let lhs = patterns.pop().unwrap();
+        if lhs.len() != rhs.len() {
+            return Err(Error::new(
+                self.rhs.span(),
+                format!(
+                    "patterns have different lengths: {} and {} bits",
+                    lhs.len(),
+                    rhs.len()
+                ),
+            ));
+        }
+        Ok((lhs, rhs))
+    }
+}
+
+/// `true` or `false` literal of whether a value matches both patterns, which
+/// is if their '0' and '1' bits agree where both have one.
+pub(crate) fn gen_overlap(input: PairInput) -> Result<TokenStream> {
+    let (lhs, rhs) = input.parse_patterns()?;
+    let overlap = (lhs.value() ^ rhs.value()) & lhs.mask() & rhs.mask() == 0;
+    Ok(quote! { #overlap })
+}
+
+/// String literal of the pattern matching the values of both patterns.
+///
+/// Each bit is the '0' or '1' bit of either pattern, then the letter of the
+/// first one, then the letter of the second one. The separators of the first
+/// pattern are kept.
+pub(crate) fn gen_intersect(input: PairInput) -> Result<TokenStream> {
+    let (lhs, rhs) = input.parse_patterns()?;
+    let len = lhs.len();
+    let letters = lhs.letters();
+    let mut bits = Vec::new();
+    for (i, (x, y)) in lhs.bits.iter().zip(&rhs.bits).enumerate() {
+        let bit = match (*x, *y) {
+            (Bit::Zero, Bit::One) | (Bit::One, Bit::Zero) => {
+                return Err(Error::new(
+                    input.rhs.span(),
+                    format!(
+                        "patterns conflict at bit {}: '{}' and '{}'",
+                        len - i - 1,
+                        bit_char(*x),
+                        bit_char(*y)
+                    ),
+                ));
+            }
+            (Bit::Zero, _) | (Bit::One, _) => *x,
+            (_, Bit::Zero) | (_, Bit::One) => *y,
+            (Bit::Letter(_), _) => *x,
+            (Bit::Any, Bit::Letter(c)) if letters.contains(&c) => {
+                return Err(Error::new(
+                    letter_span(&input.rhs, c),
+                    format!("letter '{}' is in both patterns", c),
+                ));
+            }
+            (Bit::Any, _) => *y,
+        };
+        bits.push(bit);
+    }
+
+    let mut bits = bits.into_iter();
+    let merged: String = input
+        .lhs
+        .value()
+        .chars()
+        .map(|c| {
+            if c == '_' || c.is_whitespace() {
+                c
+            } else {
+                bit_char(bits.next().unwrap())
+            }
+        })
+        .collect();
+    Ok(quote! { #merged })
+}
+
+fn bit_char(bit: Bit) -> char {
+    match bit {
+        Bit::Zero => '0',
+        Bit::One => '1',
+        Bit::Any => '?',
+        Bit::Letter(x) => x,
+    }
+}
+
+/// `const` or `static` item followed by a `const` assertion that the '0' and
+/// '1' bits of the pattern match its value, and unless `low`, that it has no
+/// bits set above the pattern.
+///
+/// The value is converted like `bitmatches!`, so it can have any integer
+/// type, and the assertion is evaluated by the compiler like any other
+/// constant.
+pub(crate) fn gen_bitcheck(attr: TokenStream, item: TokenStream) -> Result<TokenStream> {
+    let (low, lit) = syn::parse::Parser::parse2(
+        |input: ParseStream| Ok((parse_low(input)?, parse_lits(input)?)),
+        attr,
+    )?;
+    let item: Item = syn::parse2(item)?;
+    let (pattern, ty) = Pattern::parse_lit(&lit)?;
+    let (name, value) = match &item {
+        Item::Const(x) => {
+            let name = &x.ident;
+            (name, quote! { #name })
+        }
+        Item::Static(x) => {
+            let ty = &x.ty;
+            let expr = &x.expr;
+            (&x.ident, quote! { { let value: #ty = #expr; value } })
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                &item,
+                "`#[bitcheck]` expects a `const` or `static` item",
+            ));
+        }
+    };
+    let binding = Ident::new("value", Span::mixed_site());
+    let mut checks = Vec::new();
+    if !low {
+        checks.push(gen_fits(&binding, pattern.len() as u32));
+    }
+    if pattern.mask() != 0 {
+        let word = gen_word(&binding, ty);
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        checks.push(quote! { #word & #bit_mask == #bit_pattern });
+    }
+    if checks.is_empty() {
+        return Ok(quote! { #item });
+    }
+
+    // The message is a format string, so braces of fragments are escaped.
+    let message = format!("`{}` doesn't match `\"{}\"`", name, lit.value())
+        .replace('{', "{{")
+        .replace('}', "}}");
+    Ok(quote! {
+        #item
+
+        const _: () = {
+            let #binding = #value;
+            ::core::assert!(#(#checks)&&*, #message);
+        };
+    })
+}
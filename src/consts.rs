@@ -0,0 +1,485 @@
+use crate::pattern::{letter_span, parse_lits, Pattern, PatternInput, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Error, Ident, LitStr, Result, Token, Visibility};
+
+/// Input of `bitmask!`: a pattern with an optional type.
+pub(crate) struct MaskInput {
+    pub(crate) pattern: LitStr,
+    pub(crate) ty: Option<Ident>,
+}
+
+impl Parse for MaskInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        let mut ty = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            ty = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(MaskInput { pattern, ty })
+    }
+}
+
+/// Parses `pattern`, selecting its type by length unless `ty` is given.
+pub(crate) fn parse_pattern(pattern: &LitStr, ty: Option<&Ident>) -> Result<(Pattern, Ty)> {
+    let (parsed, len_ty) = Pattern::parse_lit(pattern)?;
+    let ty = match ty {
+        None => len_ty,
+        Some(ident) => match Ty::from_ident(ident) {
+            Some(x) if x < len_ty => {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("{}-bit pattern doesn't fit in `{}`", parsed.len(), ident),
+                ));
+            }
+            Some(x) => x,
+            None => {
+                return Err(Error::new(
+                    ident.span(),
+                    "expected one of `u8`, `u16`, `u32`, `u64` or `u128`",
+                ));
+            }
+        },
+    };
+    Ok((parsed, ty))
+}
+
+pub(crate) fn gen_mask(input: MaskInput) -> Result<TokenStream> {
+    let (pattern, ty) = parse_pattern(&input.pattern, input.ty.as_ref())?;
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    Ok(quote! { (#bit_mask, #bit_pattern) })
+}
+
+/// Input of `bitconst!`: `vis NAME, "...", ty`.
+pub(crate) struct ConstInput {
+    vis: Visibility,
+    name: Ident,
+    mask: MaskInput,
+}
+
+impl Parse for ConstInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let mask = input.parse()?;
+        Ok(ConstInput { vis, name, mask })
+    }
+}
+
+/// Constants of the mask and value of a pattern, and the position and width
+/// of each letter.
+pub(crate) fn gen_const(input: ConstInput) -> Result<TokenStream> {
+    let lit = &input.mask.pattern;
+    let (pattern, ty) = parse_pattern(lit, input.mask.ty.as_ref())?;
+    gen_consts(lit, &pattern, ty, &input.vis, Some(&input.name))
+}
+
+/// Constants of `gen_const` named by `prefix`, or without a prefix.
+fn gen_consts(
+    lit: &LitStr,
+    pattern: &Pattern,
+    ty: Ty,
+    vis: &Visibility,
+    prefix: Option<&Ident>,
+) -> Result<TokenStream> {
+    let x = ty.ident();
+    let source = lit.value();
+
+    let full_name = |suffix: &str| match prefix {
+        Some(name) => format!("{}_{}", name, suffix),
+        None => suffix.to_string(),
+    };
+    let span = prefix.map_or_else(|| lit.span(), |x| x.span());
+    let ident = |suffix: &str| Ident::new(&full_name(suffix), span);
+    let mask_name = ident("MASK");
+    let value_name = ident("VALUE");
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    let mask_doc = format!("Mask of the '0' and '1' bits of `\"{}\"`.", source);
+    let value_doc = format!("Value of the '0' and '1' bits of `\"{}\"`.", source);
+
+    let letters = pattern.letters();
+    let mut fields = Vec::new();
+    for &letter in &letters {
+        let runs: Vec<_> = pattern.fields.iter().filter(|x| x.name == letter).collect();
+        if runs.len() > 1 {
+            return Err(Error::new(
+                lit.span(),
+                format!(
+                    "letter '{}' is split into several runs, so it has no single shift",
+                    letter
+                ),
+            ));
+        }
+        let upper: String = letter.to_uppercase().collect();
+        // The names are uppercase, so both cases of a letter would clash.
+        let other = letter.to_ascii_uppercase();
+        if other != letter && letters.contains(&other) {
+            return Err(Error::new(
+                lit.span(),
+                format!(
+                    "letters '{}' and '{}' would both name the constant `{}`",
+                    letter,
+                    other,
+                    full_name(&format!("{}_SHIFT", upper))
+                ),
+            ));
+        }
+        if syn::parse_str::<Ident>(&full_name(&format!("{}_SHIFT", upper))).is_err() {
+            return Err(Error::new(
+                lit.span(),
+                format!("letter '{}' can't be used in a constant name", letter),
+            ));
+        }
+        let field = runs[0];
+        let shift_name = ident(&format!("{}_SHIFT", upper));
+        let width_name = ident(&format!("{}_WIDTH", upper));
+        let field_mask_name = ident(&format!("{}_MASK", upper));
+        let pos = field.pos;
+        let width = field.width;
+        let field_mask = ty.lit(field.mask());
+        let shift_doc = format!("Position of '{}' in `\"{}\"`.", letter, source);
+        let width_doc = format!("Width of '{}' in `\"{}\"`.", letter, source);
+        let field_mask_doc = format!(
+            "Mask of '{}' in `\"{}\"`, before shifting by the position.",
+            letter, source
+        );
+        fields.push(quote! {
+            #[doc = #shift_doc]
+            #vis const #shift_name: u32 = #pos;
+            #[doc = #width_doc]
+            #vis const #width_name: u32 = #width;
+            #[doc = #field_mask_doc]
+            #vis const #field_mask_name: #x = #field_mask;
+        });
+    }
+
+    Ok(quote! {
+        #[doc = #mask_doc]
+        #vis const #mask_name: #x = #bit_mask;
+        #[doc = #value_doc]
+        #vis const #value_name: #x = #bit_pattern;
+        #(#fields)*
+    })
+}
+
+/// Input of `bitfns!`: `vis fn name, "...", ty`.
+pub(crate) struct FnsInput {
+    vis: Visibility,
+    name: Ident,
+    mask: MaskInput,
+}
+
+impl Parse for FnsInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<Token![fn]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let mask = input.parse()?;
+        Ok(FnsInput { vis, name, mask })
+    }
+}
+
+/// Functions testing, extracting, and matching a pattern like `bitpattern!`.
+pub(crate) fn gen_fns(input: FnsInput) -> Result<TokenStream> {
+    let lit = &input.mask.pattern;
+    let (pattern, ty) = parse_pattern(lit, input.mask.ty.as_ref())?;
+    let vis = &input.vis;
+    let name = &input.name;
+    let x = ty.ident();
+    let source = lit.value();
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+
+    let is_body = if pattern.mask() == 0 {
+        quote! { let _ = x; true }
+    } else {
+        quote! { x & #bit_mask == #bit_pattern }
+    };
+    let is_name = Ident::new(&format!("is_{}", name), name.span());
+    let is_doc = format!("Returns true if `x` matches `\"{}\"`.", source);
+    let is_fn = quote! {
+        #[doc = #is_doc]
+        #[inline]
+        #vis const fn #is_name(x: #x) -> bool {
+            #is_body
+        }
+    };
+    if pattern.fields.is_empty() {
+        return Ok(is_fn);
+    }
+
+    let extract_name = Ident::new(&format!("extract_{}", name), name.span());
+    let extract_doc = format!(
+        "Extracts the letters of `\"{}\"` from `x` without matching.",
+        source
+    );
+    let match_doc = format!(
+        "Extracts the letters of `\"{}\"` from `x` if it matches.",
+        source
+    );
+    // Uppercase runs are sign-extended like `bitpattern!`.
+    let signed = crate::signed_ident(&x);
+    let types = pattern
+        .fields
+        .iter()
+        .map(|field| if field.signed() { &signed } else { &x });
+    let ret = quote! { (#(#types),*) };
+    let bits = Ident::new("bits", Span::mixed_site());
+    let args = crate::gen_fields(&pattern, ty, &bits);
+    Ok(quote! {
+        #is_fn
+
+        #[doc = #extract_doc]
+        #[inline]
+        #vis const fn #extract_name(x: #x) -> #ret {
+            let #bits = x;
+            (#(#args),*)
+        }
+
+        #[doc = #match_doc]
+        #[inline]
+        #vis const fn #name(x: #x) -> ::core::option::Option<#ret> {
+            if #is_name(x) {
+                ::core::option::Option::Some(#extract_name(x))
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    })
+}
+
+/// Description of a pattern: its mask and value, and the position and width
+/// of each run of letters, as a value of a local struct.
+pub(crate) fn gen_explain(input: MaskInput) -> Result<TokenStream> {
+    let lit = &input.pattern;
+    let (pattern, ty) = parse_pattern(lit, input.ty.as_ref())?;
+    let x = ty.ident();
+    let len = pattern.len() as u32;
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    let n = pattern.fields.len();
+    let fields = pattern.fields.iter().map(|field| {
+        let name = field.name;
+        let pos = field.pos;
+        let width = field.width;
+        quote! { (#name, #pos, #width) }
+    });
+    Ok(quote! {
+        {
+            /// Mask, value and runs of letters of a pattern.
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            struct BitExplain {
+                /// The pattern as written.
+                pattern: &'static str,
+                /// Number of bits, without separators.
+                len: u32,
+                /// Mask of the '0' and '1' bits.
+                mask: #x,
+                /// Value of the '0' and '1' bits.
+                value: #x,
+                /// Letter, shift and width of each run of letters, in order.
+                fields: [(char, u32, u32); #n],
+            }
+
+            BitExplain {
+                pattern: #lit,
+                len: #len,
+                mask: #bit_mask,
+                value: #bit_pattern,
+                fields: [#(#fields),*],
+            }
+        }
+    })
+}
+
+/// Array of each letter with its value extracted from a value, in the order
+/// of the pattern, whether the '0' and '1' bits match or not.
+pub(crate) fn gen_fields_iter(input: PatternInput) -> Result<TokenStream> {
+    let (pattern, ty) = input.parse_pattern()?;
+    let x = ty.ident();
+    let bits = Ident::new("bits", Span::mixed_site());
+    let letters = pattern.letters();
+    let mut fields = Vec::new();
+    for letter in &letters {
+        let width = pattern.width(*letter);
+        if width > 32 {
+            return Err(Error::new(
+                letter_span(&input.pattern, *letter),
+                format!(
+                    "letter '{}' has {} bits, but the values are `u32`",
+                    letter, width
+                ),
+            ));
+        }
+        let extract = pattern.extract(*letter, ty, &bits);
+        let value = if ty == Ty::U32 {
+            extract
+        } else {
+            quote! { (#extract as u32) }
+        };
+        fields.push(quote! { (#letter, #value) });
+    }
+    let n = letters.len();
+    let value = &input.value;
+    Ok(quote! {
+        {
+            let #bits = (#value) as #x;
+            let fields: [(char, u32); #n] = [#(#fields),*];
+            fields
+        }
+    })
+}
+
+/// Register of `bitspec!`: `#[attr] vis NAME(ty) = "...";`.
+struct Register {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    ty: Ident,
+    pattern: LitStr,
+}
+
+impl Parse for Register {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        let content;
+        syn::parenthesized!(content in input);
+        let ty = content.parse()?;
+        input.parse::<Token![=]>()?;
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![;]>()?;
+        Ok(Register {
+            attrs,
+            vis,
+            name,
+            ty,
+            pattern,
+        })
+    }
+}
+
+/// Input of `bitspec!`: any number of registers.
+pub(crate) struct SpecInput {
+    registers: Vec<Register>,
+}
+
+impl Parse for SpecInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut registers = Vec::new();
+        while !input.is_empty() {
+            registers.push(input.parse()?);
+        }
+        Ok(SpecInput { registers })
+    }
+}
+
+/// A module for each register, named by the register in snake case, with the
+/// constants of `bitconst!` without a prefix, and functions testing the
+/// pattern and extracting and updating each letter.
+pub(crate) fn gen_spec(input: SpecInput) -> Result<TokenStream> {
+    let mut names: Vec<String> = Vec::new();
+    let mut modules = Vec::new();
+    for register in &input.registers {
+        let name = &register.name;
+        let module = snake_case(&name.to_string());
+        if syn::parse_str::<Ident>(&module).is_err() {
+            return Err(Error::new(
+                name.span(),
+                format!("register `{}` can't be used as a module name", name),
+            ));
+        }
+        if names.contains(&module) {
+            return Err(Error::new(
+                name.span(),
+                format!("duplicate register `{}`", name),
+            ));
+        }
+        names.push(module.clone());
+
+        let lit = &register.pattern;
+        let (pattern, ty) = parse_pattern(lit, Some(&register.ty))?;
+        let public: Visibility = syn::parse_quote!(pub);
+        let consts = gen_consts(lit, &pattern, ty, &public, None)?;
+
+        let x = ty.ident();
+        let source = lit.value();
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        let is_body = if pattern.mask() == 0 {
+            quote! { let _ = bits; true }
+        } else {
+            quote! { bits & #bit_mask == #bit_pattern }
+        };
+        let mut fns = Vec::new();
+        for letter in pattern.letters() {
+            let upper: String = letter.to_uppercase().collect();
+            let lower: String = letter.to_lowercase().collect();
+            let shift = Ident::new(&format!("{}_SHIFT", upper), lit.span());
+            let mask = Ident::new(&format!("{}_MASK", upper), lit.span());
+            let extract = Ident::new(&format!("extract_{}", lower), name.span());
+            let update = Ident::new(&format!("update_{}", lower), name.span());
+            let extract_doc = format!("Extracts '{}' of `\"{}\"`.", letter, source);
+            let update_doc = format!(
+                "Replaces '{}' of `\"{}\"` by the low bits of `value`.",
+                letter, source
+            );
+            fns.push(quote! {
+                #[doc = #extract_doc]
+                #[inline]
+                pub const fn #extract(bits: #x) -> #x {
+                    (bits >> #shift) & #mask
+                }
+
+                #[doc = #update_doc]
+                #[inline]
+                pub const fn #update(bits: #x, value: #x) -> #x {
+                    (bits & !(#mask << #shift)) | ((value & #mask) << #shift)
+                }
+            });
+        }
+
+        let attrs = &register.attrs;
+        let vis = &register.vis;
+        let module = Ident::new(&module, name.span());
+        let doc = format!("Register `{}`: `\"{}\"`.", name, source);
+        let matches_doc = format!("Returns true if `bits` matches `\"{}\"`.", source);
+        modules.push(quote! {
+            #(#attrs)*
+            #[doc = #doc]
+            #vis mod #module {
+                #consts
+
+                #[doc = #matches_doc]
+                #[inline]
+                pub const fn matches(bits: #x) -> bool {
+                    #is_body
+                }
+
+                #(#fns)*
+            }
+        });
+    }
+    Ok(quote! { #(#modules)* })
+}
+
+/// `UartStatus` or `UART_STATUS` as `uart_status`.
+fn snake_case(name: &str) -> String {
+    let mut ret = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && prev_lower {
+            ret.push('_');
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        ret.extend(c.to_lowercase());
+    }
+    ret
+}
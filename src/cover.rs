@@ -0,0 +1,110 @@
+use crate::pattern::Pattern;
+
+/// The set of values whose `mask` bits are equal to `value`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Cube {
+    pub(crate) mask: u128,
+    pub(crate) value: u128,
+}
+
+impl Cube {
+    pub(crate) fn from_pattern(pattern: &Pattern) -> Cube {
+        Cube {
+            mask: pattern.mask(),
+            value: pattern.value(),
+        }
+    }
+
+    pub(crate) fn intersects(&self, other: &Cube) -> bool {
+        (self.value ^ other.value) & self.mask & other.mask == 0
+    }
+
+    pub(crate) fn contains(&self, other: &Cube) -> bool {
+        other.mask & self.mask == self.mask && (other.value ^ self.value) & self.mask == 0
+    }
+
+    /// Common values of `self` and `other`.
+    pub(crate) fn intersection(&self, other: &Cube) -> Option<Cube> {
+        if self.intersects(other) {
+            Some(Cube {
+                mask: self.mask | other.mask,
+                value: (self.value & self.mask) | (other.value & other.mask),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Subtracts `other` from `self`, giving disjoint cubes.
+    fn subtract(&self, other: &Cube) -> Vec<Cube> {
+        if !self.intersects(other) {
+            return vec![*self];
+        }
+
+        let mut ret = Vec::new();
+        let mut cube = *self;
+        let mut free = other.mask & !self.mask;
+        while free != 0 {
+            let bit = free & free.wrapping_neg();
+            free &= !bit;
+            ret.push(Cube {
+                mask: cube.mask | bit,
+                value: cube.value | (!other.value & bit),
+            });
+            cube = Cube {
+                mask: cube.mask | bit,
+                value: cube.value | (other.value & bit),
+            };
+        }
+        ret
+    }
+
+    /// Formats as a pattern string of `len` bits.
+    pub(crate) fn to_pattern(self, len: usize) -> String {
+        (0..len)
+            .rev()
+            .map(|i| {
+                if (self.mask >> i) & 1 == 0 {
+                    '?'
+                } else if (self.value >> i) & 1 == 0 {
+                    '0'
+                } else {
+                    '1'
+                }
+            })
+            .collect()
+    }
+
+    /// Formats the smallest value in the cube as `len` binary digits.
+    pub(crate) fn example(self, len: usize) -> String {
+        Cube {
+            mask: !0,
+            value: self.value & self.mask,
+        }
+        .to_pattern(len)
+    }
+}
+
+/// Upper limit of the number of cubes tracked by `uncovered`.
+const LIMIT: usize = 1 << 16;
+
+/// Values not matched by any of `cubes`, or `None` if that is too complex to compute.
+pub(crate) fn uncovered(cubes: &[Cube]) -> Option<Vec<Cube>> {
+    remaining(Cube { mask: 0, value: 0 }, cubes)
+}
+
+/// Values of `start` not matched by any of `cubes`, or `None` if that is too
+/// complex to compute.
+pub(crate) fn remaining(start: Cube, cubes: &[Cube]) -> Option<Vec<Cube>> {
+    let mut ret = vec![start];
+    for cube in cubes {
+        ret = ret.iter().flat_map(|x| x.subtract(cube)).collect();
+        if ret.len() > LIMIT {
+            return None;
+        }
+        if ret.is_empty() {
+            break;
+        }
+    }
+    Some(ret)
+}
@@ -0,0 +1,345 @@
+use crate::consts::{self, MaskInput};
+use crate::decode::{prim, Prim};
+use crate::pattern::{parse_lits, Pattern, PatternInput};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Field, Fields, Ident, Result, Token, Type, Visibility};
+
+/// Field written by `Debug` or `defmt::Format`.
+pub(crate) struct Item {
+    /// Field name, or `None` for tuple fields.
+    pub(crate) name: Option<String>,
+    pub(crate) spec: Option<&'static str>,
+    pub(crate) value: TokenStream,
+}
+
+impl Item {
+    pub(crate) fn new(field: &Field, width: u32, value: TokenStream) -> Item {
+        Item {
+            name: field.ident.as_ref().map(|x| x.to_string()),
+            spec: spec(width, &field.ty),
+            value,
+        }
+    }
+}
+
+/// Format spec of a field, or `None` to use the formatting of its type.
+fn spec(width: u32, ty: &Type) -> Option<&'static str> {
+    match prim(ty) {
+        Some(Prim::Int { signed: false, .. }) => Some(radix(width)),
+        _ => None,
+    }
+}
+
+/// Unsigned fields narrower than a byte are written in binary, and wider ones
+/// in hex.
+pub(crate) fn radix(width: u32) -> &'static str {
+    if width < 8 {
+        "{:#b}"
+    } else {
+        "{:#X}"
+    }
+}
+
+/// Body of `Debug::fmt` writing `name` with `items`.
+pub(crate) fn gen_debug(name: &str, shape: &Fields, items: &[Item]) -> TokenStream {
+    let args = items.iter().map(|x| {
+        let value = &x.value;
+        match x.spec {
+            Some(spec) => quote! { &::core::format_args!(#spec, #value) },
+            None => quote! { &#value },
+        }
+    });
+    match shape {
+        Fields::Unit => quote! { f.write_str(#name) },
+        Fields::Unnamed(_) => quote! { f.debug_tuple(#name) #(.field(#args))* .finish() },
+        Fields::Named(_) => {
+            let names = items.iter().map(|x| x.name.as_ref().unwrap());
+            quote! { f.debug_struct(#name) #(.field(#names, #args))* .finish() }
+        }
+    }
+}
+
+/// Body of `defmt::Format::format` writing `name` with `items`, like `Debug`.
+pub(crate) fn gen_defmt(name: &str, shape: &Fields, items: &[Item]) -> TokenStream {
+    let specs: Vec<String> = items
+        .iter()
+        .map(|x| {
+            let spec = x.spec.unwrap_or("{}");
+            match &x.name {
+                Some(name) => format!("{}: {}", name, spec),
+                None => spec.to_string(),
+            }
+        })
+        .collect();
+    let fmt = match shape {
+        Fields::Unit => name.to_string(),
+        Fields::Unnamed(_) => format!("{}({})", name, specs.join(", ")),
+        Fields::Named(_) => format!("{} {{{{ {} }}}}", name, specs.join(", ")),
+    };
+    let values = items.iter().map(|x| &x.value);
+    quote! { ::defmt::write!(f, #fmt #(, #values)*) }
+}
+
+/// Input of `bitfmt!`: `"...", value` with an optional `, any_bits` and
+/// `, defmt`.
+pub(crate) struct FmtInput {
+    input: PatternInput,
+    /// Write the bits of '?' instead of '-'.
+    any_bits: bool,
+    /// Implement `defmt::Format` too.
+    defmt: bool,
+}
+
+impl Parse for FmtInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let value = input.parse()?;
+        let mut any_bits = false;
+        let mut defmt = false;
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            let option = if ident == "any_bits" {
+                &mut any_bits
+            } else if ident == "defmt" {
+                if !cfg!(feature = "defmt") {
+                    return Err(Error::new(
+                        ident.span(),
+                        "`defmt` requires the \"defmt\" feature of bitpattern",
+                    ));
+                }
+                &mut defmt
+            } else {
+                return Err(Error::new(ident.span(), "expected `any_bits` or `defmt`"));
+            };
+            if *option {
+                return Err(Error::new(
+                    ident.span(),
+                    format!("`{}` is given twice", ident),
+                ));
+            }
+            *option = true;
+        }
+        if !input.is_empty() {
+            return Err(input.error("expected `,`"));
+        }
+        Ok(FmtInput {
+            input: PatternInput { pattern, value },
+            any_bits,
+            defmt,
+        })
+    }
+}
+
+/// Format string writing the letters like `a=2 b=0xB`.
+fn long_fmt(pattern: &Pattern) -> String {
+    pattern
+        .letters()
+        .iter()
+        .map(|x| {
+            if pattern.width(*x) < 4 {
+                format!("{}={{}}", x)
+            } else {
+                format!("{}={{:#X}}", x)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Adapter writing the value laid out like the pattern.
+///
+/// `Display` writes the bits with '?' bits as '-', and mismatched '0' and '1'
+/// bits in brackets. The alternate form writes the letters like `a=2 b=0xB`,
+/// and `Debug` writes them like `a: 0b10, b: 0xB`.
+pub(crate) fn gen_code(input: FmtInput) -> Result<TokenStream> {
+    let (any_bits, defmt) = (input.any_bits, input.defmt);
+    let input = input.input;
+    let (pattern, ty) = input.parse_pattern()?;
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let letters = pattern.letters();
+    let fmt = letters
+        .iter()
+        .map(|x| format!("{}: {}", x, radix(pattern.width(*x))))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let long_fmt = long_fmt(&pattern);
+    let values: Vec<_> = letters
+        .iter()
+        .map(|x| pattern.extract(*x, ty, &bits))
+        .collect();
+
+    let defmt = if defmt {
+        Some(quote! {
+            impl ::defmt::Format for BitFmt {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    let #bits = self.0;
+                    ::defmt::write!(f, #fmt #(, #values)*)
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    let x = ty.ident();
+    let len = pattern.len();
+    let lit = &input.pattern;
+    let value = &input.value;
+    let raw = Ident::new("raw", Span::mixed_site());
+    Ok(quote! {
+        match (#value) as #x {
+            #raw => {
+                struct BitFmt(#x);
+
+                impl ::core::fmt::Display for BitFmt {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        let #bits = self.0;
+                        if f.alternate() {
+                            return ::core::write!(f, #long_fmt #(, #values)*);
+                        }
+                        let mut pos = #len;
+                        for c in #lit.chars() {
+                            if c == '_' || c.is_whitespace() {
+                                ::core::fmt::Write::write_char(f, c)?;
+                                continue;
+                            }
+                            pos -= 1;
+                            let bit = if (#bits >> pos) & 1 == 0 { '0' } else { '1' };
+                            match c {
+                                '?' if !#any_bits => f.write_str("-")?,
+                                '0' | '1' if c != bit => ::core::write!(f, "[{}]", bit)?,
+                                _ => ::core::fmt::Write::write_char(f, bit)?,
+                            }
+                        }
+                        ::core::result::Result::Ok(())
+                    }
+                }
+
+                impl ::core::fmt::Debug for BitFmt {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        let #bits = self.0;
+                        ::core::write!(f, #fmt #(, #values)*)
+                    }
+                }
+
+                #defmt
+
+                BitFmt(#raw)
+            }
+        }
+    })
+}
+
+/// Input of `bitdebug!`: `vis Name, "...", ty`.
+pub(crate) struct NewtypeInput {
+    vis: Visibility,
+    name: Ident,
+    mask: MaskInput,
+}
+
+impl Parse for NewtypeInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let vis = input.parse()?;
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let mask = input.parse()?;
+        Ok(NewtypeInput { vis, name, mask })
+    }
+}
+
+/// Newtype of a raw value with getters for the letters, writing them by
+/// `Debug` and `Display`.
+pub(crate) fn gen_newtype(input: NewtypeInput) -> Result<TokenStream> {
+    let lit = &input.mask.pattern;
+    let (pattern, ty) = consts::parse_pattern(lit, input.mask.ty.as_ref())?;
+    let vis = &input.vis;
+    let name = &input.name;
+    let x = ty.ident();
+    let source = lit.value();
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let letters = pattern.letters();
+    let mut getters = Vec::new();
+    let mut items = Vec::new();
+    for letter in &letters {
+        let getter = match syn::parse_str::<Ident>(&format!("field_{}", letter)) {
+            Ok(mut x) => {
+                x.set_span(name.span());
+                x
+            }
+            Err(_) => {
+                return Err(Error::new(
+                    lit.span(),
+                    format!("letter '{}' can't be used in a method name", letter),
+                ));
+            }
+        };
+        let doc = format!("Extracts '{}' of `\"{}\"`.", letter, source);
+        let extract = pattern.extract(*letter, ty, &bits);
+        getters.push(quote! {
+            #[doc = #doc]
+            #[inline]
+            #vis const fn #getter(&self) -> #x {
+                let #bits = self.0;
+                #extract
+            }
+        });
+        items.push(Item {
+            name: Some(letter.to_string()),
+            spec: Some(radix(pattern.width(*letter))),
+            value: quote! { self.#getter() },
+        });
+    }
+
+    let string = name.to_string();
+    let named = Fields::Named(syn::parse_quote!({}));
+    let debug = gen_debug(&string, &named, &items);
+    let long_fmt = long_fmt(&pattern);
+    let values = items.iter().map(|x| &x.value);
+    let check = if pattern.mask() == 0 {
+        None
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        Some(quote! {
+            ::core::debug_assert!(
+                #bits & #bit_mask == #bit_pattern,
+                "fixed bits of the pattern don't match"
+            );
+        })
+    };
+    let doc = format!("Raw value of `\"{}\"`.", source);
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        #vis struct #name(pub #x);
+
+        impl #name {
+            /// Wraps a raw value, checking the '0' and '1' bits if
+            /// `debug_assertions` are enabled.
+            #[inline]
+            #vis const fn new(#bits: #x) -> Self {
+                #check
+                Self(#bits)
+            }
+
+            #(#getters)*
+        }
+
+        impl ::core::fmt::Debug for #name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                #debug
+            }
+        }
+
+        impl ::core::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::write!(f, #long_fmt #(, #values)*)
+            }
+        }
+    })
+}
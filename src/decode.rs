@@ -0,0 +1,714 @@
+use crate::debug;
+use crate::pack;
+use crate::pattern::{parse_lits, peek_lits, Pattern, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DeriveInput, Error, Expr, ExprLit, Field, Fields, Ident, Lit, LitStr, Meta,
+    MetaNameValue, Result, Token, Type, Variant,
+};
+
+/// `#[bitmatch(...)]` on a variant.
+struct VariantAttr {
+    kind: Kind,
+    /// Template given by `fmt = "..."`.
+    fmt: Option<LitStr>,
+}
+
+enum Kind {
+    Pattern(LitStr),
+    Default,
+}
+
+fn parse_attr(variant: &Variant) -> Result<VariantAttr> {
+    let attrs: Vec<&Attribute> = variant
+        .attrs
+        .iter()
+        .filter(|x| x.path().is_ident("bitmatch"))
+        .collect();
+    let attr = match attrs.as_slice() {
+        [x] => x,
+        [] => {
+            return Err(Error::new(
+                variant.ident.span(),
+                "missing `#[bitmatch(\"...\")]` or `#[bitmatch(default)]` on variant",
+            ));
+        }
+        [_, x, ..] => {
+            return Err(Error::new(x.span(), "duplicate `#[bitmatch]` on variant"));
+        }
+    };
+
+    attr.parse_args_with(|input: syn::parse::ParseStream| {
+        let kind = if peek_lits(input) {
+            Kind::Pattern(parse_lits(input)?)
+        } else {
+            let ident: Ident = input.parse()?;
+            if ident == "default" {
+                Kind::Default
+            } else {
+                return Err(Error::new(ident.span(), "expected a pattern or `default`"));
+            }
+        };
+
+        let mut fmt = None;
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let ident: Ident = input.parse()?;
+            if ident != "fmt" {
+                return Err(Error::new(ident.span(), "unsupported bitmatch option"));
+            }
+            input.parse::<Token![=]>()?;
+            fmt = Some(input.parse()?);
+        }
+        Ok(VariantAttr { kind, fmt })
+    })
+}
+
+/// Letter of each field of a variant or struct named `name`, in field order.
+///
+/// Tuple fields take the letters in order of appearance, and named fields take
+/// the letter given by `#[bits = "x"]` or the first character of their name.
+pub(crate) fn field_letters<'a>(
+    name: &Ident,
+    fields: &'a Fields,
+    pattern: &Pattern,
+) -> Result<Vec<(char, &'a Field)>> {
+    let letters = pattern.letters();
+    let ret: Vec<(char, &Field)> = match fields {
+        Fields::Named(fields) => {
+            let mut ret = Vec::new();
+            for field in &fields.named {
+                let letter = field_letter(field)?;
+                if !letters.contains(&letter) {
+                    return Err(Error::new(
+                        field.span(),
+                        format!("pattern has no field '{}'", letter),
+                    ));
+                }
+                if ret.iter().any(|(x, _)| *x == letter) {
+                    return Err(Error::new(
+                        field.span(),
+                        format!("duplicate field for letter '{}'", letter),
+                    ));
+                }
+                ret.push((letter, field));
+            }
+            ret
+        }
+        fields => {
+            if fields.len() != letters.len() {
+                return Err(Error::new(
+                    name.span(),
+                    format!(
+                        "expected {} fields for the pattern letters, found {}",
+                        letters.len(),
+                        fields.len()
+                    ),
+                ));
+            }
+            letters.iter().copied().zip(fields).collect()
+        }
+    };
+
+    if let Some(letter) = letters.iter().find(|x| !ret.iter().any(|(y, _)| y == *x)) {
+        return Err(Error::new(
+            name.span(),
+            format!("missing field for letter '{}'", letter),
+        ));
+    }
+    Ok(ret)
+}
+
+/// Letter of a named field.
+fn field_letter(field: &Field) -> Result<char> {
+    for attr in &field.attrs {
+        if attr.path().is_ident("bits") {
+            let lit = match &attr.meta {
+                Meta::NameValue(MetaNameValue {
+                    value:
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(x), ..
+                        }),
+                    ..
+                }) => x,
+                _ => return Err(Error::new(attr.span(), "expected `#[bits = \"x\"]`")),
+            };
+            let value = lit.value();
+            let mut chars = value.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(x), None) => Ok(x),
+                _ => Err(Error::new(lit.span(), "expected a single letter")),
+            };
+        }
+    }
+    let name = field.ident.as_ref().unwrap().to_string();
+    Ok(name.trim_start_matches("r#").chars().next().unwrap())
+}
+
+/// Primitive field type of known width.
+pub(crate) enum Prim {
+    Int { signed: bool, bits: u32 },
+    Bool,
+}
+
+pub(crate) fn prim(ty: &Type) -> Option<Prim> {
+    let ident = match ty {
+        Type::Path(x) if x.qself.is_none() => x.path.get_ident()?.to_string(),
+        _ => return None,
+    };
+    let (signed, bits) = match ident.as_str() {
+        "bool" => return Some(Prim::Bool),
+        "u8" => (false, 8),
+        "u16" => (false, 16),
+        "u32" => (false, 32),
+        "u64" => (false, 64),
+        "u128" => (false, 128),
+        "i8" => (true, 8),
+        "i16" => (true, 16),
+        "i32" => (true, 32),
+        "i64" => (true, 64),
+        "i128" => (true, 128),
+        _ => return None,
+    };
+    Some(Prim::Int { signed, bits })
+}
+
+/// Converts the extracted bits of a `width` bit field to the type of `field`.
+///
+/// Signed types are sign extended from the field width, and other types are
+/// converted with `as`.
+pub(crate) fn field_value(extract: TokenStream, width: u32, field: &Field) -> Result<TokenStream> {
+    let ty = &field.ty;
+    match prim(ty) {
+        Some(Prim::Bool) if width == 1 => Ok(quote! { (#extract != 0) }),
+        Some(Prim::Int { bits, .. }) if bits < width => Err(Error::new(
+            ty.span(),
+            format!("{}-bit field doesn't fit in `{}`", width, quote! { #ty }),
+        )),
+        Some(Prim::Bool) => Err(Error::new(
+            ty.span(),
+            format!("{}-bit field doesn't fit in `bool`", width),
+        )),
+        Some(Prim::Int { signed: true, .. }) => {
+            let shift = 128 - width;
+            Ok(quote! { ((((#extract as i128) << #shift) >> #shift) as #ty) })
+        }
+        _ => Ok(quote! { (#extract as #ty) }),
+    }
+}
+
+/// `#[bitmatch(...)]` on the enum.
+#[derive(Default)]
+struct Options {
+    checked: bool,
+    debug: bool,
+    defmt: bool,
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<Options> {
+    let mut options = Options::default();
+    for attr in attrs {
+        if attr.path().is_ident("bitmatch") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("checked") {
+                    options.checked = true;
+                } else if meta.path.is_ident("debug") {
+                    options.debug = true;
+                } else if meta.path.is_ident("defmt") {
+                    if !cfg!(feature = "defmt") {
+                        return Err(
+                            meta.error("`defmt` requires the \"defmt\" feature of bitpattern")
+                        );
+                    }
+                    options.defmt = true;
+                } else {
+                    return Err(meta.error("unsupported bitmatch option"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(options)
+}
+
+/// Arm of `encode` packing the fields of a variant.
+///
+/// Values are truncated to their field width, or with `checked`, `None` is
+/// returned if any value doesn't fit. With `widen`, the value is converted to
+/// that type.
+fn gen_encode(
+    variant: &Variant,
+    pattern: &Pattern,
+    ty: Ty,
+    fields: &[(char, &Field)],
+    checked: bool,
+    widen: Option<Ty>,
+) -> TokenStream {
+    let (pat, binds) = gen_pat(variant, fields);
+
+    // `pack::gen_expr` takes the values in order of the letters.
+    let mut lets = Vec::new();
+    let mut values = Vec::new();
+    let mut checks = Vec::new();
+    for (i, letter) in pattern.letters().into_iter().enumerate() {
+        let index = fields.iter().position(|(x, _)| *x == letter).unwrap();
+        let bind = &binds[index];
+        let value = Ident::new(&format!("value{}", i), Span::mixed_site());
+        lets.push(quote! { let #value = *#bind; });
+        checks.extend(gen_fit(&value, pattern.width(letter), fields[index].1));
+        values.push(value);
+    }
+    let mut packed = pack::gen_expr(pattern, ty, &values, None);
+    if let Some(x) = widen {
+        let x = x.ident();
+        packed = quote! { (#packed as #x) };
+    }
+
+    if checked {
+        quote! {
+            #pat => {
+                #(#lets)*
+                if true #(&& #checks)* {
+                    ::core::option::Option::Some(#packed)
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+        }
+    } else {
+        quote! {
+            #pat => {
+                #(#lets)*
+                #packed
+            }
+        }
+    }
+}
+
+/// Pattern of a variant binding its fields in order of `fields`.
+fn gen_pat(variant: &Variant, fields: &[(char, &Field)]) -> (TokenStream, Vec<Ident>) {
+    let name = &variant.ident;
+    let binds: Vec<Ident> = (0..fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let pat = match &variant.fields {
+        Fields::Unit => quote! { Self::#name },
+        Fields::Unnamed(_) => quote! { Self::#name(#(#binds),*) },
+        Fields::Named(_) => {
+            let names = fields.iter().map(|(_, x)| &x.ident);
+            quote! { Self::#name { #(#names: #binds),* } }
+        }
+    };
+    (pat, binds)
+}
+
+/// Condition that `value` fits in a `width` bit field, if it may not.
+pub(crate) fn gen_fit(value: &Ident, width: u32, field: &Field) -> Option<TokenStream> {
+    match prim(&field.ty) {
+        Some(Prim::Bool) => None,
+        Some(Prim::Int { bits, .. }) if bits <= width => None,
+        // Values in range have only sign bits above the field.
+        Some(Prim::Int { signed: true, .. }) => {
+            let shift = width - 1;
+            Some(quote! { ::core::matches!((#value as i128) >> #shift, 0 | -1) })
+        }
+        _ if width >= 128 => None,
+        _ => Some(quote! { ((#value as u128) >> #width == 0) }),
+    }
+}
+
+pub(crate) fn gen_code(input: DeriveInput) -> Result<TokenStream> {
+    let data = match &input.data {
+        Data::Enum(x) => x,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "BitDecode can only be derived for enums",
+            ));
+        }
+    };
+
+    let options = parse_options(&input.attrs)?;
+    let checked = options.checked;
+    let word = Ident::new("word", Span::call_site());
+    let mut patterns = Vec::new();
+    let mut default = None;
+    for variant in &data.variants {
+        let attr = parse_attr(variant)?;
+        match attr.kind {
+            Kind::Pattern(lit) => {
+                let (pattern, ty) = Pattern::parse_lit(&lit)?;
+                patterns.push((variant, pattern, ty, attr.fmt));
+            }
+            Kind::Default if attr.fmt.is_some() => {
+                return Err(Error::new(
+                    attr.fmt.unwrap().span(),
+                    "`fmt` can't be used on the default variant",
+                ));
+            }
+            Kind::Default if default.is_some() => {
+                return Err(Error::new(
+                    variant.span(),
+                    "only one variant can be `#[bitmatch(default)]`",
+                ));
+            }
+            Kind::Default => default = Some(variant),
+        }
+    }
+
+    // With patterns of several types, each pattern matches the low bits of
+    // the widest type, and the number of bits consumed is returned as well.
+    let ty = match patterns.iter().map(|(_, _, ty, _)| *ty).max() {
+        Some(x) => x,
+        None => {
+            return Err(Error::new(
+                input.ident.span(),
+                "at least one variant needs a `#[bitmatch(\"...\")]` pattern",
+            ));
+        }
+    };
+    let mixed = patterns.iter().any(|(_, _, x, _)| *x != ty);
+    let min_len = patterns.iter().map(|(_, x, _, _)| x.len()).min().unwrap();
+
+    let mut arms = Vec::new();
+    let mut encode_arms = Vec::new();
+    let mut display_arms = Vec::new();
+    let mut debug_arms = Vec::new();
+    let mut defmt_arms = Vec::new();
+    for (variant, pattern, pattern_ty, fmt) in &patterns {
+        let name = &variant.ident;
+        let fields = field_letters(name, &variant.fields, pattern)?;
+        let values = fields
+            .iter()
+            .map(|(letter, field)| {
+                let extract = pattern.extract(*letter, *pattern_ty, &word);
+                field_value(extract, pattern.width(*letter), field)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let make = match &variant.fields {
+            Fields::Unit => quote! { Self::#name },
+            Fields::Unnamed(_) => quote! { Self::#name(#(#values),*) },
+            Fields::Named(_) => {
+                let names = fields.iter().map(|(_, x)| &x.ident);
+                quote! { Self::#name { #(#names: #values),* } }
+            }
+        };
+
+        let bit_mask = pattern_ty.lit(pattern.mask());
+        let bit_pattern = pattern_ty.lit(pattern.value());
+        if mixed {
+            let x = pattern_ty.ident();
+            let len = pattern.len();
+            arms.push(quote! {
+                {
+                    let #word = #word as #x;
+                    if #word & #bit_mask == #bit_pattern {
+                        return ::core::option::Option::Some((#make, #len));
+                    }
+                }
+            });
+        } else {
+            arms.push(quote! {
+                if #word & #bit_mask == #bit_pattern {
+                    return ::core::option::Option::Some(#make);
+                }
+            });
+        }
+        let widen = if mixed { Some(ty) } else { None };
+        encode_arms.push(gen_encode(
+            variant,
+            pattern,
+            *pattern_ty,
+            &fields,
+            checked,
+            widen,
+        ));
+        if let Some(fmt) = fmt {
+            display_arms.push(gen_display(variant, &fields, fmt)?);
+        }
+
+        let (pat, binds) = gen_pat(variant, &fields);
+        let items: Vec<_> = fields
+            .iter()
+            .zip(&binds)
+            .map(|((letter, field), bind)| {
+                debug::Item::new(field, pattern.width(*letter), quote! { *#bind })
+            })
+            .collect();
+        let string = name.to_string();
+        let debug = debug::gen_debug(&string, &variant.fields, &items);
+        let defmt = debug::gen_defmt(&string, &variant.fields, &items);
+        debug_arms.push(quote! { #pat => #debug, });
+        defmt_arms.push(quote! { #pat => #defmt, });
+    }
+    if let Some(variant) = default {
+        // A newtype default variant has the width of the value.
+        let name = &variant.ident;
+        let string = name.to_string();
+        let bind = Ident::new("raw", Span::mixed_site());
+        let (pat, items) = match &variant.fields {
+            Fields::Unnamed(x) if x.unnamed.len() == 1 => (
+                quote! { Self::#name(#bind) },
+                vec![debug::Item::new(
+                    &x.unnamed[0],
+                    ty.bits() as u32,
+                    quote! { *#bind },
+                )],
+            ),
+            _ => (quote! { Self::#name }, Vec::new()),
+        };
+        let debug = debug::gen_debug(&string, &variant.fields, &items);
+        let defmt = debug::gen_defmt(&string, &variant.fields, &items);
+        debug_arms.push(quote! { #pat => #debug, });
+        defmt_arms.push(quote! { #pat => #defmt, });
+    }
+
+    let (fallback, default_encode) = match default {
+        Some(x) => gen_default(x, &word, checked)?,
+        None => (quote! { ::core::option::Option::None }, quote! {}),
+    };
+    let fallback = if mixed {
+        quote! { #fallback.map(|x| (x, #min_len)) }
+    } else {
+        fallback
+    };
+
+    let x = ty.ident();
+    let decode_ty = if mixed {
+        quote! { ::core::option::Option<(Self, usize)> }
+    } else {
+        quote! { ::core::option::Option<Self> }
+    };
+    let encode_ty = if checked {
+        quote! { ::core::option::Option<#x> }
+    } else {
+        quote! { #x }
+    };
+    let decode_doc = if mixed {
+        "Decodes the low bits of `word` by the pattern of each variant in order, \
+         returning the number of bits of the pattern."
+    } else {
+        "Decodes `word` by the pattern of each variant in order."
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let decode_bytes = if mixed && patterns.iter().all(|(_, x, _, _)| x.len() % 8 == 0) {
+        let bytes = ty.bits() / 8;
+        Some(quote! {
+            /// Decodes the start of `bytes` in little endian, returning the
+            /// number of bytes consumed.
+            pub fn decode_le_bytes(bytes: &[u8]) -> ::core::option::Option<(Self, usize)> {
+                let mut buf = [0u8; #bytes];
+                let len = ::core::cmp::min(bytes.len(), #bytes);
+                buf[..len].copy_from_slice(&bytes[..len]);
+                match Self::decode(#x::from_le_bytes(buf)) {
+                    ::core::option::Option::Some((x, bits)) if bits / 8 <= len => {
+                        ::core::option::Option::Some((x, bits / 8))
+                    }
+                    _ => ::core::option::Option::None,
+                }
+            }
+        })
+    } else {
+        None
+    };
+    // Variants without a template are displayed by name.
+    let variant_names = data.variants.iter().map(|x| {
+        let name = &x.ident;
+        let string = name.to_string();
+        quote! { Self::#name { .. } => #string, }
+    });
+    let display = if display_arms.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            impl #impl_generics ::core::fmt::Display for #name #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    match self {
+                        #(#display_arms)*
+                        #[allow(unreachable_patterns)]
+                        _ => f.write_str(match self {
+                            #(#variant_names)*
+                        }),
+                    }
+                }
+            }
+        })
+    };
+
+    let debug = if options.debug {
+        Some(quote! {
+            impl #impl_generics ::core::fmt::Debug for #name #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    match self {
+                        #(#debug_arms)*
+                    }
+                }
+            }
+        })
+    } else {
+        None
+    };
+    let defmt = if options.defmt {
+        Some(quote! {
+            impl #impl_generics ::defmt::Format for #name #ty_generics #where_clause {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match self {
+                        #(#defmt_arms)*
+                    }
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    Ok(quote! {
+        #display
+        #debug
+        #defmt
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc = #decode_doc]
+            pub fn decode(#word: #x) -> #decode_ty {
+                #(#arms)*
+                #fallback
+            }
+
+            #decode_bytes
+
+            /// Encodes by the pattern of the variant, with '?' bits set to 0.
+            pub fn encode(&self) -> #encode_ty {
+                match self {
+                    #(#encode_arms)*
+                    #default_encode
+                }
+            }
+        }
+    })
+}
+
+/// Arm of `Display::fmt` writing a variant by its template.
+///
+/// Placeholders name a field, or the letter of a field.
+fn gen_display(variant: &Variant, fields: &[(char, &Field)], fmt: &LitStr) -> Result<TokenStream> {
+    let name = &variant.ident;
+    let (pat, binds) = gen_pat(variant, fields);
+
+    let mut args = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for placeholder in placeholders(&fmt.value()) {
+        if names.contains(&placeholder) {
+            continue;
+        }
+        let index = fields
+            .iter()
+            .position(|(_, x)| x.ident.as_ref().is_some_and(|x| *x == placeholder))
+            .or_else(|| {
+                fields
+                    .iter()
+                    .position(|(x, _)| placeholder == x.to_string())
+            });
+        let index = match index {
+            Some(x) => x,
+            None if placeholder.is_empty() => {
+                return Err(Error::new(fmt.span(), "placeholders must name a field"));
+            }
+            None => {
+                return Err(Error::new(
+                    fmt.span(),
+                    format!("unknown placeholder '{}' in `{}`", placeholder, name),
+                ));
+            }
+        };
+        let arg = Ident::new(&placeholder, fmt.span());
+        let bind = &binds[index];
+        args.push(quote! { #arg = #bind });
+        names.push(placeholder);
+    }
+
+    if fields.is_empty() {
+        Ok(quote! { #pat => ::core::write!(f, #fmt), })
+    } else {
+        Ok(quote! {
+            #[allow(unused_variables)]
+            #pat => ::core::write!(f, #fmt #(, #args)*),
+        })
+    }
+}
+
+/// Names of the `{name:spec}` placeholders of a format string.
+fn placeholders(fmt: &str) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let inside: String = chars.by_ref().take_while(|x| *x != '}').collect();
+                let name = inside.split(':').next().unwrap().trim();
+                ret.push(name.to_string());
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Decoded value and `encode` arm of the default variant.
+fn gen_default(
+    variant: &Variant,
+    word: &Ident,
+    checked: bool,
+) -> Result<(TokenStream, TokenStream)> {
+    let name = &variant.ident;
+    let (make, encode) = match &variant.fields {
+        Fields::Unit if checked => (
+            quote! { Self::#name },
+            quote! { Self::#name => ::core::option::Option::None, },
+        ),
+        Fields::Unit => {
+            // `decode` returns it, so `encode` must take it without panicking.
+            return Err(Error::new(
+                variant.span(),
+                "a unit default variant can't be encoded, so it needs `#[bitmatch(checked)]` on the enum, or a field capturing the value",
+            ));
+        }
+        Fields::Unnamed(x) if x.unnamed.len() == 1 => {
+            // Integers are converted with `as`, so they can be narrower than
+            // the value.
+            let field_ty = &x.unnamed[0].ty;
+            let (from, into) = match prim(field_ty) {
+                Some(Prim::Int { .. }) => (quote! { #word as #field_ty }, quote! { *raw as _ }),
+                _ => (
+                    quote! { ::core::convert::From::from(#word) },
+                    quote! { ::core::convert::Into::into(*raw) },
+                ),
+            };
+            let into = if checked {
+                quote! { ::core::option::Option::Some(#into) }
+            } else {
+                into
+            };
+            (
+                quote! { Self::#name(#from) },
+                quote! { Self::#name(raw) => #into, },
+            )
+        }
+        _ => {
+            return Err(Error::new(
+                variant.span(),
+                "the default variant must be a unit or newtype variant",
+            ));
+        }
+    };
+    Ok((quote! { ::core::option::Option::Some(#make) }, encode))
+}
@@ -0,0 +1,282 @@
+use crate::debug;
+use crate::decode::{field_letters, field_value, gen_fit};
+use crate::pack;
+use crate::pattern::{parse_lits, Pattern};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Error, Fields, Ident, LitStr, Result, Token};
+
+/// `#[bitmatch("...")]` on the struct.
+struct StructAttr {
+    pattern: LitStr,
+    /// Given by `serde`.
+    serde: Option<Ident>,
+    debug: bool,
+    defmt: bool,
+}
+
+fn parse_attr(input: &DeriveInput) -> Result<StructAttr> {
+    let attrs: Vec<&Attribute> = input
+        .attrs
+        .iter()
+        .filter(|x| x.path().is_ident("bitmatch"))
+        .collect();
+    let attr = match attrs.as_slice() {
+        [x] => x,
+        [] => {
+            return Err(Error::new(
+                input.ident.span(),
+                "missing `#[bitmatch(\"...\")]` on struct",
+            ));
+        }
+        [_, x, ..] => {
+            return Err(Error::new(x.span(), "duplicate `#[bitmatch]` on struct"));
+        }
+    };
+
+    attr.parse_args_with(|input: syn::parse::ParseStream| {
+        let pattern = parse_lits(input)?;
+        let mut attr = StructAttr {
+            pattern,
+            serde: None,
+            debug: false,
+            defmt: false,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "debug" => attr.debug = true,
+                "serde" if cfg!(feature = "serde") => attr.serde = Some(ident),
+                "defmt" if cfg!(feature = "defmt") => attr.defmt = true,
+                "serde" | "defmt" => {
+                    return Err(Error::new(
+                        ident.span(),
+                        format!("`{0}` requires the \"{0}\" feature of bitpattern", ident),
+                    ));
+                }
+                _ => return Err(Error::new(ident.span(), "unsupported bitmatch option")),
+            }
+        }
+        Ok(attr)
+    })
+}
+
+pub(crate) fn gen_code(input: DeriveInput) -> Result<TokenStream> {
+    let data = match &input.data {
+        Data::Struct(x) => x,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "BitFields can only be derived for structs",
+            ));
+        }
+    };
+
+    let attr = parse_attr(&input)?;
+    let lit = &attr.pattern;
+    let (pattern, ty) = Pattern::parse_lit(lit)?;
+    let name = &input.ident;
+    let fields = field_letters(name, &data.fields, &pattern)?;
+
+    let bits = Ident::new("bits", Span::call_site());
+    let values = fields
+        .iter()
+        .map(|(letter, field)| {
+            let extract = pattern.extract(*letter, ty, &bits);
+            field_value(extract, pattern.width(*letter), field)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let make = gen_make(&data.fields, &fields, &values);
+
+    let members: Vec<TokenStream> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, (_, field))| match &field.ident {
+            Some(x) => quote! { #x },
+            None => {
+                let index = syn::Index::from(i);
+                quote! { #index }
+            }
+        })
+        .collect();
+
+    // `pack::gen_expr` takes the values in order of the letters.
+    let mut lets = Vec::new();
+    let mut names = Vec::new();
+    for (i, letter) in pattern.letters().into_iter().enumerate() {
+        let index = fields.iter().position(|(x, _)| *x == letter).unwrap();
+        let member = &members[index];
+        let value = Ident::new(&format!("value{}", i), Span::mixed_site());
+        lets.push(quote! { let #value = self.#member; });
+        names.push(value);
+    }
+    let packed = pack::gen_expr(&pattern, ty, &names, None);
+
+    let x = ty.ident();
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    let serde = match &attr.serde {
+        Some(x) => Some(gen_serde(&input, x, &fields, &pattern)?),
+        None => None,
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let items: Vec<_> = fields
+        .iter()
+        .zip(&members)
+        .map(|((letter, field), member)| {
+            debug::Item::new(field, pattern.width(*letter), quote! { self.#member })
+        })
+        .collect();
+    let string = name.to_string();
+    let debug = if attr.debug {
+        let body = debug::gen_debug(&string, &data.fields, &items);
+        Some(quote! {
+            impl #impl_generics ::core::fmt::Debug for #name #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    #body
+                }
+            }
+        })
+    } else {
+        None
+    };
+    let defmt = if attr.defmt {
+        let body = debug::gen_defmt(&string, &data.fields, &items);
+        Some(quote! {
+            impl #impl_generics ::defmt::Format for #name #ty_generics #where_clause {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    #body
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    Ok(quote! {
+        #serde
+        #debug
+        #defmt
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Unpacks the fields from `bits`, or returns `None` if the fixed
+            /// bits of the pattern don't match.
+            pub fn from_bits(#bits: #x) -> ::core::option::Option<Self> {
+                if #bits & #bit_mask == #bit_pattern {
+                    ::core::option::Option::Some(#make)
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+
+            /// Packs the fields into the pattern, with '?' bits set to 0 and
+            /// values truncated to their field width.
+            pub fn to_bits(&self) -> #x {
+                #(#lets)*
+                #packed
+            }
+        }
+    })
+}
+
+/// Constructor of the struct from the values of `fields`.
+fn gen_make(shape: &Fields, fields: &[(char, &syn::Field)], values: &[TokenStream]) -> TokenStream {
+    match shape {
+        Fields::Unit => quote! { Self },
+        Fields::Unnamed(_) => quote! { Self(#(#values),*) },
+        Fields::Named(_) => {
+            let names = fields.iter().map(|(_, x)| &x.ident);
+            quote! { Self { #(#names: #values),* } }
+        }
+    }
+}
+
+/// `Serialize` and `Deserialize` by the named fields.
+///
+/// Deserialization goes through a private struct deriving `Deserialize`, and
+/// fails if any value doesn't fit its field width.
+fn gen_serde(
+    input: &DeriveInput,
+    option: &Ident,
+    fields: &[(char, &syn::Field)],
+    pattern: &Pattern,
+) -> Result<TokenStream> {
+    if fields.iter().any(|(_, x)| x.ident.is_none()) {
+        return Err(Error::new(
+            option.span(),
+            "`serde` can only be used on structs with named fields",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "`serde` can't be used on generic structs",
+        ));
+    }
+
+    let name = &input.ident;
+    let string = name.to_string();
+    let len = fields.len();
+    let names: Vec<_> = fields
+        .iter()
+        .map(|(_, x)| x.ident.as_ref().unwrap())
+        .collect();
+    let strings = names.iter().map(|x| x.to_string());
+    let types = fields.iter().map(|(_, x)| &x.ty);
+
+    let value = Ident::new("value", Span::mixed_site());
+    let checks = fields.iter().filter_map(|(letter, field)| {
+        let width = pattern.width(*letter);
+        let check = gen_fit(&value, width, field)?;
+        let field = field.ident.as_ref().unwrap();
+        let message = format!("`{}` doesn't fit in {} bits", field, width);
+        Some(quote! {
+            let #value = fields.#field;
+            if !#check {
+                return ::core::result::Result::Err(
+                    <D::Error as ::serde::de::Error>::custom(#message),
+                );
+            }
+        })
+    });
+
+    Ok(quote! {
+        impl ::serde::Serialize for #name {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                use ::serde::ser::SerializeStruct;
+                let mut state = serializer.serialize_struct(#string, #len)?;
+                #( state.serialize_field(#strings, &self.#names)?; )*
+                state.end()
+            }
+        }
+
+        const _: () = {
+            #[derive(::serde::Deserialize)]
+            #[serde(rename = #string)]
+            struct __BitFieldsRepr {
+                #( #names: #types, )*
+            }
+
+            impl<'de> ::serde::Deserialize<'de> for #name {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                {
+                    let fields = <__BitFieldsRepr as ::serde::Deserialize>::deserialize(deserializer)?;
+                    #(#checks)*
+                    ::core::result::Result::Ok(Self {
+                        #( #names: fields.#names, )*
+                    })
+                }
+            }
+        };
+    })
+}
@@ -0,0 +1,90 @@
+use crate::pattern::Pattern;
+
+/// A pattern fragment given by `define(name = "...")`.
+#[derive(Clone, Debug)]
+pub(crate) struct Fragment {
+    pub(crate) name: String,
+    pub(crate) pattern: String,
+}
+
+/// Replaces each `{name}` or `{name: a=01, b=1}` in `pattern` by the fragment
+/// `name`, with the given letters substituted.
+pub(crate) fn expand(pattern: &str, fragments: &[Fragment]) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        ret.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(x) => start + x,
+            None => return Err("unterminated pattern fragment: missing '}'".to_string()),
+        };
+        ret.push_str(&splice(&rest[start + 1..end], fragments)?);
+        rest = &rest[end + 1..];
+    }
+    ret.push_str(rest);
+    Ok(ret)
+}
+
+/// Expands the inside of `{...}`.
+fn splice(reference: &str, fragments: &[Fragment]) -> Result<String, String> {
+    let (name, substitutions) = match reference.find(':') {
+        Some(x) => (&reference[..x], Some(&reference[x + 1..])),
+        None => (reference, None),
+    };
+    let name = name.trim();
+    let fragment = match fragments.iter().find(|x| x.name == name) {
+        Some(x) => x,
+        None => return Err(format!("undefined pattern fragment '{}'", name)),
+    };
+
+    let mut ret = fragment.pattern.clone();
+    let parsed = Pattern::parse(&fragment.pattern);
+    for substitution in substitutions.into_iter().flat_map(|x| x.split(',')) {
+        let substitution = substitution.trim();
+        if substitution.is_empty() {
+            continue;
+        }
+        let (letter, bits) = match substitution.split_once('=') {
+            Some((letter, bits)) => (letter.trim(), bits.trim()),
+            None => {
+                return Err(format!(
+                    "expected `letter=bits` in fragment '{}', found '{}'",
+                    name, substitution
+                ));
+            }
+        };
+        let mut chars = letter.chars();
+        let letter = match (chars.next(), chars.next()) {
+            (Some(x), None) if parsed.letters().contains(&x) => x,
+            _ => {
+                return Err(format!(
+                    "pattern fragment '{}' has no field '{}'",
+                    name, letter
+                ));
+            }
+        };
+
+        let bits: Vec<char> = bits
+            .chars()
+            .filter(|c| *c != '_' && !c.is_whitespace())
+            .collect();
+        let width = parsed.width(letter) as usize;
+        if bits.len() != width {
+            return Err(format!(
+                "field '{}' of pattern fragment '{}' is {} bits wide, found {} bits",
+                letter,
+                name,
+                width,
+                bits.len()
+            ));
+        }
+
+        // Earlier runs of a split field take the more significant bits.
+        let mut bits = bits.into_iter();
+        ret = ret
+            .chars()
+            .map(|c| if c == letter { bits.next().unwrap() } else { c })
+            .collect();
+    }
+    Ok(ret)
+}
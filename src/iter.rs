@@ -0,0 +1,242 @@
+use crate::pattern::{gen_fits, gen_word, ones, parse_lits, LowInput, Pattern, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Ident, LitStr, Result, Token};
+
+/// Most free bits `bitgen!` accepts without `allow_large`.
+const MAX_FREE_BITS: u32 = 24;
+
+/// Input of `bitgen!`: `"..."` with an optional `, allow_large`.
+pub(crate) struct GenInput {
+    pattern: LitStr,
+    allow_large: bool,
+}
+
+impl Parse for GenInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        let mut allow_large = false;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            if ident != "allow_large" {
+                return Err(Error::new(ident.span(), "expected `allow_large`"));
+            }
+            allow_large = true;
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(GenInput {
+            pattern,
+            allow_large,
+        })
+    }
+}
+
+/// Iterator over all values of the pattern.
+///
+/// The free bits are the letters and '?' bits. Each value is the next subset
+/// of them: setting all other bits before adding one carries through them, so
+/// the values are in ascending order without any table.
+pub(crate) fn gen_code(input: GenInput) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let free = ones(pattern.len() as u32) & !pattern.mask();
+    let free_bits = free.count_ones();
+    if free_bits > MAX_FREE_BITS && !input.allow_large {
+        return Err(Error::new(
+            input.pattern.span(),
+            format!(
+                "pattern has {} free bits, more than {}; add `allow_large` to generate all values",
+                free_bits, MAX_FREE_BITS
+            ),
+        ));
+    }
+
+    let x = ty.ident();
+    // Without free bits, the only value is never advanced.
+    let advance = if free == 0 {
+        None
+    } else {
+        let free = ty.lit(free);
+        Some(quote! { self.next = (value | !#free).wrapping_add(1) & #free; })
+    };
+    let bit_pattern = ty.lit(pattern.value());
+    let last = Ty::U128.lit(ones(free_bits));
+    // The 2^free_bits values must fit in `usize` for `len`, which depends on
+    // the target.
+    let exact = match free_bits {
+        0..=15 => Some(quote! {}),
+        16..=63 => {
+            let widths = ["32", "64"]
+                .iter()
+                .filter(|x| x.parse::<u32>().unwrap() > free_bits);
+            Some(quote! { #[cfg(any(#(target_pointer_width = #widths),*))] })
+        }
+        _ => None,
+    }
+    .map(|cfg| {
+        quote! {
+            #cfg
+            impl ::core::iter::ExactSizeIterator for BitGen {}
+        }
+    });
+    Ok(quote! {
+        {
+            #[derive(Clone, Debug)]
+            struct BitGen {
+                next: #x,
+                /// Values left after `next`, or `None` when done.
+                rest: ::core::option::Option<u128>,
+            }
+
+            impl ::core::iter::Iterator for BitGen {
+                type Item = #x;
+
+                #[inline]
+                fn next(&mut self) -> ::core::option::Option<#x> {
+                    let rest = self.rest?;
+                    let value = self.next;
+                    self.rest = rest.checked_sub(1);
+                    #advance
+                    ::core::option::Option::Some(value | #bit_pattern)
+                }
+
+                #[inline]
+                fn size_hint(&self) -> (usize, ::core::option::Option<usize>) {
+                    let len = match self.rest {
+                        ::core::option::Option::Some(x) => {
+                            <usize as ::core::convert::TryFrom<u128>>::try_from(x)
+                                .ok()
+                                .and_then(|x| x.checked_add(1))
+                        }
+                        ::core::option::Option::None => ::core::option::Option::Some(0),
+                    };
+                    match len {
+                        ::core::option::Option::Some(x) => (x, ::core::option::Option::Some(x)),
+                        ::core::option::Option::None => (usize::MAX, ::core::option::Option::None),
+                    }
+                }
+            }
+
+            #exact
+
+            impl ::core::iter::FusedIterator for BitGen {}
+
+            BitGen {
+                next: 0,
+                rest: ::core::option::Option::Some(#last),
+            }
+        }
+    })
+}
+
+/// Iterator adaptor yielding the fields of the words matching the pattern,
+/// with their index if `indexed`.
+///
+/// The words can be integers or references to them, which a local trait
+/// converts to the pattern type like `bitpattern!`: a word with bits set
+/// above the pattern doesn't match unless `low` is given.
+pub(crate) fn gen_filter(input: LowInput, indexed: bool) -> Result<TokenStream> {
+    let LowInput { low, input } = input;
+    let (pattern, ty) = input.parse_pattern()?;
+    let x = ty.ident();
+    let bits = Ident::new("bits", Span::mixed_site());
+    let index = Ident::new("index", Span::mixed_site());
+    let item = Ident::new("item", Span::mixed_site());
+    let fields = crate::gen_fields(&pattern, ty, &bits);
+    let (arg, found) = if indexed {
+        (
+            quote! { (#index, #item) },
+            quote! { (#index, #(#fields),*) },
+        )
+    } else {
+        (quote! { #item }, quote! { (#(#fields),*) })
+    };
+    let value = &input.value;
+    let iter = quote! {
+        ::core::iter::IntoIterator::into_iter(#value)
+    };
+    let iter = if indexed {
+        quote! { ::core::iter::Iterator::enumerate(#iter) }
+    } else {
+        iter
+    };
+    // Without '0' and '1' bits every word matches, unless `low` is missing
+    // and it has bits set above the pattern.
+    let adaptor = if pattern.mask() == 0 && low {
+        quote! {
+            ::core::iter::Iterator::map(#iter, |#arg| {
+                let #bits = Word::word(#item).unwrap();
+                #found
+            })
+        }
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        quote! {
+            ::core::iter::Iterator::filter_map(#iter, |#arg| {
+                let #bits = Word::word(#item)?;
+                if #bits & #bit_mask == #bit_pattern {
+                    ::core::option::Option::Some(#found)
+                } else {
+                    ::core::option::Option::None
+                }
+            })
+        }
+    };
+
+    let words = [
+        quote!(u8),
+        quote!(u16),
+        quote!(u32),
+        quote!(u64),
+        quote!(u128),
+        quote!(usize),
+        quote!(i8),
+        quote!(i16),
+        quote!(i32),
+        quote!(i64),
+        quote!(i128),
+        quote!(isize),
+    ];
+    let value = Ident::new("value", Span::mixed_site());
+    let word = gen_word(&value, ty);
+    let word = if low {
+        quote! { ::core::option::Option::Some(#word) }
+    } else {
+        let fits = gen_fits(&value, pattern.len() as u32);
+        quote! {
+            if #fits {
+                ::core::option::Option::Some(#word)
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+    Ok(quote! {
+        {
+            trait Word {
+                fn word(self) -> ::core::option::Option<#x>;
+            }
+
+            #(
+                impl Word for #words {
+                    #[inline]
+                    fn word(self) -> ::core::option::Option<#x> {
+                        let #value = self;
+                        #word
+                    }
+                }
+            )*
+
+            impl<'a, T: Word + Copy> Word for &'a T {
+                #[inline]
+                fn word(self) -> ::core::option::Option<#x> {
+                    Word::word(*self)
+                }
+            }
+
+            #adaptor
+        }
+    })
+}
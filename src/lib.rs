@@ -1,5 +1,31 @@
-use proc_macro2::{TokenStream, TokenTree};
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::Token;
+
+mod arbitrary;
+mod assign;
+mod bitmatch;
+mod bytes;
+mod check;
+mod consts;
+mod cover;
+mod debug;
+mod decode;
+mod fields;
+mod fragment;
+mod iter;
+mod limbs;
+mod newtype;
+mod pack;
+mod parser;
+mod pattern;
+mod range;
+mod scan;
+mod slice;
+mod volatile;
+
+use pattern::{Pattern, Ty};
 
 /// bitwise pattern matching and extracting.
 ///
@@ -16,6 +42,9 @@ use quote::quote;
 /// assert_eq!(bitpattern!("1010_1100", x), Some(()));
 /// assert_eq!(bitpattern!("1010_0100", x), None);
 ///
+/// // Whitespace can be used as separator too.
+/// assert_eq!(bitpattern!("1010 1100", x), Some(()));
+///
 /// // '?' means the bit can be 0 or 1.
 /// assert_eq!(bitpattern!("1?10_1?00", x), Some(()));
 ///
@@ -32,140 +61,3851 @@ use quote::quote;
 ///
 /// // If the extracting fields are adjacent, the different charactors can be used.
 /// assert_eq!(bitpattern!("1aab_bccc", x), Some((1, 1, 4)));
+///
+/// // Runs of an uppercase letter are sign-extended from their top bit, and
+/// // have the signed type of the same width.
+/// assert_eq!(bitpattern!("SSSS SSSS aaaa 0011", 0xff53u16), Some((-1i16, 5u16)));
+/// assert_eq!(bitpattern!("1aSS_S100", x), Some((0u8, -3i8)));
+///
+/// // The value can be any expression, and it's evaluated once.
+/// let regs = [0x12u8, x];
+/// assert_eq!(bitpattern!("1aab_bccc", regs[1]), Some((1, 1, 4)));
+///
+/// // Signed values are matched by their two's complement bits, which are
+/// // zero-extended to a longer pattern.
+/// assert_eq!(bitpattern!("1aaa aaaa", -2i8), Some(0x7e));
+/// assert_eq!(bitpattern!("aaaa aaaa aaaa", -2i8), Some(0xfe));
+///
+/// // References to integers are matched by their referent.
+/// let words = [0x12u8, 0xac, 0x9c];
+/// let found: Vec<_> = words.iter().filter_map(|w| bitpattern!("1aab_bccc", w)).collect();
+/// assert_eq!(found, [(1, 1, 4), (0, 3, 4)]);
+///
+/// // A value doesn't match if it has bits set above the pattern, unless
+/// // `low` is given to match only its low bits.
+/// let insn = 0xffff_ff33u32;
+/// assert_eq!(bitpattern!("0?11 0011", insn), None);
+/// assert_eq!(bitpattern!(low, "0?11 0011", insn), Some(()));
+///
+/// // With a type after the value, the value is compared as that type with
+/// // the pattern zero-extended, and the fields have that type.
+/// let fields: Option<(u32, u32)> = bitpattern!("0110011 aa bb", 0x33du32, u32);
+/// assert_eq!(fields, Some((3, 1)));
+/// assert_eq!(bitpattern!("0110011", 0x1033u32, u32), None);
+///
+/// // With `source`, the value can have other types too, read by local
+/// // traits. Floats are matched by the bits of `to_bits`, so a pattern for
+/// // an `f32` has 32 bits, and one for an `f64` has 64.
+/// let f = -1.5f32;
+/// assert_eq!(
+///     bitpattern!(source, "s eeeeeeee mmmmmmmmmmmmmmmmmmmmmmm", f),
+///     Some((1, 0x7f, 0x40_0000))
+/// );
+///
+/// // `Wrapping` and the `NonZero` types are matched by the integer they hold.
+/// let reg = core::num::NonZeroU8::new(0xac).unwrap();
+/// assert_eq!(bitpattern!(source, "1aab_bccc", reg), Some((1, 1, 4)));
+/// assert_eq!(bitpattern!(source, "1aab_bccc", core::num::Wrapping(x)), Some((1, 1, 4)));
+///
+/// // A `char` is matched by its scalar value.
+/// assert_eq!(bitpattern!(source, "aaaa bbbbbb cccccc", '€'), Some((0x2, 0x02, 0x2c)));
+/// assert_eq!(bitpattern!(source, "0aaa aaaa", 'é'), None);
+///
+/// // A value of another type is read once through its `bits(&self)` method,
+/// // which can be inherent or from a trait in scope, like the register
+/// // readers of many peripheral access crates. It isn't moved.
+/// trait BitSource {
+///     type Raw;
+///     fn bits(&self) -> Self::Raw;
+/// }
+/// struct Insn(u32);
+/// impl BitSource for Insn {
+///     type Raw = u32;
+///     fn bits(&self) -> u32 {
+///         self.0
+///     }
+/// }
+/// let insn = Insn(0x33d);
+/// assert_eq!(bitpattern!(source, "0110011 aa bb", insn, u32), Some((3, 1)));
+/// assert_eq!(bitpattern!(source, "0110011 aa bb", &insn, u32), Some((3, 1)));
+///
+/// // A `Clone` type converting into one unsigned type with `Into`, like a
+/// // C-like enum, is matched as that type, so the pattern should have the
+/// // width of its `repr`.
+/// #[derive(Clone, Copy)]
+/// #[repr(u8)]
+/// enum Frame {
+///     Data = 0x00,
+///     Ack = 0x42,
+/// }
+/// impl From<Frame> for u8 {
+///     fn from(x: Frame) -> u8 {
+///         x as u8
+///     }
+/// }
+/// assert_eq!(bitpattern!(source, "01 aa bbbb", Frame::Ack), Some((0, 2)));
+/// assert_eq!(bitpattern!(source, "01 aa bbbb", Frame::Data), None);
+///
+/// // With `narrow`, each field has the smallest type holding it.
+/// let fields: Option<(u8, u16, u16)> =
+///     bitpattern!(narrow, "aaaa bbbbbbbbbbbb cccccccccccccccc", 0x1234_5678u32);
+/// assert_eq!(fields, Some((1, 0x234, 0x5678)));
+///
+/// // With `flags`, each field of one bit is a `bool`.
+/// let ctrl = 0b1_0_1_101_0u8;
+/// if let Some((enable, irq, mode, lock)) = bitpattern!(flags, "e?i mmm l", ctrl) {
+///     assert!(enable && !lock);
+///     assert_eq!((irq, mode), (true, 5));
+/// }
+///
+/// // With `tuple`, a single field is a tuple of one too, so the shape of
+/// // the result is the same for any number of fields.
+/// assert_eq!(bitpattern!(tuple, "1aaa_a100", x), Some((5,)));
+/// assert_eq!(bitpattern!(tuple, "1aa0_aa00", x), Some((1, 3)));
+/// assert_eq!(bitpattern!(tuple, "1010_1100", x), Some(()));
+///
+/// // With `rest`, the value masked to the '?' bits follows the fields,
+/// // unshifted, so the bits the pattern ignores can be checked or kept.
+/// assert_eq!(bitpattern!(rest, "1aa? ??00", x), Some((1, 0x0c)));
+/// assert_eq!(bitpattern!(rest, "1010_1100", x), Some(0));
+///
+/// // With `bool`, a pattern without letters returns whether the value
+/// // matches, rather than `Some(())` or `None`.
+/// assert!(bitpattern!(bool, "1010_1100", x));
+/// assert!(!bitpattern!(bool, "1010_0000", x));
+///
+/// // The value can be a `usize` too. A macro can't see the type of its
+/// // value, so the fields still have the type picked by the length of the
+/// // pattern, `u32` here, unless `usize` is given after the value.
+/// let addr = 0x0040_3000usize;
+/// let page: Option<u32> = bitpattern!("aaaa aaaa aaaa aaaa aaaa ???? ???? ????", addr);
+/// assert_eq!(page, Some(0x403));
+/// assert_eq!(bitpattern!("aaaa aaaa aaaa aaaa ????", addr), None);
+/// let page: Option<usize> = bitpattern!("aaaa aaaa aaaa aaaa aaaa ???? ???? ????", addr, usize);
+/// assert_eq!(page, Some(0x403));
+///
+/// // Long patterns can be split into adjacent literals, which are joined
+/// // like `concat!`. A trailing comma is allowed.
+/// assert_eq!(bitpattern!("1aab" "_bccc", x,), Some((1, 1, 4)));
+///
+/// // `concat!` of string literals works too, also from `macro_rules!`. The
+/// // value of a const can't be read by the macro.
+/// assert_eq!(bitpattern!(concat!("1aab", "_bccc"), x), Some((1, 1, 4)));
+///
+/// // '@' applies the pattern from a bit of a wider value, ignoring the bits
+/// // above it.
+/// // The offset must be constant, and the pattern must fit in the value.
+/// let y = 0x0022_ac00u32;
+/// assert_eq!(bitpattern!("10aa bbbb cccc", y @ 10), Some((0, 0xa, 0xb)));
+///```
+///
+/// # Named fields
+///
+/// `=> { name: letter, ... }` after the value returns a struct with a member
+/// of each name instead of a tuple, so the order of the letters doesn't
+/// matter. A letter alone is its own name, and each letter must have a
+/// single run in the pattern.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// let insn = bitpattern!("oo dd iiii", 0b1001_0110u8 => { opcode: o, dest: d, imm: i });
+/// let insn = insn.unwrap();
+/// assert_eq!((insn.opcode, insn.dest, insn.imm), (2, 1, 6));
+///
+/// let fields = bitpattern!("1aab_bccc", 0xacu8 => { c, a }).unwrap();
+/// assert_eq!((fields.a, fields.c), (1, 4));
+///```
+///
+/// # Field maps
+///
+/// `letter as Type` after the value converts the field of a letter with
+/// `TryFrom`, and the value doesn't match if the conversion fails. The type
+/// is resolved where the macro is called.
+///
+///```rust
+/// use bitpattern::bitpattern;
+/// use core::convert::TryFrom;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Cond {
+///     Eq = 0,
+///     Ne = 1,
+///     Lt = 3,
+/// }
+///
+/// impl TryFrom<u8> for Cond {
+///     type Error = u8;
+///
+///     fn try_from(x: u8) -> Result<Cond, u8> {
+///         match x {
+///             0 => Ok(Cond::Eq),
+///             1 => Ok(Cond::Ne),
+///             3 => Ok(Cond::Lt),
+///             x => Err(x),
+///         }
+///     }
+/// }
+///
+/// assert_eq!(bitpattern!("10cc aaaa", 0xb7u8, c as Cond), Some((Cond::Lt, 7)));
+/// assert_eq!(bitpattern!("10cc aaaa", 0xa7u8, c as Cond), None);
+///
+/// // Encoded immediates are fixed up with `letter << shift`, `letter + n`,
+/// // or `letter => expr` with the field bound to the letter. Uppercase
+/// // fields are sign-extended first.
+/// assert_eq!(bitpattern!("1010 OOOO OOOO", 0xafeu16, O << 2), Some(-8i16));
+/// assert_eq!(bitpattern!("1010 cccc iiii", 0xa2fu16, c + 1, i => i * 3), Some((3, 45)));
+///
+/// // The result keeps the type of the field, so `+` wraps around and `<<`
+/// // drops the bits shifted out of it.
+/// assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a + 1), Some(0));
+/// assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a << 2), Some(0xfc));
+///```
+///
+/// # Closures
+///
+/// A closure as the last argument is called with the fields named by its
+/// parameters, in any order and not necessarily all of them, and its result
+/// is returned instead of the fields. Each parameter must be a letter with a
+/// single run in the pattern.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// let regs = [10u32, 20, 30, 40];
+/// let sum = bitpattern!("aabb cc??", 0x6cu8, |c, a| regs[a as usize] + u32::from(c));
+/// assert_eq!(sum, Some(23));
+///```
+///
+/// `=> expr` after the value reads like a match arm instead: the expression
+/// is returned with each letter bound to its field, and a letter with
+/// several runs isn't bound. Braces starting with a name, like `{ a }` or
+/// `{ op: o, ... }`, are the struct of named fields above.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Op {
+///     Move { dst: u8, src: u8 },
+///     Jump(u8),
+/// }
+///
+/// let decode = |x: u8| {
+///     bitpattern!("0000 aabb", x => Op::Move { dst: a, src: b })
+///         .or_else(|| bitpattern!("1aaa aaaa", x => Op::Jump(a)))
+/// };
+/// assert_eq!(decode(0x06), Some(Op::Move { dst: 1, src: 2 }));
+/// assert_eq!(decode(0x85), Some(Op::Jump(5)));
+/// assert_eq!(decode(0x45), None);
+///```
+///
+/// # Constant evaluation
+///
+/// An integer value is converted to the type of the comparison with
+/// operators and casts only, so the expansion can be evaluated in a
+/// `const fn` or a constant at any width, with an offset, the options, and
+/// maps other than `letter as Type`. This is kept for any integer type, so
+/// a `const fn` using it doesn't break. References and the local traits of
+/// `source` can't be evaluated in constants. With `const`, the value must
+/// already have the type of the comparison, the one selected by the pattern
+/// length or given after the value, like an integer literal without a
+/// suffix.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// const fn classify(x: u8) -> Option<(u8, u8)> {
+///     bitpattern!("10aa bb??", x)
+/// }
+///
+/// const TABLE: [Option<(u8, u8)>; 2] = [classify(0xb4), classify(0x34)];
+/// assert_eq!(TABLE, [Some((3, 1)), None]);
+///```
+///
+/// # Fallbacks
+///
+/// `else expr` as the last argument returns the fields without an `Option`
+/// when the value matches, and the expression otherwise, which can diverge
+/// with `return`, `break` or `continue`.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// assert_eq!(bitpattern!("10aa bb??", 0xb4u8, else (0, 0)), (3, 1));
+/// assert_eq!(bitpattern!("10aa bb??", 0x34u8, else (0, 0)), (0, 0));
+///
+/// fn opcode(x: u8) -> Result<u8, u8> {
+///     let op = bitpattern!("1ooo ????", x, else return Err(x));
+///     Ok(op)
+/// }
+/// assert_eq!(opcode(0xc5), Ok(4));
+/// assert_eq!(opcode(0x45), Err(0x45));
+///```
+///
+/// # Dropped fields
+///
+/// `~letter` among the options leaves the runs of a letter out of the
+/// fields, so it can keep the letters around it apart without being
+/// returned. Its bits match anything, like '?', but aren't part of `rest`.
+/// Naming it in `=> { ... }`, a map, a closure or `bitmatch_into!` is an
+/// error saying it was dropped.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// assert_eq!(bitpattern!(~b, "aabb bccc", 0xacu8), Some((2, 4)));
+/// assert_eq!(bitpattern!(~b, ~c, "aabb bccc", 0xacu8), Some(2));
+///```
+///
+/// # Runtime patterns
+///
+/// With only a pattern, `bitpattern!("10aa bb??")` is a struct literal of
+/// `BitPattern` with the mask and value of the '0' and '1' bits, and the
+/// shift and width of each run of letters as `BitField`, in the order
+/// `bitpattern!` extracts them. The mask and value have the type selected by
+/// the pattern length, and the shift and width are `u32`. Both structs are
+/// defined by the caller, so patterns of the same type can be stored in a
+/// table and tested at runtime:
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// struct BitPattern<T: 'static> {
+///     mask: T,
+///     value: T,
+///     fields: &'static [BitField],
+/// }
+///
+/// struct BitField {
+///     shift: u32,
+///     width: u32,
+/// }
+///
+/// impl BitPattern<u8> {
+///     fn matches(&self, x: u8) -> bool {
+///         x & self.mask == self.value
+///     }
+///
+///     fn extract(&self, x: u8) -> impl Iterator<Item = u8> + '_ {
+///         self.fields
+///             .iter()
+///             .map(move |f| (x >> f.shift) & ((1 << f.width) - 1) as u8)
+///     }
+/// }
+///
+/// let table = [bitpattern!("10aa bb??"), bitpattern!("11?? aaaa")];
+/// let x = 0b1001_1100;
+/// let found = table.iter().position(|p| p.matches(x)).unwrap();
+/// assert_eq!(found, 0);
+/// assert_eq!(table[0].extract(x).collect::<Vec<_>>(), [0b01, 0b11]);
+///```
+///
+/// # 256-bit values
+///
+/// With the type `(u128, u128)`, a pattern of up to 256 bits matches a value
+/// given as its high and low 128 bits. The fields are `u128`, and can
+/// straddle the limbs.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// // 'a' is bits 131 to 124 of a 136-bit pattern.
+/// let value = (0xa5u128, 0xc3u128 << 120);
+/// let x = bitpattern!(
+///     "1010 aaaaaaaa ???? ???????? ???????? ???????? ???????? ???????? ????????"
+///     "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+///     "????????",
+///     value,
+///     (u128, u128)
+/// );
+/// assert_eq!(x, Some(0x5c));
+///```
+///
+/// # Exact-width fields
+///
+/// With the `arbitrary-int` feature, `exact` before the pattern returns each
+/// field as the `arbitrary-int` type of its width, like `u3` for a 3-bit
+/// field, or as the primitive type for 8, 16, 32, 64 and 128 bits. The fields
+/// are masked, so the values always fit. It can be combined with `low`, the
+/// offset and the type.
+///
+///```rust,ignore
+/// use arbitrary_int::{u3, u4};
+/// use bitpattern::bitpattern;
+///
+/// let fields: Option<(u3, u4)> = bitpattern!(exact, "1 aaa bbbb", 0xacu8);
+/// assert_eq!(fields, Some((u3::new(2), u4::new(0xc))));
+///```
+///
+/// # Byte slices
+///
+/// A byte string pattern matches a `&[u8]`, with byte 0 as the most
+/// significant 8 bits of the pattern. So does any pattern longer than 128
+/// bits without the type `(u128, u128)`. The pattern length must be a
+/// multiple of 8, and a slice shorter than the pattern doesn't match. Fields
+/// can span bytes, and have the type selected by the pattern length like
+/// above, or `u128` beyond 128 bits.
+///
+///```rust
+/// use bitpattern::bitpattern;
+///
+/// let buf = [0x45u8, 0x00, 0x1c, 0x8f];
+/// assert_eq!(
+///     bitpattern!(b"0100 hhhh ssssssss llllllll llllllll", &buf[..]),
+///     Some((5, 0, 0x1c8f))
+/// );
+/// assert_eq!(bitpattern!(b"0100 hhhh ssssssss llllllll llllllll", &buf[..2]), None);
 ///```
 #[proc_macro]
 pub fn bitpattern(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input: TokenStream = input.into();
+    let input = syn::parse_macro_input!(input as BitpatternInput);
+    gen_pattern(input)
+}
 
-    let mut input = input.into_iter();
-    let pattern = input.next().expect("too less arguments");
-    let comma = input.next().expect("too less arguments");
-    let expr: TokenStream = input.collect();
+fn gen_pattern(input: BitpatternInput) -> proc_macro::TokenStream {
+    let pattern = match parse_pattern(&input) {
+        Ok(x) => x,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if let Some(value @ Value { result: true, .. }) = &input.value {
+        if let Some(error) = check_result(&input, &pattern, value) {
+            return error.to_compile_error().into();
+        }
+    }
+    if let Some(value @ Value { rest: true, .. }) = &input.value {
+        if let Some(error) = check_rest(&input, &pattern, value) {
+            return error.to_compile_error().into();
+        }
+    }
+    if let Some(value @ Value { boolean: true, .. }) = &input.value {
+        if let Some(error) = check_bool(&input, &pattern, value) {
+            return error.to_compile_error().into();
+        }
+    }
+    if let Some(Value {
+        exact: true,
+        narrow: true,
+        ..
+    }) = &input.value
+    {
+        return syn::Error::new(
+            input.pattern.span(),
+            "`narrow` and `exact` can't be combined",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if let Some(Value {
+        closure: Some(closure),
+        names,
+        tuple,
+        ..
+    }) = &input.value
+    {
+        if names.is_some() || *tuple {
+            let option = if *tuple { "`tuple`" } else { "`=> { ... }`" };
+            return syn::Error::new(
+                syn::spanned::Spanned::span(closure),
+                format!("a closure can't be combined with {}", option),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    if let Some(Value {
+        arm: Some(arm),
+        closure,
+        tuple,
+        ..
+    }) = &input.value
+    {
+        if closure.is_some() || *tuple {
+            let option = if *tuple { "`tuple`" } else { "a closure" };
+            return syn::Error::new(
+                syn::spanned::Spanned::span(arm),
+                format!("`=> expr` can't be combined with {}", option),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    if let Some(Value {
+        tuple: true,
+        names: Some(_),
+        ..
+    }) = &input.value
+    {
+        return syn::Error::new(
+            input.pattern.span(),
+            "`tuple` can't be combined with `=> { ... }`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if let Some(Value {
+        source: true,
+        constant,
+        offset,
+        limbs,
+        ..
+    }) = &input.value
+    {
+        let other = if *constant {
+            Some("`const`")
+        } else if offset.is_some() {
+            Some("an offset")
+        } else if *limbs {
+            Some("the type `(u128, u128)`")
+        } else {
+            None
+        };
+        if let Some(other) = other {
+            return syn::Error::new(
+                input.pattern.span(),
+                format!("`source` can't be combined with {}", other),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    if let Some(Value { exact: true, .. }) = &input.value {
+        if let Some(field) = pattern.fields.iter().find(|x| x.signed()) {
+            return syn::Error::new(
+                input.pattern.span(),
+                format!(
+                    "uppercase letter '{}' can't be sign-extended with `exact`",
+                    field.name
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    let code = match &input.value {
+        Some(value) if value.limbs && !input.bytes => {
+            limbs::gen_code(&pattern, input.pattern.span(), value)
+        }
+        _ if input.bytes || pattern.len() > 128 => gen_bytes(&input, &pattern),
+        value => match (pattern.len_ty(input.pattern.span()), value) {
+            // Any expression is accepted, and it's bound to a local so that
+            // it's evaluated once.
+            (Ok(ty), Some(value)) => gen_code(&pattern, ty, value),
+            (Ok(ty), None) => return gen_object(&pattern, ty),
+            (Err(err), _) => Err(err),
+        },
+    };
+    let code = match &input.value {
+        Some(value) if !value.maps.is_empty() => {
+            code.and_then(|code| gen_maps(&pattern, value, code))
+        }
+        _ => code,
+    };
+    let code = match &input.value {
+        Some(
+            value @ Value {
+                closure: Some(closure),
+                ..
+            },
+        ) => code.and_then(|code| gen_closure(&pattern, closure, value, code)),
+        Some(value @ Value { arm: Some(arm), .. }) => {
+            code.map(|code| gen_arm(&pattern, arm, value, code))
+        }
+        Some(
+            value @ Value {
+                names: Some(names), ..
+            },
+        ) => code.and_then(|code| gen_names(&pattern, names, value, code)),
+        // Without fields the result is already `()`, and with more than one
+        // it's a tuple.
+        Some(value @ Value { tuple: true, .. })
+            if pattern.fields.len() + value.rest as usize == 1 =>
+        {
+            code.map(|code| {
+                let (found, mismatch) = gen_found(value);
+                let field = Ident::new("field", Span::mixed_site());
+                quote! {
+                    match #code {
+                        #found(#field) => #found((#field,)),
+                        #mismatch
+                    }
+                }
+            })
+        }
+        _ => code,
+    };
+    // Divergence is allowed in a match arm.
+    let code = match &input.value {
+        Some(Value {
+            otherwise: Some(otherwise),
+            ..
+        }) => code.map(|code| {
+            let fields = Ident::new("fields", Span::mixed_site());
+            quote! {
+                match #code {
+                    ::core::option::Option::Some(#fields) => #fields,
+                    ::core::option::Option::None => #otherwise,
+                }
+            }
+        }),
+        _ => code,
+    };
+    let code = match &input.value {
+        Some(Value { boolean: true, .. }) => code.map(|code| {
+            quote! {
+                match #code {
+                    ::core::option::Option::Some(()) => true,
+                    ::core::option::Option::None => false,
+                }
+            }
+        }),
+        _ => code,
+    };
+    code.unwrap_or_else(|e| e.to_compile_error()).into()
+}
 
-    let pattern = match pattern {
-        TokenTree::Literal(x) => x.to_string(),
-        _ => {
-            panic!("1st argument must be string literal");
+/// Parses the pattern, leaving out the fields of the letters dropped with
+/// `~letter`. Their bits are neither compared nor part of `rest`.
+fn parse_pattern(input: &BitpatternInput) -> syn::Result<Pattern> {
+    let mut pattern = Pattern::parse(&input.pattern.value());
+    let dropped = match &input.value {
+        Some(value) => &value.dropped,
+        None => return Ok(pattern),
+    };
+    for letter in dropped {
+        let name = letter.to_string();
+        if !pattern.fields.iter().any(|x| name.chars().eq([x.name])) {
+            return Err(syn::Error::new(
+                letter.span(),
+                format!("'{}' isn't a letter of the pattern", name),
+            ));
         }
+    }
+    pattern.fields.retain(|field| {
+        !dropped
+            .iter()
+            .any(|x| x.to_string().chars().eq([field.name]))
+    });
+    Ok(pattern)
+}
+
+/// Error for a letter that has no field, saying so if it was dropped with
+/// `~letter`.
+fn unknown_letter(value: &Value, span: Span, letter: &str, message: String) -> syn::Error {
+    if value.dropped.iter().any(|x| x == letter) {
+        syn::Error::new(span, format!("'{}' is dropped with `~{}`", letter, letter))
+    } else {
+        syn::Error::new(span, message)
+    }
+}
+
+/// Error for a `bool` pattern with fields, which would be dropped, and for
+/// the arguments that only apply to fields or an `Option`.
+fn check_bool(input: &BitpatternInput, pattern: &Pattern, value: &Value) -> Option<syn::Error> {
+    let span = input.pattern.span();
+    if let Some(field) = pattern.fields.first() {
+        return Some(syn::Error::new(
+            span,
+            format!(
+                "`bool` needs a pattern without letters, found '{}'",
+                field.name
+            ),
+        ));
+    }
+    let option = if value.result {
+        "`bitmatch_result!`"
+    } else if value.tuple {
+        "`tuple`"
+    } else if value.rest {
+        "`rest`"
+    } else if value.names.is_some() {
+        "`=> { ... }`"
+    } else if value.arm.is_some() {
+        "`=> expr`"
+    } else if value.closure.is_some() {
+        "a closure"
+    } else if value.otherwise.is_some() {
+        "`else`"
+    } else {
+        return None;
     };
-    let pattern = if pattern.starts_with('\"') & pattern.ends_with('\"') {
-        String::from(&pattern[1..pattern.len() - 1]).replace("_", "")
+    Some(syn::Error::new(
+        span,
+        format!("`bool` can't be combined with {}", option),
+    ))
+}
+
+/// Error for the arguments that the `rest` residue can't be returned with.
+fn check_rest(input: &BitpatternInput, pattern: &Pattern, value: &Value) -> Option<syn::Error> {
+    let span = input.pattern.span();
+    if input.bytes || pattern.len() > 128 {
+        return Some(syn::Error::new(
+            span,
+            "`rest` can't be used with a byte slice",
+        ));
+    }
+    if value.limbs {
+        return Some(syn::Error::new(
+            span,
+            "`rest` can't be used with `(u128, u128)`",
+        ));
+    }
+    let (option, span) = if let Some(names) = &value.names {
+        (
+            "`=> { ... }`",
+            names.first().map_or(span, |x| x.name.span()),
+        )
+    } else if let Some(arm) = &value.arm {
+        ("`=> expr`", syn::spanned::Spanned::span(arm))
+    } else if let Some(closure) = &value.closure {
+        ("a closure", syn::spanned::Spanned::span(closure))
+    } else if let Some(map) = value.maps.first() {
+        ("a field map", map.letter.span())
     } else {
-        panic!("1st argument must be string literal");
+        return None;
     };
+    Some(syn::Error::new(
+        span,
+        format!("`rest` can't be combined with {}", option),
+    ))
+}
 
-    match comma {
-        TokenTree::Punct(x) => {
-            if x.as_char() != ',' {
-                panic!("',' is required");
+/// Error for the arguments of `bitmatch_result!` that `bitpattern!` takes
+/// but that can't report mismatching bits.
+fn check_result(input: &BitpatternInput, pattern: &Pattern, value: &Value) -> Option<syn::Error> {
+    let span = input.pattern.span();
+    if input.bytes || pattern.len() > 128 {
+        return Some(syn::Error::new(
+            span,
+            "`bitmatch_result!` can't match a byte slice",
+        ));
+    }
+    if value.limbs {
+        return Some(syn::Error::new(
+            span,
+            "`bitmatch_result!` can't match `(u128, u128)`",
+        ));
+    }
+    if let Some(otherwise) = &value.otherwise {
+        return Some(syn::Error::new(
+            syn::spanned::Spanned::span(otherwise),
+            "`bitmatch_result!` can't have `else`",
+        ));
+    }
+    value.maps.iter().find_map(|map| match map.kind {
+        MapKind::Convert(_) => Some(syn::Error::new(
+            map.letter.span(),
+            "`letter as Type` can't be used with `bitmatch_result!`",
+        )),
+        _ => None,
+    })
+}
+
+/// Constructor of the value returned when the pattern matches, either
+/// `Some` or `Ok` for `bitmatch_result!`, and the arm passing on any other
+/// value.
+fn gen_found(value: &Value) -> (TokenStream, TokenStream) {
+    if value.result {
+        let mismatch = Ident::new("mismatch", Span::mixed_site());
+        (
+            quote! { ::core::result::Result::Ok },
+            quote! {
+                ::core::result::Result::Err(#mismatch) => ::core::result::Result::Err(#mismatch),
+            },
+        )
+    } else {
+        (
+            quote! { ::core::option::Option::Some },
+            quote! { ::core::option::Option::None => ::core::option::Option::None, },
+        )
+    }
+}
+
+/// Error of `bitmatch_result!` for `bits` of type `x`, which don't match the
+/// pattern value given by `bit_pattern` under `bit_mask`.
+///
+/// The struct is declared by each expansion, since a proc-macro crate can't
+/// export it.
+fn gen_mismatch(
+    x: &Ident,
+    bits: &Ident,
+    bit_mask: &proc_macro2::Literal,
+    bit_pattern: &proc_macro2::Literal,
+    high_bits: TokenStream,
+) -> TokenStream {
+    let error = Ident::new("BitMismatch", Span::mixed_site());
+    quote! {
+        {
+            /// Bits of a value that didn't match a pattern.
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            struct #error {
+                /// Bits of the pattern the value has the wrong value for.
+                mismatch: #x,
+                /// Bits checked by the pattern.
+                mask: #x,
+                /// The value, truncated to the pattern type.
+                value: #x,
+                /// The value has bits set above the pattern.
+                high_bits: bool,
             }
-        }
-        _ => {
-            panic!("',' is required");
+
+            impl ::core::fmt::Display for #error {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    if self.mismatch != 0 {
+                        ::core::write!(
+                            f,
+                            "bits {:#x} of {:#x} don't match the pattern (mask {:#x})",
+                            self.mismatch, self.value, self.mask,
+                        )?;
+                        if self.high_bits {
+                            f.write_str(", and ")?;
+                        }
+                    }
+                    if self.high_bits {
+                        f.write_str("bits above the pattern are set")?;
+                    }
+                    ::core::result::Result::Ok(())
+                }
+            }
+
+            ::core::result::Result::Err(#error {
+                mismatch: (#bits & #bit_mask) ^ #bit_pattern,
+                mask: #bit_mask,
+                value: #bits,
+                high_bits: #high_bits,
+            })
         }
     }
+}
 
-    match pattern.len() {
-        1..=8 => gen_code_u8(pattern, expr),
-        9..=16 => gen_code_u16(pattern, expr),
-        17..=32 => gen_code_u32(pattern, expr),
-        33..=64 => gen_code_u64(pattern, expr),
-        65..=128 => gen_code_u128(pattern, expr),
-        _ => {
-            panic!("unsupported pattern length: {}", pattern.len());
+/// Calls `closure` with the fields of `code` named by its parameters, in
+/// their order, and returns its result instead of the fields.
+///
+/// A parameter may have a type, and the letters without one are dropped.
+fn gen_closure(
+    pattern: &Pattern,
+    closure: &syn::ExprClosure,
+    value: &Value,
+    code: TokenStream,
+) -> syn::Result<TokenStream> {
+    let mut closure = closure.clone();
+    let mut args = Vec::new();
+    for input in &mut closure.inputs {
+        let pat = match input {
+            syn::Pat::Type(x) => &mut *x.pat,
+            x => x,
+        };
+        let ident = match pat {
+            syn::Pat::Ident(x) if x.subpat.is_none() => x,
+            x => {
+                return Err(syn::Error::new(
+                    syn::spanned::Spanned::span(x),
+                    "expected a letter of the pattern",
+                ));
+            }
+        };
+        let letter = ident.ident.to_string();
+        let runs: Vec<_> = pattern
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| letter.chars().eq([x.name]))
+            .collect();
+        match runs.as_slice() {
+            [(index, field)] => {
+                args.push(*index);
+                // Uppercase letters are bound as written.
+                if field.signed() {
+                    ident
+                        .attrs
+                        .push(syn::parse_quote!(#[allow(non_snake_case)]));
+                }
+            }
+            [] => {
+                return Err(unknown_letter(
+                    value,
+                    ident.ident.span(),
+                    &letter,
+                    format!("parameter `{}` isn't a letter of the pattern", letter),
+                ));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    ident.ident.span(),
+                    format!(
+                        "'{}' has {} runs in the pattern, but a parameter needs one",
+                        letter,
+                        runs.len()
+                    ),
+                ));
+            }
         }
     }
+
+    let values: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let args = args.iter().map(|index| &values[*index]);
+    let (found, mismatch) = gen_found(value);
+    Ok(quote! {
+        match #code {
+            #found((#(#values),*)) => #found((#closure)(#(#args),*)),
+            #mismatch
+        }
+    })
 }
 
-macro_rules! gen_code {
-    ($x:ty) => {
-        paste::item! {
-            fn [<gen_code_$x>](pattern: String, expr: TokenStream) -> proc_macro::TokenStream {
-                let mut bit_mask: $x = 0;
-                let mut bit_pattern: $x = 0;
+/// Evaluates `arm` with each letter of a single run bound to its field, and
+/// returns it instead of the fields.
+///
+/// The letters are bound at the call site, so the expression sees them like
+/// its own variables, and a letter that isn't bound is an unresolved name in
+/// the expression.
+fn gen_arm(pattern: &Pattern, arm: &syn::Expr, value: &Value, code: TokenStream) -> TokenStream {
+    let values: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let bindings = pattern
+        .fields
+        .iter()
+        .zip(&values)
+        .filter_map(|(field, value)| {
+            if pattern
+                .fields
+                .iter()
+                .filter(|x| x.name == field.name)
+                .count()
+                > 1
+            {
+                return None;
+            }
+            let letter = Ident::new(&field.name.to_string(), Span::call_site());
+            Some(quote! {
+                #[allow(unused_variables, non_snake_case)]
+                let #letter = #value;
+            })
+        });
+    let (found, mismatch) = gen_found(value);
+    quote! {
+        match #code {
+            #found((#(#values),*)) => {
+                #(#bindings)*
+                #found(#arm)
+            }
+            #mismatch
+        }
+    }
+}
 
-                let mut args_pos = Vec::new();
-                let mut args_mask = Vec::new();
+/// Converts the tuple of fields of `code` to a struct with the names given
+/// for their letters.
+///
+/// The struct is generic over the types of the fields, so it works with any
+/// of them. Letters without a name are dropped.
+fn gen_names(
+    pattern: &Pattern,
+    names: &[Name],
+    value: &Value,
+    code: TokenStream,
+) -> syn::Result<TokenStream> {
+    let mut fields = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].iter().any(|x| x.name == name.name) {
+            return Err(syn::Error::new(
+                name.name.span(),
+                format!("`{}` is given twice", name.name),
+            ));
+        }
+        let letter = name.letter.to_string();
+        let runs: Vec<_> = pattern
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| letter.chars().eq([x.name]))
+            .collect();
+        match runs.as_slice() {
+            [(index, _)] => fields.push((&name.name, *index)),
+            [] => {
+                return Err(unknown_letter(
+                    value,
+                    name.letter.span(),
+                    &letter,
+                    format!("'{}' isn't a letter of the pattern", letter),
+                ));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    name.letter.span(),
+                    format!(
+                        "'{}' has {} runs in the pattern, but a named field needs one",
+                        letter,
+                        runs.len()
+                    ),
+                ));
+            }
+        }
+    }
 
-                let mut prev = None;
-                let mut count = 0;
+    let values: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let types: Vec<_> = (0..fields.len())
+        .map(|i| Ident::new(&format!("T{}", i), Span::mixed_site()))
+        .collect();
+    let members = fields.iter().map(|(name, _)| name);
+    let members2 = members.clone();
+    let inits = fields.iter().map(|(_, index)| &values[*index]);
+    let fields = Ident::new("Fields", Span::mixed_site());
+    let (found, mismatch) = gen_found(value);
+    Ok(quote! {
+        {
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            #[allow(dead_code)]
+            struct #fields<#(#types),*> {
+                #(#members: #types),*
+            }
 
-                for (i, bit) in pattern.chars().enumerate() {
-                    bit_mask <<= 1;
-                    bit_pattern <<= 1;
-                    match bit {
-                        '0' => {
-                            bit_mask |= 1;
-                            bit_pattern |= 0;
-                        }
-                        '1' => {
-                            bit_mask |= 1;
-                            bit_pattern |= 1;
-                        }
-                        '_' => {
-                            bit_mask |= 0;
-                            bit_pattern |= 0;
-                        }
-                        _ => {
-                            bit_mask |= 0;
-                            bit_pattern |= 0;
-                        }
-                    }
-                    if let Some(x) = prev {
-                        if x != bit && x != '0' && x != '1' && x != '?' {
-                            let pos = (pattern.len() - i) as $x;
-                            let mut mask: $x = 0;
-                            for _ in 0..count {
-                                mask <<= 1;
-                                mask |= 1;
-                            }
-                            args_pos.push(pos);
-                            args_mask.push(mask);
-
-                            count = 0;
-                        } else if x != bit {
-                            count = 0;
-                        }
-                    }
-                    count += 1;
-                    prev = Some(bit);
-                }
-                if let Some(x) = prev {
-                    if x != '0' && x != '1' && x != '?' {
-                        let pos = 0 as $x;
-                        let mut mask: $x = 0;
-                        for _ in 0..count {
-                            mask <<= 1;
-                            mask |= 1;
-                        }
-                        args_pos.push(pos);
-                        args_mask.push(mask);
-                    }
+            match #code {
+                #found((#(#values),*)) => {
+                    #found(#fields {
+                        #(#members2: #inits),*
+                    })
                 }
+                #mismatch
+            }
+        }
+    })
+}
+
+/// Applies the maps given after the value to the fields of `code`, so the
+/// tuple already holds their results.
+///
+/// A field given as `letter as Type` is converted with `TryFrom`, and a
+/// failed conversion doesn't match. The types and expressions are spliced as
+/// written, so they resolve in the scope of the caller, and each run of the
+/// letter is mapped. The fields are sign-extended before. `letter + n` and
+/// `letter << shift` keep the type of the field, wrapping around and
+/// dropping the bits shifted out rather than overflowing.
+fn gen_maps(pattern: &Pattern, value: &Value, code: TokenStream) -> syn::Result<TokenStream> {
+    let maps = &value.maps;
+    let mut kinds = vec![None; pattern.fields.len()];
+    for (i, map) in maps.iter().enumerate() {
+        let letter = map.letter.to_string();
+        if maps[..i].iter().any(|x| x.letter == map.letter) {
+            return Err(syn::Error::new(
+                map.letter.span(),
+                format!("'{}' is mapped twice", letter),
+            ));
+        }
+        let mut found = false;
+        for (kind, field) in kinds.iter_mut().zip(&pattern.fields) {
+            if letter.chars().eq([field.name]) {
+                *kind = Some(map);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(unknown_letter(
+                value,
+                map.letter.span(),
+                &letter,
+                format!("'{}' isn't a letter of the pattern", letter),
+            ));
+        }
+    }
 
-                let gen = quote! {
+    let values: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("field{}", i), Span::mixed_site()))
+        .collect();
+    let converted: Vec<_> = (0..pattern.fields.len())
+        .map(|i| Ident::new(&format!("converted{}", i), Span::mixed_site()))
+        .collect();
+    let mut tries = Vec::new();
+    let mut oks = Vec::new();
+    let mut results = Vec::new();
+    for ((map, value), converted) in kinds.iter().zip(&values).zip(&converted) {
+        let map = match map {
+            Some(map) => map,
+            None => {
+                results.push(quote! { #value });
+                continue;
+            }
+        };
+        match &map.kind {
+            MapKind::Convert(ty) => {
+                tries.push(quote! { <#ty as ::core::convert::TryFrom<_>>::try_from(#value) });
+                oks.push(quote! { ::core::result::Result::Ok(#converted) });
+                results.push(quote! { #converted });
+            }
+            // `unwrap_or` isn't `const`.
+            MapKind::Shift(shift) => results.push(quote! {
+                match #value.checked_shl(#shift) {
+                    ::core::option::Option::Some(x) => x,
+                    ::core::option::Option::None => 0,
+                }
+            }),
+            MapKind::Add(add) => results.push(quote! { #value.wrapping_add(#add) }),
+            MapKind::Expr(expr) => {
+                let letter = &map.letter;
+                results.push(quote! {
                     {
-                        let value = (#expr) as $x;
-                        if value & #bit_mask == #bit_pattern {
-                            Some((
-                                    #(
-                                        (value >> #args_pos) & #args_mask
-                                    ),*
-                                ))
-                        } else {
-                            None
-                        }
+                        // Uppercase letters are bound as written.
+                        #[allow(non_snake_case)]
+                        let #letter = #value;
+                        #expr
                     }
-                };
-
-                gen.into()
+                });
+            }
+        }
+    }
+    let (some, mismatch) = gen_found(value);
+    let found = quote! { #some((#(#results),*)) };
+    let found = if tries.is_empty() {
+        found
+    } else {
+        quote! {
+            match (#(#tries,)*) {
+                (#(#oks,)*) => #found,
+                _ => ::core::option::Option::None,
             }
         }
     };
+    Ok(quote! {
+        match #code {
+            #some((#(#values),*)) => #found,
+            #mismatch
+        }
+    })
+}
+
+fn gen_bytes(input: &BitpatternInput, pattern: &Pattern) -> syn::Result<TokenStream> {
+    let span = input.pattern.span();
+    match &input.value {
+        Some(
+            value @ Value {
+                offset: None,
+                ty: None,
+                low: false,
+                exact: false,
+                source: false,
+                limbs: false,
+                ..
+            },
+        ) => bytes::gen_code(pattern, span, value),
+        Some(Value {
+            offset: Some(offset),
+            ..
+        }) => Err(syn::Error::new(
+            syn::spanned::Spanned::span(offset),
+            "patterns matching a byte slice can't have an offset",
+        )),
+        Some(Value { ty: Some(ty), .. }) => Err(syn::Error::new(
+            ty.span(),
+            "patterns matching a byte slice can't have a type",
+        )),
+        Some(Value { low: true, .. }) => Err(syn::Error::new(
+            span,
+            "patterns matching a byte slice can't have `low`",
+        )),
+        Some(Value { source: true, .. }) => Err(syn::Error::new(
+            span,
+            "patterns matching a byte slice can't have `source`",
+        )),
+        Some(Value { exact: true, .. }) => Err(syn::Error::new(
+            span,
+            "patterns matching a byte slice can't have `exact`",
+        )),
+        Some(Value { limbs: true, .. }) => Err(syn::Error::new(
+            span,
+            "patterns matching a byte slice can't have a type",
+        )),
+        None => Err(syn::Error::new(
+            span,
+            "patterns matching a byte slice need a slice to match",
+        )),
+    }
+}
+
+/// Input of `bitpattern!`:
+/// `[options,] "..."[, value [@ offset] [=> { ... } | => expr][, map]...[, ty][, |...| ...][, else expr]]`,
+/// where the options are `low`, `narrow`, `flags`, `tuple`, `const`,
+/// `source`, `rest`, `bool`, `exact` and `~letter` in any order, with an
+/// optional trailing comma.
+struct BitpatternInput {
+    pattern: syn::LitStr,
+    /// The pattern was a byte string, matching a byte slice like patterns
+    /// longer than 128 bits.
+    bytes: bool,
+    value: Option<Value>,
+}
+
+/// Value matched by `bitpattern!`.
+struct Value {
+    expr: syn::Expr,
+    offset: Option<syn::Expr>,
+    /// Type of the comparison and the fields.
+    ty: Option<Ident>,
+    /// Given as `low, "..."`, matching the low bits of a wider value.
+    low: bool,
+    /// Given as `source, "..."`, reading a value of any type through local
+    /// traits rather than as an integer.
+    source: bool,
+    /// Given as `exact, "..."`, returning the fields as `arbitrary-int`
+    /// types of their width.
+    exact: bool,
+    /// Given as `narrow, "..."`, returning each field as the smallest type
+    /// holding it.
+    narrow: bool,
+    /// Given as `flags, "..."`, returning each field of one bit as `bool`.
+    flags: bool,
+    /// Given as `tuple, "..."`, returning a single field as a tuple of one.
+    tuple: bool,
+    /// Given as `const, "..."`, reading the value as the type of the
+    /// comparison, so that the expansion can be evaluated in constants.
+    constant: bool,
+    /// Given as `rest, "..."`, also returning the '?' bits of the value in
+    /// place, after the fields.
+    rest: bool,
+    /// Given as `bool, "..."` with a pattern without letters, returning
+    /// whether the value matches rather than `Option<()>`.
+    boolean: bool,
+    /// Given as `~letter, "..."`, keeping the runs of the letter apart from
+    /// their neighbours but leaving them out of the fields.
+    dropped: Vec<Ident>,
+    /// Matched by `bitmatch_result!`, returning a `Result` with the bits that
+    /// didn't match.
+    result: bool,
+    /// Given the type `(u128, u128)`, matching the high and low halves of a
+    /// pattern of up to 256 bits.
+    limbs: bool,
+    /// Given as `=> { name: letter, ... }`, returning a struct of the fields.
+    names: Option<Vec<Name>>,
+    /// Given as `=> expr`, returning the expression with the letters bound
+    /// to their fields, like a match arm.
+    arm: Option<syn::Expr>,
+    /// Given as `letter as Type`, `letter << shift`, `letter + n` or
+    /// `letter => expr` after the value, mapping the field.
+    maps: Vec<Map>,
+    /// Given as an argument after the type, called with the fields of its
+    /// parameters.
+    closure: Option<syn::ExprClosure>,
+    /// Given as `else expr` last, returning the fields without an `Option`,
+    /// or the value of the expression.
+    otherwise: Option<syn::Expr>,
+}
+
+/// Map of the field of a letter, given after the value.
+struct Map {
+    letter: Ident,
+    kind: MapKind,
+}
+
+enum MapKind {
+    /// `letter as Type`, converting with `TryFrom`.
+    Convert(syn::Type),
+    /// `letter << shift`, with a `u32` shift, 0 if it's the width of the
+    /// field's type or more.
+    Shift(syn::Expr),
+    /// `letter + n`, wrapping around.
+    Add(syn::Expr),
+    /// `letter => expr`, with the field bound to the letter.
+    Expr(syn::Expr),
 }
 
-gen_code!(u8);
-gen_code!(u16);
-gen_code!(u32);
-gen_code!(u64);
-gen_code!(u128);
+impl Map {
+    /// The input starts with a map, rather than a type.
+    fn peek(input: ParseStream) -> bool {
+        input.peek(syn::Ident)
+            && (input.peek2(Token![as])
+                || input.peek2(Token![<<])
+                || input.peek2(Token![+])
+                || input.peek2(Token![=>]))
+    }
+}
+
+impl Parse for Map {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let letter = input.parse()?;
+        let kind = if input.parse::<Option<Token![as]>>()?.is_some() {
+            MapKind::Convert(input.parse()?)
+        } else if input.parse::<Option<Token![<<]>>()?.is_some() {
+            MapKind::Shift(input.parse()?)
+        } else if input.parse::<Option<Token![+]>>()?.is_some() {
+            MapKind::Add(input.parse()?)
+        } else if input.parse::<Option<Token![=>]>>()?.is_some() {
+            MapKind::Expr(input.parse()?)
+        } else {
+            return Err(input.error("expected `as`, `<<`, `+` or `=>`"));
+        };
+        Ok(Map { letter, kind })
+    }
+}
+
+/// Member of the struct returned by `bitpattern!` for a letter, written
+/// `name: letter`, or just `letter` to use it as the name.
+struct Name {
+    name: Ident,
+    letter: Ident,
+}
+
+impl Name {
+    /// The input is a list of names in braces, rather than a block: it's
+    /// empty, or starts with a name followed by the end, `,`, `:` or
+    /// another name, so that errors in it are reported for names.
+    fn peek_list(input: ParseStream) -> bool {
+        let list = |input: ParseStream| -> syn::Result<bool> {
+            let content;
+            syn::braced!(content in input);
+            if content.is_empty() {
+                return Ok(true);
+            }
+            content.parse::<Ident>()?;
+            Ok(content.is_empty()
+                || content.peek(Token![,])
+                || (content.peek(Token![:]) && !content.peek(Token![::]))
+                || content.peek(syn::Ident))
+        };
+        list(&input.fork()).unwrap_or(false)
+    }
+}
+
+impl Parse for Name {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        let letter = if input.parse::<Option<Token![:]>>()?.is_some() {
+            input.parse()?
+        } else {
+            name.clone()
+        };
+        Ok(Name { name, letter })
+    }
+}
+
+impl BitpatternInput {
+    /// Parses the options, the pattern, and the value with its offset, which
+    /// start the input of the macros matching a value like `bitpattern!`.
+    fn parse_head(input: ParseStream) -> syn::Result<Self> {
+        let mut low = None;
+        let mut source = None;
+        let mut exact = None;
+        let mut narrow = None;
+        let mut flags = None;
+        let mut tuple = None;
+        let mut constant = None;
+        let mut rest = None;
+        let mut boolean = None;
+        let mut dropped: Vec<Ident> = Vec::new();
+        while (input.peek(syn::Ident) || input.peek(Token![const])) && input.peek2(Token![,])
+            || input.peek(Token![~])
+        {
+            if input.parse::<Option<Token![~]>>()?.is_some() {
+                let letter: Ident = input.parse()?;
+                if letter.to_string().chars().count() != 1 {
+                    return Err(syn::Error::new(
+                        letter.span(),
+                        "expected a letter of the pattern",
+                    ));
+                }
+                if dropped.contains(&letter) {
+                    return Err(syn::Error::new(
+                        letter.span(),
+                        format!("'{}' is dropped twice", letter),
+                    ));
+                }
+                input.parse::<Token![,]>()?;
+                dropped.push(letter);
+                continue;
+            }
+            let ident = input.call(<Ident as syn::ext::IdentExt>::parse_any)?;
+            let option = if ident == "low" {
+                &mut low
+            } else if ident == "narrow" {
+                &mut narrow
+            } else if ident == "flags" {
+                &mut flags
+            } else if ident == "tuple" {
+                &mut tuple
+            } else if ident == "const" {
+                &mut constant
+            } else if ident == "source" {
+                &mut source
+            } else if ident == "rest" {
+                &mut rest
+            } else if ident == "bool" {
+                &mut boolean
+            } else if ident == "exact" {
+                if !cfg!(feature = "arbitrary-int") {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "`exact` requires the \"arbitrary-int\" feature of bitpattern",
+                    ));
+                }
+                &mut exact
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `low`, `narrow`, `flags`, `tuple`, `const`, `source`, `rest`, `bool` or `exact`",
+                ));
+            };
+            if option.is_some() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("`{}` is given twice", ident),
+                ));
+            }
+            input.parse::<Token![,]>()?;
+            *option = Some(ident);
+        }
+        let bytes = input.peek(syn::LitByteStr);
+        let pattern = if bytes {
+            let lit: syn::LitByteStr = input.parse()?;
+            match String::from_utf8(lit.value()) {
+                Ok(x) => syn::LitStr::new(&x, lit.span()),
+                Err(_) => return Err(syn::Error::new(lit.span(), "pattern isn't UTF-8")),
+            }
+        } else {
+            pattern::parse_lits(input)?
+        };
+        if !input.is_empty() && !input.peek(Token![,]) {
+            return Err(input.error("expected `,` after the pattern"));
+        }
+        input.parse::<Option<Token![,]>>()?;
+        if input.is_empty() {
+            if let Some(option) = low
+                .or(narrow)
+                .or(flags)
+                .or(tuple)
+                .or(constant)
+                .or(source)
+                .or(rest)
+                .or(boolean)
+                .or(exact)
+            {
+                return Err(syn::Error::new(
+                    option.span(),
+                    format!("`{}` needs a value", option),
+                ));
+            }
+            if let Some(letter) = dropped.first() {
+                return Err(syn::Error::new(
+                    letter.span(),
+                    format!("`~{}` needs a value", letter),
+                ));
+            }
+            return Ok(BitpatternInput {
+                pattern,
+                bytes,
+                value: None,
+            });
+        }
+        let expr = input.parse()?;
+        let mut offset = None;
+        if input.parse::<Option<Token![@]>>()?.is_some() {
+            offset = Some(input.parse()?);
+        }
+        Ok(BitpatternInput {
+            pattern,
+            bytes,
+            value: Some(Value {
+                expr,
+                offset,
+                ty: None,
+                low: low.is_some(),
+                exact: exact.is_some(),
+                narrow: narrow.is_some(),
+                flags: flags.is_some(),
+                tuple: tuple.is_some(),
+                constant: constant.is_some(),
+                source: source.is_some(),
+                rest: rest.is_some(),
+                boolean: boolean.is_some(),
+                dropped,
+                result: false,
+                limbs: false,
+                names: None,
+                maps: Vec::new(),
+                closure: None,
+                arm: None,
+                otherwise: None,
+            }),
+        })
+    }
+}
+
+impl Parse for BitpatternInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut head = Self::parse_head(input)?;
+        let value = match &mut head.value {
+            Some(value) => value,
+            None => return Ok(head),
+        };
+        let mut names = None;
+        let mut arm = None;
+        if input.parse::<Option<Token![=>]>>()?.is_some() {
+            if input.peek(syn::token::Brace) && Name::peek_list(input) {
+                let content;
+                syn::braced!(content in input);
+                let list = content.parse_terminated(Name::parse, Token![,])?;
+                names = Some(list.into_iter().collect());
+            } else {
+                arm = Some(input.parse()?);
+            }
+        }
+        let mut maps = Vec::new();
+        loop {
+            let fork = input.fork();
+            if fork.parse::<Token![,]>().is_err() || !Map::peek(&fork) {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+            maps.push(input.parse()?);
+        }
+        let mut ty = None;
+        let mut limbs = false;
+        let mut closure = None;
+        let is_closure = |input: ParseStream| {
+            input.peek(Token![|]) || input.peek(Token![||]) || input.peek(Token![move])
+        };
+        let mut comma = input.parse::<Option<Token![,]>>()?.is_some();
+        if comma && !input.is_empty() && !is_closure(input) && !input.peek(Token![else]) {
+            if input.peek(syn::token::Paren) {
+                let tuple: syn::TypeTuple = input.parse()?;
+                let u128 = |x: &syn::Type| quote!(#x).to_string() == "u128";
+                if tuple.elems.len() != 2 || !tuple.elems.iter().all(u128) {
+                    return Err(syn::Error::new(
+                        syn::spanned::Spanned::span(&tuple),
+                        "expected `(u128, u128)`",
+                    ));
+                }
+                limbs = true;
+            } else {
+                ty = Some(input.parse()?);
+            }
+            comma = input.parse::<Option<Token![,]>>()?.is_some();
+        }
+        if comma && is_closure(input) {
+            closure = Some(input.parse()?);
+            comma = input.parse::<Option<Token![,]>>()?.is_some();
+        }
+        let mut otherwise = None;
+        if comma && input.parse::<Option<Token![else]>>()?.is_some() {
+            otherwise = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        if !input.is_empty() {
+            return Err(input.error(
+                "expected the end of the arguments: a pattern, a value with an optional `@ offset` and `=> { ... }`, optional maps of letters, an optional type, an optional closure, and an optional `else`",
+            ));
+        }
+        value.ty = ty;
+        value.limbs = limbs;
+        value.names = names;
+        value.maps = maps;
+        value.closure = closure;
+        value.arm = arm;
+        value.otherwise = otherwise;
+        Ok(head)
+    }
+}
+
+fn gen_code(pattern: &Pattern, ty: Ty, value: &Value) -> syn::Result<TokenStream> {
+    let expr = &value.expr;
+    match (&value.offset, &value.ty) {
+        (Some(offset), None) => {
+            let x = ty.ident();
+            let bit_mask = ty.lit(pattern.mask());
+            let bit_pattern = ty.lit(pattern.value());
+            let bits = Ident::new("bits", Span::mixed_site());
+            let args = gen_value_args(pattern, ty.ident(), &|v| ty.lit(v), &bits, value);
+            let (found, mismatch) = if value.result {
+                (
+                    quote! { ::core::result::Result::Ok },
+                    // The bits above the offset pattern are ignored.
+                    gen_mismatch(&x, &bits, &bit_mask, &bit_pattern, quote! { false }),
+                )
+            } else {
+                (quote! { Some }, quote! { None })
+            };
+            let value = gen_offset(pattern, ty, quote! { #expr }, quote! { #offset });
+            Ok(quote! {
+                {
+                    let #bits: #x = #value;
+                    if #bits & #bit_mask == #bit_pattern {
+                        #found(#args)
+                    } else {
+                        #mismatch
+                    }
+                }
+            })
+        }
+        (Some(_), Some(ty)) => Err(syn::Error::new(
+            ty.span(),
+            "a type can't be given with an offset",
+        )),
+        (None, None) => {
+            let x = ty.ident();
+            Ok(gen_bits(pattern, x, &|v| ty.lit(v), value))
+        }
+        (None, Some(ident)) if ident == "usize" => {
+            let len = pattern.len();
+            if len > 64 {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("{}-bit pattern is wider than `usize`", len),
+                ));
+            }
+            let code = gen_bits(
+                pattern,
+                Ident::new("usize", Span::call_site()),
+                &|v| proc_macro2::Literal::usize_suffixed(v as usize),
+                value,
+            );
+            Ok(quote! {
+                {
+                    const _: () = ::core::assert!(
+                        #len <= ::core::mem::size_of::<usize>() * 8,
+                        "the pattern is wider than `usize`",
+                    );
+                    #code
+                }
+            })
+        }
+        (None, Some(ident)) => {
+            let ty = match Ty::from_ident(ident) {
+                Some(x) => x,
+                None => {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        "expected one of `u8`, `u16`, `u32`, `u64`, `u128` or `usize`",
+                    ));
+                }
+            };
+            if pattern.len() > ty.bits() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("{}-bit pattern is wider than `{}`", pattern.len(), ident),
+                ));
+            }
+            let x = ty.ident();
+            Ok(gen_bits(pattern, x, &|v| ty.lit(v), value))
+        }
+    }
+}
+
+/// `let` statement evaluating `expr` once into `value`, which has the type
+/// `x` if `expr` is an unsuffixed literal, rather than defaulting to `i32`.
+pub(crate) fn gen_binding(value: &Ident, x: &Ident, expr: &syn::Expr) -> TokenStream {
+    if is_unsuffixed(expr) {
+        quote! { let #value: #x = #expr; }
+    } else {
+        quote! { let #value = #expr; }
+    }
+}
+
+/// Returns true if `expr` is an integer literal without a suffix.
+fn is_unsuffixed(mut expr: &syn::Expr) -> bool {
+    while let syn::Expr::Group(group) = expr {
+        expr = &group.expr;
+    }
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.suffix().is_empty(),
+        _ => false,
+    }
+}
+
+/// Matches the pattern against `expr` as the type `x`, whose literals `lit`
+/// makes.
+///
+/// The value is an integer or a reference to one, read with `const` methods
+/// only. The two's complement bits of a signed value are taken at its own
+/// width, so a negative `i8` is zero-extended to a 16-bit pattern rather
+/// than sign-extended like `as`. A value with bits set above the pattern
+/// doesn't match, unless `low` is set, which truncates it like `as`.
+///
+/// With `source`, the value is converted by local traits instead. It is
+/// borrowed by a wrapper with a `bits` method taking it by value for each
+/// integer type and references to them, for `Wrapping` and the `NonZero`
+/// types of the integer they hold, for `f32` and `f64` through `to_bits`, and
+/// for `char` as its scalar value. Types converting into an unsigned type
+/// with `Into` have one taking it by reference, found next. For other types,
+/// method resolution derefs the wrapper to the value, so its own `bits`
+/// method gives the integer.
+fn gen_bits(
+    pattern: &Pattern,
+    x: Ident,
+    lit: &dyn Fn(u128) -> proc_macro2::Literal,
+    value: &Value,
+) -> TokenStream {
+    let (expr, low, result) = (&value.expr, value.low, value.result);
+    let bit_mask = lit(pattern.mask());
+    let bit_pattern = lit(pattern.value());
+    // The value is evaluated once into a temporary, which the comparison and
+    // each extraction read.
+    let bits = Ident::new("bits", Span::mixed_site());
+    let args = gen_value_args(pattern, x.clone(), lit, &bits, value);
+    let found = if value.result {
+        quote! { ::core::result::Result::Ok }
+    } else {
+        quote! { Some }
+    };
+    // The error tells whether bits above the pattern are set, which
+    // `high_bits` gives.
+    let mismatch = |high_bits: TokenStream| {
+        if value.result {
+            gen_mismatch(&x, &bits, &bit_mask, &bit_pattern, high_bits)
+        } else {
+            quote! { None }
+        }
+    };
+
+    let len = pattern.len() as u32;
+    // The trait would make an unsuffixed literal `i32`, and its methods
+    // can't be called in constants.
+    if is_unsuffixed(expr) || value.constant {
+        let (high, mismatch) = if low {
+            (None, mismatch(quote! { false }))
+        } else {
+            let fits = pattern::gen_fits(&bits, len);
+            (Some(quote! { #fits && }), mismatch(quote! { !#fits }))
+        };
+        return quote! {
+            {
+                let #bits: #x = #expr;
+                if #high #bits & #bit_mask == #bit_pattern {
+                    #found(#args)
+                } else {
+                    #mismatch
+                }
+            }
+        };
+    }
+
+    if !value.source {
+        let value = Ident::new("value", Span::mixed_site());
+        // A `usize` is read as a `u64`, which holds it on any target.
+        let word = match Ty::from_ident(&x) {
+            Some(ty) => pattern::gen_word(&value, ty),
+            None => {
+                let word = pattern::gen_word(&value, Ty::U64);
+                quote! { (#word as #x) }
+            }
+        };
+        let (high, mismatch) = if low {
+            (None, mismatch(quote! { false }))
+        } else {
+            let fits = pattern::gen_fits(&value, len);
+            (Some(quote! { #fits && }), mismatch(quote! { !#fits }))
+        };
+        return quote! {
+            {
+                // Shifting by zero copies an integer out of a reference, and
+                // rejects floats, which `as` would convert.
+                let #value = (#expr) >> 0u32;
+                let #bits: #x = #word;
+                if #high #bits & #bit_mask == #bit_pattern {
+                    #found(#args)
+                } else {
+                    #mismatch
+                }
+            }
+        };
+    }
+
+    let unsigned = [
+        (quote!(u8), quote!(i8)),
+        (quote!(u16), quote!(i16)),
+        (quote!(u32), quote!(i32)),
+        (quote!(u64), quote!(i64)),
+        (quote!(u128), quote!(i128)),
+        (quote!(usize), quote!(isize)),
+    ];
+    let impls = unsigned.iter().map(|(unsigned, signed)| {
+        let convert = if low {
+            quote! { ::core::option::Option::Some(self as #x) }
+        } else {
+            quote! {
+                if self.checked_shr(#len).unwrap_or(0) == 0 {
+                    ::core::option::Option::Some(self as #x)
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+        };
+        quote! {
+            impl Bits for #unsigned {
+                #[inline]
+                fn bits(self) -> ::core::option::Option<#x> {
+                    #convert
+                }
+            }
+
+            impl Bits for #signed {
+                #[inline]
+                fn bits(self) -> ::core::option::Option<#x> {
+                    Bits::bits(self as #unsigned)
+                }
+            }
+        }
+    });
+    let primitives: Vec<_> = unsigned
+        .iter()
+        .flat_map(|(unsigned, signed)| [unsigned, signed])
+        .collect();
+    let nonzero = primitives.iter().map(|x| {
+        let name = x.to_string();
+        let (sign, bits) = name.split_at(1);
+        Ident::new(
+            &format!("NonZero{}{}", sign.to_uppercase(), bits),
+            Span::call_site(),
+        )
+    });
+    // A type converting into an unsigned type, like a C-like enum with
+    // `From<Enum> for u8`, is found by autoref after the primitives and
+    // before `Deref`.
+    let conversions = unsigned[..5].iter().map(|(unsigned, _)| {
+        let name = Ident::new(
+            &format!("Into{}", unsigned.to_string().to_uppercase()),
+            Span::mixed_site(),
+        );
+        quote! {
+            trait #name {
+                fn bits(&self) -> #unsigned;
+            }
+
+            impl<'a, T> #name for Source<'a, T>
+            where
+                T: ::core::clone::Clone + ::core::convert::Into<#unsigned>,
+            {
+                #[inline]
+                fn bits(&self) -> #unsigned {
+                    ::core::convert::Into::into(::core::clone::Clone::clone(self.0))
+                }
+            }
+        }
+    });
+    // A float gives the bits of `to_bits`, so only a pattern of its width
+    // can match it. Otherwise `Raw` names the width it needs in the error.
+    let floats = [
+        (quote!(f32), quote!(u32), 32, "F32NeedsA32BitPattern"),
+        (quote!(f64), quote!(u64), 64, "F64NeedsA64BitPattern"),
+    ];
+    let floats = floats.iter().map(|(float, raw, bits, error)| {
+        if len == *bits {
+            quote! {
+                impl<'a> Primitive for Source<'a, #float> {
+                    type Raw = #raw;
+
+                    #[inline]
+                    fn bits(self) -> #raw {
+                        self.0.to_bits()
+                    }
+                }
+            }
+        } else {
+            let error = Ident::new(error, Span::mixed_site());
+            quote! {
+                struct #error;
+
+                impl<'a> Primitive for Source<'a, #float> {
+                    type Raw = #error;
+
+                    #[inline]
+                    fn bits(self) -> #error {
+                        #error
+                    }
+                }
+            }
+        }
+    });
+    let value = Ident::new("value", Span::mixed_site());
+    let matched = if result {
+        // A value with bits above the pattern is truncated to its type, which
+        // can leave no mismatching bit of the pattern, so the error tells
+        // they're set.
+        let raw = Ident::new("raw", Span::mixed_site());
+        let high_bits = Ident::new("high_bits", Span::mixed_site());
+        let mismatch = mismatch(quote! { #high_bits });
+        quote! {
+            let #raw = Source(#value).bits();
+            match Bits::bits(#raw) {
+                ::core::option::Option::Some(#bits) if #bits & #bit_mask == #bit_pattern => {
+                    #found(#args)
+                }
+                #bits => {
+                    let #high_bits = #bits.is_none();
+                    let #bits = #bits.unwrap_or(#raw as #x);
+                    #mismatch
+                }
+            }
+        }
+    } else {
+        let mismatch = mismatch(quote! { false });
+        quote! {
+            match Bits::bits(Source(#value).bits()) {
+                ::core::option::Option::Some(#bits) if #bits & #bit_mask == #bit_pattern => {
+                    #found(#args)
+                }
+                _ => #mismatch,
+            }
+        }
+    };
+    quote! {
+        {
+            // The parentheses are spanned by the macro, so parentheses
+            // around the value don't warn. The value is evaluated before the
+            // traits are in scope, and borrowed so it isn't moved.
+            let #value = &(#expr);
+            {
+                /// Value of another type, whose `bits` method is found
+                /// through `Deref` when no primitive has it.
+                struct Source<'a, T>(&'a T);
+
+                impl<'a, T> ::core::ops::Deref for Source<'a, T> {
+                    type Target = T;
+
+                    #[inline]
+                    fn deref(&self) -> &T {
+                        self.0
+                    }
+                }
+
+                trait Primitive {
+                    type Raw;
+
+                    fn bits(self) -> Self::Raw;
+                }
+
+                #(
+                    impl<'a> Primitive for Source<'a, #primitives> {
+                        type Raw = #primitives;
+
+                        #[inline]
+                        fn bits(self) -> #primitives {
+                            *self.0
+                        }
+                    }
+                )*
+
+                // A reference gives the bits of its referent.
+                impl<'a, 'b, T> Primitive for Source<'a, &'b T>
+                where
+                    for<'c> Source<'c, T>: Primitive,
+                {
+                    type Raw = <Source<'b, T> as Primitive>::Raw;
+
+                    #[inline]
+                    fn bits(self) -> Self::Raw {
+                        Primitive::bits(Source(*self.0))
+                    }
+                }
+
+                impl<'a, 'b, T> Primitive for Source<'a, &'b mut T>
+                where
+                    for<'c> Source<'c, T>: Primitive,
+                {
+                    type Raw = <Source<'a, T> as Primitive>::Raw;
+
+                    #[inline]
+                    fn bits(self) -> Self::Raw {
+                        Primitive::bits(Source(&**self.0))
+                    }
+                }
+
+                // The std wrappers give the bits of the integer they hold.
+                #(
+                    impl<'a> Primitive for Source<'a, ::core::num::Wrapping<#primitives>> {
+                        type Raw = #primitives;
+
+                        #[inline]
+                        fn bits(self) -> #primitives {
+                            self.0 .0
+                        }
+                    }
+
+                    impl<'a> Primitive for Source<'a, ::core::num::#nonzero> {
+                        type Raw = #primitives;
+
+                        #[inline]
+                        fn bits(self) -> #primitives {
+                            self.0.get()
+                        }
+                    }
+                )*
+
+                #(#floats)*
+
+                #(#conversions)*
+
+                impl<'a> Primitive for Source<'a, char> {
+                    type Raw = u32;
+
+                    #[inline]
+                    fn bits(self) -> u32 {
+                        *self.0 as u32
+                    }
+                }
+
+                trait Bits {
+                    fn bits(self) -> ::core::option::Option<#x>;
+                }
+
+                #(#impls)*
+
+                #matched
+            }
+        }
+    }
+}
+
+/// Struct literal of `BitPattern` and `BitField`, which are defined by the
+/// caller.
+fn gen_object(pattern: &Pattern, ty: Ty) -> proc_macro::TokenStream {
+    let bit_mask = ty.lit(pattern.mask());
+    let bit_pattern = ty.lit(pattern.value());
+    let shifts = pattern.fields.iter().map(|x| x.pos);
+    let widths = pattern.fields.iter().map(|x| x.width);
+
+    let gen = quote! {
+        BitPattern {
+            mask: #bit_mask,
+            value: #bit_pattern,
+            fields: &[#(BitField { shift: #shifts, width: #widths }),*],
+        }
+    };
+
+    gen.into()
+}
+
+/// Bits of `expr` from `offset`, without truncating it to the pattern type.
+///
+/// The offset must be constant, since the pattern is checked to fit in the
+/// type of `expr` at compile time.
+fn gen_offset(pattern: &Pattern, ty: Ty, expr: TokenStream, offset: TokenStream) -> TokenStream {
+    let x = ty.ident();
+    let len = pattern.len();
+    let value = Ident::new("value", Span::mixed_site());
+    quote! {
+        {
+            struct Fits<T>(::core::marker::PhantomData<T>);
+
+            impl<T> Fits<T> {
+                const OK: () = ::core::assert!(
+                    (#offset) as usize + #len <= ::core::mem::size_of::<T>() * 8,
+                    "the pattern runs past the top of the value at this offset",
+                );
+            }
+
+            const fn fits<T>(_: &T) {
+                let () = Fits::<T>::OK;
+            }
+
+            let #value = #expr;
+            fits(&#value);
+            (#value >> (#offset)) as #x
+        }
+    }
+}
+
+/// Tuple of the runs of letters extracted from `bits` of the type `x`.
+fn gen_value_args(
+    pattern: &Pattern,
+    x: Ident,
+    lit: &dyn Fn(u128) -> proc_macro2::Literal,
+    bits: &Ident,
+    value: &Value,
+) -> TokenStream {
+    let mut args: Vec<_> = pattern
+        .fields
+        .iter()
+        .map(|field| {
+            let pos = lit(field.pos as u128);
+            let mask = lit(field.mask());
+            gen_field(field, quote! { ((#bits >> #pos) & #mask) }, &x, lit, value)
+        })
+        .collect();
+    if value.rest {
+        let any_mask = lit(pattern.any_mask());
+        args.push(quote! { (#bits & #any_mask) });
+    }
+    quote! { (#(#args),*) }
+}
+
+/// Masked `bits` of `field`, of the type `x`, converted to the type of the
+/// field.
+///
+/// With `flags`, runs of one bit are `bool`. Uppercase runs are
+/// sign-extended to the signed type of the same width. With `narrow`, the
+/// type is the smallest one holding the run. With `exact`, each run has the
+/// type of its width from `arbitrary-int`, or the primitive type of that
+/// width. The runs are masked, so the range check of `new` is always true and
+/// is optimized out.
+pub(crate) fn gen_field(
+    field: &pattern::Field,
+    bits: TokenStream,
+    x: &Ident,
+    lit: &dyn Fn(u128) -> proc_macro2::Literal,
+    value: &Value,
+) -> TokenStream {
+    if value.flags && field.width == 1 {
+        let zero = lit(0);
+        return quote! { (#bits != #zero) };
+    }
+    if value.exact {
+        return gen_exact(bits, field.width);
+    }
+    let narrow = if value.narrow {
+        Ty::from_len(field.width as usize).map(Ty::ident)
+    } else {
+        None
+    };
+    let x = narrow.as_ref().unwrap_or(x);
+    if field.signed() {
+        gen_signed(bits, field.width, lit, &signed_ident(x))
+    } else if narrow.is_some() {
+        quote! { (#bits as #x) }
+    } else {
+        bits
+    }
+}
+
+/// Signed type of the same width as the unsigned type `x`.
+pub(crate) fn signed_ident(x: &Ident) -> Ident {
+    Ident::new(&x.to_string().replacen('u', "i", 1), x.span())
+}
+
+/// Masked `value` of a field of `width` bits, sign-extended from its top bit
+/// and converted to `signed`.
+///
+/// Flipping the sign bit and subtracting it leaves a positive value and
+/// borrows through the high bits of a negative one.
+pub(crate) fn gen_signed(
+    value: TokenStream,
+    width: u32,
+    lit: &dyn Fn(u128) -> proc_macro2::Literal,
+    signed: &Ident,
+) -> TokenStream {
+    let sign = lit(1 << (width - 1));
+    quote! { ((#value ^ #sign).wrapping_sub(#sign) as #signed) }
+}
+
+/// Masked `value` of a field of `width` bits, as the type of that width.
+fn gen_exact(value: TokenStream, width: u32) -> TokenStream {
+    // The storage of `arbitrary-int` types is the smallest primitive holding
+    // them.
+    let storage = Ty::from_len(width as usize).unwrap();
+    let x = storage.ident();
+    let value = quote! { #value as #x };
+    if storage.bits() == width as usize {
+        value
+    } else {
+        let exact = Ident::new(&format!("u{}", width), Span::call_site());
+        quote! { ::arbitrary_int::#exact::new(#value) }
+    }
+}
+
+/// Runs of letters extracted from `bits`, with uppercase runs sign-extended
+/// like `bitpattern!`.
+pub(crate) fn gen_fields(pattern: &Pattern, ty: Ty, bits: &Ident) -> Vec<TokenStream> {
+    let x = ty.ident();
+    pattern
+        .fields
+        .iter()
+        .map(|field| {
+            let pos = ty.lit(field.pos as u128);
+            let mask = ty.lit(field.mask());
+            let run = quote! { ((#bits >> #pos) & #mask) };
+            if field.signed() {
+                gen_signed(run, field.width, &|v| ty.lit(v), &signed_ident(&x))
+            } else {
+                run
+            }
+        })
+        .collect()
+}
+
+/// bitwise pattern matching, reporting the bits that didn't match.
+///
+/// `bitmatch_result!("0101 aa??", x)` takes the arguments of `bitpattern!`
+/// and returns `Ok` with its fields, or `Err` with a `BitMismatch`, a struct
+/// declared by each call since a proc-macro crate can't export it. It has
+/// the fields `mismatch`, the bits of the pattern the value disagrees with,
+/// `mask`, the bits the pattern checks, and `value`, all of the pattern
+/// type, and `high_bits`, and implements `Debug` and `Display`. It matches
+/// the same values as `bitpattern!` with the same fields.
+///
+/// A value with bits set above the pattern doesn't match without `low`. Its
+/// `value` is truncated to the pattern type, so `mismatch` can be `0`, and
+/// `high_bits` is `true` instead. Byte slices, `(u128, u128)` and `letter as
+/// Type` aren't supported.
+///
+///```rust
+/// use bitpattern::bitmatch_result;
+///
+/// assert_eq!(bitmatch_result!("0101 aa??", 0x5bu8).ok(), Some(2));
+///
+/// let error = bitmatch_result!("0101 aa??", 0x9bu8).unwrap_err();
+/// assert_eq!((error.mismatch, error.mask, error.value), (0xc0, 0xf0, 0x9b));
+/// assert_eq!(
+///     error.to_string(),
+///     "bits 0xc0 of 0x9b don't match the pattern (mask 0xf0)"
+/// );
+///
+/// let error = bitmatch_result!("0101 aa??", 0x15bu16).unwrap_err();
+/// assert_eq!((error.mismatch, error.value, error.high_bits), (0, 0x5b, true));
+/// assert_eq!(error.to_string(), "bits above the pattern are set");
+///```
+#[proc_macro]
+pub fn bitmatch_result(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut input = syn::parse_macro_input!(input as BitpatternInput);
+    match &mut input.value {
+        Some(value) => value.result = true,
+        None => {
+            return syn::Error::new(
+                input.pattern.span(),
+                "`bitmatch_result!` needs a value to match",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    gen_pattern(input)
+}
+
+/// bitwise pattern matching into existing variables.
+///
+/// `bitmatch_into!("oo dd iiii", word, opcode, dest, imm)` takes the
+/// options, pattern and value of `bitpattern!`, then a place for each run of
+/// a letter in order, or `place = letter` for some of them. If the value
+/// matches, the fields are assigned to the places and it evaluates to
+/// `true`, and otherwise the places are untouched and it's `false`. A number
+/// of places other than the runs is an error.
+///
+/// Each place must have the type of its field, so a narrower variable is a
+/// type error rather than truncated. With `narrow`, each field has the
+/// smallest type holding it.
+///
+///```rust
+/// use bitpattern::bitmatch_into;
+///
+/// let (mut opcode, mut dest, mut imm) = (0u8, 0u8, 0u8);
+/// assert!(bitmatch_into!("oo dd iiii", 0x96u8, opcode, dest, imm));
+/// assert_eq!((opcode, dest, imm), (2, 1, 6));
+///
+/// assert!(!bitmatch_into!("1o dd iiii", 0x45u8, opcode, dest, imm));
+/// assert_eq!((opcode, dest, imm), (2, 1, 6));
+///
+/// let mut regs = [0u8; 2];
+/// assert!(bitmatch_into!(narrow, "0000 aaaa bbbb ????", 0x0ab0u16, regs[1] = a, regs[0] = b));
+/// assert_eq!(regs, [0xb, 0xa]);
+///```
+#[proc_macro]
+pub fn bitmatch_into(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as assign::IntoInput);
+    assign::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise matching of a volatile register.
+///
+/// `bitmatch_reg!("e??? tttt rrrr ????", &reg)` reads the register behind a
+/// reference to an integer once with `read_volatile`, then matches and
+/// extracts the value like `bitpattern!`. The reference is evaluated once,
+/// so registers cleared by reading are safe to match.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_reg;
+///
+/// let status = 0x8a50u16;
+/// assert_eq!(bitmatch_reg!("e??? tttt rrrr ????", &status), Some((1, 0xa, 5)));
+/// assert_eq!(bitmatch_reg!("0??? ???? ???? ????", &status), None);
+///```
+#[proc_macro]
+pub fn bitmatch_reg(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    volatile::gen_code(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise matching of a volatile register behind a raw pointer.
+///
+/// `bitmatch_reg_ptr!("...", ptr)` is like `bitmatch_reg!`, but reads a raw
+/// pointer like `*const u32`. The read is unsafe, so the macro must be used
+/// in an `unsafe` block, where the caller states why the pointer is valid.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_reg_ptr;
+///
+/// let status = 0x8000_0005u32;
+/// let ptr: *const u32 = &status;
+/// // SAFETY: `ptr` points to a live `u32`.
+/// let found = unsafe { bitmatch_reg_ptr!("1??? ???? ???? ???? ???? ???? ???? aaaa", ptr) };
+/// assert_eq!(found, Some(5));
+///```
+#[proc_macro]
+pub fn bitmatch_reg_ptr(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    volatile::gen_code(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise extracting without matching.
+///
+/// This is the same as `bitpattern!`, but returns the extracted value
+/// directly instead of an `Option`. The '0' and '1' bits are ignored like
+/// '?', so the pattern of an earlier `bitpattern!` can be reused. Bits of `x`
+/// above the pattern are ignored too, like `bitpattern!(low, "...", x)`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitextract;
+///
+/// let x = 0xacu8; // 10101100
+///
+/// assert_eq!(bitextract!("1aaa_a100", x), 5);
+/// assert_eq!(bitextract!("1aa0_aa00", x), (1, 3));
+/// assert_eq!(bitextract!("???? ??aa", x), 0);
+/// assert_eq!(bitextract!("0000 0000", x), ());
+/// assert_eq!(bitextract!("aaaa", x), 0xc);
+///```
+#[proc_macro]
+pub fn bitextract(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    input
+        .parse_pattern()
+        .map(|(pattern, ty)| {
+            let x = ty.ident();
+            let value = Ident::new("value", Span::mixed_site());
+            let bits = Ident::new("bits", Span::mixed_site());
+            let binding = gen_binding(&value, &x, &input.value);
+            let word = pattern::gen_word(&value, ty);
+            if pattern.fields.is_empty() {
+                return quote! { { #binding let _ = #word; } };
+            }
+            let args = gen_fields(&pattern, ty, &bits);
+            quote! {
+                {
+                    #binding
+                    let #bits = #word;
+                    (#(#args),*)
+                }
+            }
+        })
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise extracting of bit ranges.
+///
+/// `bitrange!(x[14:12])` is bits 14 to 12 of `x`, like `(x >> 12) & 0b111`.
+/// Several ranges like `bitrange!(x[31:25], x[11:7])` are a tuple, and ranges
+/// joined by `++` like `bitrange!(x[31:25] ++ x[11:7])` are concatenated with
+/// the first one as the most significant bits. The result has the type of
+/// the value.
+///
+/// The bounds must be integer literals with `hi >= lo`. A leading type like
+/// `bitrange!(u32; x[31:25])` also checks that the ranges are within it, and
+/// the values must have that type.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitrange;
+///
+/// let insn = 0x00a1_2423u32; // sw x10, 8(x2)
+/// assert_eq!(bitrange!(insn[14:12]), 0b010);
+/// assert_eq!(bitrange!(insn[24:20], insn[19:15]), (10, 2));
+/// assert_eq!(bitrange!(u32; insn[31:25] ++ insn[11:7]), 8);
+///```
+#[proc_macro]
+pub fn bitrange(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as range::RangeInput);
+    range::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern testing.
+///
+/// `bitmatches!("1111 ????", x)` is true if the '0' and '1' bits of the
+/// pattern match `x`, like `bitpattern!("1111 ????", x).is_some()` without
+/// extracting anything. Letters are don't-care bits like '?'. Like
+/// `bitpattern!`, `x` doesn't match if it has bits set above the pattern,
+/// unless it's given as `bitmatches!(low, "...", x)`. The expansion is a
+/// constant expression if `x` is constant, so it can be used in `const`
+/// items and in `match` guards.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatches;
+///
+/// assert!(bitmatches!("1111 ????", 0xf3u8));
+/// assert!(!bitmatches!("1111 ????", 0x73u8));
+/// assert!(bitmatches!("10aa", 0b1011u8));
+/// assert!(!bitmatches!("1010 1100", 0xffff_ffacu32));
+/// assert!(bitmatches!(low, "1010 1100", 0xffff_ffacu32));
+///
+/// const IS_JAL: bool = bitmatches!("????????????????????????? 1101111", 0x0000_00efu32);
+/// assert!(IS_JAL);
+///```
+#[proc_macro]
+pub fn bitmatches(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let pattern::LowInput { low, input } = syn::parse_macro_input!(input as pattern::LowInput);
+    input
+        .parse_pattern()
+        .map(|(pattern, ty)| {
+            let x = ty.ident();
+            let value = Ident::new("value", Span::mixed_site());
+            let binding = gen_binding(&value, &x, &input.value);
+            let mut checks = Vec::new();
+            if !low {
+                checks.push(pattern::gen_fits(&value, pattern.len() as u32));
+            }
+            let word = pattern::gen_word(&value, ty);
+            if pattern.mask() != 0 {
+                let bit_mask = ty.lit(pattern.mask());
+                let bit_pattern = ty.lit(pattern.value());
+                checks.push(quote! { #word & #bit_mask == #bit_pattern });
+            }
+            if checks.is_empty() {
+                return quote! { { #binding let _ = #word; true } };
+            }
+            quote! { { #binding #(#checks)&&* } }
+        })
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise classification by the first matching pattern.
+///
+/// `bitmatch_any!(x, ["000? ????", "001? ????", "01?? ????"])` is the index of
+/// the first pattern whose '0' and '1' bits match `x`, or `None`. `x` is
+/// evaluated once, and letters are ignored like '?'. All patterns must have
+/// the same length, which selects the type like `bitpattern!`. A value with
+/// bits set above the patterns matches none of them, unless it's given as
+/// `bitmatch_any!(low, x, [...])`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_any;
+///
+/// let class = |x: u8| bitmatch_any!(x, ["000? ????", "001? ????", "01?? ????"]);
+/// assert_eq!(class(0x1f), Some(0));
+/// assert_eq!(class(0x20), Some(1));
+/// assert_eq!(class(0x7f), Some(2));
+/// assert_eq!(class(0x80), None);
+/// assert_eq!(bitmatch_any!(0x1a6u32, ["1??? ????"]), None);
+/// assert_eq!(bitmatch_any!(low, 0x1a6u32, ["1??? ????"]), Some(0));
+///```
+#[proc_macro]
+pub fn bitmatch_any(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as check::AnyInput);
+    check::gen_any(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise classification by all matching patterns.
+///
+/// `bitmatch_all!(x, ["...", ...])` is like `bitmatch_any!`, but is a `u32`
+/// with bit `i` set if pattern `i` matches, so there can be at most 32
+/// patterns. It's 0 if the value has bits set above the patterns, unless
+/// `low` is given.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_all;
+///
+/// let x = 0b1010_0000u8;
+/// assert_eq!(bitmatch_all!(x, ["1??? ????", "?0?? ????", "???? ???1"]), 0b011);
+///```
+#[proc_macro]
+pub fn bitmatch_all(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as check::AnyInput);
+    check::gen_all(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise comparison under a pattern.
+///
+/// `biteq!("1111 ??aa", x, y)` is true if `x` and `y` are equal on all bits
+/// of the pattern except the '?' bits, so they have the same '0' and '1'
+/// bits and the same letters. `biteq!(fixed, "1111 ??aa", x, y)` compares
+/// only the '0' and '1' bits. Like `bitmatches!`, it's false if either value
+/// has bits set above the pattern, unless `low` is given, and the expansion
+/// is a constant expression if the values are constant.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::biteq;
+///
+/// assert!(biteq!("1111 ??aa", 0xf3u8, 0xffu8));
+/// assert!(!biteq!("1111 ??aa", 0xf3u8, 0xf2u8));
+/// assert!(biteq!(fixed, "1111 ??aa", 0xf3u8, 0xf2u8));
+/// assert!(!biteq!("1010 ????", 0x1a6u32, 0xa6u32));
+/// assert!(biteq!(low, "1010 ????", 0x1a6u32, 0xa6u32));
+///
+/// const SAME: bool = biteq!("aaaa ????", 0x12u8, 0x1fu8);
+/// assert!(SAME);
+///```
+#[proc_macro]
+pub fn biteq(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as check::EqInput);
+    check::gen_eq(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise overlap check of two patterns.
+///
+/// `bitoverlap!("000 ??aa", "0?0 ??bb")` is `true` if a value can match both
+/// patterns, which is if their '0' and '1' bits agree wherever both have one,
+/// and `false` otherwise. Letters are ignored like '?'. The patterns must
+/// have the same length. It's computed when the macro is expanded, so the
+/// expansion is a plain `true` or `false` usable in `const` assertions.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitoverlap;
+///
+/// const _: () = assert!(!bitoverlap!("000 ??aa", "001 ??bb"));
+/// const _: () = assert!(bitoverlap!("000 ??aa", "0?? 1???"));
+///```
+#[proc_macro]
+pub fn bitoverlap(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as check::PairInput);
+    check::gen_overlap(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise intersection of two patterns.
+///
+/// `bitintersect!("ooo fff 01", "??? 000 ??")` is a `&'static str` literal of
+/// the pattern matching the values that match both patterns. Each bit is a
+/// '0' or '1' bit of either pattern, or else the letter of the first one, or
+/// else a letter or '?' of the second one. The result keeps the separators of
+/// the first pattern.
+///
+/// The patterns must have the same length. It's an error if they have
+/// different '0' and '1' bits at the same position, or if a letter of the
+/// second pattern used in the result is also in the first one.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitintersect;
+///
+/// const ADDI: &str = bitintersect!(
+///     "iiiiiiiiiiii aaaaa fff ddddd 0010011",
+///     "???????????? ????? 000 ????? ???????"
+/// );
+/// assert_eq!(ADDI, "iiiiiiiiiiii aaaaa 000 ddddd 0010011");
+/// assert_eq!(bitintersect!("1a?? ????", "?b?1 cc??"), "1a?1 cc??");
+///```
+#[proc_macro]
+pub fn bitintersect(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as check::PairInput);
+    check::gen_intersect(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern check of a constant.
+///
+/// `#[bitcheck("0000000 ????? ????? 000 ????? 0110011")]` on a `const` or
+/// `static` item adds a `const` assertion that the '0' and '1' bits of the
+/// pattern match its value, so compilation fails naming the item if they
+/// drift apart. Letters are ignored like '?'. The value is checked by const
+/// evaluation in its own type, so it can be any constant expression. It
+/// mustn't have bits set above the pattern either, unless it's given as
+/// `#[bitcheck(low, "...")]`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitcheck;
+///
+/// #[bitcheck("0000000 ????? ????? 000 ????? 0110011")]
+/// const OPC_ADD: u32 = 0x33;
+///
+/// #[bitcheck("0100000 ????? ????? 000 ????? 0110011")]
+/// static OPC_SUB: u32 = OPC_ADD | 0x4000_0000;
+///
+/// #[bitcheck(low, "1??? aaaa")]
+/// const FLAGS: u32 = 0xffff_ffa5;
+///```
+#[proc_macro_attribute]
+pub fn bitcheck(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    check::gen_bitcheck(attr.into(), item.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern assertion.
+///
+/// `bitassert!("0101 aa??", x)` panics if the '0' and '1' bits of the pattern
+/// don't match `x`, or if `x` has bits set above the pattern, unless it's
+/// given as `bitassert!(low, "...", x)`. The message shows the value in binary laid out like the
+/// pattern, marks the mismatched bits, and lists the values of the letters,
+/// which are otherwise ignored:
+///
+///```text
+/// assertion failed: bit pattern mismatch
+///  pattern: 0101 aa??
+///   actual: 0111 1000
+///             ^
+///   fields: a = 0b10
+///```
+///
+/// The message is written with `core::fmt`, so it works in `no_std`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitassert;
+///
+/// bitassert!("0101 aa??", 0x5au8);
+/// bitassert!(low, "0101 aa??", 0x15au32);
+///```
+///
+///```rust,should_panic
+/// use bitpattern::bitassert;
+///
+/// bitassert!("0101 aa??", 0x78u8);
+///```
+#[proc_macro]
+pub fn bitassert(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::LowInput);
+    check::gen_assert(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern assertion in debug builds.
+///
+/// This is the same as `bitassert!`, but only checked if `debug_assertions`
+/// are enabled, like `debug_assert!`.
+#[proc_macro]
+pub fn debug_bitassert(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::LowInput);
+    check::gen_assert(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise mask and value of a pattern.
+///
+/// `bitmask!("...")` is a tuple of the mask of the '0' and '1' bits and their
+/// value, so `x & mask == value` if the pattern matches. Letters are ignored
+/// like '?'. The type is selected by the pattern length like `bitpattern!`,
+/// or given by a second argument like `bitmask!("...", u64)`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmask;
+///
+/// const ADD: (u32, u32) = bitmask!("0000000 ????? ????? 000 ????? 0110011");
+/// assert_eq!(ADD, (0xfe00_707f, 0x0000_0033));
+///
+/// static FLAG: (u64, u64) = bitmask!("1 aaaa", u64);
+/// assert_eq!(FLAG, (0x10, 0x10));
+///```
+#[proc_macro]
+pub fn bitmask(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::MaskInput);
+    consts::gen_mask(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise description of a pattern.
+///
+/// `bitexplain!("ooo aabb")` is a value of a local struct with the fields
+/// `pattern`, the string itself, `len`, the number of bits, `mask` and
+/// `value`, like `bitmask!`, and `fields`, an array of the letter, shift and
+/// width of each run of letters, like `bitpattern!` extracts them. It's a
+/// constant expression, and the struct implements `Debug`.
+/// The type is optional like `bitmask!`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitexplain;
+///
+/// let explain = bitexplain!("101 aabb");
+/// assert_eq!((explain.mask, explain.value), (0x70, 0x50));
+/// assert_eq!(explain.fields, [('a', 2, 2), ('b', 0, 2)]);
+/// println!("{:?}", explain);
+///```
+#[proc_macro]
+pub fn bitexplain(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::MaskInput);
+    consts::gen_explain(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise list of the fields of a value.
+///
+/// `bitfields_iter!("ee pp? aaaa", x)` is an array of `(char, u32)` with
+/// each letter and its value extracted from `x`, in the order the letters
+/// first appear in the pattern. The '0' and '1' bits aren't checked, and
+/// letters can have at most 32 bits. Its length is the number of letters, so
+/// one function can dump the fields of any register without allocating.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitfields_iter;
+///
+/// let fields = bitfields_iter!("ee pp? aaaa", 0x1d3u16);
+/// assert_eq!(fields, [('e', 0b11), ('p', 0b10), ('a', 0b0011)]);
+///
+/// for (letter, value) in bitfields_iter!("10aa bbbb", 0u8) {
+///     println!("{}: {:#x}", letter, value);
+/// }
+///```
+#[proc_macro]
+pub fn bitfields_iter(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    consts::gen_fields_iter(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise register map.
+///
+/// `bitspec! { pub CTRL(u32) = "..."; }` defines a module for each register,
+/// named by the register in snake case like `ctrl`. It holds the constants
+/// of `bitconst!` without a prefix, like `MASK` and `E_SHIFT`, and the
+/// `const fn`s `matches` testing the '0' and '1' bits, and `extract_e` and
+/// `update_e` for each letter. Attributes like doc comments are put on the
+/// module.
+///
+/// The type of each register is required, and a letter split into several
+/// runs is an error like `bitconst!`.
+///
+/// # Example
+///
+///```rust
+/// mod regs {
+///     bitpattern::bitspec! {
+///         /// Control register.
+///         pub CTRL(u32) = "e??? pppp ???? ???? ???? ???? ???? mmmm";
+///         pub UART_STATUS(u16) = "0000 ???? tttt rrrr";
+///     }
+/// }
+/// use regs::{ctrl, uart_status};
+///
+/// assert_eq!(ctrl::P_SHIFT, 24);
+/// assert_eq!(ctrl::extract_p(0x0a00_0000), 0xa);
+/// assert_eq!(ctrl::update_m(0x8000_0000, 3), 0x8000_0003);
+/// assert_eq!(uart_status::MASK, 0xf000);
+/// assert!(uart_status::matches(0x0fff));
+///```
+#[proc_macro]
+pub fn bitspec(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::SpecInput);
+    consts::gen_spec(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise constants of a pattern.
+///
+/// `bitconst!(pub CTRL, "10ee ?aaa", u8);` defines the constants `CTRL_MASK`
+/// and `CTRL_VALUE` like `bitmask!`, and for each letter, `CTRL_E_SHIFT` and
+/// `CTRL_E_WIDTH` of type `u32` and `CTRL_E_MASK`, the mask of the field
+/// before shifting. The constants have the visibility given, and the type is
+/// optional like `bitmask!`. A letter split into several runs is an error,
+/// and so are both cases of a letter, as they'd name the same constants.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitconst;
+///
+/// bitconst!(pub CTRL, "10ee ?aaa", u8);
+///
+/// assert_eq!((CTRL_MASK, CTRL_VALUE), (0xc0, 0x80));
+/// assert_eq!((CTRL_E_SHIFT, CTRL_E_WIDTH, CTRL_E_MASK), (4, 2, 0x3));
+/// assert_eq!((CTRL_A_SHIFT, CTRL_A_WIDTH, CTRL_A_MASK), (0, 3, 0x7));
+///
+/// let x = 0xb5u8;
+/// assert_eq!((x >> CTRL_E_SHIFT) & CTRL_E_MASK, 3);
+///```
+#[proc_macro]
+pub fn bitconst(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::ConstInput);
+    consts::gen_const(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern functions.
+///
+/// `bitfns!(pub fn lui, "????????????????????????? 0110111", u32);` defines
+/// `is_lui`, returning true if the value matches like `bitmatches!`. If the
+/// pattern has letters, it also defines `extract_lui`, extracting them like
+/// `bitextract!`, and `lui`, returning them if the value matches like
+/// `bitpattern!`, so uppercase runs are sign-extended. The functions are `const fn` with the visibility given, and
+/// take the type given, which is optional like `bitmask!`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitfns;
+///
+/// bitfns!(pub fn lui, "iiiiiiiiiiiiiiiiiiii ddddd 0110111", u32);
+/// bitfns!(fn ecall, "0000000 00000 00000 000 00000 1110011");
+///
+/// assert!(is_lui(0x1234_50b7));
+/// assert_eq!(extract_lui(0x1234_50b7), (0x12345, 1));
+/// assert_eq!(lui(0x1234_50b7), Some((0x12345, 1)));
+/// assert_eq!(lui(0x73), None);
+/// assert!(is_ecall(0x73));
+///```
+#[proc_macro]
+pub fn bitfns(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::FnsInput);
+    consts::gen_fns(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise packing, the inverse of `bitpattern!`.
+///
+/// The expansion is a constant expression if all values are constant,
+/// so it can be used in `const` and `static` items and in `const fn`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitpack;
+///
+/// // '0' and '1' are set literally, and '?' is set to 0.
+/// // Each field takes the low bits of the supplied value.
+/// assert_eq!(bitpack!("ooo aabb", o = 0b101, a = 2, b = 1), 0x59u8);
+/// assert_eq!(bitpack!("1?aa_00bb", a = 3, b = 6), 0xb2u8);
+///
+/// // Values can be given in order of field appearance.
+/// assert_eq!(bitpack!("ooo aabb", 0b101, 2, 1), 0x59u8);
+///
+/// // If a field is split, the earlier runs take the more significant bits.
+/// assert_eq!(bitpack!("aaa0 bbaa", a = 0b10110, b = 2), 0xaau8);
+///
+/// // A trailing `default = 1` sets '?' bits to 1, and `default = x` copies
+/// // them from x. This is useful for read-modify-write of registers.
+/// assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = 1), 0xfcu8);
+/// let old = 0x12u8;
+/// assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = old), 0xf4u8);
+///
+/// const ADD: u32 = bitpack!("0000000 bbbbb aaaaa 000 ddddd 0110011", a = 1, b = 2, d = 3);
+/// assert_eq!(ADD, 0x0020_81b3);
+///```
+#[proc_macro]
+pub fn bitpack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::PackInput);
+    pack::gen_code(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise packing with range checking.
+///
+/// This is the same as `bitpack!`, but returns `None` if any value has bits
+/// set above the width of its field.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitpack_checked;
+///
+/// assert_eq!(bitpack_checked!("1aaa b000", 5, 1), Some(0xd8u8));
+/// assert_eq!(bitpack_checked!("1aaa b000", a = 9, b = 1), None);
+///```
+#[proc_macro]
+pub fn bitpack_checked(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::PackInput);
+    pack::gen_code(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern formatting.
+///
+/// `bitfmt!("vv_pp_e ???? aaaa", x)` wraps the value in an adapter writing it
+/// laid out like the pattern. `Display` writes each bit, with '?' bits as `-`
+/// and '0' and '1' bits that don't match in brackets. A trailing `any_bits`
+/// writes the '?' bits too. The alternate form `{:#}` writes the value of
+/// each letter instead, and `Debug` writes the letters with fields narrower
+/// than a byte in binary, and wider ones in hex.
+///
+/// The adapter doesn't allocate. With the `defmt` feature, a trailing `defmt`
+/// implements `defmt::Format` for it like `Debug`, which needs `defmt` as a
+/// dependency of the caller.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitfmt;
+///
+/// let fmt = bitfmt!("vv_pp_e ???? aaaa", 0x130bu16);
+/// assert_eq!(fmt.to_string(), "10_01_1 ---- 1011");
+/// assert_eq!(format!("{:#}", fmt), "v=2 p=1 e=1 a=0xB");
+/// assert_eq!(format!("{:?}", fmt), "v: 0b10, p: 0b1, e: 0b1, a: 0b1011");
+///
+/// assert_eq!(bitfmt!("10?? aaaa", 0x7bu8, any_bits).to_string(), "[0][1]11 1011");
+///```
+#[proc_macro]
+pub fn bitfmt(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as debug::FmtInput);
+    debug::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise destructuring into local variables.
+///
+/// `bitlet!("ooo ddd ii" = x);` binds each letter of the pattern to the
+/// value extracted from `x`, like `let "ooo ddd ii" = x;` in `#[bitmatch]`.
+/// If the pattern has '0' or '1' bits, it needs an `else` branch, which
+/// runs if they don't match and must diverge. A value with bits set above
+/// the pattern runs `else` as well, unless it's given as
+/// `bitlet!(low, "..." = x)`. Without `else`, those bits are dropped like
+/// `bitextract!`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitlet;
+///
+/// fn decode(x: u8) -> Result<(u8, u8), ()> {
+///     bitlet!("10 aa bbbb" = x, else return Err(()));
+///     Ok((a, b))
+/// }
+///
+/// assert_eq!(decode(0b1001_0011), Ok((1, 3)));
+/// assert_eq!(decode(0b0001_0011), Err(()));
+/// assert_eq!(decode(0xff), Err(()));
+///
+/// bitlet!("ooo ddd ii" = 0b1010_1011u8);
+/// assert_eq!((o, d, i), (5, 2, 3));
+///```
+#[proc_macro]
+pub fn bitlet(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as bitmatch::LetInput);
+    bitmatch::gen_bitlet(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise read-modify-write of fields.
+///
+/// `bitset_fields!("???? aa?b", reg, a = 2, b = 1)` is `reg` with the bits of
+/// the named letters replaced by the low bits of their values, and all other
+/// bits unchanged. Letters that aren't named are unchanged too. The '0' and
+/// '1' bits aren't written, but are checked on the old value by
+/// `debug_assert!`.
+///
+/// The result has the type of `reg`, which can be wider than the pattern,
+/// whose bits are then the low bits of `reg`. The values must have that type
+/// too, and a pattern which doesn't fit in it is a compile error.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitset_fields;
+///
+/// let mut reg = 0xffu8;
+/// reg = bitset_fields!("???? aa?b", reg, a = 2, b = 0);
+/// assert_eq!(reg, 0b1111_1010);
+///
+/// // All runs of a split letter are written.
+/// let reg = bitset_fields!("aa?? ?aa1", 0x01u8, a = 0b1011);
+/// assert_eq!(reg, 0b1000_0111);
+///
+/// let reg = bitset_fields!("???? aa?b", 0xdead_beefu32, a = 2, b = 0);
+/// assert_eq!(reg, 0xdead_beea);
+///```
+#[proc_macro]
+pub fn bitset_fields(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::SetInput);
+    pack::gen_set(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise atomic update of fields.
+///
+/// `bitcas!("???? aa?b", &atomic, a = 2, b = 1, Ordering::AcqRel)` replaces
+/// the bits of the named letters of an atomic integer like `bitset_fields!`,
+/// keeping concurrent updates of the other bits, and returns the previous
+/// value. The pattern length selects the integer type like `bitpattern!`, so
+/// it must be the width of the atomic.
+///
+/// The update is a compare-exchange loop with the given ordering, and the
+/// loads use it without its release part like `fetch_update`. The values are
+/// evaluated once, before the loop.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitcas;
+/// use std::sync::atomic::{AtomicU8, Ordering};
+///
+/// let state = AtomicU8::new(0xff);
+/// let prev = bitcas!("???? aa?b", &state, a = 2, b = 0, Ordering::AcqRel);
+/// assert_eq!(prev, 0xff);
+/// assert_eq!(state.load(Ordering::Relaxed), 0b1111_1010);
+///```
+#[proc_macro]
+pub fn bitcas(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::CasInput);
+    pack::gen_cas(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise merging of fields.
+///
+/// `bitsplice!("??aa bb??", old, new)` is `old` with the bits of the letters
+/// taken from the same bits of `new`. With named values like
+/// `bitsplice!("??aa bb??", old, a = 1, b = x)`, the bits of each named
+/// letter are taken from the low bits of its value like `bitpack!`, and other
+/// letters are kept from `old`. The '?' bits are kept from `old`, and the '0'
+/// and '1' bits are set to the pattern.
+///
+/// Like `bitset_fields!`, the result has the type of `old`, whose bits above
+/// a shorter pattern are kept too, and `new` or the values must have that
+/// type.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitsplice;
+///
+/// assert_eq!(bitsplice!("??aa bb??", 0x00u8, 0xffu8), 0x3c);
+/// assert_eq!(bitsplice!("??aa bb??", 0xffu8, a = 0, b = 1), 0xc7);
+/// assert_eq!(bitsplice!("10?? aaaa", 0x7fu8, 0x00u8), 0xb0);
+/// assert_eq!(bitsplice!("???? aa??", 0xdead_beefu32, 0u32), 0xdead_bee3);
+///```
+#[proc_macro]
+pub fn bitsplice(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::SpliceInput);
+    pack::gen_splice(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise clearing of fields.
+///
+/// `bitclear!("???? aa??", reg)` is `reg` with the bits of all letters of the
+/// pattern cleared. If letters are given like `bitclear!("aabb", reg, b)`,
+/// only their bits are cleared. Other bits are unchanged, including those
+/// of a `reg` wider than the pattern, and the result has the type of `reg`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitclear;
+///
+/// assert_eq!(bitclear!("???? aa??", 0xffu8), 0xf3);
+/// assert_eq!(bitclear!("aabb", 0xfu8, b), 0xc);
+/// assert_eq!(bitclear!("???? aa??", 0xdead_beefu32), 0xdead_bee3);
+///```
+#[proc_macro]
+pub fn bitclear(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::MaskInput);
+    pack::gen_mask(input, pack::MaskOp::Clear)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise toggling of fields.
+///
+/// This is the same as `bitclear!`, but flips the bits instead.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bittoggle;
+///
+/// assert_eq!(bittoggle!("???? ??bb", 0x5au8), 0x59);
+/// assert_eq!(bittoggle!("aabb", 0x5u8, a), 0x9);
+/// assert_eq!(bittoggle!("???? aa??", 0xdead_beefu32), 0xdead_bee3);
+///```
+#[proc_macro]
+pub fn bittoggle(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::MaskInput);
+    pack::gen_mask(input, pack::MaskOp::Toggle)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise counting of set bits in fields.
+///
+/// `bitcount!("?aa? a?aa", x)` is the number of set bits of `x` among the
+/// bits of the letters, or of the given letters like `bitcount!("...", x, a)`.
+/// The '0', '1' and '?' bits aren't counted. The result is a `u32` like
+/// `count_ones`, and the expansion is a constant expression if `x` is
+/// constant.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitcount;
+///
+/// assert_eq!(bitcount!("?aa? a?aa", 0xffu8), 5);
+/// assert_eq!(bitcount!("11aa bbbb", 0x3cu8, a), 2);
+///
+/// const PARITY: u32 = bitcount!("aaaa ?bbb", 0xf1u8, b) % 2;
+/// assert_eq!(PARITY, 1);
+///```
+#[proc_macro]
+pub fn bitcount(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::MaskInput);
+    pack::gen_mask(input, pack::MaskOp::Count)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise permutation between two patterns.
+///
+/// `bitpermute!("aabb ccdd" => "ddcc bbaa", x)` extracts the letters of `x`
+/// by the first pattern and packs them by the second one. Each letter of the
+/// second pattern must have the same width in the first one, and letters
+/// only in the first one are dropped. The '0' and '1' bits of the second
+/// pattern are set and its '?' bits are 0. The type is selected by the
+/// length of the second pattern like `bitpack!`.
+///
+/// The '0' and '1' bits of the first pattern are checked, so the result is
+/// an `Option` like `bitpattern!` if it has any. It's `None` if `x` has bits
+/// set above the first pattern too, unless it's given as
+/// `bitpermute!(low, "..." => "...", x)`. Without any, those bits are
+/// dropped.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitpermute;
+///
+/// assert_eq!(bitpermute!("aabb ccdd" => "ddcc bbaa", 0b0110_1100u8), 0b0011_1001);
+/// assert_eq!(bitpermute!("10aa aaaa" => "1111 aaaa aa00", 0x8bu8), Some(0xf2cu16));
+/// assert_eq!(bitpermute!("10aa aaaa" => "1111 aaaa aa00", 0x4bu8), None);
+///```
+#[proc_macro]
+pub fn bitpermute(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pack::PermuteInput);
+    pack::gen_permute(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise search of a pattern at any offset.
+///
+/// `bitscan!("0 dddddddd 11", word, u64)` tries the pattern at each offset
+/// of a `u64` from the lowest one, and returns `Some((offset, d))` with the
+/// letters extracted at the first offset where the '0' and '1' bits match,
+/// or `None`. Offsets where the pattern doesn't fit are never tried.
+/// `bitscan!(msb_first, ...)` tries the highest offset first. The type is
+/// selected by the pattern length if omitted, and the value must have that
+/// type.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitscan;
+///
+/// let word = 0x0000_0000_00ab_3000u64;
+/// assert_eq!(bitscan!("0 dddddddd 11", word, u64), Some((12, 0xac)));
+/// assert_eq!(bitscan!(msb_first, "0 dddddddd 11", word, u64), Some((16, 0x2a)));
+/// assert_eq!(bitscan!("1111", word, u64), None);
+///```
+#[proc_macro]
+pub fn bitscan(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as scan::ScanInput);
+    scan::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise search of a pattern in a byte slice.
+///
+/// `bitfind!("1011 1111 0001 aaaa", buf)` tries the pattern at each bit
+/// offset of `buf`, with the bits of each byte from the most significant
+/// one, and returns `Some((offset, a))` with the letters extracted at the
+/// first match, or `None`. Matches may span several bytes. The offset is in
+/// bits from the start of `buf`, and the letters have the type selected by
+/// the pattern length like `bitpattern!`. Patterns longer than 120 bits are
+/// an error.
+///
+/// It doesn't allocate, so it works in `no_std`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitfind;
+///
+/// let buf = [0x00, 0x0b, 0xf1, 0x50];
+/// assert_eq!(bitfind!("1011 1111 0001 aaaa", buf), Some((12, 0x5)));
+/// assert_eq!(bitfind!("1011 1111 0001 aaaa", buf[2..]), None);
+///```
+#[proc_macro]
+pub fn bitfind(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    scan::gen_find(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise search of all matches of a pattern in a byte slice.
+///
+/// `bitfind_iter!("...", buf)` is an iterator over all matches of
+/// `bitfind!`, by ascending offset. Matches may overlap.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitfind_iter;
+///
+/// let buf = [0b1010_0000, 0b0101_0000];
+/// let offsets: Vec<usize> = bitfind_iter!("1?1", buf).map(|(x,)| x).collect();
+/// assert_eq!(offsets, [0, 9]);
+///```
+#[proc_macro]
+pub fn bitfind_iter(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    scan::gen_find(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise reading of a pattern from a byte slice.
+///
+/// `bitread!("hhh lllll", buf, &mut pos)` reads the bits of `buf` from the
+/// bit position `pos`, a `usize`, and returns the letters like `bitscan!` if
+/// the '0' and '1' bits match, advancing `pos` by the pattern length. It
+/// returns `None` without advancing if they don't match or fewer bits are
+/// left.
+///
+/// The bits of each byte are read from the most significant one, and the
+/// first bit read is the first bit of the pattern. With
+/// `bitread!(lsb_first, ...)`, they are read from the least significant one
+/// like DEFLATE, and the first bit read is the last bit of the pattern.
+/// Patterns longer than 120 bits are an error.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitread;
+///
+/// let buf = [0b1010_1100, 0b0111_0000];
+/// let mut pos = 0;
+/// assert_eq!(bitread!("aaa", buf, &mut pos), Some(0b101));
+/// assert_eq!(bitread!("01 bbbbbb", buf, &mut pos), Some(0b100_011));
+/// assert_eq!(bitread!("1111", buf, &mut pos), None);
+/// assert_eq!(pos, 11);
+///
+/// // A DEFLATE block header: BFINAL, then BTYPE.
+/// let mut pos = 0;
+/// assert_eq!(bitread!(lsb_first, "tt f", [0x05u8], &mut pos), Some((0b10, 1)));
+///```
+#[proc_macro]
+pub fn bitread(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as scan::ReadInput);
+    scan::gen_read(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise matching of a pattern at a bit offset of a byte slice.
+///
+/// `bitpeek!("101 aaaa", buf, offset)` is `bitread!` at the bit offset
+/// `offset`, a `usize` known at runtime, without a position to advance. It
+/// returns `None` if the '0' and '1' bits don't match or the slice ends
+/// before the pattern. Unlike `bitpattern!` with `@`, the offset needn't be
+/// constant.
+///
+/// The bits of each byte are numbered from the most significant one, or
+/// from the least significant one with `bitpeek!(lsb_first, ...)`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitpeek;
+///
+/// let buf = [0x00, 0x00, 0x00, 0x00, 0b0000_0101, 0b1100_0000];
+/// assert_eq!(bitpeek!("101 aaaa", buf, 37), Some(0b1100));
+/// assert_eq!(bitpeek!("101 aaaa", buf, 38), None);
+/// assert_eq!(bitpeek!("101 aaaa", buf, 42), None);
+/// assert_eq!(bitpeek!(lsb_first, "aaa 1", [0b0011_0000u8], 4), Some(0b001));
+///```
+#[proc_macro]
+pub fn bitpeek(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as scan::ReadInput);
+    scan::gen_read(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise search of the bytes matching a pattern.
+///
+/// `bitmatch_simd!("1010 ??11", haystack)` is an iterator over the indices of
+/// the bytes of a slice for which the '0' and '1' bits match, in ascending
+/// order. The pattern has at most 8 bits, and letters are ignored like '?'.
+///
+/// With the "simd" feature, the bytes are compared 16 at a time with SSE2, or
+/// 32 with AVX2, when the target enables them. Other targets, and the bytes
+/// after the last full chunk, are compared one at a time.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_simd;
+///
+/// let haystack = [0xa3u8, 0x00, 0xaf, 0x13, 0xab];
+/// let found: Vec<_> = bitmatch_simd!("1010 ??11", &haystack).collect();
+/// assert_eq!(found, [0, 2, 4]);
+///```
+#[proc_macro]
+pub fn bitmatch_simd(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    scan::gen_simd(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise packed newtype.
+///
+/// `bitnewtype!(pub struct Pte(u64): "...");` defines a `#[repr(transparent)]`
+/// newtype over the integer, whose bits stay packed. For each letter, a
+/// `const fn` getter named after it extracts the letter, returning `bool` for
+/// 1-bit letters, and a `with_` setter returns a copy with the letter
+/// replaced. The methods of an uppercase letter are named in lowercase, so
+/// the pattern can't have both cases of a letter, and its getter
+/// sign-extends it like `bitpattern!`. `from_raw` and `raw` convert from and to the integer, and
+/// `is_valid` checks the '0' and '1' bits. Attributes like docs are passed to
+/// the struct.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitnewtype;
+///
+/// bitnewtype! {
+///     /// Sv39 page table entry.
+///     pub struct Pte(u64): "?????????? pppppppppppppppppppppppppppppppppppppppppppp ?? dagu xwrv"
+/// }
+///
+/// let pte = Pte::from_raw(0).with_p(0x8_0000).with_r(true).with_v(true);
+/// assert_eq!(pte.raw(), 0x2000_0003);
+/// assert_eq!(pte.p(), 0x8_0000);
+/// assert!(pte.v() && pte.r() && !pte.w());
+/// assert!(pte.is_valid());
+///```
+#[proc_macro]
+pub fn bitnewtype(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as newtype::NewtypeInput);
+    newtype::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise newtype of a raw value.
+///
+/// `bitdebug!(pub UartStatus, "???? ef?? tttt rrrr", u32);` defines
+/// `pub struct UartStatus(pub u32)` with a getter like `field_e` for each
+/// letter, and `const fn new`, which checks the '0' and '1' bits if
+/// `debug_assertions` are enabled. `Debug` writes the letters by name like
+/// `bitfmt!`, and `Display` like its alternate form. The type is optional
+/// like `bitmask!`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitdebug;
+///
+/// bitdebug!(pub UartStatus, "???? ef?? tttt rrrr", u32);
+///
+/// let status = UartStatus::new(0x0ba3);
+/// assert_eq!(status.field_t(), 0xa);
+/// assert_eq!(
+///     format!("{:?}", status),
+///     "UartStatus { e: 0b1, f: 0b0, t: 0b1010, r: 0b11 }"
+/// );
+/// assert_eq!(status.to_string(), "e=1 f=0 t=0xA r=0x3");
+///```
+#[proc_macro]
+pub fn bitdebug(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as debug::NewtypeInput);
+    debug::gen_newtype(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern matching in an expression.
+///
+/// `bitswitch!(x { "000 aabb" => f(a, b), _ => g() })` is the same as a
+/// `match` in `#[bitmatch]`: the arms are tried in order, and the letters of
+/// the matching arm are bound in its body. Without a catch-all arm, the
+/// result is wrapped in `Some`, and it's `None` if no arm matches. Values
+/// with bits set above the patterns don't match them, unless it's given as
+/// `bitswitch!(low, x { ... })` like `#[bitmatch(low)]`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitswitch;
+///
+/// let decode = |x: u8| {
+///     bitswitch!(x {
+///         "000 aa bbb" => a + b,
+///         "001 ?? ccc" => c,
+///         _ => 0,
+///     })
+/// };
+/// assert_eq!(decode(0b000_10_011), 5);
+/// assert_eq!(decode(0b001_10_011), 3);
+/// assert_eq!(decode(0xff), 0);
+///
+/// assert_eq!(bitswitch!(0xffu8 { "0??? aaaa" => a }), None);
+/// assert_eq!(bitswitch!(0x1ffu16 { "1??? aaaa" => a }), None);
+/// assert_eq!(bitswitch!(low, 0x1ffu16 { "1??? aaaa" => a }), Some(0xf));
+///```
+#[proc_macro]
+pub fn bitswitch(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as bitmatch::SwitchInput);
+    bitmatch::gen_bitswitch(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise iterator over all values of a pattern.
+///
+/// `bitgen!("10?? aa?1")` is an iterator yielding every value matching the
+/// pattern, with the '0' and '1' bits fixed and the letters and '?' bits
+/// taking all combinations, in ascending order. The type is selected by the
+/// pattern length like `bitpattern!`.
+///
+/// More than 24 free bits is an error, since that's at least 2^25 values.
+/// `bitgen!("...", allow_large)` allows any number of them. The iterator is
+/// an `ExactSizeIterator` if the number of values fits in `usize` on the
+/// target.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitgen;
+///
+/// let values: Vec<u8> = bitgen!("10?? aa?1").collect();
+/// assert_eq!(values.len(), 32);
+/// assert_eq!(values[..3], [0x81, 0x83, 0x85]);
+/// assert_eq!(values[31], 0xbf);
+///
+/// assert_eq!(bitgen!("1??? ???? ???? ???? ???? ???? ???? ????", allow_large).len(), 1 << 31);
+///```
+#[proc_macro]
+pub fn bitgen(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as iter::GenInput);
+    iter::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise filtering of words matching a pattern.
+///
+/// `bitmatch_filter!("1010 aa??", words)` is an iterator over the fields of
+/// the words matching the pattern. The words can be any iterator, slice or
+/// array of integers or references to them. They're matched like
+/// `bitpattern!`: a word with bits set above the pattern doesn't match,
+/// unless it's given as `bitmatch_filter!(low, "...", words)`, and runs of
+/// uppercase letters are sign-extended. It doesn't allocate, so it can be
+/// used in `no_std`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_filter;
+///
+/// let trace = [0xa4u8, 0x13, 0xab, 0xa0];
+/// let found: Vec<_> = bitmatch_filter!("1010 aabb", &trace).collect();
+/// assert_eq!(found, [(1, 0), (2, 3), (0, 0)]);
+///
+/// let trace = [0xa4u16, 0x1a4];
+/// assert_eq!(bitmatch_filter!("1010 aabb", &trace).count(), 1);
+/// assert_eq!(bitmatch_filter!(low, "1010 aabb", &trace).count(), 2);
+///
+/// let found: Vec<_> = bitmatch_filter!("SSSS aaaa", [0xf3u8, 0x73]).collect();
+/// assert_eq!(found, [(-1, 3), (7, 3)]);
+///```
+#[proc_macro]
+pub fn bitmatch_filter(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::LowInput);
+    iter::gen_filter(input, false)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise filtering of words matching a pattern, with their index.
+///
+/// `bitmatch_filter_indexed!("1010 aa??", words)` is like `bitmatch_filter!`,
+/// but yields the index of each matching word before its fields.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch_filter_indexed;
+///
+/// let trace = [0xa4u8, 0x13, 0xab, 0xa0];
+/// let found: Vec<_> = bitmatch_filter_indexed!("1010 aa??", &trace).collect();
+/// assert_eq!(found, [(0, 1), (2, 2), (3, 0)]);
+///```
+#[proc_macro]
+pub fn bitmatch_filter_indexed(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::LowInput);
+    iter::gen_filter(input, true)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// `nom` parser of a pattern.
+///
+/// With the `nom` feature, `bitparser!("vvvv hhhh tttttttt")` is a function
+/// `fn(&[u8]) -> nom::IResult<&[u8], (u8, u8, u8)>` consuming the bytes of
+/// the pattern, which must have a multiple of 8 bits. The bits of each byte
+/// are in big-endian order. It fails with `ErrorKind::Verify` if the '0' and
+/// '1' bits don't match, and with `ErrorKind::Eof` if the input is too short,
+/// like the `complete` parsers of `nom`. Each
+/// letter is returned in the smallest unsigned type holding it, in the order
+/// the letters first appear like `bitfields_iter!`. The function captures
+/// nothing, so it is `Copy` and composes with other parsers.
+///
+/// # Example
+///
+///```rust,ignore
+/// use bitpattern::bitparser;
+///
+/// // Version, IHL, DSCP, ECN and total length of an IPv4 header.
+/// let word = bitparser!("0100 hhhh dddddd ee llllllll llllllll");
+/// let header = [0x45, 0x00, 0x00, 0x54, 0xab];
+/// assert_eq!(word(&header), Ok((&header[4..], (5, 0, 0, 84))));
+///```
+#[proc_macro]
+pub fn bitparser(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input with pattern::parse_lits);
+    parser::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise matching of a `bitvec` bit slice.
+///
+/// With the `bitvec` feature, `bitmatch_bitslice!("10 aaaa bb", &bits[..])`
+/// matches a `&BitSlice` like `bitpattern!`, reading each bit by its index,
+/// so the first bit of the pattern is index 0 in any `BitOrder`. It returns
+/// `None` if the slice is shorter than the pattern, and ignores the bits
+/// after it, so patterns can be longer than 128 bits. Each run of letters is
+/// the smallest unsigned type holding it, with its first bit as the most
+/// significant, or a subslice if it has more than 128 bits.
+///
+/// It is a macro of its own because a macro doesn't see the type of its
+/// value. `bitpattern!` picks the code for a `&[u8]` from the pattern, and a
+/// `&BitSlice` would need a trait implemented for both, which a proc-macro
+/// crate can't export.
+///
+/// # Example
+///
+///```rust,ignore
+/// use bitpattern::bitmatch_bitslice;
+/// use bitvec::prelude::*;
+///
+/// let bits = bits![u8, Msb0; 1, 0, 1, 1, 0, 0, 1, 0, 1];
+/// assert_eq!(bitmatch_bitslice!("10 aaaa bb", bits), Some((0xc, 2)));
+/// assert_eq!(bitmatch_bitslice!("11 aaaa bb", bits), None);
+/// assert_eq!(bitmatch_bitslice!("10 aaaa bb", &bits[..4]), None);
+///```
+#[proc_macro]
+pub fn bitmatch_bitslice(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as pattern::PatternInput);
+    slice::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// `proptest` strategy of the values of a pattern.
+///
+/// With the `proptest` feature, `bitstrategy!("...")` is a
+/// `proptest::strategy::Strategy` generating values matching the pattern
+/// uniformly: the letters and '?' bits are drawn together and the '0' and '1'
+/// bits are fixed. Values shrink toward the free bits being 0. The type is
+/// optional like `bitmask!`.
+///
+/// # Example
+///
+///```rust,ignore
+/// use bitpattern::bitstrategy;
+/// use proptest::prelude::*;
+///
+/// proptest! {
+///     #[test]
+///     fn decodes_store(x in bitstrategy!("??????? ????? ????? 010 ????? 0100011")) {
+///         prop_assert!(matches!(decode(x), Insn::Store { .. }));
+///     }
+/// }
+///```
+#[proc_macro]
+pub fn bitstrategy(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as consts::MaskInput);
+    arbitrary::gen_strategy(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// `quickcheck` newtype of the values of a pattern.
+///
+/// With the `quickcheck` feature, `bitarbitrary!(pub Store, "...", u32);`
+/// defines `pub struct Store(pub u32)` implementing `quickcheck::Arbitrary`,
+/// with values generated and shrunk like `bitstrategy!`. The type is optional
+/// like `bitmask!`.
+///
+/// # Example
+///
+///```rust,ignore
+/// use bitpattern::bitarbitrary;
+///
+/// bitarbitrary!(Store, "??????? ????? ????? 010 ????? 0100011");
+///
+/// #[quickcheck_macros::quickcheck]
+/// fn decodes_store(x: Store) -> bool {
+///     matches!(decode(x.0), Insn::Store { .. })
+/// }
+///```
+#[proc_macro]
+pub fn bitarbitrary(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as arbitrary::ArbitraryInput);
+    arbitrary::gen_arbitrary(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// bitwise pattern matching in `match` expressions.
+///
+/// In the annotated item, `match` arms with string literal patterns are
+/// matched like `bitpattern!`, and each letter of the pattern is bound to
+/// the extracted value in the arm body. Arms are tried in order, and other
+/// arms are passed through untouched. Guards can use the extracted values,
+/// and matching continues with the next arm if the guard fails.
+/// Alternatives like `"1111 00aa" | "1110 00aa"` must have the same fields
+/// with the same widths. `raw @ "..."` binds `raw` to the matched value
+/// with its original type.
+///
+/// A tuple scrutinee like `match (hi, lo)` is matched by tuples of patterns
+/// like `("1111 aaaa", "00aa bbbb")`, which are joined into a single pattern
+/// with the first element as the most significant bits. Letters in several
+/// elements are concatenated, and `_` elements match anything.
+///
+/// `let "ooo_aabb" = x;` binds the letters in the enclosing scope. A pattern
+/// with fixed bits needs an `else` branch, like `let "10_aabb" = x else {
+/// return None };`. `if let "110a aaaa" = x` and `while let "1aaa aaaa" =
+/// next()` bind the letters in their block, and `while let` evaluates the
+/// scrutinee once per iteration. A parameter like `#[bitmatch("aaaa bbbb")]
+/// x: u8` binds the letters at the top of the function body, and the pattern
+/// must not have fixed bits.
+///
+/// `#[bitmatch(define(rtype = "ggggggg bbbbb aaaaa fff ddddd 0110011"))]`
+/// defines a pattern fragment, and `"{rtype: f=000, g=0000000}"` in a pattern
+/// is replaced by it with the letters `f` and `g` substituted by the given
+/// bits, which must have the width of the field.
+///
+/// Like `bitpattern!`, a value with bits set above the pattern doesn't match
+/// it. With `#[bitmatch(low)]`, only the low bits of the value are matched.
+/// `let` without `else` and parameters can't fail, so they always drop those
+/// bits like `bitextract!`.
+///
+/// If the last arm is a string pattern, the string patterns must cover every
+/// value of the pattern width, or an uncovered value is reported as a compile
+/// error. Without `low`, values with bits set above the pattern aren't
+/// covered either, so the `match` needs a `_` arm. `#[bitmatch(non_exhaustive)]` on the item or on a `match` skips
+/// the check, and the `match` needs a `_` arm for the values left over
+/// instead.
+///
+/// An arm which can't match because earlier arms cover all of its values is a
+/// compile error. With `#[bitmatch(warn_overlap)]`, an arm partially
+/// overlapping an earlier arm raises a warning as well.
+///
+/// Large decoders can use `#[bitmatch(table_bits = 8)]` to look up the arms
+/// which can match by the up to 8 bits discriminating them best, and test
+/// only those in order instead of every arm. `table_bits` is between 1 and
+/// 16. As the arms are then tested separately from the generated `match`,
+/// the exhaustiveness check applies even if the last arm is not a string.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::bitmatch;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Op {
+///     Add(u32, u32, u32),
+///     Jal(u32),
+///     Illegal,
+/// }
+///
+/// #[bitmatch]
+/// fn decode(insn: u32) -> Op {
+///     match insn {
+///         "0000000 bbbbb aaaaa 000 ddddd 0110011" => Op::Add(d, a, b),
+///         "???????????????????? ddddd 1101111" => Op::Jal(d),
+///         _ => Op::Illegal,
+///     }
+/// }
+///
+/// assert_eq!(decode(0x0020_81b3), Op::Add(3, 1, 2));
+/// assert_eq!(decode(0x0000_00ef), Op::Jal(1));
+/// assert_eq!(decode(0), Op::Illegal);
+///```
+#[proc_macro_attribute]
+pub fn bitmatch(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    bitmatch::gen_code(attr.into(), item.into())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derives `decode` and `encode` for an enum from a bit pattern on each variant.
+///
+/// Every variant has either `#[bitmatch("...")]`, whose letters are extracted
+/// into its fields, or `#[bitmatch(default)]`, which is used if no pattern
+/// matches. A newtype default variant captures the value. The patterns are
+/// tried in declaration order, and their lengths select the type of the value
+/// like `bitpattern!`.
+///
+/// If the lengths select several types, like 16-bit and 32-bit instructions,
+/// each pattern matches the low bits of the widest type, and `decode` returns
+/// the length of the matched pattern in bits as well. The default variant has
+/// the length of the shortest pattern. If all lengths are multiples of 8,
+/// `decode_le_bytes` decodes the start of a byte slice in little endian and
+/// returns the number of bytes consumed.
+///
+/// `#[bitmatch("...", fmt = "lui x{d}, {imm:#x}")]` gives a template for
+/// `Display`, whose placeholders name a field or its letter, followed by the
+/// usual format spec. `Display` is implemented if any variant has a template,
+/// and variants without one are displayed by name.
+///
+/// Tuple fields take the letters in order of appearance. Named fields take the
+/// letter given by `#[bits = "x"]`, or else the first character of their
+/// name. Signed fields are sign extended from the field width, `bool` fields
+/// take 1-bit fields, and a primitive type narrower than its field is an
+/// error.
+///
+/// `encode` is the inverse, packing the fields into the pattern of the variant
+/// with '?' bits set to 0, and values truncated to their field width. A
+/// newtype default variant encodes the captured value, so `decode` of the
+/// result gives it back. With `#[bitmatch(checked)]` on the enum, `encode`
+/// returns `None` instead of truncating, and a unit default variant, which
+/// has no value to encode, is allowed and encodes to `None`.
+///
+/// `#[bitmatch(debug)]` and `#[bitmatch(defmt)]` on the enum implement `Debug`
+/// and `defmt::Format` like `BitFields`.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::BitDecode;
+///
+/// #[derive(BitDecode, Debug, PartialEq)]
+/// enum Op {
+///     #[bitmatch("000 aa bbb")]
+///     Move(u8, u8),
+///     #[bitmatch("001 ?????")]
+///     Nop,
+///     #[bitmatch("01 iiii dd")]
+///     Add { d: u8, #[bits = "i"] imm: i8 },
+///     #[bitmatch(default)]
+///     Unknown(u8),
+/// }
+///
+/// assert_eq!(Op::decode(0b000_10_011), Some(Op::Move(2, 3)));
+/// assert_eq!(Op::decode(0b001_10_011), Some(Op::Nop));
+/// assert_eq!(Op::decode(0b01_1110_11), Some(Op::Add { d: 3, imm: -2 }));
+///
+/// assert_eq!(Op::Move(2, 3).encode(), 0b000_10_011);
+/// assert_eq!(Op::Add { d: 3, imm: -2 }.encode(), 0b01_1110_11);
+/// assert_eq!(Op::decode(0xff), Some(Op::Unknown(0xff)));
+///```
+#[proc_macro_derive(BitDecode, attributes(bitmatch, bits))]
+pub fn bit_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    decode::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derives `from_bits` and `to_bits` for a struct from a bit pattern.
+///
+/// The struct has `#[bitmatch("...")]`, whose letters are unpacked into its
+/// fields like a variant of `BitDecode`, and whose length selects the type of
+/// the value like `bitpattern!`. `from_bits` returns `None` if the '0' and '1'
+/// bits don't match, and `to_bits` packs the fields with '?' bits set to 0 and
+/// values truncated to their field width.
+///
+/// With the `serde` feature, `#[bitmatch("...", serde)]` also implements
+/// `Serialize` and `Deserialize` by the named fields, which requires `serde`
+/// with the `derive` feature. Deserialization fails if a value doesn't fit its
+/// field width.
+///
+/// `#[bitmatch("...", debug)]` implements `Debug`, writing unsigned fields
+/// narrower than a byte in binary and wider ones in hex, like
+/// `Ctrl { v: 0b101, e: true, a: 0x3C }`. With the `defmt` feature,
+/// `defmt` implements `defmt::Format` the same way.
+///
+/// # Example
+///
+///```rust
+/// use bitpattern::BitFields;
+///
+/// #[derive(BitFields, Debug, PartialEq)]
+/// #[bitmatch("vvv_ppp_e_c aaaaaaaa")]
+/// struct Ctrl {
+///     v: u8,
+///     p: u8,
+///     e: bool,
+///     c: bool,
+///     a: u8,
+/// }
+///
+/// let ctrl = Ctrl { v: 5, p: 2, e: true, c: false, a: 0x3c };
+/// assert_eq!(ctrl.to_bits(), 0b1010_1010_0011_1100);
+/// assert_eq!(Ctrl::from_bits(0b1010_1010_0011_1100), Some(ctrl));
+///```
+#[proc_macro_derive(BitFields, attributes(bitmatch, bits))]
+pub fn bit_fields(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    fields::gen_code(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
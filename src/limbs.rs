@@ -0,0 +1,116 @@
+use crate::pattern::{Pattern, Ty};
+use crate::Value;
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::{Error, Ident, Result};
+
+/// Most bits of a pattern matching two `u128` limbs.
+const MAX_BITS: usize = 256;
+
+/// Matches a pattern against a value of type `(u128, u128)`, its high and
+/// low 128 bits.
+///
+/// The pattern is zero-extended to 256 bits, and each limb is compared with
+/// its half of the mask. A field is shifted out of the low limb, the high
+/// one, or both when it straddles bit 128, and is converted from a `u128` like
+/// the fields of integers.
+pub(crate) fn gen_code(pattern: &Pattern, span: Span, value: &Value) -> Result<TokenStream> {
+    let len = pattern.len();
+    if len > MAX_BITS {
+        return Err(Error::new(
+            span,
+            format!(
+                "patterns matching `(u128, u128)` have at most {} bits, found {}",
+                MAX_BITS, len
+            ),
+        ));
+    }
+    if let Some(field) = pattern.fields.iter().find(|x| x.width > 128) {
+        return Err(Error::new(
+            span,
+            format!(
+                "field '{}' has {} bits, more than the 128 of `u128`",
+                field.name, field.width
+            ),
+        ));
+    }
+    if value.offset.is_some() {
+        return Err(Error::new(span, "a type can't be given with an offset"));
+    }
+
+    let split = len.saturating_sub(128);
+    let high = Pattern {
+        bits: pattern.bits[..split].to_vec(),
+        fields: Vec::new(),
+    };
+    let low = Pattern {
+        bits: pattern.bits[split..].to_vec(),
+        fields: Vec::new(),
+    };
+    let hi = Ident::new("hi", Span::mixed_site());
+    let lo = Ident::new("lo", Span::mixed_site());
+    let mut checks = Vec::new();
+    if !value.low {
+        // Bits above the pattern must be 0.
+        if len <= 128 {
+            checks.push(quote! { #hi == 0 });
+            if len < 128 {
+                let len = Literal::usize_unsuffixed(len);
+                checks.push(quote! { #lo >> #len == 0 });
+            }
+        } else if len < MAX_BITS {
+            let split = Literal::usize_unsuffixed(split);
+            checks.push(quote! { #hi >> #split == 0 });
+        }
+    }
+    for (limb, half) in [(&hi, &high), (&lo, &low)] {
+        if half.mask() != 0 {
+            let mask = Ty::U128.lit(half.mask());
+            let value = Ty::U128.lit(half.value());
+            checks.push(quote! { #limb & #mask == #value });
+        }
+    }
+
+    let x = Ty::U128.ident();
+    let fields: Vec<_> = pattern
+        .fields
+        .iter()
+        .map(|field| {
+            let pos = field.pos as usize;
+            let mask = Ty::U128.lit(field.mask());
+            let bits = if pos >= 128 {
+                let shift = Literal::usize_unsuffixed(pos - 128);
+                quote! { (#hi >> #shift) }
+            } else if pos + field.width as usize <= 128 {
+                let pos = Literal::usize_unsuffixed(pos);
+                quote! { (#lo >> #pos) }
+            } else {
+                let shift = Literal::usize_unsuffixed(128 - pos);
+                let pos = Literal::usize_unsuffixed(pos);
+                quote! { ((#lo >> #pos) | (#hi << #shift)) }
+            };
+            let bits = quote! { (#bits & #mask) };
+            crate::gen_field(field, bits, &x, &|v| Ty::U128.lit(v), value)
+        })
+        .collect();
+    let found = quote! { ::core::option::Option::Some((#(#fields),*)) };
+    let found = if checks.is_empty() {
+        found
+    } else {
+        quote! {
+            if #(#checks)&&* {
+                #found
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    let expr = &value.expr;
+    Ok(quote! {
+        {
+            let (#hi, #lo): (u128, u128) = #expr;
+            #found
+        }
+    })
+}
@@ -0,0 +1,157 @@
+use crate::consts;
+use crate::pack::{gen_field, letter_mask};
+use crate::pattern::parse_lits;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Attribute, Error, Ident, LitStr, Result, Token, Visibility};
+
+/// Input of `bitnewtype!`: `#[attr] vis struct Name(ty): "..."`.
+pub(crate) struct NewtypeInput {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    name: Ident,
+    ty: Ident,
+    pattern: LitStr,
+}
+
+impl Parse for NewtypeInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name = input.parse()?;
+        let content;
+        syn::parenthesized!(content in input);
+        let ty = content.parse()?;
+        input.parse::<Token![:]>()?;
+        let pattern = parse_lits(input)?;
+        input.parse::<Option<Token![;]>>()?;
+        Ok(NewtypeInput {
+            attrs,
+            vis,
+            name,
+            ty,
+            pattern,
+        })
+    }
+}
+
+/// Transparent newtype keeping the bits packed, with a getter and a `with_`
+/// setter for each letter.
+///
+/// Getters of 1-bit letters return `bool`, and those of uppercase letters are
+/// sign-extended. Setters keep the other bits, and only the low bits of the
+/// value are written like `bitset_fields!`.
+pub(crate) fn gen_code(input: NewtypeInput) -> Result<TokenStream> {
+    let lit = &input.pattern;
+    let (pattern, ty) = consts::parse_pattern(lit, Some(&input.ty))?;
+    let attrs = &input.attrs;
+    let vis = &input.vis;
+    let name = &input.name;
+    let x = ty.ident();
+    let source = lit.value();
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let value = Ident::new("value", Span::mixed_site());
+    let letters = pattern.letters();
+    let mut methods = Vec::new();
+    for &letter in &letters {
+        // Methods are snake case, so an uppercase letter names them in
+        // lowercase, unless that letter is used too.
+        let method = letter.to_ascii_lowercase();
+        if method != letter && letters.contains(&method) {
+            return Err(Error::new(
+                lit.span(),
+                format!(
+                    "letters '{}' and '{}' would both name the method `{}`",
+                    method, letter, method
+                ),
+            ));
+        }
+        let (getter, setter) = match (
+            syn::parse_str::<Ident>(&method.to_string()),
+            syn::parse_str::<Ident>(&format!("with_{}", method)),
+        ) {
+            (Ok(mut getter), Ok(mut setter)) => {
+                getter.set_span(name.span());
+                setter.set_span(name.span());
+                (getter, setter)
+            }
+            _ => {
+                return Err(Error::new(
+                    lit.span(),
+                    format!("letter '{}' can't be used in a method name", letter),
+                ));
+            }
+        };
+        let extract = pattern.extract(letter, ty, &bits);
+        let width = pattern.width(letter);
+        let (field_ty, extract) = if width == 1 {
+            (quote! { bool }, quote! { #extract != 0 })
+        } else if letter.is_ascii_uppercase() {
+            let signed = crate::signed_ident(&x);
+            let extract = crate::gen_signed(extract, width, &|v| ty.lit(v), &signed);
+            (quote! { #signed }, extract)
+        } else {
+            (quote! { #x }, extract)
+        };
+        let fields = gen_field(&pattern, Some(ty), letter, &value);
+        let clear = ty.lit(letter_mask(&pattern, letter));
+        let get_doc = format!("Extracts '{}' of `\"{}\"`.", letter, source);
+        let set_doc = format!("Replaces '{}' of `\"{}\"`.", letter, source);
+        methods.push(quote! {
+            #[doc = #get_doc]
+            #[inline]
+            #vis const fn #getter(&self) -> #field_ty {
+                let #bits = self.0;
+                #extract
+            }
+
+            #[doc = #set_doc]
+            #[inline]
+            #[must_use]
+            #vis const fn #setter(self, #value: #field_ty) -> Self {
+                Self((self.0 & !#clear) #( | #fields )*)
+            }
+        });
+    }
+
+    let valid = if pattern.mask() == 0 {
+        quote! { true }
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        quote! { self.0 & #bit_mask == #bit_pattern }
+    };
+    let doc = format!("Packed value of `\"{}\"`.", source);
+    Ok(quote! {
+        #[doc = #doc]
+        #(#attrs)*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[repr(transparent)]
+        #vis struct #name(#x);
+
+        impl #name {
+            /// Wraps a raw value without checking it.
+            #[inline]
+            #vis const fn from_raw(#bits: #x) -> Self {
+                Self(#bits)
+            }
+
+            /// Raw value.
+            #[inline]
+            #vis const fn raw(self) -> #x {
+                self.0
+            }
+
+            /// Returns true if the '0' and '1' bits of the pattern match.
+            #[inline]
+            #vis const fn is_valid(&self) -> bool {
+                #valid
+            }
+
+            #(#methods)*
+        }
+    })
+}
@@ -0,0 +1,773 @@
+use crate::pattern::{gen_fits, gen_word, letter_span, parse_lits, parse_low, Pattern, Ty};
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Error, Expr, Ident, LitStr, Result, Token};
+
+enum Arg {
+    Named(Ident, Expr),
+    Positional(Expr),
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Ok(Arg::Named(name, input.parse()?))
+        } else {
+            Ok(Arg::Positional(input.parse()?))
+        }
+    }
+}
+
+pub(crate) struct PackInput {
+    pattern: LitStr,
+    args: Vec<Arg>,
+    /// Value of '?' bits given by trailing `default = ...`.
+    default: Option<Expr>,
+}
+
+impl Parse for PackInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        let mut args: Vec<Arg> = if input.is_empty() {
+            Vec::new()
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::<Arg, Token![,]>::parse_terminated(input)?
+                .into_iter()
+                .collect()
+        };
+
+        let mut default = None;
+        if let Some(Arg::Named(name, _)) = args.last() {
+            if name == "default" {
+                if let Some(Arg::Named(_, expr)) = args.pop() {
+                    default = Some(expr);
+                }
+            }
+        }
+        for arg in &args {
+            if let Arg::Named(name, _) = arg {
+                if name == "default" {
+                    return Err(Error::new(
+                        name.span(),
+                        "'default' must be the last argument",
+                    ));
+                }
+            }
+        }
+
+        Ok(PackInput {
+            pattern,
+            args,
+            default,
+        })
+    }
+}
+
+/// Field values in order of `Pattern::letters`.
+fn resolve(pattern: &Pattern, input: &PackInput) -> Result<Vec<Expr>> {
+    let letters = pattern.letters();
+    let span = input.pattern.span();
+
+    let positional = input.args.iter().any(|x| matches!(x, Arg::Positional(_)));
+    let named = input.args.iter().any(|x| matches!(x, Arg::Named(..)));
+    if positional && named {
+        return Err(Error::new(
+            span,
+            "named and positional values can't be mixed",
+        ));
+    }
+
+    if positional {
+        if input.args.len() != letters.len() {
+            return Err(Error::new(
+                span,
+                format!(
+                    "expected {} values, found {}",
+                    letters.len(),
+                    input.args.len()
+                ),
+            ));
+        }
+        let ret = input
+            .args
+            .iter()
+            .map(|x| match x {
+                Arg::Positional(x) => x.clone(),
+                Arg::Named(..) => unreachable!(),
+            })
+            .collect();
+        return Ok(ret);
+    }
+
+    let mut ret: Vec<Option<Expr>> = vec![None; letters.len()];
+    for arg in &input.args {
+        if let Arg::Named(name, expr) = arg {
+            let name_str = name.to_string();
+            let index = letters.iter().position(|x| name_str == x.to_string());
+            match index {
+                Some(i) if ret[i].is_some() => {
+                    return Err(Error::new(
+                        name.span(),
+                        format!("duplicate value for field '{}'", name),
+                    ));
+                }
+                Some(i) => ret[i] = Some(expr.clone()),
+                None => {
+                    return Err(Error::new(
+                        name.span(),
+                        format!("pattern has no field '{}'", name),
+                    ));
+                }
+            }
+        }
+    }
+
+    letters
+        .iter()
+        .zip(ret)
+        .map(|(letter, expr)| {
+            expr.ok_or_else(|| Error::new(span, format!("missing value for field '{}'", letter)))
+        })
+        .collect()
+}
+
+pub(crate) fn gen_code(input: PackInput, checked: bool) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let values = resolve(&pattern, &input)?;
+    let names: Vec<_> = (0..values.len())
+        .map(|i| Ident::new(&format!("value{}", i), Span::mixed_site()))
+        .collect();
+    let default = Ident::new("default", Span::mixed_site());
+    let (default_let, default_term) = gen_default(&pattern, ty, &input.default, &default);
+    let packed = gen_expr(&pattern, ty, &names, default_term);
+
+    if checked {
+        // A value fits if no bits are set above the field width.
+        // Signed values are sign extended by the cast, so negative values never fit.
+        let checks = pattern
+            .letters()
+            .into_iter()
+            .zip(&names)
+            .map(|(letter, name)| (pattern.width(letter), name))
+            .filter(|(width, _)| *width < 128)
+            .map(|(width, name)| quote! { ((#name as u128) >> #width == 0) })
+            .collect::<Vec<_>>();
+        let packed = quote! { ::core::option::Option::Some(#packed) };
+        let packed = if checks.is_empty() {
+            packed
+        } else {
+            quote! {
+                if #(#checks)&&* {
+                    #packed
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+        };
+
+        Ok(quote! {
+            {
+                #( let #names = #values; )*
+                #default_let
+                #packed
+            }
+        })
+    } else {
+        Ok(quote! {
+            {
+                #( let #names = #values; )*
+                #default_let
+                #packed
+            }
+        })
+    }
+}
+
+/// Returns the binding and the term filling '?' bits.
+///
+/// Literal `0` and `1` fill all '?' bits with that value, and any other
+/// expression fills them with the corresponding bits of its value.
+fn gen_default(
+    pattern: &Pattern,
+    ty: Ty,
+    default: &Option<Expr>,
+    name: &Ident,
+) -> (Option<TokenStream>, Option<TokenStream>) {
+    let expr = match default {
+        Some(x) => x,
+        None => return (None, None),
+    };
+    let x = ty.ident();
+    let any_mask = ty.lit(pattern.any_mask());
+
+    if let Expr::Lit(syn::ExprLit {
+        lit: syn::Lit::Int(lit),
+        ..
+    }) = expr
+    {
+        match lit.base10_digits() {
+            "0" => return (None, None),
+            "1" => return (None, Some(quote! { #any_mask })),
+            _ => (),
+        }
+    }
+
+    (
+        Some(quote! { let #name = #expr; }),
+        Some(quote! { ((#name as #x) & #any_mask) }),
+    )
+}
+
+pub(crate) fn gen_expr(
+    pattern: &Pattern,
+    ty: Ty,
+    values: &[Ident],
+    default: Option<TokenStream>,
+) -> TokenStream {
+    let bit_pattern = ty.lit(pattern.value());
+    let mut fields: Vec<_> = default.into_iter().collect();
+    for (letter, value) in pattern.letters().into_iter().zip(values) {
+        fields.extend(gen_field(pattern, Some(ty), letter, value));
+    }
+
+    quote! {
+        (#bit_pattern #( | #fields )*)
+    }
+}
+
+/// Terms placing `value` into the runs of a letter.
+///
+/// Without a type, `value` isn't cast and the literals are unsuffixed, so the
+/// terms have the type of the target they're merged into.
+pub(crate) fn gen_field(
+    pattern: &Pattern,
+    ty: Option<Ty>,
+    letter: char,
+    value: &Ident,
+) -> Vec<TokenStream> {
+    let value = match ty {
+        Some(x) => {
+            let x = x.ident();
+            quote! { (#value as #x) }
+        }
+        None => quote! { #value },
+    };
+    // Earlier runs of a split field take the more significant bits.
+    pattern
+        .runs(letter)
+        .into_iter()
+        .map(|(field, offset)| {
+            let offset = lit(ty, offset as u128);
+            let pos = lit(ty, field.pos as u128);
+            let mask = lit(ty, field.mask());
+            quote! { (((#value >> #offset) & #mask) << #pos) }
+        })
+        .collect()
+}
+
+/// Literal of the type `ty`, or unsuffixed to take the type of the target.
+fn lit(ty: Option<Ty>, value: u128) -> Literal {
+    match ty {
+        Some(x) => x.lit(value),
+        None => Literal::u128_unsuffixed(value),
+    }
+}
+
+/// Mask of the bits of a letter.
+pub(crate) fn letter_mask(pattern: &Pattern, letter: char) -> u128 {
+    pattern
+        .runs(letter)
+        .into_iter()
+        .fold(0, |acc, (field, _)| acc | (field.mask() << field.pos))
+}
+
+pub(crate) struct SetInput {
+    pattern: LitStr,
+    target: Expr,
+    values: Vec<(Ident, Expr)>,
+}
+
+impl Parse for SetInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let target = input.parse()?;
+        let mut values = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            values.push((name, input.parse()?));
+        }
+        Ok(SetInput {
+            pattern,
+            target,
+            values,
+        })
+    }
+}
+
+/// Replaces the bits of the given letters of `target`, keeping other bits.
+///
+/// The bits are replaced in the type of `target`, which the values must have
+/// too. The '0' and '1' bits are checked by `debug_assert!` on the old value.
+pub(crate) fn gen_set(input: SetInput) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let (lets, fields, clear) = gen_named(&pattern, None, &input.values)?;
+
+    let old = Ident::new("old", Span::mixed_site());
+    let binding = crate::gen_binding(&old, &ty.ident(), &input.target);
+    let check = if pattern.mask() == 0 {
+        None
+    } else {
+        let mut bit_mask = Literal::u128_unsuffixed(pattern.mask());
+        let mut bit_pattern = Literal::u128_unsuffixed(pattern.value());
+        bit_mask.set_span(input.pattern.span());
+        bit_pattern.set_span(input.pattern.span());
+        Some(quote! {
+            ::core::debug_assert!(
+                #old & #bit_mask == #bit_pattern,
+                "fixed bits of the pattern don't match",
+            );
+        })
+    };
+    // The literals have the span of the pattern, so one which doesn't fit the
+    // type of the target is reported there.
+    let keep = if clear == 0 {
+        quote! { #old }
+    } else {
+        let mut clear = Literal::u128_unsuffixed(clear);
+        clear.set_span(input.pattern.span());
+        quote! { (#old & !#clear) }
+    };
+    Ok(quote! {
+        {
+            #binding
+            #check
+            #(#lets)*
+            #keep #( | #fields )*
+        }
+    })
+}
+
+/// Input of `bitcas!`: `"...", atomic, a = x, ..., ordering`.
+pub(crate) struct CasInput {
+    pattern: LitStr,
+    atomic: Expr,
+    values: Vec<(Ident, Expr)>,
+    ordering: Expr,
+}
+
+impl Parse for CasInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let atomic = input.parse()?;
+        let mut values = Vec::new();
+        input.parse::<Token![,]>()?;
+        while input.peek(Ident) && input.peek2(Token![=]) {
+            let name: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            values.push((name, input.parse()?));
+            input.parse::<Token![,]>()?;
+        }
+        let ordering = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(CasInput {
+            pattern,
+            atomic,
+            values,
+            ordering,
+        })
+    }
+}
+
+/// Replaces the bits of the given letters of an atomic integer by a
+/// compare-exchange loop, returning the previous value.
+///
+/// The ordering is used for the exchange, and the loads use it without its
+/// release part, like `fetch_update`.
+pub(crate) fn gen_cas(input: CasInput) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let (lets, fields, clear) = gen_named(&pattern, Some(ty), &input.values)?;
+
+    let x = ty.ident();
+    let atomic = Ident::new("atomic", Span::mixed_site());
+    let order = Ident::new("order", Span::mixed_site());
+    let load = Ident::new("load", Span::mixed_site());
+    let old = Ident::new("old", Span::mixed_site());
+    let check = if pattern.mask() == 0 {
+        None
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        Some(quote! {
+            ::core::debug_assert!(
+                #old & #bit_mask == #bit_pattern,
+                "fixed bits of the pattern don't match",
+            );
+        })
+    };
+    let keep = if clear == 0 {
+        quote! { #old }
+    } else {
+        let clear = ty.lit(clear);
+        quote! { (#old & !#clear) }
+    };
+    let target = &input.atomic;
+    let ordering = &input.ordering;
+    Ok(quote! {
+        {
+            let #atomic = #target;
+            let #order: ::core::sync::atomic::Ordering = #ordering;
+            let #load = match #order {
+                ::core::sync::atomic::Ordering::Release => ::core::sync::atomic::Ordering::Relaxed,
+                ::core::sync::atomic::Ordering::AcqRel => ::core::sync::atomic::Ordering::Acquire,
+                x => x,
+            };
+            #(#lets)*
+            let mut #old: #x = #atomic.load(#load);
+            loop {
+                #check
+                let new = #keep #( | #fields )*;
+                match #atomic.compare_exchange_weak(#old, new, #order, #load) {
+                    ::core::result::Result::Ok(x) => break x,
+                    ::core::result::Result::Err(x) => #old = x,
+                }
+            }
+        }
+    })
+}
+
+/// Bindings of named values, the terms placing them, and the mask of their
+/// bits.
+///
+/// Without a type, the terms have the type of the target like `gen_field`.
+fn gen_named(
+    pattern: &Pattern,
+    ty: Option<Ty>,
+    values: &[(Ident, Expr)],
+) -> Result<(Vec<TokenStream>, Vec<TokenStream>, u128)> {
+    let letters = pattern.letters();
+    let mut clear = 0;
+    let mut lets = Vec::new();
+    let mut fields = Vec::new();
+    let mut seen: Vec<char> = Vec::new();
+    for (i, (name, expr)) in values.iter().enumerate() {
+        let name_str = name.to_string();
+        let letter = match letters.iter().find(|x| name_str == x.to_string()) {
+            Some(x) => *x,
+            None => {
+                return Err(Error::new(
+                    name.span(),
+                    format!("pattern has no field '{}'", name),
+                ));
+            }
+        };
+        if seen.contains(&letter) {
+            return Err(Error::new(
+                name.span(),
+                format!("duplicate value for field '{}'", name),
+            ));
+        }
+        seen.push(letter);
+
+        let value = Ident::new(&format!("value{}", i), Span::mixed_site());
+        lets.push(quote! { let #value = #expr; });
+        fields.extend(gen_field(pattern, ty, letter, &value));
+        clear |= letter_mask(pattern, letter);
+    }
+    Ok((lets, fields, clear))
+}
+
+/// Source of the letters of `bitsplice!`.
+enum Source {
+    /// All letters from the same bits of one value.
+    Value(Expr),
+    /// Each named letter from the low bits of its value.
+    Named(Vec<(Ident, Expr)>),
+}
+
+pub(crate) struct SpliceInput {
+    pattern: LitStr,
+    old: Expr,
+    source: Source,
+}
+
+impl Parse for SpliceInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let old = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let source = if input.peek(Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+            let mut values = Vec::new();
+            while !input.is_empty() {
+                let name: Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                values.push((name, input.parse()?));
+                if input.parse::<Option<Token![,]>>()?.is_none() {
+                    break;
+                }
+            }
+            Source::Named(values)
+        } else {
+            let value = input.parse()?;
+            input.parse::<Option<Token![,]>>()?;
+            Source::Value(value)
+        };
+        Ok(SpliceInput {
+            pattern,
+            old,
+            source,
+        })
+    }
+}
+
+/// Merges the letters of a new value into `old`, and sets the '0' and '1'
+/// bits of the pattern.
+///
+/// The '?' bits, and letters without a named value, are kept from `old`. The
+/// bits are merged in the type of `old` like `gen_set`, which the new value
+/// must have too.
+pub(crate) fn gen_splice(input: SpliceInput) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let span = input.pattern.span();
+    let lit = |value| {
+        let mut lit = Literal::u128_unsuffixed(value);
+        lit.set_span(span);
+        lit
+    };
+    let old = Ident::new("old", Span::mixed_site());
+    let new = Ident::new("new", Span::mixed_site());
+    let (lets, fields, letters) = match &input.source {
+        Source::Value(expr) => {
+            let letters = pattern
+                .letters()
+                .into_iter()
+                .fold(0, |acc, x| acc | letter_mask(&pattern, x));
+            let fields = if letters == 0 {
+                vec![]
+            } else {
+                let mask = lit(letters);
+                vec![quote! { (#new & #mask) }]
+            };
+            (vec![quote! { let #new = #expr; }], fields, letters)
+        }
+        Source::Named(values) => gen_named(&pattern, None, values)?,
+    };
+
+    let binding = crate::gen_binding(&old, &ty.ident(), &input.old);
+    let clear = letters | pattern.mask();
+    let mut terms = vec![if clear == 0 {
+        quote! { #old }
+    } else {
+        let clear = lit(clear);
+        quote! { (#old & !#clear) }
+    }];
+    terms.extend(fields);
+    if pattern.value() != 0 {
+        terms.push(lit(pattern.value()).into_token_stream());
+    }
+    Ok(quote! {
+        {
+            #binding
+            #(#lets)*
+            #(#terms)|*
+        }
+    })
+}
+
+pub(crate) struct MaskInput {
+    pattern: LitStr,
+    target: Expr,
+    letters: Vec<Ident>,
+}
+
+impl Parse for MaskInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let target = input.parse()?;
+        let mut letters = Vec::new();
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            letters.push(input.parse()?);
+        }
+        Ok(MaskInput {
+            pattern,
+            target,
+            letters,
+        })
+    }
+}
+
+/// Operation of `bitclear!`, `bittoggle!` and `bitcount!`.
+#[derive(Clone, Copy)]
+pub(crate) enum MaskOp {
+    Clear,
+    Toggle,
+    Count,
+}
+
+/// Clears, toggles or counts the bits of the given letters of `target`, or of
+/// all letters if none are given.
+///
+/// Cleared and toggled bits are changed in the type of `target`.
+pub(crate) fn gen_mask(input: MaskInput, op: MaskOp) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+
+    let letters = pattern.letters();
+    let mut mask = 0;
+    if input.letters.is_empty() {
+        for letter in &letters {
+            mask |= letter_mask(&pattern, *letter);
+        }
+    }
+    for name in &input.letters {
+        let name_str = name.to_string();
+        match letters.iter().find(|x| name_str == x.to_string()) {
+            Some(x) => mask |= letter_mask(&pattern, *x),
+            None => {
+                return Err(Error::new(
+                    name.span(),
+                    format!("pattern has no field '{}'", name),
+                ));
+            }
+        }
+    }
+
+    let x = ty.ident();
+    let target = &input.target;
+    if let (MaskOp::Count, 0) = (op, mask) {
+        return Ok(quote! { { let _ = (#target) as #x; 0u32 } });
+    }
+    if let MaskOp::Count = op {
+        let mask = ty.lit(mask);
+        return Ok(quote! { (((#target) as #x) & #mask).count_ones() });
+    }
+
+    // The bits are changed in the type of the target, with the mask spanned
+    // like in `gen_set`.
+    let old = Ident::new("old", Span::mixed_site());
+    let binding = crate::gen_binding(&old, &x, target);
+    let mut mask = Literal::u128_unsuffixed(mask);
+    mask.set_span(input.pattern.span());
+    Ok(match op {
+        MaskOp::Clear => quote! { { #binding #old & !#mask } },
+        _ => quote! { { #binding #old ^ #mask } },
+    })
+}
+
+/// Input of `bitpermute!`: `"..." => "...", value`.
+pub(crate) struct PermuteInput {
+    /// Given as `low, "..."`, moving the letters of a wider value.
+    low: bool,
+    from: LitStr,
+    to: LitStr,
+    value: Expr,
+}
+
+impl Parse for PermuteInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let low = parse_low(input)?;
+        let from = parse_lits(input)?;
+        input.parse::<Token![=>]>()?;
+        let to = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let value = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(PermuteInput {
+            low,
+            from,
+            to,
+            value,
+        })
+    }
+}
+
+/// Moves the letters of `value` from their bits in one pattern to their bits
+/// in another.
+///
+/// The '0' and '1' bits of the source pattern are checked, making the result
+/// an `Option` unless there are none, and those of the destination pattern
+/// are set. The `Option` is `None` if the value has bits set above the
+/// source pattern too, unless `low`. Without one, the bits above the source
+/// pattern are dropped.
+pub(crate) fn gen_permute(input: PermuteInput) -> Result<TokenStream> {
+    let (from, from_ty) = Pattern::parse_lit(&input.from)?;
+    let (to, to_ty) = Pattern::parse_lit(&input.to)?;
+
+    let from_letters = from.letters();
+    for letter in to.letters() {
+        if !from_letters.contains(&letter) {
+            return Err(Error::new(
+                letter_span(&input.to, letter),
+                format!("letter '{}' isn't in the source pattern", letter),
+            ));
+        }
+        let (from_width, to_width) = (from.width(letter), to.width(letter));
+        if from_width != to_width {
+            return Err(Error::new(
+                letter_span(&input.to, letter),
+                format!(
+                    "letter '{}' has {} bits, but {} in the source pattern",
+                    letter, to_width, from_width
+                ),
+            ));
+        }
+    }
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let mut lets = Vec::new();
+    let mut names = Vec::new();
+    for (i, letter) in to.letters().into_iter().enumerate() {
+        let value = Ident::new(&format!("value{}", i), Span::mixed_site());
+        let extract = from.extract(letter, from_ty, &bits);
+        lets.push(quote! { let #value = #extract; });
+        names.push(value);
+    }
+    let packed = gen_expr(&to, to_ty, &names, None);
+
+    let x = from_ty.ident();
+    let value = Ident::new("value", Span::mixed_site());
+    let binding = crate::gen_binding(&value, &x, &input.value);
+    let word = gen_word(&value, from_ty);
+    if from.mask() == 0 {
+        return Ok(quote! {
+            {
+                #binding
+                let #bits = #word;
+                #(#lets)*
+                #packed
+            }
+        });
+    }
+    let bit_mask = from_ty.lit(from.mask());
+    let bit_pattern = from_ty.lit(from.value());
+    let high = if input.low {
+        None
+    } else {
+        let fits = gen_fits(&value, from.len() as u32);
+        Some(quote! { #fits && })
+    };
+    Ok(quote! {
+        {
+            #binding
+            let #bits = #word;
+            if #high #bits & #bit_mask == #bit_pattern {
+                #(#lets)*
+                ::core::option::Option::Some(#packed)
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    })
+}
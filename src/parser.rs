@@ -0,0 +1,92 @@
+use crate::pattern::{Pattern, Ty};
+use proc_macro2::{Literal, Span, TokenStream};
+use quote::quote;
+use syn::{Error, Ident, LitStr, Result};
+
+/// `nom` parser of a byte-aligned pattern, as a function item.
+///
+/// The bytes are read in big-endian order, so the first letter is in the
+/// most significant bits of the first byte. Each letter is returned in the
+/// smallest type holding it, in the order the letters first appear. A short
+/// input is an error rather than `Incomplete`, like `nom`'s `complete`
+/// parsers.
+pub(crate) fn gen_code(lit: LitStr) -> Result<TokenStream> {
+    if !cfg!(feature = "nom") {
+        return Err(Error::new(
+            lit.span(),
+            "`bitparser!` requires the \"nom\" feature of bitpattern",
+        ));
+    }
+    let (pattern, ty) = Pattern::parse_lit(&lit)?;
+    if !pattern.len().is_multiple_of(8) {
+        return Err(Error::new(
+            lit.span(),
+            format!(
+                "`bitparser!` reads whole bytes, but the pattern has {} bits",
+                pattern.len()
+            ),
+        ));
+    }
+
+    let x = ty.ident();
+    let n = pattern.len() / 8;
+    let bits = Ident::new("bits", Span::mixed_site());
+    let bytes = (0..n).map(|i| {
+        let shift = 8 * (n - i - 1);
+        let i = Literal::usize_unsuffixed(i);
+        match (ty, shift) {
+            (Ty::U8, _) => quote! { input[#i] },
+            (_, 0) => quote! { (input[#i] as #x) },
+            _ => {
+                let shift = ty.lit(shift as u128);
+                quote! { ((input[#i] as #x) << #shift) }
+            }
+        }
+    });
+    let letters = pattern.letters();
+    let mut types = Vec::new();
+    let mut values = Vec::new();
+    for letter in &letters {
+        let field_ty = Ty::from_len(pattern.width(*letter) as usize).unwrap();
+        let extract = pattern.extract(*letter, ty, &bits);
+        let field_x = field_ty.ident();
+        if field_ty == ty {
+            values.push(extract);
+        } else {
+            values.push(quote! { (#extract as #field_x) });
+        }
+        types.push(field_x);
+    }
+    let (output, found) = match (types.as_slice(), values.as_slice()) {
+        ([t], [v]) => (quote! { #t }, quote! { #v }),
+        _ => (quote! { (#(#types),*) }, quote! { (#(#values),*) }),
+    };
+    let check = if pattern.mask() == 0 {
+        None
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        Some(quote! {
+            if #bits & #bit_mask != #bit_pattern {
+                return ::core::result::Result::Err(::nom::Err::Error(
+                    ::nom::error::Error::new(input, ::nom::error::ErrorKind::Verify),
+                ));
+            }
+        })
+    };
+    Ok(quote! {
+        {
+            fn parser(input: &[u8]) -> ::nom::IResult<&[u8], #output> {
+                if input.len() < #n {
+                    return ::core::result::Result::Err(::nom::Err::Error(
+                        ::nom::error::Error::new(input, ::nom::error::ErrorKind::Eof),
+                    ));
+                }
+                let #bits: #x = #(#bytes)|*;
+                #check
+                ::core::result::Result::Ok((&input[#n..], #found))
+            }
+            parser
+        }
+    })
+}
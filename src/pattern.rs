@@ -0,0 +1,438 @@
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Error, Expr, Lit, LitStr, Macro, Path, Result, Token};
+
+/// A single bit of a parsed pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Bit {
+    Zero,
+    One,
+    Any,
+    Letter(char),
+}
+
+/// A continuous run of the same letter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Field {
+    pub(crate) name: char,
+    /// Bit position of the least significant bit of the run.
+    pub(crate) pos: u32,
+    pub(crate) width: u32,
+}
+
+impl Field {
+    pub(crate) fn mask(&self) -> u128 {
+        ones(self.width)
+    }
+
+    /// The letter is uppercase, so `bitpattern!` sign-extends the run.
+    pub(crate) fn signed(&self) -> bool {
+        self.name.is_ascii_uppercase()
+    }
+}
+
+/// Integer type selected by pattern length.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Ty {
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+}
+
+impl Ty {
+    pub(crate) fn from_len(len: usize) -> Option<Ty> {
+        match len {
+            1..=8 => Some(Ty::U8),
+            9..=16 => Some(Ty::U16),
+            17..=32 => Some(Ty::U32),
+            33..=64 => Some(Ty::U64),
+            65..=128 => Some(Ty::U128),
+            _ => None,
+        }
+    }
+
+    /// Type named by `ident`, if it's one of the unsigned integer types.
+    pub(crate) fn from_ident(ident: &Ident) -> Option<Ty> {
+        match ident.to_string().as_str() {
+            "u8" => Some(Ty::U8),
+            "u16" => Some(Ty::U16),
+            "u32" => Some(Ty::U32),
+            "u64" => Some(Ty::U64),
+            "u128" => Some(Ty::U128),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn ident(self) -> Ident {
+        let name = match self {
+            Ty::U8 => "u8",
+            Ty::U16 => "u16",
+            Ty::U32 => "u32",
+            Ty::U64 => "u64",
+            Ty::U128 => "u128",
+        };
+        Ident::new(name, Span::call_site())
+    }
+
+    pub(crate) fn bits(self) -> usize {
+        match self {
+            Ty::U8 => 8,
+            Ty::U16 => 16,
+            Ty::U32 => 32,
+            Ty::U64 => 64,
+            Ty::U128 => 128,
+        }
+    }
+
+    /// Suffixed integer literal of this type.
+    pub(crate) fn lit(self, value: u128) -> Literal {
+        match self {
+            Ty::U8 => Literal::u8_suffixed(value as u8),
+            Ty::U16 => Literal::u16_suffixed(value as u16),
+            Ty::U32 => Literal::u32_suffixed(value as u32),
+            Ty::U64 => Literal::u64_suffixed(value as u64),
+            Ty::U128 => Literal::u128_suffixed(value),
+        }
+    }
+}
+
+/// Parsed bit pattern.
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    /// Bits from the most significant one.
+    pub(crate) bits: Vec<Bit>,
+    /// Letter runs in order of appearance.
+    pub(crate) fields: Vec<Field>,
+}
+
+impl Pattern {
+    /// Parses a pattern string.
+    ///
+    /// '_' and whitespace are separators and are removed before anything else,
+    /// so "aa_aa" is a single 4-bit field.
+    pub(crate) fn parse(pattern: &str) -> Pattern {
+        let bits: Vec<Bit> = pattern
+            .chars()
+            .filter(|c| *c != '_' && !c.is_whitespace())
+            .map(|c| match c {
+                '0' => Bit::Zero,
+                '1' => Bit::One,
+                '?' => Bit::Any,
+                x => Bit::Letter(x),
+            })
+            .collect();
+
+        let mut fields: Vec<Field> = Vec::new();
+        let mut prev = None;
+        for (i, bit) in bits.iter().enumerate() {
+            let pos = (bits.len() - i - 1) as u32;
+            if let Bit::Letter(x) = *bit {
+                if prev == Some(x) {
+                    let field = fields.last_mut().unwrap();
+                    field.pos = pos;
+                    field.width += 1;
+                } else {
+                    fields.push(Field {
+                        name: x,
+                        pos,
+                        width: 1,
+                    });
+                }
+                prev = Some(x);
+            } else {
+                prev = None;
+            }
+        }
+
+        Pattern { bits, fields }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    pub(crate) fn ty(&self) -> Option<Ty> {
+        Ty::from_len(self.len())
+    }
+
+    /// Like `ty`, but with an error at `span` if no type has the length.
+    pub(crate) fn len_ty(&self, span: Span) -> Result<Ty> {
+        self.ty()
+            .ok_or_else(|| Error::new(span, format!("unsupported pattern length: {}", self.len())))
+    }
+
+    /// Parses a pattern literal, with the type of its length.
+    pub(crate) fn parse_lit(lit: &LitStr) -> Result<(Pattern, Ty)> {
+        let pattern = Pattern::parse(&lit.value());
+        let ty = pattern.len_ty(lit.span())?;
+        Ok((pattern, ty))
+    }
+
+    /// Mask of the '0' and '1' bits.
+    pub(crate) fn mask(&self) -> u128 {
+        self.fold(|bit| matches!(bit, Bit::Zero | Bit::One))
+    }
+
+    /// Value of the '0' and '1' bits.
+    pub(crate) fn value(&self) -> u128 {
+        self.fold(|bit| bit == Bit::One)
+    }
+
+    /// Mask of the '?' bits.
+    pub(crate) fn any_mask(&self) -> u128 {
+        self.fold(|bit| bit == Bit::Any)
+    }
+
+    /// Distinct letters in order of first appearance.
+    pub(crate) fn letters(&self) -> Vec<char> {
+        let mut ret = Vec::new();
+        for field in &self.fields {
+            if !ret.contains(&field.name) {
+                ret.push(field.name);
+            }
+        }
+        ret
+    }
+
+    /// Total width of all runs of a letter.
+    pub(crate) fn width(&self, letter: char) -> u32 {
+        self.fields
+            .iter()
+            .filter(|x| x.name == letter)
+            .map(|x| x.width)
+            .sum()
+    }
+
+    /// Runs of a letter with the offset of each run within the letter's value.
+    ///
+    /// Earlier runs hold the more significant bits, so the last run has offset 0.
+    pub(crate) fn runs(&self, letter: char) -> Vec<(&Field, u32)> {
+        let mut offset = self.width(letter);
+        self.fields
+            .iter()
+            .filter(|x| x.name == letter)
+            .map(|x| {
+                offset -= x.width;
+                (x, offset)
+            })
+            .collect()
+    }
+
+    /// Expression extracting a letter from `bits`.
+    ///
+    /// All runs of the letter are concatenated, with earlier runs as the more
+    /// significant bits.
+    pub(crate) fn extract(&self, letter: char, ty: Ty, bits: &Ident) -> TokenStream {
+        let runs = self.runs(letter).into_iter().map(|(field, offset)| {
+            let pos = ty.lit(field.pos as u128);
+            let mask = ty.lit(field.mask());
+            let offset = ty.lit(offset as u128);
+            quote! { (((#bits >> #pos) & #mask) << #offset) }
+        });
+        quote! { (#(#runs)|*) }
+    }
+
+    fn fold(&self, f: impl Fn(Bit) -> bool) -> u128 {
+        self.bits
+            .iter()
+            .fold(0, |acc, bit| (acc << 1) | f(*bit) as u128)
+    }
+}
+
+/// Pattern written as one or more adjacent string literals or `concat!` of
+/// them, which are concatenated like `concat!`.
+///
+/// Raw strings are parsed like other string literals, so `r"..."` and
+/// `r#"..."#` are allowed. Other literals are rejected.
+pub(crate) fn parse_lits(input: ParseStream) -> Result<LitStr> {
+    let first = parse_part(input)?;
+    if !peek_lits(input) {
+        return Ok(first);
+    }
+    let mut value = first.value();
+    let mut span = first.span();
+    while peek_lits(input) {
+        let next = parse_part(input)?;
+        value.push_str(&next.value());
+        span = span.join(next.span()).unwrap_or(span);
+    }
+    Ok(LitStr::new(&value, span))
+}
+
+/// Returns true if a pattern accepted by `parse_lits` follows.
+pub(crate) fn peek_lits(input: ParseStream) -> bool {
+    input.peek(LitStr) || peek_concat(input)
+}
+
+fn peek_concat(input: ParseStream) -> bool {
+    match input.cursor().ident() {
+        Some((ident, rest)) => {
+            ident == "concat" && matches!(rest.punct(), Some((x, _)) if x.as_char() == '!')
+        }
+        None => false,
+    }
+}
+
+/// A string literal or `concat!` of string literals.
+fn parse_part(input: ParseStream) -> Result<LitStr> {
+    if peek_concat(input) {
+        let mac: Macro = input.parse()?;
+        let parts = mac.parse_body_with(|input: ParseStream| {
+            Punctuated::<LitStr, Token![,]>::parse_terminated_with(input, parse_part)
+        })?;
+        let value: String = parts.iter().map(LitStr::value).collect();
+        return Ok(LitStr::new(&value, mac.span()));
+    }
+    if input.peek(LitStr) {
+        return input.parse();
+    }
+    if input.peek(Lit) {
+        let lit: Lit = input.parse()?;
+        return Err(Error::new(
+            lit.span(),
+            "expected a string literal pattern like `\"1010 aabb\"`",
+        ));
+    }
+    // A proc macro sees only the tokens of its input, so neither the value of
+    // a const nor the expansion of another macro is available.
+    if input.peek(syn::Ident::peek_any) || input.peek(Token![::]) {
+        let path: Path = input.parse()?;
+        let name = quote!(#path).to_string().replace(' ', "");
+        let message = if input.peek(Token![!]) {
+            format!(
+                "`{}!` can't be expanded in a pattern; write the pattern as string literals or `concat!` of them",
+                name
+            )
+        } else {
+            format!(
+                "the value of `{}` can't be read by a macro; write the pattern as string literals or `concat!` of them",
+                name
+            )
+        };
+        return Err(Error::new(path.span(), message));
+    }
+    input.parse()
+}
+
+/// Input of the form `"...", value`.
+pub(crate) struct PatternInput {
+    pub(crate) pattern: LitStr,
+    pub(crate) value: Expr,
+}
+
+impl Parse for PatternInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let value = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(PatternInput { pattern, value })
+    }
+}
+
+impl PatternInput {
+    /// Parses the pattern and selects its type.
+    pub(crate) fn parse_pattern(&self) -> Result<(Pattern, Ty)> {
+        Pattern::parse_lit(&self.pattern)
+    }
+}
+
+/// Input of the form `[low,] "...", value`.
+pub(crate) struct LowInput {
+    /// Given as `low, "..."`, matching the low bits of a wider value.
+    pub(crate) low: bool,
+    pub(crate) input: PatternInput,
+}
+
+impl Parse for LowInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let low = parse_low(input)?;
+        let input = input.parse()?;
+        Ok(LowInput { low, input })
+    }
+}
+
+/// Parses a leading `low,`.
+pub(crate) fn parse_low(input: ParseStream) -> Result<bool> {
+    if !(input.peek(syn::Ident) && input.peek2(Token![,])) {
+        return Ok(false);
+    }
+    let ident: Ident = input.parse()?;
+    if ident != "low" {
+        return Err(Error::new(ident.span(), "expected `low`"));
+    }
+    input.parse::<Token![,]>()?;
+    Ok(true)
+}
+
+/// Expression of the integer `value` as the type of `ty`, like the
+/// conversion of `bitpattern!`: a signed value is zero-extended from its own
+/// width rather than sign-extended like `as`, and a wider value is truncated.
+///
+/// Only operators, casts and `size_of_val` are used here and in
+/// [`gen_fits`], so they can be evaluated in constants, and an integer
+/// variable whose type isn't inferred yet falls back to `i32` like it does
+/// with `as`.
+pub(crate) fn gen_word(value: &Ident, ty: Ty) -> TokenStream {
+    let x = ty.ident();
+    let all = ty.lit(ones(ty.bits() as u32));
+    let x_bits = ty.bits() as u32;
+    let width = gen_width(value);
+    quote! {
+        ({
+            let width = #width;
+            if width < #x_bits {
+                (#value as #x) & (#all >> (#x_bits - width))
+            } else {
+                #value as #x
+            }
+        })
+    }
+}
+
+/// Condition that the integer `value` has no bits set above a pattern of
+/// `len` bits, so that [`gen_word`] doesn't truncate it.
+pub(crate) fn gen_fits(value: &Ident, len: u32) -> TokenStream {
+    if len >= 128 {
+        return quote! { true };
+    }
+    let width = gen_width(value);
+    // The bits of the value are zero-extended to `u128` from its own width.
+    quote! {
+        ({
+            let width = #width;
+            width <= #len || (#value as u128) << (128 - width) >> (128 - width) >> #len == 0
+        })
+    }
+}
+
+/// Width in bits of the type of the integer `value`.
+fn gen_width(value: &Ident) -> TokenStream {
+    quote! { (::core::mem::size_of_val(&#value) * 8) as u32 }
+}
+
+/// Span of the first `letter` in `lit`, or of the whole literal if the
+/// compiler can't point into it.
+pub(crate) fn letter_span(lit: &LitStr, letter: char) -> Span {
+    let token = lit.token();
+    let source = token.to_string();
+    let start = source.find('"').map_or(0, |x| x + 1);
+    source[start..]
+        .find(letter)
+        .and_then(|x| token.subspan(start + x..start + x + letter.len_utf8()))
+        .unwrap_or_else(|| lit.span())
+}
+
+pub(crate) fn ones(width: u32) -> u128 {
+    if width >= 128 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
@@ -0,0 +1,194 @@
+use crate::pattern::{ones, Ty};
+use proc_macro2::{Delimiter, Literal, Span, TokenStream, TokenTree};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::{Error, Expr, Ident, LitInt, Result, Token};
+
+/// Bits `hi` to `lo` of a value, written `value[hi:lo]`.
+struct Range {
+    value: Expr,
+    hi: u32,
+    lo: u32,
+    span: Span,
+}
+
+impl Range {
+    fn width(&self) -> u32 {
+        self.hi - self.lo + 1
+    }
+}
+
+impl Parse for Range {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // The range is the last bracket group before the next `,` or `++`.
+        let mut tokens: Vec<TokenTree> = input.step(|cursor| {
+            let mut rest = *cursor;
+            let mut tokens = Vec::new();
+            while let Some((token, next)) = rest.token_tree() {
+                if let TokenTree::Punct(x) = &token {
+                    let concat = x.as_char() == '+'
+                        && matches!(next.punct(), Some((y, _)) if y.as_char() == '+');
+                    if x.as_char() == ',' || concat {
+                        break;
+                    }
+                }
+                tokens.push(token);
+                rest = next;
+            }
+            Ok((tokens, rest))
+        })?;
+
+        let group = match tokens.pop() {
+            Some(TokenTree::Group(x)) if x.delimiter() == Delimiter::Bracket => x,
+            x => {
+                let span = x.map_or_else(|| input.span(), |x| x.span());
+                return Err(Error::new(span, "expected a bit range like `x[14:12]`"));
+            }
+        };
+        let span = group.span();
+        let (hi, lo) = syn::parse::Parser::parse2(
+            |input: ParseStream| {
+                let hi: LitInt = input.parse()?;
+                input.parse::<Token![:]>()?;
+                let lo: LitInt = input.parse()?;
+                Ok((hi.base10_parse()?, lo.base10_parse()?))
+            },
+            group.stream(),
+        )?;
+        let value = syn::parse2(tokens.into_iter().collect())?;
+        Ok(Range {
+            value,
+            hi,
+            lo,
+            span,
+        })
+    }
+}
+
+/// Input of `bitrange!`: `[ty;] x[hi:lo] ++ ..., ...`.
+pub(crate) struct RangeInput {
+    ty: Option<Ident>,
+    /// Values of the result, each concatenated from its ranges.
+    terms: Vec<Vec<Range>>,
+}
+
+impl Parse for RangeInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut ty = None;
+        if input.peek(Ident) && input.peek2(Token![;]) {
+            ty = Some(input.parse()?);
+            input.parse::<Token![;]>()?;
+        }
+
+        let mut terms = Vec::new();
+        while !input.is_empty() {
+            let mut term = vec![input.parse()?];
+            while input.peek(Token![+]) && input.peek2(Token![+]) {
+                input.parse::<Token![+]>()?;
+                input.parse::<Token![+]>()?;
+                term.push(input.parse()?);
+            }
+            terms.push(term);
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        if terms.is_empty() {
+            return Err(Error::new(
+                input.span(),
+                "expected a bit range like `x[14:12]`",
+            ));
+        }
+        Ok(RangeInput { ty, terms })
+    }
+}
+
+/// Extracts each range by shifting and masking.
+///
+/// The ranges of a term are concatenated with the first one as the most
+/// significant bits. Without a type, the masks are unsuffixed, so the result
+/// has the type of the value.
+pub(crate) fn gen_code(input: RangeInput) -> Result<TokenStream> {
+    let ty = match &input.ty {
+        Some(ident) => match Ty::from_ident(ident) {
+            Some(x) => Some(x),
+            None => {
+                return Err(Error::new(
+                    ident.span(),
+                    "expected one of `u8`, `u16`, `u32`, `u64` or `u128`",
+                ));
+            }
+        },
+        None => None,
+    };
+    let bits = ty.map_or(128, |x| x.bits() as u32);
+    let name = ty.unwrap_or(Ty::U128).ident();
+
+    let mut terms = Vec::new();
+    for term in &input.terms {
+        for range in term {
+            if range.hi < range.lo {
+                return Err(Error::new(
+                    range.span,
+                    format!(
+                        "range [{}:{}] is reversed, expected [hi:lo]",
+                        range.hi, range.lo
+                    ),
+                ));
+            }
+            if range.hi >= bits {
+                return Err(Error::new(
+                    range.span,
+                    format!("bit {} is out of range of `{}`", range.hi, name),
+                ));
+            }
+        }
+        let width: u32 = term.iter().map(Range::width).sum();
+        if width > bits {
+            return Err(Error::new(
+                term[0].value.span(),
+                format!(
+                    "concatenated ranges have {} bits, more than `{}`",
+                    width, name
+                ),
+            ));
+        }
+
+        let mut ranges = term.iter().map(|range| gen_range(range, ty));
+        let mut value = ranges.next().unwrap();
+        for (range, next) in term.iter().skip(1).zip(ranges) {
+            let width = lit(ty, range.width() as u128);
+            value = quote! { ((#value << #width) | #next) };
+        }
+        terms.push(value);
+    }
+
+    match terms.as_slice() {
+        [x] => Ok(x.clone()),
+        _ => Ok(quote! { (#(#terms),*) }),
+    }
+}
+
+fn gen_range(range: &Range, ty: Option<Ty>) -> TokenStream {
+    let value = &range.value;
+    let lo = lit(ty, range.lo as u128);
+    let mask = lit(ty, ones(range.width()));
+    match ty {
+        Some(ty) => {
+            let x = ty.ident();
+            let bits = Ident::new("bits", Span::mixed_site());
+            quote! { { let #bits: #x = #value; (#bits >> #lo) & #mask } }
+        }
+        None => quote! { (((#value) >> #lo) & #mask) },
+    }
+}
+
+/// Literal of `ty`, or unsuffixed without a type.
+fn lit(ty: Option<Ty>, value: u128) -> Literal {
+    match ty {
+        Some(x) => x.lit(value),
+        None => Literal::u128_unsuffixed(value),
+    }
+}
@@ -0,0 +1,500 @@
+use crate::consts;
+use crate::pattern::{ones, parse_lits, Pattern, PatternInput, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Expr, Ident, LitStr, Result, Token};
+
+/// Input of `bitscan!`: `[msb_first,] "...", value[, ty]`.
+pub(crate) struct ScanInput {
+    msb_first: bool,
+    pattern: LitStr,
+    value: Expr,
+    ty: Option<Ident>,
+}
+
+impl Parse for ScanInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut msb_first = false;
+        if input.peek(Ident) {
+            let ident: Ident = input.parse()?;
+            if ident != "msb_first" {
+                return Err(Error::new(ident.span(), "expected `msb_first`"));
+            }
+            msb_first = true;
+            input.parse::<Token![,]>()?;
+        }
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let value = input.parse()?;
+        let mut ty = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            ty = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(ScanInput {
+            msb_first,
+            pattern,
+            value,
+            ty,
+        })
+    }
+}
+
+/// Lowest or highest offset where the pattern matches, with its letters.
+///
+/// Every offset where the whole pattern fits in the type is tried by its own
+/// comparison, so there is no loop.
+pub(crate) fn gen_code(input: ScanInput) -> Result<TokenStream> {
+    let (pattern, ty) = consts::parse_pattern(&input.pattern, input.ty.as_ref())?;
+    let x = ty.ident();
+    let bits = Ident::new("bits", Span::mixed_site());
+    let shifted = Ident::new("shifted", Span::mixed_site());
+    let letters = pattern.letters();
+    let values: Vec<_> = letters
+        .iter()
+        .map(|x| pattern.extract(*x, ty, &shifted))
+        .collect();
+
+    let mut offsets: Vec<u32> = (0..=(ty.bits() - pattern.len()) as u32).collect();
+    if input.msb_first {
+        offsets.reverse();
+    }
+    let found = |offset: u32| {
+        quote! {
+            {
+                let #shifted = #bits >> #offset;
+                ::core::option::Option::Some((#offset, #(#values),*))
+            }
+        }
+    };
+
+    let body = if pattern.mask() == 0 {
+        found(offsets[0])
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        let arms = offsets.iter().map(|offset| {
+            let found = found(*offset);
+            quote! {
+                if (#bits >> #offset) & #bit_mask == #bit_pattern #found
+            }
+        });
+        quote! {
+            #(#arms)else* else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    let value = &input.value;
+    Ok(quote! {
+        {
+            let #bits: #x = #value;
+            #body
+        }
+    })
+}
+
+/// Iterator over the matches of the pattern at any bit offset of a byte
+/// slice, or the first match if `!iter`.
+///
+/// The bytes are shifted into a window one at a time, most significant bit
+/// first, and each offset ending in the last byte is tried.
+pub(crate) fn gen_find(input: PatternInput, iter: bool) -> Result<TokenStream> {
+    let (pattern, ty) = input.parse_pattern()?;
+    // The window holds the pattern and the 8 bits of a byte shifted in after it.
+    let window_ty = match Ty::from_len(pattern.len() + 8) {
+        Some(x) => x,
+        None => {
+            return Err(Error::new(
+                input.pattern.span(),
+                format!(
+                    "{}-bit pattern is too long to find, the limit is 120 bits",
+                    pattern.len()
+                ),
+            ));
+        }
+    };
+
+    let x = ty.ident();
+    let w = window_ty.ident();
+    let len = pattern.len();
+    let ones = window_ty.lit(ones(len as u32));
+    let bits = Ident::new("bits", Span::mixed_site());
+    let letters = pattern.letters();
+    let types = letters.iter().map(|_| &x);
+    let values = letters.iter().map(|x| pattern.extract(*x, ty, &bits));
+    let found = quote! {
+        return ::core::option::Option::Some((end - #len - shift as usize, #(#values),*));
+    };
+    let check = if pattern.mask() == 0 {
+        found
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        quote! {
+            if #bits & #bit_mask == #bit_pattern {
+                #found
+            }
+        }
+    };
+    let cast = if ty == window_ty {
+        None
+    } else {
+        Some(quote! { as #x })
+    };
+
+    let value = &input.value;
+    let find = quote! {
+        struct BitFind<'a> {
+            buf: &'a [u8],
+            /// Number of bytes shifted into `window`.
+            index: usize,
+            window: #w,
+            /// Offsets of the last byte left to try.
+            shift: u32,
+        }
+
+        impl<'a> ::core::iter::Iterator for BitFind<'a> {
+            type Item = (usize, #(#types),*);
+
+            fn next(&mut self) -> ::core::option::Option<Self::Item> {
+                loop {
+                    if self.shift == 0 {
+                        let byte = *self.buf.get(self.index)?;
+                        self.window = (self.window << 8) | byte as #w;
+                        self.index += 1;
+                        self.shift = 8;
+                    }
+                    self.shift -= 1;
+                    let shift = self.shift;
+                    let end = self.index * 8;
+                    if end < #len + shift as usize {
+                        continue;
+                    }
+                    let #bits = ((self.window >> shift) & #ones) #cast;
+                    #check
+                }
+            }
+        }
+
+        impl<'a> ::core::iter::FusedIterator for BitFind<'a> {}
+
+        let buf: &[u8] = &(#value)[..];
+    };
+    let init = quote! {
+        BitFind {
+            buf,
+            index: 0,
+            window: 0,
+            shift: 0,
+        }
+    };
+    if iter {
+        Ok(quote! {{ #find #init }})
+    } else {
+        Ok(quote! {{ #find ::core::iter::Iterator::next(&mut #init) }})
+    }
+}
+
+/// Input of `bitread!`: `[lsb_first,] "...", buf, pos`.
+pub(crate) struct ReadInput {
+    lsb_first: bool,
+    pattern: LitStr,
+    buf: Expr,
+    pos: Expr,
+}
+
+impl Parse for ReadInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut lsb_first = false;
+        if input.peek(Ident) {
+            let ident: Ident = input.parse()?;
+            match ident.to_string().as_str() {
+                "lsb_first" => lsb_first = true,
+                "msb_first" => (),
+                _ => {
+                    return Err(Error::new(
+                        ident.span(),
+                        "expected `msb_first` or `lsb_first`",
+                    ));
+                }
+            }
+            input.parse::<Token![,]>()?;
+        }
+        let pattern = parse_lits(input)?;
+        input.parse::<Token![,]>()?;
+        let buf = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let pos = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(ReadInput {
+            lsb_first,
+            pattern,
+            buf,
+            pos,
+        })
+    }
+}
+
+/// Reads the pattern from `buf` at the bit position `pos`, advancing it if
+/// the pattern matches and `advance` is set. Without `advance`, `pos` is a
+/// `usize` rather than a `&mut usize`.
+///
+/// The bytes holding the bits are assembled into a window, in big-endian
+/// order for MSB-first reading, or little-endian for LSB-first.
+pub(crate) fn gen_read(input: ReadInput, advance: bool) -> Result<TokenStream> {
+    let (pattern, ty) = Pattern::parse_lit(&input.pattern)?;
+    // The window holds the pattern and less than a byte on each side, and
+    // is wider than a byte to shift one in.
+    let window_ty = match Ty::from_len(pattern.len() + 8) {
+        Some(x) => x,
+        None => {
+            return Err(Error::new(
+                input.pattern.span(),
+                format!(
+                    "{}-bit pattern is too long to read, the limit is 120 bits",
+                    pattern.len()
+                ),
+            ));
+        }
+    };
+
+    let x = ty.ident();
+    let w = window_ty.ident();
+    let len = pattern.len();
+    let ones = window_ty.lit(ones(len as u32));
+    let bits = Ident::new("bits", Span::mixed_site());
+    let letters = pattern.letters();
+    let values: Vec<_> = letters
+        .iter()
+        .map(|x| pattern.extract(*x, ty, &bits))
+        .collect();
+    let cast = if ty == window_ty {
+        None
+    } else {
+        Some(quote! { as #x })
+    };
+    let assemble = if input.lsb_first {
+        quote! {
+            for (i, byte) in buf[first..=last].iter().enumerate() {
+                window |= (*byte as #w) << (8 * i);
+            }
+            let #bits = ((window >> (start % 8)) & #ones) #cast;
+        }
+    } else {
+        quote! {
+            for byte in &buf[first..=last] {
+                window = (window << 8) | *byte as #w;
+            }
+            let shift = (last - first + 1) * 8 - start % 8 - #len;
+            let #bits = ((window >> shift) & #ones) #cast;
+        }
+    };
+    let found = match values.as_slice() {
+        [x] => quote! { #x },
+        _ => quote! { (#(#values),*) },
+    };
+    let found = if advance {
+        quote! {
+            *pos = end;
+            ::core::option::Option::Some(#found)
+        }
+    } else {
+        quote! { ::core::option::Option::Some(#found) }
+    };
+    let found = if pattern.mask() == 0 {
+        found
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        quote! {
+            if #bits & #bit_mask == #bit_pattern {
+                #found
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    let buf = &input.buf;
+    let pos = &input.pos;
+    let start = if advance {
+        quote! {
+            let (buf, pos): (&[u8], &mut usize) = (&(#buf)[..], #pos);
+            let start = *pos;
+        }
+    } else {
+        quote! {
+            let (buf, start): (&[u8], usize) = (&(#buf)[..], #pos);
+        }
+    };
+    Ok(quote! {
+        {
+            #start
+            if buf.len().saturating_mul(8).saturating_sub(start) < #len {
+                ::core::option::Option::None
+            } else {
+                let end = start + #len;
+                let first = start / 8;
+                let last = (end - 1) / 8;
+                let mut window: #w = 0;
+                #assemble
+                #found
+            }
+        }
+    })
+}
+
+/// Iterator over the indices of the bytes of a slice matching the pattern.
+///
+/// With the "simd" feature, the bytes are compared in chunks of 16 with SSE2,
+/// or 32 with AVX2, if the target has them. The rest of the slice, and all
+/// of it on other targets, is compared one byte at a time with the same mask
+/// and value.
+pub(crate) fn gen_simd(input: PatternInput) -> Result<TokenStream> {
+    let pattern = Pattern::parse(&input.pattern.value());
+    if pattern.len() == 0 || pattern.len() > 8 {
+        return Err(Error::new(
+            input.pattern.span(),
+            format!(
+                "`bitmatch_simd!` matches bytes, so the pattern must have 1 to 8 bits, not {}",
+                pattern.len()
+            ),
+        ));
+    }
+    let mask = pattern.mask() as u8;
+    let value = pattern.value() as u8;
+    let matches = if mask == 0 {
+        quote! { let _ = x; true }
+    } else {
+        quote! { x & #mask == #value }
+    };
+
+    let x86 = quote! { any(target_arch = "x86", target_arch = "x86_64") };
+    let sse2 = quote! { all(#x86, target_feature = "sse2", not(target_feature = "avx2")) };
+    let avx2 = quote! { all(#x86, target_feature = "avx2") };
+    let chunk = if cfg!(feature = "simd") {
+        quote! {
+            #[cfg(#avx2)]
+            const LANES: usize = 32;
+            #[cfg(not(#avx2))]
+            const LANES: usize = 16;
+
+            #[cfg(#sse2)]
+            #[inline]
+            fn chunk(lanes: &[u8]) -> u32 {
+                #[cfg(target_arch = "x86")]
+                use ::core::arch::x86::*;
+                #[cfg(target_arch = "x86_64")]
+                use ::core::arch::x86_64::*;
+
+                // SAFETY: `lanes` has 16 bytes, and the target has SSE2.
+                unsafe {
+                    let lanes = _mm_loadu_si128(lanes.as_ptr() as *const __m128i);
+                    let masked = _mm_and_si128(lanes, _mm_set1_epi8(#mask as i8));
+                    let eq = _mm_cmpeq_epi8(masked, _mm_set1_epi8(#value as i8));
+                    _mm_movemask_epi8(eq) as u32
+                }
+            }
+
+            #[cfg(#avx2)]
+            #[inline]
+            fn chunk(lanes: &[u8]) -> u32 {
+                #[cfg(target_arch = "x86")]
+                use ::core::arch::x86::*;
+                #[cfg(target_arch = "x86_64")]
+                use ::core::arch::x86_64::*;
+
+                // SAFETY: `lanes` has 32 bytes, and the target has AVX2.
+                unsafe {
+                    let lanes = _mm256_loadu_si256(lanes.as_ptr() as *const __m256i);
+                    let masked = _mm256_and_si256(lanes, _mm256_set1_epi8(#mask as i8));
+                    let eq = _mm256_cmpeq_epi8(masked, _mm256_set1_epi8(#value as i8));
+                    _mm256_movemask_epi8(eq) as u32
+                }
+            }
+
+            #[cfg(not(any(#sse2, #avx2)))]
+            #[inline]
+            fn chunk(lanes: &[u8]) -> u32 {
+                let mut found = 0;
+                for (i, x) in lanes.iter().enumerate() {
+                    found |= (matches(*x) as u32) << i;
+                }
+                found
+            }
+        }
+    } else {
+        quote! {
+            const LANES: usize = 16;
+
+            #[inline]
+            fn chunk(lanes: &[u8]) -> u32 {
+                let mut found = 0;
+                for (i, x) in lanes.iter().enumerate() {
+                    found |= (matches(*x) as u32) << i;
+                }
+                found
+            }
+        }
+    };
+
+    let value = &input.value;
+    Ok(quote! {
+        {
+            struct BitMatchSimd<'a> {
+                buf: &'a [u8],
+                /// Index of the first byte not compared yet.
+                index: usize,
+                /// Index of the first byte of the last chunk.
+                base: usize,
+                /// Bits of the matches in the last chunk not returned yet.
+                found: u32,
+            }
+
+            #[inline]
+            fn matches(x: u8) -> bool {
+                #matches
+            }
+
+            #chunk
+
+            impl<'a> ::core::iter::Iterator for BitMatchSimd<'a> {
+                type Item = usize;
+
+                fn next(&mut self) -> ::core::option::Option<usize> {
+                    loop {
+                        if self.found != 0 {
+                            let i = self.found.trailing_zeros() as usize;
+                            self.found &= self.found - 1;
+                            return ::core::option::Option::Some(self.base + i);
+                        }
+                        let rest = &self.buf[self.index..];
+                        if rest.len() >= LANES {
+                            self.base = self.index;
+                            self.found = chunk(&rest[..LANES]);
+                            self.index += LANES;
+                        } else {
+                            let x = *rest.first()?;
+                            self.index += 1;
+                            if matches(x) {
+                                return ::core::option::Option::Some(self.index - 1);
+                            }
+                        }
+                    }
+                }
+            }
+
+            impl<'a> ::core::iter::FusedIterator for BitMatchSimd<'a> {}
+
+            BitMatchSimd {
+                buf: &(#value)[..],
+                index: 0,
+                base: 0,
+                found: 0,
+            }
+        }
+    })
+}
@@ -0,0 +1,82 @@
+use crate::pattern::{Bit, Pattern, PatternInput, Ty};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Error, Ident, Result};
+
+/// Matches a pattern against a `bitvec::slice::BitSlice`.
+///
+/// Pattern bit 0 is index 0 of the slice, whatever its `BitOrder`, and the
+/// slice may be longer than the pattern. Each '0' and '1' bit is read by
+/// index, and each run of letters is assembled from its bits with the first
+/// one as the most significant. Runs of up to 128 bits have the smallest type
+/// holding them, and longer ones are subslices, so nothing is copied.
+pub(crate) fn gen_code(input: PatternInput) -> Result<TokenStream> {
+    if !cfg!(feature = "bitvec") {
+        return Err(Error::new(
+            input.pattern.span(),
+            "`bitmatch_bitslice!` requires the \"bitvec\" feature of bitpattern",
+        ));
+    }
+    let pattern = Pattern::parse(&input.pattern.value());
+    let len = pattern.len();
+    if len == 0 {
+        return Err(Error::new(input.pattern.span(), "the pattern is empty"));
+    }
+
+    let bits = Ident::new("bits", Span::mixed_site());
+    let checks: Vec<_> = pattern
+        .bits
+        .iter()
+        .enumerate()
+        .filter_map(|(index, bit)| match bit {
+            Bit::Zero => Some(quote! { !#bits[#index] }),
+            Bit::One => Some(quote! { #bits[#index] }),
+            _ => None,
+        })
+        .collect();
+    let fields = pattern.fields.iter().map(|field| {
+        let start = len - field.pos as usize - field.width as usize;
+        let end = len - field.pos as usize;
+        match Ty::from_len(field.width as usize) {
+            Some(ty) => {
+                let x = ty.ident();
+                let field = Ident::new("field", Span::mixed_site());
+                let index = Ident::new("index", Span::mixed_site());
+                quote! {
+                    {
+                        let mut #field: #x = 0;
+                        for #index in #start..#end {
+                            #field = (#field << 1) | (#bits[#index] as #x);
+                        }
+                        #field
+                    }
+                }
+            }
+            None => quote! { &#bits[#start..#end] },
+        }
+    });
+    let found = quote! { ::core::option::Option::Some((#(#fields),*)) };
+    let found = if checks.is_empty() {
+        found
+    } else {
+        quote! {
+            if #(#checks)&&* {
+                #found
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    let value = &input.value;
+    Ok(quote! {
+        {
+            let #bits: &::bitvec::slice::BitSlice<_, _> = #value;
+            if #bits.len() < #len {
+                ::core::option::Option::None
+            } else {
+                #found
+            }
+        }
+    })
+}
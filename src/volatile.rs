@@ -0,0 +1,50 @@
+use crate::gen_fields;
+use crate::pattern::PatternInput;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Ident, Result};
+
+/// Matching of a register read once by `read_volatile`, from a reference or
+/// a raw pointer if `ptr`.
+///
+/// Only the read of a raw pointer is unsafe, so it must be in the caller's
+/// `unsafe` block. The letters are extracted like `bitpattern!`.
+pub(crate) fn gen_code(input: PatternInput, ptr: bool) -> Result<TokenStream> {
+    let (pattern, ty) = input.parse_pattern()?;
+    let x = ty.ident();
+    let reg = &input.value;
+    let bits = Ident::new("bits", Span::mixed_site());
+    let read = if ptr {
+        quote! {
+            let reg: *const #x = #reg;
+            let #bits = ::core::ptr::read_volatile(reg);
+        }
+    } else {
+        quote! {
+            let reg: &#x = #reg;
+            // SAFETY: a reference is valid for reads.
+            let #bits = unsafe { ::core::ptr::read_volatile(reg) };
+        }
+    };
+    let args = gen_fields(&pattern, ty, &bits);
+    let args = quote! { (#(#args),*) };
+    let body = if pattern.mask() == 0 {
+        quote! { ::core::option::Option::Some(#args) }
+    } else {
+        let bit_mask = ty.lit(pattern.mask());
+        let bit_pattern = ty.lit(pattern.value());
+        quote! {
+            if #bits & #bit_mask == #bit_pattern {
+                ::core::option::Option::Some(#args)
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+    Ok(quote! {
+        {
+            #read
+            #body
+        }
+    })
+}
@@ -0,0 +1,38 @@
+use arbitrary_int::{u1, u12, u20, u3, u4, u44, u7};
+use bitpattern::bitpattern;
+
+#[test]
+fn test_exact() {
+    let fields: Option<(u3, u4)> = bitpattern!(exact, "1 aaa bbbb", 0xacu8);
+    assert_eq!(fields, Some((u3::new(2), u4::new(0xc))));
+    assert_eq!(bitpattern!(exact, "1 aaa bbbb", 0x2cu8), None);
+
+    let fields: Option<(u12, u4)> = bitpattern!(exact, "aaaaaaaaaaaa bbbb", 0xabcdu16);
+    assert_eq!(fields, Some((u12::new(0xabc), u4::new(0xd))));
+
+    // Runs of a primitive width have the primitive type.
+    let fields: Option<(u8, u7, u1)> = bitpattern!(exact, "aaaaaaaa bbbbbbb c", 0x12ffu16);
+    assert_eq!(fields, Some((0x12, u7::new(0x7f), u1::new(1))));
+    let fields: Option<(u20, u64, u44)> = bitpattern!(
+        exact,
+        "aaaaaaaaaaaaaaaaaaaa \
+         bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb \
+         cccccccccccccccccccccccccccccccccccccccccccc",
+        u128::MAX
+    );
+    assert_eq!(
+        fields,
+        Some((u20::new(0xf_ffff), u64::MAX, u44::new(0xfff_ffff_ffff)))
+    );
+    let full: Option<u32> =
+        bitpattern!(exact, "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa", 0x1234_5678u32);
+    assert_eq!(full, Some(0x1234_5678));
+
+    // The other forms are exact too.
+    let fields: Option<(u4, u4)> = bitpattern!(low, exact, "aaaa bbbb", 0x1fau32);
+    assert_eq!(fields, Some((u4::new(0xf), u4::new(0xa))));
+    let fields: Option<(u4, u4)> = bitpattern!(exact, "aaaa bbbb", 0x1fau32 @ 1);
+    assert_eq!(fields, Some((u4::new(0xf), u4::new(0xd))));
+    let fields: Option<(u4, u12)> = bitpattern!(exact, "aaaa bbbbbbbbbbbb", 0x1234usize, usize);
+    assert_eq!(fields, Some((u4::new(1), u12::new(0x234))));
+}
@@ -0,0 +1,82 @@
+use bitpattern::bitcas;
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn test_bitcas() {
+    let x = AtomicU8::new(0x0f);
+    assert_eq!(bitcas!("aaa? ?bbb", &x, a = 5, Ordering::SeqCst), 0x0f);
+    assert_eq!(x.load(Ordering::SeqCst), 0xaf);
+    assert_eq!(
+        bitcas!("aaa? ?bbb", &x, b = 0, a = 0, Ordering::Relaxed),
+        0xaf
+    );
+    assert_eq!(x.load(Ordering::SeqCst), 0x08);
+
+    // All runs of a split letter are written, and long values are truncated.
+    let x = AtomicU16::new(0);
+    bitcas!("aa?? ???? ???? ??aa", &x, a = 0x1f, Ordering::Release);
+    assert_eq!(x.into_inner(), 0xc003);
+
+    let x = AtomicU32::new(0x8000_0000);
+    let prev = bitcas!(
+        "1??? ???? ???? ???? ???? aaa? ???? ????",
+        &x,
+        a = 7,
+        Ordering::Acquire
+    );
+    assert_eq!(prev, 0x8000_0000);
+    assert_eq!(x.into_inner(), 0x8000_0e00);
+}
+
+#[test]
+fn test_bitcas_threads() {
+    const ROUNDS: u32 = 10000;
+    let x = Arc::new(AtomicU32::new(0));
+    let threads: Vec<_> = (0..4)
+        .map(|i| {
+            let x = Arc::clone(&x);
+            thread::spawn(move || {
+                for j in 0..ROUNDS {
+                    // Each thread only writes its own byte, so it finds the
+                    // value it wrote last.
+                    let prev = match i {
+                        0 => bitcas!(
+                            "???????? ???????? ???????? aaaaaaaa",
+                            &*x,
+                            a = j,
+                            Ordering::AcqRel
+                        ),
+                        1 => bitcas!(
+                            "???????? ???????? aaaaaaaa ????????",
+                            &*x,
+                            a = j,
+                            Ordering::AcqRel
+                        ),
+                        2 => bitcas!(
+                            "???????? aaaaaaaa ???????? ????????",
+                            &*x,
+                            a = j,
+                            Ordering::AcqRel
+                        ),
+                        _ => bitcas!(
+                            "aaaaaaaa ???????? ???????? ????????",
+                            &*x,
+                            a = j,
+                            Ordering::AcqRel
+                        ),
+                    };
+                    let last = j.wrapping_sub(1) & 0xff;
+                    let expected = if j == 0 { 0 } else { last };
+                    assert_eq!((prev >> (8 * i)) & 0xff, expected);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let last = (ROUNDS - 1) & 0xff;
+    assert_eq!(x.load(Ordering::SeqCst), last * 0x0101_0101);
+}
@@ -0,0 +1,821 @@
+use bitpattern::{bitmatch, bitswitch};
+
+#[derive(Debug, PartialEq)]
+enum Op {
+    Add(u32, u32, u32),
+    Sub(u32, u32, u32),
+    Jal(u32),
+    Zero,
+    Illegal,
+}
+
+#[bitmatch]
+fn decode(insn: u32) -> Op {
+    match insn {
+        "0000000 bbbbb aaaaa 000 ddddd 0110011" => Op::Add(d, a, b),
+        "0100000 bbbbb aaaaa 000 ddddd 0110011" => Op::Sub(d, a, b),
+        "???????????????????? ddddd 1101111" => Op::Jal(d),
+        _ => Op::Illegal,
+    }
+}
+
+#[test]
+fn test_match() {
+    assert_eq!(decode(0x0020_81b3), Op::Add(3, 1, 2));
+    assert_eq!(decode(0x4020_81b3), Op::Sub(3, 1, 2));
+    assert_eq!(decode(0x0000_00ef), Op::Jal(1));
+    assert_eq!(decode(0xffff_ffef), Op::Jal(31));
+    assert_eq!(decode(0), Op::Illegal);
+}
+
+#[bitmatch]
+fn decode_raw(insn: u32) -> Op {
+    match insn {
+        r"0000000 bbbbb aaaaa 000 ddddd 0110011" => Op::Add(d, a, b),
+        r#"0100000 bbbbb aaaaa 000 ddddd 0110011"# => Op::Sub(d, a, b),
+        _ => Op::Illegal,
+    }
+}
+
+#[test]
+fn test_match_raw() {
+    assert_eq!(decode_raw(0x0020_81b3), Op::Add(3, 1, 2));
+    assert_eq!(decode_raw(0x4020_81b3), Op::Sub(3, 1, 2));
+    assert_eq!(decode_raw(0x0000_00ef), Op::Illegal);
+}
+
+#[bitmatch]
+fn decode_mixed(insn: u32) -> Op {
+    match insn {
+        0 => Op::Zero,
+        "0000000 bbbbb aaaaa 000 ddddd 0110011" => Op::Add(d, a, b),
+        0x4020_81b3 | 0x4020_81b4 => Op::Sub(3, 1, 2),
+        "???????????????????? ddddd 1101111" => Op::Jal(d),
+        x => Op::Jal(x),
+    }
+}
+
+#[test]
+fn test_match_mixed() {
+    assert_eq!(decode_mixed(0), Op::Zero);
+    assert_eq!(decode_mixed(0x0020_81b3), Op::Add(3, 1, 2));
+    assert_eq!(decode_mixed(0x4020_81b4), Op::Sub(3, 1, 2));
+    assert_eq!(decode_mixed(0x0000_00ef), Op::Jal(1));
+    assert_eq!(decode_mixed(0x1234), Op::Jal(0x1234));
+}
+
+#[bitmatch]
+fn first_match(x: u8) -> u8 {
+    match x {
+        "1??? ???0" => 1,
+        "11?? ????" => 2,
+        "?1?? ????" => 3,
+        _ => 4,
+    }
+}
+
+#[test]
+fn test_match_order() {
+    assert_eq!(first_match(0xc0), 1);
+    assert_eq!(first_match(0xc1), 2);
+    assert_eq!(first_match(0x80), 1);
+    assert_eq!(first_match(0x41), 3);
+    assert_eq!(first_match(0x01), 4);
+}
+
+#[bitmatch]
+fn nested(x: u16) -> u16 {
+    match x {
+        "0000 0000 aaaa aaaa" => match a {
+            "1111 bbbb" => b as u16,
+            _ => 0x100,
+        },
+        "aaaa aaaa bbbb bbbb" => a + b,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_match_nested() {
+    assert_eq!(nested(0x00f5), 5);
+    assert_eq!(nested(0x0015), 0x100);
+    assert_eq!(nested(0x0102), 3);
+}
+
+#[bitmatch]
+fn split(x: u8) -> u8 {
+    match x {
+        "aa0b baa0" => a << 4 | b,
+        "aa1? ????" => a,
+        _ => 0xff,
+    }
+}
+
+#[test]
+fn test_match_split() {
+    assert_eq!(split(0b1101_1100), 0b1110 << 4 | 0b11);
+    assert_eq!(split(0b1011_1100), 0b10);
+    assert_eq!(split(0b0000_0001), 0xff);
+}
+
+#[bitmatch]
+fn hygiene(value: u8, bits: u8) -> u8 {
+    match value {
+        "1aaa aaaa" => a + bits,
+        _ => value,
+    }
+}
+
+#[test]
+fn test_match_hygiene() {
+    assert_eq!(hygiene(0x81, 2), 3);
+    assert_eq!(hygiene(0x01, 2), 1);
+}
+
+struct Counter {
+    count: u32,
+    value: u8,
+}
+
+impl Counter {
+    fn next(&mut self) -> u8 {
+        self.count += 1;
+        self.value
+    }
+
+    #[bitmatch]
+    fn classify(&mut self) -> u8 {
+        match self.next() {
+            "0??? ????" => 0,
+            "10?? ????" => 1,
+            "110? ????" => 2,
+            _ => 3,
+        }
+    }
+}
+
+#[test]
+fn test_match_once() {
+    let mut x = Counter {
+        count: 0,
+        value: 0xe0,
+    };
+    assert_eq!(x.classify(), 3);
+    assert_eq!(x.count, 1);
+}
+
+#[bitmatch]
+fn untouched(x: u8) -> u8 {
+    match x {
+        0 => 1,
+        _ => 2,
+    }
+}
+
+#[test]
+fn test_match_untouched() {
+    assert_eq!(untouched(0), 1);
+    assert_eq!(untouched(1), 2);
+}
+
+#[bitmatch(low)]
+fn exhaustive(x: u8) -> u8 {
+    match x {
+        "0??? ????" => 0,
+        "10aa aaaa" => a,
+        "110? ????" => 2,
+        "111? ???0" => 3,
+        "111? ???1" => 4,
+    }
+}
+
+#[test]
+fn test_match_exhaustive() {
+    assert_eq!(exhaustive(0x00), 0);
+    assert_eq!(exhaustive(0x85), 5);
+    assert_eq!(exhaustive(0xc0), 2);
+    assert_eq!(exhaustive(0xe0), 3);
+    assert_eq!(exhaustive(0xff), 4);
+}
+
+#[bitmatch]
+fn exhaustive_wide(x: u128) -> u128 {
+    match x {
+        "0aaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa" => a,
+        "1???_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????_???a" => a,
+    }
+}
+
+#[test]
+fn test_match_exhaustive_wide() {
+    assert_eq!(exhaustive_wide(5), 5);
+    assert_eq!(exhaustive_wide(!0), 1);
+}
+
+#[bitmatch(non_exhaustive)]
+fn non_exhaustive(x: u8) -> u8 {
+    match x {
+        "1aaa aaaa" => a,
+        _ => 0xff,
+    }
+}
+
+#[bitmatch]
+fn non_exhaustive_match(x: u8) -> u8 {
+    #[bitmatch(non_exhaustive, table_bits = 1)]
+    match x {
+        "1aaa aaaa" => a,
+        _ => 0xff,
+    }
+}
+
+#[test]
+fn test_match_non_exhaustive() {
+    assert_eq!(non_exhaustive(0x81), 1);
+    assert_eq!(non_exhaustive_match(0x81), 1);
+    assert_eq!(non_exhaustive(0x01), 0xff);
+    assert_eq!(non_exhaustive_match(0x01), 0xff);
+}
+
+#[derive(Debug, PartialEq)]
+enum Compressed {
+    Addi16Sp(u16),
+    Lui(u16, u16),
+    Other,
+}
+
+#[bitmatch]
+fn decode_compressed(insn: u16) -> Compressed {
+    match insn {
+        "011 a ddddd aaaaa 01" if d == 2 => Compressed::Addi16Sp(a),
+        "011 a ddddd aaaaa 01" => Compressed::Lui(d, a),
+        _ => Compressed::Other,
+    }
+}
+
+#[test]
+fn test_match_guard() {
+    assert_eq!(decode_compressed(0x7105), Compressed::Addi16Sp(0x21));
+    assert_eq!(decode_compressed(0x6185), Compressed::Lui(3, 1));
+    assert_eq!(decode_compressed(0x0001), Compressed::Other);
+}
+
+#[bitmatch]
+fn guard_fallthrough(x: u8, limit: u8) -> u8 {
+    match x {
+        "0000 aaaa" if a > limit => 1,
+        "0??? ????" if x > 0x40 => 2,
+        y if y == limit => 3,
+        "0??? ????" => 4,
+        _ => 5,
+    }
+}
+
+#[test]
+fn test_match_guard_fallthrough() {
+    assert_eq!(guard_fallthrough(0x05, 4), 1);
+    assert_eq!(guard_fallthrough(0x05, 5), 3);
+    assert_eq!(guard_fallthrough(0x05, 6), 4);
+    assert_eq!(guard_fallthrough(0x45, 6), 2);
+    assert_eq!(guard_fallthrough(0x85, 6), 5);
+}
+
+#[bitmatch(low)]
+fn guard_exhaustive(x: u8) -> u8 {
+    match x {
+        "0aaa aaaa" if a == 0 => 0,
+        "0aaa aaaa" => a,
+        "1??? ????" => 0x80,
+    }
+}
+
+#[test]
+fn test_match_guard_exhaustive() {
+    assert_eq!(guard_exhaustive(0x00), 0);
+    assert_eq!(guard_exhaustive(0x05), 5);
+    assert_eq!(guard_exhaustive(0x85), 0x80);
+}
+
+#[bitmatch(low)]
+fn control_flow(xs: &[u8]) -> u32 {
+    let mut sum = 0;
+    for x in xs {
+        match *x {
+            "0000 0000" => break,
+            "1??? ????" => continue,
+            "0aaa aaaa" => sum += a as u32,
+        }
+    }
+    sum
+}
+
+#[test]
+fn test_match_control_flow() {
+    assert_eq!(control_flow(&[1, 0x80, 2, 0, 4]), 3);
+}
+
+#[bitmatch]
+fn alternatives(x: u8) -> u8 {
+    match x {
+        "1111 00aa" | "1110 00aa" => a,
+        "0aaa 1bb?" | "0bba aa0?" if a != 0 => a << 4 | b,
+        ("0000 0000" | "0000 0001") | "0000 0010" => 0xfe,
+        _ => 0xff,
+    }
+}
+
+#[test]
+fn test_match_alternatives() {
+    assert_eq!(alternatives(0xf2), 2);
+    assert_eq!(alternatives(0xe3), 3);
+    assert_eq!(alternatives(0xf6), 0xff);
+    assert_eq!(alternatives(0b0101_1100), 0x52);
+    assert_eq!(alternatives(0b0111_0100), 0x53);
+    assert_eq!(alternatives(0b0000_1100), 0xff);
+    assert_eq!(alternatives(0x01), 0xfe);
+    assert_eq!(alternatives(0x02), 0xfe);
+}
+
+#[bitmatch(low)]
+fn alternatives_exhaustive(x: u8) -> u8 {
+    match x {
+        "0??? ????" | "11?? ????" => 0,
+        "10aa aaaa" => a,
+    }
+}
+
+#[test]
+fn test_match_alternatives_exhaustive() {
+    assert_eq!(alternatives_exhaustive(0x45), 0);
+    assert_eq!(alternatives_exhaustive(0xc5), 0);
+    assert_eq!(alternatives_exhaustive(0x85), 5);
+}
+
+#[derive(Debug, PartialEq)]
+enum Trap {
+    Load(i16, u16, u16),
+    Illegal(i16),
+}
+
+#[bitmatch(low)]
+fn trap(insn: i16) -> Trap {
+    match insn {
+        raw @ "0000 0000 aaaa bbbb" if raw != 0 => Trap::Load(raw, a, b),
+        raw @ ("1??? ???? ???? ????" | "0??? ???? ???? ????") => Trap::Illegal(raw),
+    }
+}
+
+#[test]
+fn test_match_binding() {
+    assert_eq!(trap(0x0012), Trap::Load(0x12, 1, 2));
+    assert_eq!(trap(0), Trap::Illegal(0));
+    assert_eq!(trap(-1), Trap::Illegal(-1));
+}
+
+#[bitmatch]
+fn binding_mut(x: u8) -> u8 {
+    match x {
+        mut raw @ "0000 aaaa" => {
+            raw += a;
+            raw
+        }
+        _ => 0,
+    }
+}
+
+#[test]
+fn test_match_binding_mut() {
+    assert_eq!(binding_mut(0x05), 10);
+    assert_eq!(binding_mut(0x15), 0);
+}
+
+macro_rules! table_decoder {
+    ($name:ident, $($options:tt)*) => {
+        #[bitmatch($($options)*)]
+        fn $name(x: u16, limit: u16) -> (u8, u16) {
+            match x {
+                0 => (0, 0),
+                "0000 aaaa bbbb cccc" => (1, a + b + c),
+                "0001 aaaa aaaa ????" if a < limit => (2, a),
+                "0001 aaaa ???? bbbb" => (3, a ^ b),
+                "01?? aaaa aaaa aaaa" | "1??1 aaaa aaaa aaaa" => (4, a),
+                0x8000..=0x80ff => (5, x),
+                raw @ "1100 ???? ???? ????" if raw & 1 == 0 => (6, raw),
+                "1??? ???? ???? aaaa" => (7, a),
+                _ => (8, x),
+            }
+        }
+    };
+}
+
+table_decoder!(linear_decoder,);
+table_decoder!(table_decoder, table_bits = 4);
+table_decoder!(narrow_table_decoder, table_bits = 1);
+table_decoder!(wide_table_decoder, table_bits = 16);
+
+#[test]
+fn test_match_table() {
+    for limit in [0, 0x10, 0x100] {
+        for x in 0..=u16::MAX {
+            let expected = linear_decoder(x, limit);
+            assert_eq!(table_decoder(x, limit), expected, "{:#x}", x);
+            assert_eq!(narrow_table_decoder(x, limit), expected, "{:#x}", x);
+            assert_eq!(wide_table_decoder(x, limit), expected, "{:#x}", x);
+        }
+    }
+}
+
+#[bitmatch(low, table_bits = 8)]
+fn table_exhaustive(x: u8) -> u8 {
+    match x {
+        "0??? ????" => 0,
+        "10aa aaaa" => a,
+        "11?? ??bb" => b,
+    }
+}
+
+#[test]
+fn test_match_table_exhaustive() {
+    assert_eq!(table_exhaustive(0x12), 0);
+    assert_eq!(table_exhaustive(0x92), 0x12);
+    assert_eq!(table_exhaustive(0xc2), 2);
+}
+
+#[bitmatch]
+fn destructure(x: u8) -> (u8, u8, u8) {
+    let "oooa a??b" = x;
+    (o, a, b)
+}
+
+#[test]
+fn test_let() {
+    assert_eq!(destructure(0b1011_0101), (5, 2, 1));
+}
+
+#[bitmatch]
+fn destructure_else(x: u8) -> Result<(u8, u8), u8> {
+    let "10_aabb" = x else {
+        return Err(x);
+    };
+    Ok((a, b))
+}
+
+#[test]
+fn test_let_else() {
+    assert_eq!(destructure_else(0b10_0110), Ok((1, 2)));
+    assert_eq!(destructure_else(0b11_0110), Err(0b11_0110));
+}
+
+#[bitmatch]
+fn if_let(x: u16) -> u16 {
+    if let "110 aaa bbb" = x {
+        a + b
+    } else if let "10 aaaa ???" | "111 aaaa ??" = x {
+        a * 10
+    } else {
+        0
+    }
+}
+
+#[test]
+fn test_if_let() {
+    assert_eq!(if_let(0b110_011_001), 4);
+    assert_eq!(if_let(0b1_0001_1000), 30);
+    assert_eq!(if_let(0b1_1101_0111), 50);
+    assert_eq!(if_let(0b0_0000_0000), 0);
+}
+
+struct Bytes<'a> {
+    bytes: &'a [u8],
+    reads: usize,
+}
+
+impl Bytes<'_> {
+    fn next_byte(&mut self) -> u8 {
+        let x = self.bytes[self.reads];
+        self.reads += 1;
+        x
+    }
+}
+
+#[bitmatch]
+fn varint(bytes: &mut Bytes) -> u32 {
+    let mut ret = 0;
+    let mut shift = 0;
+    while let "1aaa aaaa" = bytes.next_byte() {
+        ret |= (a as u32) << shift;
+        shift += 7;
+    }
+    let last = bytes.bytes[bytes.reads - 1];
+    if let "0aaa aaaa" = last {
+        ret |= (a as u32) << shift;
+    }
+    ret
+}
+
+#[bitmatch]
+fn if_let_once(bytes: &mut Bytes) -> u8 {
+    if let raw @ "0aaa aaaa" = bytes.next_byte() {
+        raw + a
+    } else {
+        0
+    }
+}
+
+#[test]
+fn test_while_let() {
+    let mut bytes = Bytes {
+        bytes: &[0xe5, 0x8e, 0x26, 0xff],
+        reads: 0,
+    };
+    assert_eq!(varint(&mut bytes), 624_485);
+    assert_eq!(bytes.reads, 3);
+
+    let mut bytes = Bytes {
+        bytes: &[0x12, 0x34],
+        reads: 0,
+    };
+    assert_eq!(if_let_once(&mut bytes), 0x24);
+    assert_eq!(bytes.reads, 1);
+}
+
+#[bitmatch]
+fn alu(#[bitmatch("ffff aaaa bbbb")] insn: u16, ops: [fn(u16, u16) -> u16; 2]) -> (u16, u16) {
+    (ops[f as usize](a, b), insn)
+}
+
+struct Alu;
+
+impl Alu {
+    #[bitmatch]
+    fn add(&self, #[bitmatch("aaaa bbbb")] x: u8, #[bitmatch("?ccc dddd")] y: u8) -> u8 {
+        a + b + c + d
+    }
+}
+
+#[test]
+fn test_param() {
+    let ops: [fn(u16, u16) -> u16; 2] = [|a, b| a + b, |a, b| a * b];
+    assert_eq!(alu(0x012, ops), (3, 0x012));
+    assert_eq!(alu(0x123, ops), (6, 0x123));
+    assert_eq!(Alu.add(0x12, 0xa3), 1 + 2 + 2 + 3);
+}
+
+#[bitmatch(low)]
+fn halfwords(hi: u8, lo: u8) -> (u8, u16, u16) {
+    match (hi, lo) {
+        (0, 0) => (0, 0, 0),
+        ("1111 aaaa", "00aa bbbb") => (1, a, b),
+        ("0??? ????", "aaaa ????") => (2, a, 0),
+        (_, "1aaa ????") => (3, a, 0),
+        x @ (_, "01?? ????") => (4, x.0 as u16, x.1 as u16),
+        ("10?? ????", "00?? ????") | ("1110 ????", "00?? ????") => (5, 0, 0),
+        ("110? ????", "00?? ????") => (6, 0, 0),
+    }
+}
+
+#[test]
+fn test_match_tuple() {
+    assert_eq!(halfwords(0, 0), (0, 0, 0));
+    assert_eq!(halfwords(0xf5, 0x2c), (1, 0x16, 0xc));
+    assert_eq!(halfwords(0x12, 0x34), (2, 3, 0));
+    assert_eq!(halfwords(0xf5, 0xa0), (3, 2, 0));
+    assert_eq!(halfwords(0xf5, 0x40), (4, 0xf5, 0x40));
+    assert_eq!(halfwords(0x85, 0x00), (5, 0, 0));
+    assert_eq!(halfwords(0xc5, 0x00), (6, 0, 0));
+}
+
+#[bitmatch(define(
+    rtype = "ggggggg bbbbb aaaaa fff ddddd 0110011",
+    jtype = "????????????????????"
+))]
+fn decode_fragments(insn: u32) -> Op {
+    match insn {
+        "{rtype: f=000, g=0000000}" => Op::Add(d, a, b),
+        "{rtype: f=000, g=0100000}" => Op::Sub(d, a, b),
+        "{jtype} ddddd 1101111" => Op::Jal(d),
+        _ => Op::Illegal,
+    }
+}
+
+#[test]
+fn test_match_fragments() {
+    for insn in [0x0020_81b3, 0x4020_81b3, 0x0000_00ef, 0xffff_ffef, 0] {
+        assert_eq!(decode_fragments(insn), decode(insn));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Insn {
+    Move(u8, u8),
+    Jump(u8),
+    Wide(u8, u8),
+    Illegal,
+}
+
+fn switch_decode(x: u8) -> Insn {
+    bitswitch!(x {
+        "000 a abbb" => Insn::Move(a, b),
+        "001 ?? ccc" => Insn::Jump(c),
+        "01? ?? ???" => bitswitch!(x {
+            "01a? ?bbb" => Insn::Wide(a, b),
+            _ => unreachable!(),
+        }),
+        _ => Insn::Illegal,
+    })
+}
+
+#[test]
+fn test_bitswitch() {
+    assert_eq!(switch_decode(0b0001_0011), Insn::Move(2, 3));
+    assert_eq!(switch_decode(0b0011_0011), Insn::Jump(3));
+    assert_eq!(switch_decode(0b0111_0101), Insn::Wide(1, 5));
+    assert_eq!(switch_decode(0xff), Insn::Illegal);
+}
+
+#[test]
+fn test_bitswitch_option() {
+    let decode = |x: u8| {
+        bitswitch!(x {
+            "000 aa bbb" => {
+                let sum = a + b;
+                sum * 2
+            }
+            "001 ?? ccc" if c != 0 => c,
+        })
+    };
+    assert_eq!(decode(0b0001_0011), Some(10));
+    assert_eq!(decode(0b0011_0011), Some(3));
+    assert_eq!(decode(0b0011_0000), None);
+    assert_eq!(decode(0xff), None);
+
+    // Exhaustive arms still give an `Option`.
+    assert_eq!(bitswitch!(0x5u8 { "aaaa" => a }), Some(5));
+}
+
+#[test]
+fn test_bitswitch_once() {
+    let mut count = 0;
+    let mut next = || {
+        count += 1;
+        0x12u32
+    };
+    let x = bitswitch!(next() {
+        "1??? ????" => 1,
+        "0001 aaaa" => a,
+        _ => 0,
+    });
+    assert_eq!(x, 2);
+    assert_eq!(count, 1);
+}
+
+macro_rules! decoder {
+    ($name:ident, $pat:literal => $body:expr) => {
+        #[bitmatch]
+        fn $name(insn: u32) -> Op {
+            match insn {
+                $pat => $body,
+                _ => Op::Illegal,
+            }
+        }
+    };
+}
+
+macro_rules! decoder_nested {
+    ($name:ident, $pat:literal => $body:expr) => {
+        decoder!($name, $pat => $body);
+    };
+}
+
+macro_rules! switch {
+    ($value:expr) => {
+        bitswitch!($value {
+            "0001 aaaa" => a,
+            _ => 0,
+        })
+    };
+}
+
+macro_rules! switch_nested {
+    ($value:expr) => {
+        switch!($value)
+    };
+}
+
+decoder!(decode_jal, "???????????????????? ddddd 1101111" => Op::Jal(d));
+decoder_nested!(decode_add, "0000000 bbbbb aaaaa 000 ddddd 0110011" => Op::Add(d, a, b));
+
+#[test]
+fn test_macro_rules() {
+    assert_eq!(decode_jal(0x0000_00ef), Op::Jal(1));
+    assert_eq!(decode_jal(0x0020_81b3), Op::Illegal);
+    assert_eq!(decode_add(0x0020_81b3), Op::Add(3, 1, 2));
+    assert_eq!(switch!(0x12u8), 2);
+    assert_eq!(switch_nested!(0x10u8 | 0x5), 5);
+}
+
+#[bitmatch]
+fn high_bits(x: u32) -> u8 {
+    match x {
+        "1010 aaaa" => a,
+        _ => 0xff,
+    }
+}
+
+#[bitmatch(low)]
+fn high_bits_low(x: u32) -> u8 {
+    match x {
+        "1010 aaaa" => a,
+        _ => 0xff,
+    }
+}
+
+#[bitmatch(table_bits = 2)]
+fn high_bits_table(x: u16) -> u8 {
+    match x {
+        "00aa aaaa" => a,
+        "01?? aaaa" => a,
+        "1??? ????" => 0x80,
+        _ => 0xff,
+    }
+}
+
+#[bitmatch(low)]
+fn high_bits_exhaustive(x: i16) -> u8 {
+    match x {
+        "0aaa aaaa" => a,
+        "1??? ????" => 0x80,
+    }
+}
+
+#[test]
+fn test_match_high_bits() {
+    // Values with bits set above the patterns don't match them, unless the
+    // item has `#[bitmatch(low)]`.
+    assert_eq!(high_bits(0xac), 0xc);
+    assert_eq!(high_bits(0xffff_ffac), 0xff);
+    assert_eq!(high_bits_low(0xffff_ffac), 0xc);
+    assert_eq!(high_bits_table(0x3f), 0x3f);
+    assert_eq!(high_bits_table(0x13f), 0xff);
+    assert_eq!(high_bits_table(0x180), 0xff);
+    // Without `low`, an exhaustive `match` needs a `_` arm for them.
+    assert_eq!(high_bits_exhaustive(-0x80i8 as i16 & 0xff), 0x80);
+    assert_eq!(high_bits_exhaustive(0x7f), 0x7f);
+    assert_eq!(high_bits_exhaustive(-1), 0x80);
+}
+
+#[bitmatch]
+fn high_bits_tuple(hi: u16, lo: u8) -> u8 {
+    match (hi, lo) {
+        ("1111 aaaa", "aaaa aaaa") => a as u8,
+        _ => 0xff,
+    }
+}
+
+#[test]
+fn test_match_tuple_high_bits() {
+    assert_eq!(high_bits_tuple(0xf1, 0x23), 0x23);
+    assert_eq!(high_bits_tuple(0x1f1, 0x23), 0xff);
+}
+
+#[bitmatch]
+fn high_bits_let(x: u16) -> u8 {
+    if let "1aaa aaaa" = x {
+        return a;
+    }
+    let "01aa aaaa" = x else {
+        return 0xff;
+    };
+    a
+}
+
+#[bitmatch]
+fn high_bits_let_irrefutable(x: u16) -> u8 {
+    let "aaaa aaaa" = x;
+    a
+}
+
+#[bitmatch]
+fn high_bits_param(#[bitmatch("aaaa bbbb")] x: u16) -> (u8, u8) {
+    (a, b)
+}
+
+#[test]
+fn test_let_high_bits() {
+    assert_eq!(high_bits_let(0x81), 1);
+    assert_eq!(high_bits_let(0x41), 1);
+    assert_eq!(high_bits_let(0x181), 0xff);
+    assert_eq!(high_bits_let(0x141), 0xff);
+    // `let` without `else` and parameters drop the bits above the pattern.
+    assert_eq!(high_bits_let_irrefutable(0x1234), 0x34);
+    assert_eq!(high_bits_param(0x12), (1, 2));
+    assert_eq!(high_bits_param(0x112), (1, 2));
+}
+
+#[test]
+fn test_bitswitch_high_bits() {
+    let decode = |x: u32| bitswitch!(x { "0001 aaaa" => a });
+    assert_eq!(decode(0x12), Some(2));
+    assert_eq!(decode(0x112), None);
+    assert_eq!(bitswitch!(low, 0x112u32 { "0001 aaaa" => a }), Some(2));
+}
@@ -0,0 +1,65 @@
+use bitpattern::bitmatch_bitslice;
+use bitvec::prelude::*;
+
+#[test]
+fn test_bitslice() {
+    let bits = bits![u8, Msb0; 1, 0, 1, 1, 0, 0, 1, 0, 1];
+    assert_eq!(bitmatch_bitslice!("10 aaaa bb", bits), Some((0xc, 2)));
+    assert_eq!(bitmatch_bitslice!("11 aaaa bb", bits), None);
+    assert_eq!(bitmatch_bitslice!("1011 0010 1", bits), Some(()));
+    assert_eq!(bitmatch_bitslice!("aaaa aaaa a", bits), Some(0x165));
+
+    // A slice shorter than the pattern doesn't match, and the bits after the
+    // pattern are ignored.
+    assert_eq!(bitmatch_bitslice!("10 aaaa bb", &bits[..4]), None);
+    assert_eq!(bitmatch_bitslice!("??", &bits[..1]), None);
+    assert_eq!(bitmatch_bitslice!("10 aa", &bits[..4]), Some(3));
+    assert_eq!(bitmatch_bitslice!("0 aaa", &bits[1..]), Some(6));
+}
+
+#[test]
+fn test_bitslice_order() {
+    // Index 0 is the first bit of the pattern in either order.
+    let raw = [0xb2u8, 0x01];
+    let msb = raw.view_bits::<Msb0>();
+    let lsb = raw.view_bits::<Lsb0>();
+    assert_eq!(bitmatch_bitslice!("1011 aaaa", msb), Some(2));
+    assert_eq!(bitmatch_bitslice!("1011 aaaa", lsb), None);
+    assert_eq!(bitmatch_bitslice!("0100 aaaa", lsb), Some(0xd));
+    assert_eq!(bitmatch_bitslice!("???????? aaaa aaaa", msb), Some(0x01));
+    assert_eq!(bitmatch_bitslice!("???????? aaaa aaaa", lsb), Some(0x80));
+
+    // So is the storage width.
+    let raw = [0xb201u16];
+    assert_eq!(
+        bitmatch_bitslice!("1011 aaaa ???????? 1", raw.view_bits::<Msb0>()),
+        None
+    );
+    assert_eq!(
+        bitmatch_bitslice!("1011 aaaa ???????1", raw.view_bits::<Msb0>()),
+        Some(2)
+    );
+    assert_eq!(
+        bitmatch_bitslice!("1000 0000 0100 aaaa", raw.view_bits::<Lsb0>()),
+        Some(0xd)
+    );
+}
+
+#[test]
+fn test_bitslice_wide() {
+    let mut bits = bitvec![u8, Msb0; 1; 139];
+    bits.set(0, false);
+    bits.set(138, false);
+
+    // 128 bits are a `u128`, and longer runs are subslices.
+    let field: Option<u128> = bitmatch_bitslice!("0 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &bits[..]);
+    assert_eq!(field, Some(u128::MAX));
+    let fields: Option<(&BitSlice<u8, Msb0>, u8)> =
+        bitmatch_bitslice!("0 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbbbbbb", &bits[..]);
+    let (wide, byte) = fields.unwrap();
+    assert_eq!(wide.len(), 130);
+    assert!(wide.all());
+    assert_eq!(byte, 0xfe);
+    assert_eq!(bitmatch_bitslice!("1 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa", &bits[..]), None);
+    assert_eq!(bitmatch_bitslice!("0 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbbbbbb ?", &bits[..]), None);
+}
@@ -0,0 +1,113 @@
+use bitpattern::bitpattern;
+
+#[test]
+fn test_bytes() {
+    let buf = [0x45u8, 0x6a, 0xbc, 0xde, 0xff];
+    assert_eq!(
+        bitpattern!(b"0100 vvvv tttttttt tttttttt", &buf[..]),
+        Some((5, 0x6abc))
+    );
+    assert_eq!(bitpattern!(b"0101 vvvv", &buf[..]), None);
+    assert_eq!(bitpattern!(b"???? ????", &buf[..]), Some(()));
+
+    // Arrays and vectors coerce to slices.
+    assert_eq!(bitpattern!(b"0100 vvvv", &buf), Some(5));
+    let vec = buf.to_vec();
+    assert_eq!(bitpattern!(b"0100 vvvv", &vec), Some(5));
+}
+
+#[test]
+fn test_bytes_straddle() {
+    let buf = [0x45u8, 0x6a, 0xbc, 0xde];
+    // 'b' is 12 bits from the low half of byte 1 to the end of byte 2.
+    assert_eq!(
+        bitpattern!(b"0100 vvvv aaaa bbbb bbbbbbbb cccccccc", &buf[..]),
+        Some((5, 6, 0xabc, 0xde))
+    );
+    // 'a' spans three bytes.
+    assert_eq!(
+        bitpattern!(b"0100 aaaa aaaaaaaa aaaa ????", &buf[..]),
+        Some(0x56ab)
+    );
+    assert_eq!(
+        bitpattern!(b"??? aaaaa aaaaaaaa aaaaaaaa aaaaaaaa", &buf[..]),
+        Some(0x056a_bcde)
+    );
+}
+
+#[test]
+fn test_bytes_short() {
+    let buf = [0x45u8, 0x6a];
+    assert_eq!(bitpattern!(b"0100 vvvv tttttttt tttttttt", &buf[..]), None);
+    assert_eq!(
+        bitpattern!(b"0100 vvvv tttttttt", &buf[..]),
+        Some((5, 0x6a))
+    );
+    assert_eq!(bitpattern!(b"????????", &[][..]), None);
+}
+
+#[test]
+fn test_bytes_once() {
+    let buf = [0x45u8, 0x6a];
+    let mut count = 0;
+    let mut next = || {
+        count += 1;
+        &buf[..]
+    };
+    assert_eq!(
+        bitpattern!(b"0100 vvvv aaaabbbb", next()),
+        Some((5, 6, 0xa))
+    );
+    assert_eq!(count, 1);
+}
+
+/// 160-bit header with 'b' crossing the first 64-bit chunk.
+macro_rules! header {
+    ($value:expr) => {
+        bitpattern!(
+            "0100 aaaa ???????? ???????? ???????? ???????? ???????? ????????"
+            "1111 bbbb bbbbbbbb"
+            "cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+            "00000000 dddddddd dddddddd",
+            $value
+        )
+    };
+}
+
+#[test]
+fn test_long() {
+    let mut buf = [
+        0x4a, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0xf1, 0x23, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab,
+        0xcd, 0xef, 0x00, 0xbe, 0xef,
+    ];
+    assert_eq!(
+        header!(&buf[..]),
+        Some((0xa, 0x123, 0x0123_4567_89ab_cdef, 0xbeef))
+    );
+    assert_eq!(header!(&buf), header!(&buf[..]));
+    assert_eq!(header!(&buf[..19]), None);
+
+    buf[17] = 0x01;
+    assert_eq!(header!(&buf[..]), None);
+    buf[17] = 0x00;
+    buf[7] = 0xe1;
+    assert_eq!(header!(&buf[..]), None);
+}
+
+#[test]
+fn test_long_u128() {
+    let mut buf = [0u8; 32];
+    buf[0] = 0x80;
+    for (i, x) in buf[8..24].iter_mut().enumerate() {
+        *x = i as u8 + 1;
+    }
+    buf[31] = 0x05;
+    let x = bitpattern!(
+        "1??????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "00000000 00000000 00000000 00000000 00000000 00000000 00000000 00000bbb",
+        &buf[..]
+    );
+    assert_eq!(x, Some((0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10, 5u128)));
+}
@@ -0,0 +1,305 @@
+use bitpattern::{bitmatch, BitDecode};
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Op {
+    #[bitmatch("000 aa bbb")]
+    Move(u8, u8),
+    #[bitmatch("001 bb aaa")]
+    Swap(u8, u32),
+    #[bitmatch(concat!("01?", " ?? ???"))]
+    Nop,
+    #[bitmatch(default)]
+    Unknown(u32),
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(Op::decode(0b0001_0011), Some(Op::Move(2, 3)));
+    assert_eq!(Op::decode(0b0011_0011), Some(Op::Swap(2, 3)));
+    assert_eq!(Op::decode(0b0111_0011), Some(Op::Nop));
+    assert_eq!(Op::decode(0xff), Some(Op::Unknown(0xff)));
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Partial {
+    #[bitmatch("1aaa aaaa aaaa")]
+    Imm(u16),
+    #[bitmatch("01aa aaaa ?bbb")]
+    Reg(u8, u8),
+    #[bitmatch(default)]
+    Invalid(u16),
+}
+
+#[test]
+fn test_decode_order() {
+    assert_eq!(Partial::decode(0xfff), Some(Partial::Imm(0x7ff)));
+    assert_eq!(Partial::decode(0x4ab), Some(Partial::Reg(0x0a, 3)));
+    assert_eq!(Partial::decode(0x0ab), Some(Partial::Invalid(0x0ab)));
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum NoDefault {
+    #[bitmatch("1aaa")]
+    One(u8),
+}
+
+// The `bitmatch` attribute macro can be used alongside the derive.
+#[bitmatch]
+fn decode_no_default(x: u8) -> Option<NoDefault> {
+    match x {
+        "0000 ????" => NoDefault::decode(x),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_decode_none() {
+    assert_eq!(NoDefault::decode(0x9), Some(NoDefault::One(1)));
+    assert_eq!(NoDefault::decode(0x1), None);
+    assert_eq!(decode_no_default(0x9), Some(NoDefault::One(1)));
+    assert_eq!(decode_no_default(0x19), None);
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Rv32 {
+    #[bitmatch("ooooooo sssss rrrrr fff ddddd 0110011")]
+    RType { d: u8, r: u8, s: u8, f: u8, o: u8 },
+    #[bitmatch("iiiiiiiiiiii sssss 000 ddddd 0010011")]
+    Addi {
+        #[bits = "d"]
+        rd: u8,
+        #[bits = "s"]
+        rs1: u8,
+        #[bits = "i"]
+        imm: i16,
+    },
+    #[bitmatch("???????????????????? ddddd 1110011 ")]
+    System { d: u8 },
+    #[bitmatch(default)]
+    Unknown(u32),
+}
+
+#[test]
+fn test_decode_named() {
+    assert_eq!(
+        Rv32::decode(0x4020_81b3),
+        Some(Rv32::RType {
+            d: 3,
+            r: 1,
+            s: 2,
+            f: 0,
+            o: 0x20
+        })
+    );
+    assert_eq!(
+        Rv32::decode(0xfff0_8093),
+        Some(Rv32::Addi {
+            rd: 1,
+            rs1: 1,
+            imm: -1
+        })
+    );
+    assert_eq!(
+        Rv32::decode(0x7ff0_8093),
+        Some(Rv32::Addi {
+            rd: 1,
+            rs1: 1,
+            imm: 0x7ff
+        })
+    );
+    assert_eq!(Rv32::decode(0x0000_00f3), Some(Rv32::System { d: 1 }));
+    assert_eq!(Rv32::decode(0), Some(Rv32::Unknown(0)));
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Signed {
+    #[bitmatch("0aaa ???b")]
+    Pair(i8, bool),
+}
+
+#[test]
+fn test_decode_signed() {
+    assert_eq!(Signed::decode(0x70), Some(Signed::Pair(-1, false)));
+    assert_eq!(Signed::decode(0x31), Some(Signed::Pair(3, true)));
+    assert_eq!(Signed::decode(0x41), Some(Signed::Pair(-4, true)));
+    assert_eq!(Signed::decode(0x80), None);
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(Op::Move(2, 3).encode(), 0b0001_0011);
+    assert_eq!(Op::Move(6, 9).encode(), 0b0001_0001);
+    assert_eq!(Op::Nop.encode(), 0b0100_0000);
+    assert_eq!(Op::Unknown(0xff).encode(), 0xff);
+    assert_eq!(
+        Rv32::Addi {
+            rd: 1,
+            rs1: 1,
+            imm: -1
+        }
+        .encode(),
+        0xfff0_8093
+    );
+}
+
+#[test]
+fn test_encode_round_trip() {
+    for word in 0..=u8::MAX {
+        let x = Op::decode(word).unwrap();
+        assert_eq!(Op::decode(x.encode()), Some(x));
+        if let Some(x) = Signed::decode(word) {
+            assert_eq!(Signed::decode(x.encode()), Some(x));
+        }
+    }
+    for word in 0..=0xfff {
+        let x = Partial::decode(word).unwrap();
+        assert_eq!(Partial::decode(x.encode()), Some(x));
+    }
+    for i in 0..=0xffffu32 {
+        let word = i.wrapping_mul(0x9e37_79b9) ^ (i << 7);
+        let x = Rv32::decode(word).unwrap();
+        assert_eq!(Rv32::decode(x.encode()), Some(x));
+    }
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+#[bitmatch(checked)]
+enum Checked {
+    #[bitmatch("1aaa bbbb")]
+    Pair(u8, i8),
+    #[bitmatch("0??? ???f")]
+    Flag(bool),
+    #[bitmatch(default)]
+    Invalid,
+}
+
+#[test]
+fn test_encode_checked() {
+    assert_eq!(Checked::Pair(7, -8).encode(), Some(0xf8));
+    assert_eq!(Checked::Pair(7, 7).encode(), Some(0xf7));
+    assert_eq!(Checked::Pair(8, 0).encode(), None);
+    assert_eq!(Checked::Pair(0, 8).encode(), None);
+    assert_eq!(Checked::Pair(0, -9).encode(), None);
+    assert_eq!(Checked::Flag(true).encode(), Some(0x01));
+    assert_eq!(Checked::Invalid.encode(), None);
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Compressed {
+    #[bitmatch("010 i ddddd iiiii 01")]
+    CLi { d: u8, i: i8 },
+    #[bitmatch("100 1 ddddd sssss 10")]
+    CAdd { d: u8, s: u8 },
+    #[bitmatch("iiiiiiiiiiii sssss 000 ddddd 0010011")]
+    Addi { d: u8, s: u8, i: i16 },
+    #[bitmatch("0000000 sssss rrrrr 000 ddddd 0110011")]
+    Add { d: u8, r: u8, s: u8 },
+    #[bitmatch(default)]
+    Unknown(u16),
+}
+
+#[test]
+fn test_decode_mixed() {
+    assert_eq!(
+        Compressed::decode(0x4505),
+        Some((Compressed::CLi { d: 10, i: 1 }, 16))
+    );
+    assert_eq!(
+        Compressed::decode(0x1234_9532),
+        Some((Compressed::CAdd { d: 10, s: 12 }, 16))
+    );
+    assert_eq!(
+        Compressed::decode(0xfff0_8093),
+        Some((Compressed::Addi { d: 1, s: 1, i: -1 }, 32))
+    );
+    assert_eq!(
+        Compressed::decode(0x0003),
+        Some((Compressed::Unknown(3), 16))
+    );
+
+    // c.li a0, -1; addi x1, x1, -1; c.add a0, a2; add x3, x1, x2
+    let stream = [
+        0x7d, 0x55, 0x93, 0x80, 0xf0, 0xff, 0x32, 0x95, 0xb3, 0x81, 0x20, 0x00,
+    ];
+    let mut decoded = Vec::new();
+    let mut pos = 0;
+    while let Some((x, len)) = Compressed::decode_le_bytes(&stream[pos..]) {
+        decoded.push(x);
+        pos += len;
+    }
+    assert_eq!(pos, stream.len());
+    assert_eq!(
+        decoded,
+        [
+            Compressed::CLi { d: 10, i: -1 },
+            Compressed::Addi { d: 1, s: 1, i: -1 },
+            Compressed::CAdd { d: 10, s: 12 },
+            Compressed::Add { d: 3, r: 1, s: 2 },
+        ]
+    );
+
+    for x in decoded {
+        let len = Compressed::decode(x.encode()).unwrap().1;
+        assert_eq!(Compressed::decode(x.encode()), Some((x, len)));
+    }
+}
+
+#[derive(BitDecode, Debug, PartialEq)]
+enum Asm {
+    #[bitmatch("iiiiiiiiiiiiiiiiiiii ddddd 0110111", fmt = "lui x{d}, {imm:#x}")]
+    Lui {
+        d: u8,
+        #[bits = "i"]
+        imm: u32,
+    },
+    #[bitmatch(
+        "iiiiiiiiiiii sssss 000 ddddd 0010011",
+        fmt = "addi x{d}, x{rs1}, {i} {{{s:02}}}"
+    )]
+    Addi {
+        d: u8,
+        #[bits = "s"]
+        rs1: u8,
+        i: i16,
+    },
+    #[bitmatch("0000000 00000 00000 000 00000 1110011")]
+    Ecall,
+    #[bitmatch(default)]
+    Unknown(u32),
+}
+
+#[test]
+fn test_display() {
+    let lui = Asm::decode(0x1234_50b7).unwrap();
+    assert_eq!(lui.to_string(), "lui x1, 0x12345");
+    let addi = Asm::decode(0xfff0_8113).unwrap();
+    assert_eq!(addi.to_string(), "addi x2, x1, -1 {01}");
+    assert_eq!(Asm::decode(0x73).unwrap().to_string(), "Ecall");
+    assert_eq!(Asm::decode(0).unwrap().to_string(), "Unknown");
+}
+
+#[derive(BitDecode)]
+#[bitmatch(debug)]
+enum Logged {
+    #[bitmatch("000 aa bbb")]
+    Move(u8, u8),
+    #[bitmatch("01 iiii dd")]
+    Add {
+        d: u8,
+        #[bits = "i"]
+        imm: i8,
+    },
+    #[bitmatch("0010 ????")]
+    Nop,
+    #[bitmatch(default)]
+    Unknown(u8),
+}
+
+#[test]
+fn test_debug() {
+    let debug = |x| format!("{:?}", Logged::decode(x).unwrap());
+    assert_eq!(debug(0b0001_0011), "Move(0b10, 0b11)");
+    assert_eq!(debug(0b0111_1011), "Add { d: 0b11, imm: -2 }");
+    assert_eq!(debug(0b0010_0000), "Nop");
+    assert_eq!(debug(0xff), "Unknown(0xFF)");
+}
@@ -0,0 +1,106 @@
+use bitpattern::BitFields;
+
+#[derive(BitFields, Debug, PartialEq)]
+#[bitmatch("vvv_ppp_e_c aaaaaaaa")]
+struct Ctrl {
+    v: u8,
+    p: u8,
+    e: bool,
+    c: bool,
+    a: u8,
+}
+
+#[test]
+fn test_from_bits() {
+    assert_eq!(
+        Ctrl::from_bits(0b1010_1010_0011_1100),
+        Some(Ctrl {
+            v: 5,
+            p: 2,
+            e: true,
+            c: false,
+            a: 0x3c
+        })
+    );
+}
+
+#[test]
+fn test_to_bits() {
+    let ctrl = Ctrl {
+        v: 5,
+        p: 2,
+        e: true,
+        c: false,
+        a: 0x3c,
+    };
+    assert_eq!(ctrl.to_bits(), 0b1010_1010_0011_1100);
+
+    // Values are truncated to their field width.
+    let ctrl = Ctrl {
+        v: 0xff,
+        p: 0,
+        e: false,
+        c: false,
+        a: 0,
+    };
+    assert_eq!(ctrl.to_bits(), 0b1110_0000_0000_0000);
+}
+
+#[derive(BitFields, Debug, PartialEq)]
+#[bitmatch("10?? mmmm ssss_ssss_ssss mmmm")]
+struct Status {
+    m: u8,
+    #[bits = "s"]
+    speed: i16,
+}
+
+#[test]
+fn test_fixed_bits() {
+    assert_eq!(
+        Status::from_bits(0x9f_ffe3),
+        Some(Status { m: 0xf3, speed: -2 })
+    );
+    assert_eq!(Status::from_bits(0x5f_ffe3), None);
+    assert_eq!(Status { m: 0xf3, speed: -2 }.to_bits(), 0x8f_ffe3);
+}
+
+#[derive(BitFields, Debug, PartialEq)]
+#[bitmatch("0001 aaaa bbbb")]
+struct Pair(u8, u8);
+
+#[test]
+fn test_tuple_struct() {
+    assert_eq!(Pair::from_bits(0x1ab), Some(Pair(0xa, 0xb)));
+    assert_eq!(Pair::from_bits(0x2ab), None);
+    assert_eq!(Pair(0xa, 0xb).to_bits(), 0x1ab);
+
+    let words = [0x1ab, 0x123, 0x0ff];
+    let pairs: Vec<_> = words.iter().filter_map(|x| Pair::from_bits(*x)).collect();
+    assert_eq!(pairs, [Pair(0xa, 0xb), Pair(0x2, 0x3)]);
+    for pair in &pairs {
+        assert_eq!(Pair::from_bits(pair.to_bits()).as_ref(), Some(pair));
+    }
+}
+
+#[derive(BitFields)]
+#[bitmatch("vvv_ppp_e_c aaaaaaaa", debug)]
+struct Logged {
+    v: u8,
+    p: u8,
+    e: bool,
+    c: bool,
+    a: u8,
+}
+
+#[test]
+fn test_debug() {
+    let ctrl = Logged::from_bits(0b1010_1010_0011_1100).unwrap();
+    assert_eq!(
+        format!("{:?}", ctrl),
+        "Logged { v: 0b101, p: 0b10, e: true, c: false, a: 0x3C }"
+    );
+    assert_eq!(
+        format!("{:#?}", ctrl),
+        "Logged {\n    v: 0b101,\n    p: 0b10,\n    e: true,\n    c: false,\n    a: 0x3C,\n}"
+    );
+}
@@ -0,0 +1,95 @@
+use bitpattern::{bitmatch_filter, bitmatch_filter_indexed, bitpattern};
+
+#[test]
+fn test_bitmatch_filter_u8() {
+    let buf = [0xa4u8, 0x13, 0xab, 0xa0];
+    let found: Vec<_> = bitmatch_filter!("1010 aa??", &buf).collect();
+    assert_eq!(found, [1, 2, 0]);
+    let found: Vec<_> = bitmatch_filter!("1010 aa??", buf.iter().copied()).collect();
+    assert_eq!(found, [1, 2, 0]);
+    assert_eq!(bitmatch_filter!("1111 ????", buf).count(), 0);
+}
+
+#[test]
+fn test_bitmatch_filter_u16() {
+    let buf: &[u16] = &[0xf012, 0x0ff0, 0xf3ab];
+    let found: Vec<_> = bitmatch_filter!("1111 aaaa bbbb cccc", buf).collect();
+    assert_eq!(found, [(0, 1, 2), (3, 0xa, 0xb)]);
+}
+
+#[test]
+fn test_bitmatch_filter_u32() {
+    // `lui` and `auipc` in RISC-V code.
+    let code = [0x0001_02b7u32, 0x0000_0013, 0x0000_1517];
+    let found: Vec<_> =
+        bitmatch_filter!("iiiiiiiiiiiiiiiiiiii ddddd 0?10111", code.iter()).collect();
+    assert_eq!(found, [(0x10, 5), (1, 10)]);
+}
+
+#[test]
+fn test_bitmatch_filter_u64() {
+    let buf = vec![0x8000_0000_0000_0001u64, 0x1, 0xc000_0000_0000_00ff];
+    let found: Vec<_> = bitmatch_filter!(
+        "1aaaaaaa ???????? ???????? ???????? ???????? ???????? ???????? bbbbbbbb",
+        buf
+    )
+    .collect();
+    assert_eq!(found, [(0, 1), (0x40, 0xff)]);
+}
+
+#[test]
+fn test_bitmatch_filter_free() {
+    let found: Vec<_> = bitmatch_filter!("aaaa bbbb", [0x12u8, 0x34]).collect();
+    assert_eq!(found, [(1, 2), (3, 4)]);
+    let found: Vec<_> = bitmatch_filter_indexed!("????", [1u8, 2]).collect();
+    assert_eq!(found, [(0,), (1,)]);
+}
+
+#[test]
+fn test_bitmatch_filter_indexed() {
+    let buf = [0xa4u8, 0x13, 0xab, 0xa0];
+    let found: Vec<_> = bitmatch_filter_indexed!("1010 aa??", &buf).collect();
+    assert_eq!(found, [(0, 1), (2, 2), (3, 0)]);
+
+    let buf = [0x0000u16, 0xf00d, 0xf001];
+    let found: Vec<_> = bitmatch_filter_indexed!("1111 0000 aaaa aaaa", buf).collect();
+    assert_eq!(found, [(1, 0x0d), (2, 0x01)]);
+}
+
+#[test]
+fn test_bitmatch_filter_high_bits() {
+    // Words with bits set above the pattern don't match, unless `low` is
+    // given, like `bitpattern!`.
+    let buf = [0xacu32, 0xffff_ffac, 0x1ac];
+    assert_eq!(
+        bitmatch_filter!("1010 aaaa", buf).collect::<Vec<_>>(),
+        [0xc]
+    );
+    let found: Vec<_> = bitmatch_filter!(low, "1010 aaaa", buf).collect();
+    assert_eq!(found, [0xc, 0xc, 0xc]);
+    let found: Vec<_> = bitmatch_filter_indexed!("aaaa aaaa", &buf).collect();
+    assert_eq!(found, [(0, 0xac)]);
+    let found: Vec<_> = bitmatch_filter_indexed!(low, "aaaa aaaa", &buf).collect();
+    assert_eq!(found, [(0, 0xac), (1, 0xac), (2, 0xac)]);
+
+    // Narrower signed words are zero-extended.
+    let found: Vec<_> = bitmatch_filter!("aaaa aaaa aaaa aaaa", [-2i8, 1]).collect();
+    assert_eq!(found, [0xfe, 1]);
+    assert_eq!(bitmatch_filter!("aaaa aaaa", [-2i16]).count(), 0);
+}
+
+#[test]
+fn test_bitmatch_filter_signed() {
+    // Runs of uppercase letters are sign-extended like `bitpattern!`.
+    let words = [0xf3u8, 0x73, 0x83, 0x03];
+    let found: Vec<_> = bitmatch_filter!("SSSS aaaa", words).collect();
+    assert_eq!(found, [(-1, 3), (7, 3), (-8, 3), (0, 3)]);
+    let expected: Vec<_> = words
+        .iter()
+        .filter_map(|x| bitpattern!("SSSS aaaa", *x))
+        .collect();
+    assert_eq!(found, expected);
+
+    let found: Vec<_> = bitmatch_filter_indexed!("1 SSSSSSS", [0xffu8, 0x7f, 0xc0]).collect();
+    assert_eq!(found, [(0, -1), (2, -64)]);
+}
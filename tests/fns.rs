@@ -0,0 +1,59 @@
+use bitpattern::bitfns;
+
+bitfns!(pub fn lui, "iiiiiiiiiiiiiiiiiiii ddddd 0110111", u32);
+bitfns!(fn ecall, "0000000 00000 00000 000 00000 1110011");
+bitfns!(fn mov, "01 ddd sss");
+bitfns!(fn any, "aaaa");
+bitfns!(fn jump, "1111 aaaa aaaa aaaa");
+bitfns!(fn imm, "10 aaaaaaaa_aaaaaaaa_aaaaaaaa_aaaaaaaa bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb", u64);
+bitfns!(
+    fn big,
+    "1 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+);
+bitfns!(fn wide, "1?aa", u16);
+bitfns!(fn simm, "SSSS aaaa");
+
+const IS_ECALL: [bool; 2] = [is_ecall(0x73), is_ecall(0x74)];
+const LUI: Option<(u32, u32)> = lui(0x1234_50b7);
+
+#[test]
+fn test_u32() {
+    assert!(is_lui(0x1234_50b7));
+    assert!(!is_lui(0x1234_50b3));
+    assert_eq!(extract_lui(0x1234_50b7), (0x12345, 1));
+    assert_eq!(extract_lui(0x1234_50b3), (0x12345, 1));
+    assert_eq!(lui(0x1234_50b3), None);
+    assert_eq!(LUI, Some((0x12345, 1)));
+    assert_eq!(IS_ECALL, [true, false]);
+    assert!(!is_ecall(0x74));
+}
+
+#[test]
+fn test_widths() {
+    assert_eq!(mov(0b0101_0011), Some((2, 3)));
+    assert_eq!(mov(0b1101_0011), None);
+    assert!(is_any(0xff));
+    assert_eq!(any(0x5), Some(0x5));
+
+    assert_eq!(jump(0xf123), Some(0x123));
+    assert_eq!(jump(0x7123), None);
+
+    let x = 0b10 << 62 | 0x1234_5678 << 30 | 0x3fff_ffff;
+    assert_eq!(imm(x), Some((0x1234_5678, 0x3fff_ffff)));
+
+    let x = 1u128 << 127 | 42;
+    assert_eq!(big(x), Some(42));
+    assert_eq!(big(42), None);
+
+    let _: fn(u16) -> bool = is_wide;
+    assert_eq!(wide(0xb), Some(3));
+}
+
+#[test]
+fn test_signed() {
+    // Uppercase runs are sign-extended like `bitpattern!`.
+    let _: fn(u8) -> Option<(i8, u8)> = simm;
+    assert_eq!(simm(0xf3), Some((-1, 3)));
+    assert_eq!(simm(0x73), Some((7, 3)));
+    assert_eq!(extract_simm(0x83), (-8, 3));
+}
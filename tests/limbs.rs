@@ -0,0 +1,91 @@
+use bitpattern::bitpattern;
+
+/// 256-bit header with a field straddling bit 128.
+macro_rules! header {
+    ($value:expr) => {
+        bitpattern!(
+            "1010???? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+            "???????? ???????? ???????? ???????? ???????? ???????? ???????? aaaaaaaa"
+            "aaaaaaaa ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+            "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????bbbb",
+            $value,
+            (u128, u128)
+        )
+    };
+}
+
+#[test]
+fn test_straddle() {
+    let hi = 0xa000_0000_0000_0000_0000_0000_0000_00ab;
+    let lo = 0xcd00_0000_0000_0000_0000_0000_0000_0005;
+    assert_eq!(header!((hi, lo)), Some((0xabcd, 5)));
+    assert_eq!(
+        header!((hi | 0x0fff << 112, lo | 0xfff0)),
+        Some((0xabcd, 5))
+    );
+    assert_eq!(header!((hi ^ 1 << 127, lo)), None);
+
+    // Each bit of the field comes from the limb it is in.
+    for bit in 0..16 {
+        let (hi, lo) = if bit < 8 {
+            (0xa << 124, 1u128 << (120 + bit))
+        } else {
+            ((0xa << 124) | 1u128 << (bit - 8), 0)
+        };
+        assert_eq!(header!((hi, lo)), Some((1 << bit, 0)));
+    }
+}
+
+#[test]
+fn test_wide_field() {
+    let (hi, lo) = (1 << 70 | 0x5a5a_u128 << 50, 0x1234_u128 << 70 | 0x3f);
+    let x = bitpattern!(
+        "01cccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cc?????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "????????",
+        (hi, lo),
+        (u128, u128)
+    );
+    // 'c' is bits 197 to 70.
+    assert_eq!(x, Some((hi & ((1 << 70) - 1)) << 58 | lo >> 70));
+    assert_eq!(x, Some(0x5a5a << 108 | 0x1234));
+    // Bits above the pattern must be 0, unless `low` is given.
+    let (hi, lo) = (hi | 1 << 127, lo);
+    let y = bitpattern!(
+        "01cccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cc?????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "????????",
+        (hi, lo),
+        (u128, u128)
+    );
+    assert_eq!(y, None);
+    let y = bitpattern!(
+        low,
+        "01cccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc cccccccc"
+        "cc?????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "????????",
+        (hi, lo),
+        (u128, u128)
+    );
+    assert_eq!(y, x);
+}
+
+#[test]
+fn test_short() {
+    assert_eq!(bitpattern!("1aaa", (0, 0xd), (u128, u128)), Some(5));
+    assert_eq!(bitpattern!("1aaa", (1, 0xd), (u128, u128)), None);
+    assert_eq!(bitpattern!("1aaa", (0, 0x1d), (u128, u128)), None);
+    assert_eq!(bitpattern!(low, "1aaa", (1, 0x1d), (u128, u128)), Some(5));
+    let x = bitpattern!(
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "dddddddd dddddddd dddddddd dddddddd dddddddd dddddddd dddddddd dddddddd",
+        (u128::MAX, 7),
+        (u128, u128)
+    );
+    assert_eq!(x, Some(7));
+}
@@ -0,0 +1,117 @@
+use bitpattern::{bitdebug, bitnewtype};
+
+bitdebug!(pub UartStatus, "???? ef?? tttt rrrr", u32);
+bitdebug!(Opcode, "01 aaa bbb");
+bitdebug!(pub(crate) Word, "1aaaaaaa aaaaaaaa bbbbbbbb bbbbbbbb bbbbbbbb bbbbbbbb ???????? ????????");
+
+const STATUS: UartStatus = UartStatus::new(0x0ba3);
+
+#[test]
+fn test_getters() {
+    assert_eq!(STATUS.0, 0x0ba3);
+    assert_eq!(STATUS.field_e(), 1);
+    assert_eq!(STATUS.field_f(), 0);
+    assert_eq!(STATUS.field_t(), 0xa);
+    assert_eq!(STATUS.field_r(), 3);
+    let _: u32 = STATUS.field_r();
+
+    let op = Opcode::new(0b0110_1011);
+    assert_eq!((op.field_a(), op.field_b()), (5, 3));
+    assert_eq!(op, Opcode(0x6b));
+
+    let word = Word::new(0x8123_4567_89ab_cdef);
+    assert_eq!(word.field_a(), 0x0123);
+    assert_eq!(word.field_b(), 0x4567_89ab);
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(
+        format!("{:?}", STATUS),
+        "UartStatus { e: 0b1, f: 0b0, t: 0b1010, r: 0b11 }"
+    );
+    assert_eq!(STATUS.to_string(), "e=1 f=0 t=0xA r=0x3");
+    assert_eq!(
+        format!("{:#?}", Opcode::new(0x6b)),
+        "Opcode {\n    a: 0b101,\n    b: 0b11,\n}"
+    );
+    assert_eq!(
+        format!("{:?}", Word::new(0x8123_4567_89ab_cdef)),
+        "Word { a: 0x123, b: 0x456789AB }"
+    );
+}
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "fixed bits of the pattern don't match")
+)]
+fn test_new_check() {
+    let _ = Opcode::new(0xab);
+}
+
+bitnewtype! {
+    /// Sv39 page table entry.
+    pub struct PageTableEntry(u64): "?????????? pppppppppppppppppppppppppppppppppppppppppppp ?? dagu xwrv"
+}
+bitnewtype!(struct Descriptor(u32): "0101 tttt ???? ssss ssss ssss ssss aaaa");
+bitnewtype!(struct Branch(u16): "1??? OOOO OOOO OOOO");
+
+const ENTRY: PageTableEntry = PageTableEntry::from_raw(0).with_p(0x1234).with_w(true);
+
+#[test]
+fn test_bitnewtype() {
+    assert_eq!(core::mem::size_of::<PageTableEntry>(), 8);
+    assert_eq!(core::mem::align_of::<PageTableEntry>(), 8);
+    assert_eq!(ENTRY.raw(), 0x1234 << 10 | 0b100);
+    assert_eq!(ENTRY.p(), 0x1234);
+    assert!(ENTRY.w());
+    assert!(!ENTRY.v());
+    let _: u64 = ENTRY.p();
+    let _: bool = ENTRY.d();
+
+    // Setters only write their own bits, and truncate the value.
+    let entry = PageTableEntry::from_raw(u64::MAX)
+        .with_v(false)
+        .with_d(false);
+    assert_eq!(entry.raw(), !0x81);
+    let entry = ENTRY.with_p(u64::MAX);
+    assert_eq!(entry.p(), (1 << 44) - 1);
+    assert_eq!(entry.raw() & 0x3ff, 0b100);
+    assert_eq!(PageTableEntry::from_raw(entry.raw()), entry);
+}
+
+#[test]
+fn test_bitnewtype_round_trip() {
+    let desc = Descriptor::from_raw(0x5000_0000)
+        .with_t(0xa)
+        .with_s(0xbeef)
+        .with_a(3);
+    assert_eq!(desc.raw(), 0x5a0b_eef3);
+    assert_eq!((desc.t(), desc.s(), desc.a()), (0xa, 0xbeef, 3));
+    assert!(desc.is_valid());
+    assert!(!Descriptor::from_raw(0).is_valid());
+    assert!(PageTableEntry::from_raw(u64::MAX).is_valid());
+    for raw in [0u32, 0x5fff_ffff, 0x1234_5678] {
+        let desc = Descriptor::from_raw(raw);
+        let copy = Descriptor::from_raw(raw & 0xf0f0_0000)
+            .with_t(desc.t())
+            .with_s(desc.s())
+            .with_a(desc.a());
+        assert_eq!(copy, desc);
+    }
+}
+
+#[test]
+fn test_bitnewtype_uppercase() {
+    // Uppercase letters name the methods in lowercase.
+    let branch = Branch::from_raw(0x8000).with_o(0x123);
+    assert_eq!(branch.raw(), 0x8123);
+    assert_eq!(branch.o(), 0x123);
+
+    // Their getters sign-extend them like `bitpattern!`.
+    let branch = branch.with_o(-2);
+    assert_eq!(branch.raw(), 0x8ffe);
+    assert_eq!(branch.o(), -2);
+    let _: i16 = branch.o();
+}
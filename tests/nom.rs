@@ -0,0 +1,72 @@
+use bitpattern::bitparser;
+use nom::bytes::complete::take;
+use nom::combinator::opt;
+use nom::error::{Error, ErrorKind};
+use nom::sequence::pair;
+use nom::{Err, Parser};
+
+type FirstWord = (u8, u8, u8, u16);
+
+/// Version, IHL, DSCP, ECN and total length of an IPv4 header.
+fn first_word(input: &[u8]) -> nom::IResult<&[u8], FirstWord> {
+    bitparser!("0100 hhhh dddddd ee llllllll llllllll")(input)
+}
+
+fn is_copy<T: Copy>(x: T) -> T {
+    x
+}
+
+#[test]
+fn test_ipv4_first_word() {
+    // The first 8 bytes of a 84-byte ICMP echo over IPv4.
+    let header = [0x45, 0x00, 0x00, 0x54, 0xab, 0xcd, 0x40, 0x00];
+    assert_eq!(first_word(&header), Ok((&header[4..], (5, 0, 0, 84))));
+
+    let header = [0x46, 0xb9, 0x05, 0xdc];
+    assert_eq!(first_word(&header), Ok((&[][..], (6, 0x2e, 1, 1500))));
+
+    // IPv6 fails the fixed bits, and a short input fails like `take`.
+    let header = [0x60, 0x00, 0x00, 0x00];
+    assert_eq!(
+        first_word(&header),
+        Err(Err::Error(Error::new(&header[..], ErrorKind::Verify)))
+    );
+    let short = [0x45, 0x00];
+    assert_eq!(
+        first_word(&short),
+        Err(Err::Error(Error::new(&short[..], ErrorKind::Eof)))
+    );
+    assert_eq!(
+        take::<_, _, Error<&[u8]>>(4usize).parse(&short[..]),
+        Err(Err::Error(Error::new(&short[..], ErrorKind::Eof)))
+    );
+}
+
+#[test]
+fn test_parser_copy() {
+    let word = bitparser!("0100 hhhh dddddd ee llllllll llllllll");
+    let copy = is_copy(word);
+    // Still usable after the copy, and with no captures it's a `fn`.
+    let as_fn: fn(&[u8]) -> nom::IResult<&[u8], FirstWord> = word;
+    let header = [0x45, 0x00, 0x00, 0x54];
+    assert_eq!(copy(&header), as_fn(&header));
+}
+
+#[test]
+fn test_parser_combinators() {
+    let word = bitparser!("0100 hhhh dddddd ee llllllll llllllll");
+    let flags = bitparser!("???????? ???????? 0 d m fffff ffffffff");
+    let header = [0x45, 0x00, 0x00, 0x54, 0xab, 0xcd, 0x40, 0x00, 0x40, 0x01];
+    let (rest, ((ihl, _, _, len), (df, mf, offset))) = pair(word, flags).parse(&header).unwrap();
+    assert_eq!((ihl, len), (5, 84));
+    assert_eq!((df, mf, offset), (1, 0, 0));
+    assert_eq!(rest, [0x40, 0x01]);
+
+    let (rest, (_, ttl)) = pair(pair(word, take(4usize)), take(1usize))
+        .parse(&header)
+        .unwrap();
+    assert_eq!((ttl, rest), (&[0x40][..], &[0x01][..]));
+
+    // A short input is an error, so combinators can backtrack.
+    assert_eq!(opt(word).parse(&header[..2]), Ok((&header[..2], None)));
+}
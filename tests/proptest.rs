@@ -0,0 +1,49 @@
+use bitpattern::{bitmatches, bitstrategy};
+use proptest::prelude::*;
+use proptest::strategy::ValueTree;
+use proptest::test_runner::TestRunner;
+
+proptest! {
+    #[test]
+    fn test_strategy_matches(x in bitstrategy!("??????? ????? ????? 010 ????? 0100011")) {
+        prop_assert!(bitmatches!("??????? ????? ????? 010 ????? 0100011", x));
+    }
+
+    #[test]
+    fn test_strategy_letters(x in bitstrategy!("1aa0 ??b1")) {
+        let _: u8 = x;
+        prop_assert!(bitmatches!("1??0 ???1", x));
+    }
+
+    #[test]
+    fn test_strategy_type(x in bitstrategy!("10?? ????", u32)) {
+        let _: u32 = x;
+        prop_assert_eq!(x & 0xc0, 0x80);
+        prop_assert!(x < 0x100);
+    }
+}
+
+#[test]
+fn test_strategy_fixed() {
+    let mut runner = TestRunner::deterministic();
+    let tree = bitstrategy!("1010 1100").new_tree(&mut runner).unwrap();
+    assert_eq!(tree.current(), 0xac);
+}
+
+#[test]
+fn test_strategy_shrink() {
+    let mut runner = TestRunner::deterministic();
+    let strategy = bitstrategy!("1?0? ??11 ????");
+    for _ in 0..32 {
+        let mut tree = strategy.new_tree(&mut runner).unwrap();
+        loop {
+            let x: u16 = tree.current();
+            assert!(bitmatches!("1?0? ??11 ????", x), "{:#x}", x);
+            if !tree.simplify() {
+                break;
+            }
+        }
+        // Shrinking ends with all the free bits 0.
+        assert_eq!(tree.current(), 0x830);
+    }
+}
@@ -0,0 +1,40 @@
+use bitpattern::{bitarbitrary, bitmatches};
+use quickcheck::{Arbitrary, Gen};
+
+bitarbitrary!(Store, "??????? ????? ????? 010 ????? 0100011");
+bitarbitrary!(pub Short, "1aa0 ??b1");
+bitarbitrary!(Wide, "10?? ????", u32);
+bitarbitrary!(Fixed, "1010 1100");
+
+#[test]
+fn test_arbitrary_matches() {
+    let mut g = Gen::new(100);
+    for _ in 0..1000 {
+        let Store(x) = Store::arbitrary(&mut g);
+        assert!(bitmatches!("??????? ????? ????? 010 ????? 0100011", x));
+        let Short(x) = Short::arbitrary(&mut g);
+        assert!(bitmatches!("1??0 ???1", x));
+        let Wide(x) = Wide::arbitrary(&mut g);
+        assert_eq!(x & !0x3f, 0x80);
+        assert_eq!(Fixed::arbitrary(&mut g), Fixed(0xac));
+    }
+}
+
+#[test]
+fn test_arbitrary_shrink() {
+    let mut g = Gen::new(100);
+    for _ in 0..100 {
+        let x = Store::arbitrary(&mut g);
+        for Store(y) in x.shrink() {
+            assert!(bitmatches!("??????? ????? ????? 010 ????? 0100011", y));
+        }
+        let x = Short::arbitrary(&mut g);
+        let shrunk: Vec<_> = x.shrink().collect();
+        assert!(shrunk.iter().all(|x| bitmatches!("1??0 ???1", x.0)));
+        // The free bits shrink toward 0.
+        if x != Short(0x81) {
+            assert!(shrunk.contains(&Short(0x81)));
+        }
+    }
+    assert_eq!(Fixed(0xac).shrink().count(), 0);
+}
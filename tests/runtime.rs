@@ -0,0 +1,79 @@
+use bitpattern::bitpattern;
+
+#[derive(Debug, PartialEq)]
+struct BitPattern<T: 'static> {
+    mask: T,
+    value: T,
+    fields: &'static [BitField],
+}
+
+#[derive(Debug, PartialEq)]
+struct BitField {
+    shift: u32,
+    width: u32,
+}
+
+impl BitPattern<u32> {
+    fn matches(&self, x: u32) -> bool {
+        x & self.mask == self.value
+    }
+
+    fn extract(&self, x: u32) -> Vec<u32> {
+        self.fields
+            .iter()
+            .map(|f| (x >> f.shift) & ((1 << f.width) - 1))
+            .collect()
+    }
+}
+
+#[test]
+fn test_object() {
+    let pattern = bitpattern!("10aa bb??");
+    assert_eq!(pattern.mask, 0xc0u8);
+    assert_eq!(pattern.value, 0x80);
+    assert_eq!(
+        pattern.fields,
+        [
+            BitField { shift: 4, width: 2 },
+            BitField { shift: 2, width: 2 }
+        ]
+    );
+
+    let pattern = bitpattern!("aa00 aa00");
+    assert_eq!(
+        pattern,
+        BitPattern {
+            mask: 0x33u8,
+            value: 0x00,
+            fields: &[
+                BitField { shift: 6, width: 2 },
+                BitField { shift: 2, width: 2 }
+            ],
+        }
+    );
+
+    let pattern = bitpattern!("1111");
+    assert_eq!(pattern.fields, []);
+
+    // A trailing comma and adjacent literals are accepted here too.
+    let pattern = bitpattern!("10aa" "bb??",);
+    assert_eq!(pattern, bitpattern!("10aa bb??"));
+}
+
+#[test]
+fn test_table() {
+    const TABLE: [BitPattern<u32>; 3] = [
+        bitpattern!("0000000 aaaaa bbbbb 000 ccccc 0110011"),
+        bitpattern!("aaaaaaaaaaaa bbbbb 000 ccccc 0010011"),
+        bitpattern!("??????? ????? ????? ??? ????? ???????"),
+    ];
+    let decode = |x: u32| {
+        let (i, pattern) = TABLE.iter().enumerate().find(|(_, p)| p.matches(x))?;
+        Some((i, pattern.extract(x)))
+    };
+    // add x3, x1, x2
+    assert_eq!(decode(0x0020_81b3), Some((0, vec![2, 1, 3])));
+    // addi x3, x1, -1
+    assert_eq!(decode(0xfff0_8193), Some((1, vec![0xfff, 1, 3])));
+    assert_eq!(decode(0xffff_ffff), Some((2, vec![])));
+}
@@ -0,0 +1,241 @@
+use bitpattern::{bitfind, bitfind_iter, bitpeek, bitread, bitscan};
+
+#[test]
+fn test_bitscan() {
+    let word = 0x0000_0000_00ab_3000u64;
+    assert_eq!(bitscan!("0 dddddddd 11", word, u64), Some((12, 0xac)));
+    assert_eq!(
+        bitscan!(msb_first, "0 dddddddd 11", word, u64),
+        Some((16, 0x2a))
+    );
+}
+
+#[test]
+fn test_bitscan_bottom() {
+    let word = 0x0000_0000_0000_0005u64;
+    assert_eq!(bitscan!("1a1", word, u64), Some((0, 0)));
+    assert_eq!(bitscan!(msb_first, "1a1", word, u64), Some((0, 0)));
+}
+
+#[test]
+fn test_bitscan_top() {
+    let word = 0xa000_0000_0000_0000u64;
+    assert_eq!(bitscan!("1a1", word, u64), Some((61, 0)));
+    assert_eq!(bitscan!("1a1 0", word, u64), Some((60, 0)));
+    assert_eq!(bitscan!(msb_first, "1?1", word, u64), Some((61,)));
+}
+
+#[test]
+fn test_bitscan_none() {
+    assert_eq!(bitscan!("111", 0x6db6_db6d_b6db_6db6u64, u64), None);
+    assert_eq!(bitscan!("11 aa", 0x0u64, u64), None);
+    // "01" would only match at offset 31, where it runs past the top.
+    assert_eq!(bitscan!("01", 0x8000_0000u32, u32), None);
+    assert_eq!(bitscan!("10", 0x8000_0000u32, u32), Some((30,)));
+}
+
+#[test]
+fn test_bitscan_fields() {
+    let word = 0b1101_0110u8;
+    assert_eq!(bitscan!("01 aab", word, u8), Some((1, 0b01, 1)));
+    assert_eq!(bitscan!("aab", word, u8), Some((0, 0b11, 0)));
+    assert_eq!(bitscan!(msb_first, "aab", word, u8), Some((5, 0b11, 0)));
+    assert_eq!(bitscan!("aa", 0x3u8), Some((0, 0b11)));
+    assert_eq!(bitscan!("ab ?a", 0b0110u8, u8), Some((0, 0b00, 1)));
+}
+
+#[test]
+fn test_bitfind() {
+    let buf = [0x00, 0x0b, 0xf1, 0x50];
+    assert_eq!(bitfind!("1011 1111 0001 aaaa", buf), Some((12, 0x5)));
+    assert_eq!(bitfind!("1011 1111 0001 aaaa", &buf[1..]), Some((4, 0x5)));
+    assert_eq!(bitfind!("1011 1111 0001 aaaa", buf[2..]), None);
+    assert_eq!(bitfind!("1011 1111 0001 aaaa", [0u8; 0]), None);
+}
+
+#[test]
+fn test_bitfind_aligned() {
+    let buf: Vec<u8> = [0x12, 0x47, 0x00].to_vec();
+    assert_eq!(bitfind!("0100 0111", buf), Some((8,)));
+    assert_eq!(bitfind!("0001 0010", buf), Some((0,)));
+    assert_eq!(bitfind!("aaaa aaaa", buf), Some((0, 0x12)));
+}
+
+#[test]
+fn test_bitfind_straddle() {
+    // 0x47 shifted right by 3, across the first two bytes.
+    let buf = [0b0000_1000, 0b1110_0000];
+    assert_eq!(bitfind!("0100 0111", buf), Some((3,)));
+    assert_eq!(bitfind!("01aa a111", buf), Some((3, 0)));
+    // The pattern spans three bytes.
+    let buf = [0b0000_0001, 0b1111_1111, 0b1000_0000];
+    assert_eq!(bitfind!("1 aaaa aaaa 1", buf), Some((7, 0xff)));
+}
+
+#[test]
+fn test_bitfind_end() {
+    let buf = [0x00, 0x00, 0x05];
+    assert_eq!(bitfind!("101", buf), Some((21,)));
+    assert_eq!(bitfind!("1010", buf), None);
+    assert_eq!(bitfind!("a0a", buf), Some((0, 0b00)));
+    let buf = [0x00, 0x00, 0x01];
+    assert_eq!(bitfind!("1", buf), Some((23,)));
+}
+
+#[test]
+fn test_bitfind_long() {
+    let mut buf = [0u8; 20];
+    buf[4] = 0x0f;
+    buf[19] = 0xf0;
+    // 120 bits from the low nibble of buf[4] to the high nibble of buf[19].
+    let found = bitfind!(
+        "1111 ???????????????? ???????????????? ???????????????? ???????????????? \
+         ???????????????? ???????????????? ???????????????? 1111",
+        buf
+    );
+    assert_eq!(found, Some((36,)));
+}
+
+#[test]
+fn test_bitfind_iter() {
+    let buf = [0b1010_0000, 0b0101_0000];
+    let matches: Vec<_> = bitfind_iter!("1?1", buf).collect();
+    assert_eq!(matches, [(0,), (9,)]);
+
+    let buf = [0b1111_0000];
+    let matches: Vec<_> = bitfind_iter!("1a", buf).collect();
+    assert_eq!(matches, [(0, 1), (1, 1), (2, 1), (3, 0)]);
+
+    let mut iter = bitfind_iter!("11", [0xc0u8]);
+    assert_eq!(iter.next(), Some((0,)));
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn test_bitread() {
+    let buf = [0xde, 0xad, 0xbe, 0xef];
+    let mut pos = 4;
+    assert_eq!(bitread!("aaaa aaaa aaaa", buf, &mut pos), Some(0xead));
+    assert_eq!(pos, 16);
+    assert_eq!(bitread!(msb_first, "1011 bbbb", buf, &mut pos), Some(0xe));
+    assert_eq!(pos, 24);
+    // Neither a mismatch nor the end of the buffer advances the position.
+    assert_eq!(bitread!("0000", buf, &mut pos), None);
+    assert_eq!(bitread!("aaaa aaaa a", buf, &mut pos), None);
+    assert_eq!(pos, 24);
+    assert_eq!(bitread!("1110 ?? ab", buf, &mut pos), Some((1, 1)));
+    assert_eq!(pos, 32);
+    assert_eq!(bitread!("a", buf, &mut pos), None);
+
+    let buf = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x10];
+    let mut pos = 4;
+    let x = bitread!(
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa",
+        &buf,
+        &mut pos
+    );
+    assert_eq!(x, Some(0x1234_5678_9abc_def1));
+    assert_eq!(pos, 68);
+}
+
+#[test]
+fn test_bitread_lsb_first() {
+    let buf = [0b1011_0001, 0b0000_0011];
+    let mut pos = 0;
+    assert_eq!(bitread!(lsb_first, "aaaa", buf, &mut pos), Some(0b0001));
+    assert_eq!(
+        bitread!(lsb_first, "bbbbbb", buf, &mut pos),
+        Some(0b11_1011)
+    );
+    assert_eq!(pos, 10);
+    assert_eq!(bitread!(lsb_first, "1??", buf, &mut pos), None);
+    assert_eq!(bitread!(lsb_first, "0?????", buf, &mut pos), Some(()));
+    assert_eq!(pos, 16);
+}
+
+#[test]
+fn test_bitpeek() {
+    let buf = [0x00, 0x00, 0x00, 0x00, 0b0000_0101, 0b1010_0000];
+    assert_eq!(bitpeek!("101 aaaa", buf, 37), Some(0b1010));
+    assert_eq!(bitpeek!("101 aaaa", &buf[..], 37), Some(0b1010));
+    assert_eq!(bitpeek!("101 aaaa", buf, 36), None);
+    // The slice ends before the pattern.
+    assert_eq!(bitpeek!("101 aaaa", buf, 42), None);
+    assert_eq!(bitpeek!("aaaa", buf, 44), Some(0));
+    assert_eq!(bitpeek!("aaaa", buf, usize::MAX), None);
+
+    let mut offset = 0;
+    let mut next = || {
+        offset += 1;
+        37
+    };
+    assert_eq!(bitpeek!("101 aaaa", buf, next()), Some(0b1010));
+    assert_eq!(offset, 1);
+}
+
+/// Bytes of a xorshift generator, so the buffers are the same on every run.
+fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// Reads `len` bits at `offset` one by one, the first bit read becoming the
+/// most significant one, or the least significant one if `lsb_first`.
+fn naive_read(buf: &[u8], offset: usize, len: usize, lsb_first: bool) -> Option<u32> {
+    if offset + len > buf.len() * 8 {
+        return None;
+    }
+    let mut value = 0;
+    for i in 0..len {
+        let pos = offset + i;
+        if lsb_first {
+            let bit = (buf[pos / 8] >> (pos % 8)) & 1;
+            value |= (bit as u32) << i;
+        } else {
+            let bit = (buf[pos / 8] >> (7 - pos % 8)) & 1;
+            value = (value << 1) | bit as u32;
+        }
+    }
+    Some(value)
+}
+
+/// The fields of "1?0 aaaa bbbbbbbbb" from the naive reading of its bits.
+fn naive_fields(bits: Option<u32>) -> Option<(u32, u32)> {
+    let bits = bits?;
+    if bits >> 13 & 0b101 != 0b100 {
+        return None;
+    }
+    Some((bits >> 9 & 0xf, bits & 0x1ff))
+}
+
+#[test]
+fn test_bitpeek_random() {
+    for seed in 1..20 {
+        let buf = random_bytes(seed, 16);
+        for offset in 0..buf.len() * 8 {
+            assert_eq!(
+                bitpeek!("1?0 aaaa bbbbbbbbb", buf, offset).map(|(a, b)| (a as u32, b as u32)),
+                naive_fields(naive_read(&buf, offset, 16, false)),
+                "seed {} offset {}",
+                seed,
+                offset
+            );
+            assert_eq!(
+                bitpeek!(lsb_first, "1?0 aaaa bbbbbbbbb", buf, offset)
+                    .map(|(a, b)| (a as u32, b as u32)),
+                naive_fields(naive_read(&buf, offset, 16, true)),
+                "seed {} offset {}",
+                seed,
+                offset
+            );
+        }
+    }
+}
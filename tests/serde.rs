@@ -0,0 +1,59 @@
+use bitpattern::BitFields;
+
+#[derive(BitFields, Debug, PartialEq)]
+#[bitmatch("vvv_ppp_e_c aaaaaaaa", serde)]
+struct Ctrl {
+    v: u8,
+    p: u8,
+    e: bool,
+    c: bool,
+    a: u8,
+}
+
+#[derive(BitFields, Debug, PartialEq)]
+#[bitmatch("10?? ssss", serde)]
+struct Speed {
+    s: i8,
+}
+
+#[test]
+fn test_serialize() {
+    let ctrl = Ctrl::from_bits(0b1010_1010_0011_1100).unwrap();
+    assert_eq!(
+        serde_json::to_string(&ctrl).unwrap(),
+        r#"{"v":5,"p":2,"e":true,"c":false,"a":60}"#
+    );
+}
+
+#[test]
+fn test_deserialize() {
+    let ctrl: Ctrl = serde_json::from_str(r#"{"v":5,"p":2,"e":true,"c":false,"a":60}"#).unwrap();
+    assert_eq!(ctrl.to_bits(), 0b1010_1010_0011_1100);
+
+    let speed: Speed = serde_json::from_str(r#"{"s":-8}"#).unwrap();
+    assert_eq!(speed.to_bits(), 0b1000_1000);
+}
+
+#[test]
+fn test_deserialize_width() {
+    let err =
+        serde_json::from_str::<Ctrl>(r#"{"v":8,"p":2,"e":true,"c":false,"a":60}"#).unwrap_err();
+    assert_eq!(err.to_string(), "`v` doesn't fit in 3 bits");
+    assert!(serde_json::from_str::<Speed>(r#"{"s":8}"#).is_err());
+    assert!(serde_json::from_str::<Speed>(r#"{"s":-9}"#).is_err());
+}
+
+#[test]
+fn test_round_trip() {
+    for bits in 0..=u16::MAX {
+        let ctrl = Ctrl::from_bits(bits).unwrap();
+        let json = serde_json::to_string(&ctrl).unwrap();
+        assert_eq!(serde_json::from_str::<Ctrl>(&json).unwrap(), ctrl);
+    }
+    for bits in 0x80..=0xbf {
+        let speed = Speed::from_bits(bits).unwrap();
+        let json = serde_json::to_string(&speed).unwrap();
+        let speed: Speed = serde_json::from_str(&json).unwrap();
+        assert_eq!(speed.to_bits(), bits & 0xcf);
+    }
+}
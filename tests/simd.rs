@@ -0,0 +1,54 @@
+use bitpattern::{bitmatch_simd, bitmatches};
+
+/// Bytes of a xorshift generator, so the buffers are the same on every run.
+fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_bitmatch_simd() {
+    for (seed, len) in [
+        (1, 0),
+        (2, 1),
+        (3, 15),
+        (4, 16),
+        (5, 33),
+        (6, 100),
+        (7, 4099),
+    ] {
+        let buf = random_bytes(seed, len);
+        let naive: Vec<_> = (0..buf.len())
+            .filter(|&i| bitmatches!("1010 ??11", buf[i]))
+            .collect();
+        let found: Vec<_> = bitmatch_simd!("1010 ??11", &buf).collect();
+        assert_eq!(found, naive, "seed {}, len {}", seed, len);
+
+        let naive: Vec<_> = (0..buf.len())
+            .filter(|&i| bitmatches!("???? ?1??", buf[i]))
+            .collect();
+        let found: Vec<_> = bitmatch_simd!("aaaa ?1??", buf).collect();
+        assert_eq!(found, naive, "seed {}, len {}", seed, len);
+    }
+}
+
+#[test]
+fn test_bitmatch_simd_tail() {
+    // Matches only after the last full chunk of 16 or 32 bytes.
+    let mut buf = [0u8; 70];
+    buf[66] = 0xff;
+    buf[69] = 0x0f;
+    let found: Vec<_> = bitmatch_simd!("1111", &buf[..]).collect();
+    assert_eq!(found, [66, 69]);
+
+    let found: Vec<_> = bitmatch_simd!("????", [1u8, 2, 3]).collect();
+    assert_eq!(found, [0, 1, 2]);
+    assert_eq!(bitmatch_simd!("1", [0xfeu8; 40]).next(), None);
+}
@@ -0,0 +1,47 @@
+mod regs {
+    bitpattern::bitspec! {
+        /// Control register.
+        pub CTRL(u32) = "e??? pppp ???? ???? ???? ???? ???? mmmm";
+        pub UART_STATUS(u16) = "0000 ???? tttt rrrr";
+        pub(crate) Id(u8) = "1010 vvvv";
+        Empty(u8) = "????????";
+    }
+
+    pub fn empty_matches(x: u8) -> bool {
+        empty::matches(x)
+    }
+}
+
+use regs::{ctrl, id, uart_status};
+
+#[test]
+fn test_consts() {
+    assert_eq!((ctrl::MASK, ctrl::VALUE), (0, 0));
+    assert_eq!((ctrl::E_SHIFT, ctrl::E_WIDTH, ctrl::E_MASK), (31, 1, 1));
+    assert_eq!((ctrl::P_SHIFT, ctrl::P_WIDTH, ctrl::P_MASK), (24, 4, 0xf));
+    assert_eq!((ctrl::M_SHIFT, ctrl::M_WIDTH, ctrl::M_MASK), (0, 4, 0xf));
+    assert_eq!((uart_status::MASK, uart_status::VALUE), (0xf000u16, 0));
+    assert_eq!((id::MASK, id::VALUE), (0xf0u8, 0xa0));
+}
+
+#[test]
+fn test_fns() {
+    let x = 0x8a00_0005u32;
+    assert_eq!(ctrl::extract_e(x), 1);
+    assert_eq!(ctrl::extract_p(x), 0xa);
+    assert_eq!(ctrl::extract_m(x), 5);
+    assert_eq!(ctrl::update_p(x, 0x3), 0x8300_0005);
+    assert_eq!(ctrl::update_e(x, 0), 0x0a00_0005);
+    assert_eq!(ctrl::update_m(x, 0x1f), 0x8a00_000f);
+
+    assert!(uart_status::matches(0x0fff));
+    assert!(!uart_status::matches(0x1000));
+    assert_eq!(uart_status::extract_t(0x0ab3), 0xb);
+    assert_eq!(uart_status::update_r(0x0ab3, 0xc), 0x0abc);
+
+    assert!(id::matches(0xa7));
+    assert!(regs::empty_matches(0x12));
+
+    const P: u32 = ctrl::extract_p(0x0500_0000);
+    assert_eq!(P, 5);
+}
@@ -1,4 +1,9 @@
-use bitpattern::bitpattern;
+use bitpattern::{
+    bitassert, bitcheck, bitclear, bitconst, bitcount, biteq, bitexplain, bitextract,
+    bitfields_iter, bitfmt, bitgen, bitintersect, bitlet, bitmask, bitmatch_all, bitmatch_any,
+    bitmatch_into, bitmatch_result, bitmatches, bitoverlap, bitpack, bitpack_checked, bitpattern,
+    bitpermute, bitrange, bitset_fields, bitsplice, bittoggle, debug_bitassert,
+};
 
 #[test]
 fn test_bit1() {
@@ -144,6 +149,9 @@ fn test_readme() {
     assert_eq!(bitpattern!("1010_1100", x), Some(()));
     assert_eq!(bitpattern!("1010_0100", x), None);
 
+    // Whitespace can be used as separator too.
+    assert_eq!(bitpattern!("1010 1100", x), Some(()));
+
     // '?' means the bit can be 0 or 1.
     assert_eq!(bitpattern!("1?10_1?00", x), Some(()));
 
@@ -182,3 +190,2555 @@ fn test_expr() {
     assert_eq!(bitpattern!("1ab0?1c?", 0xad), Some((0, 1, 0)));
     assert_eq!(bitpattern!("aaa01bb1", 0xad), Some((5, 2)));
 }
+
+#[test]
+fn test_trailing_comma() {
+    let x = 0xa6u8;
+    assert_eq!(bitpattern!("1010 aabb", x,), Some((1, 2)));
+    assert_eq!(bitpattern!("aaaa bbbb", x @ 0,), Some((0xa, 6)));
+    assert_eq!(bitextract!("1010 aabb", x,), (1, 2));
+    assert!(bitmatches!("1010 ????", x,));
+    assert_eq!(bitmask!("1010 ????",), (0xf0, 0xa0));
+    const OVERLAP: [bool; 1] = [bitoverlap!("1010", "1???",)];
+    assert_eq!(OVERLAP, [true]);
+}
+
+#[test]
+fn test_concat() {
+    let x = 0x0012_3456_789a_bcdeu64;
+    assert_eq!(
+        bitpattern!(
+            "00000000 00010010 aaaaaaaa aaaaaaaa"
+            "bbbbbbbb bbbbbbbb bbbbbbbb cccccccc",
+            x
+        ),
+        Some((0x3456, 0x789abc, 0xde))
+    );
+    // The literals are joined without a separator, so runs continue.
+    assert_eq!(bitpattern!("1010 aa" "aa", 0xa6u8), Some(6));
+    assert_eq!(bitpattern!(r"1010" "????", 0xa6u8), Some(()));
+    assert_eq!(bitextract!("aaaa" "bbbb", 0xa6u8), (0xa, 6));
+    assert!(bitmatches!("1010" "0110", 0xa6u8));
+    assert_eq!(bitmask!("1???" "???1"), (0x81, 0x81));
+    assert_eq!(bitintersect!("10" "??", "??" "01"), "1001");
+    assert_eq!(
+        bitmatch_any!(0xa6u8, ["0???" "????", "1???" "????"]),
+        Some(1)
+    );
+}
+
+macro_rules! opcode {
+    ($pat:literal, $value:expr) => {
+        bitpattern!(concat!("0000 ", $pat), $value)
+    };
+}
+
+macro_rules! opcode_fields {
+    ($pat:literal, $value:expr) => {
+        opcode!($pat, $value)
+    };
+}
+
+#[test]
+fn test_concat_macro() {
+    let x = 0x0a6u16;
+    assert_eq!(
+        bitpattern!(concat!("0000 ", "1010", " aabb"), x),
+        Some((1, 2))
+    );
+    assert_eq!(
+        bitpattern!(concat!("0000 ", concat!("1010",), r" aa") "bb", x),
+        Some((1, 2))
+    );
+    assert_eq!(bitmask!(concat!("1???", " ???1")), (0x81, 0x81));
+    assert_eq!(opcode!("1010 aabb", x), Some((1, 2)));
+    assert_eq!(opcode!("0101 aabb", x), None);
+    assert_eq!(opcode_fields!("aaaa ??bb", x), Some((0xa, 2)));
+}
+
+// Metavariables reach the macros wrapped in invisible groups, nested once
+// per layer of macros.
+macro_rules! fields {
+    ($pat:literal, $value:ident) => {
+        bitpattern!($pat, $value)
+    };
+    ($pat:literal, $value:expr, $offset:expr) => {
+        bitpattern!($pat, $value @ $offset)
+    };
+}
+
+macro_rules! fields_nested {
+    ($pat:literal, $value:ident) => {
+        fields!($pat, $value)
+    };
+    ($pat:literal, $value:expr, $offset:expr) => {
+        fields!($pat, $value, $offset)
+    };
+}
+
+macro_rules! low_nibble {
+    ($value:expr) => {
+        bitrange!(u8; $value[3:0])
+    };
+}
+
+macro_rules! low_nibble_nested {
+    ($value:expr) => {
+        low_nibble!($value)
+    };
+}
+
+#[test]
+fn test_macro_rules() {
+    let x = 0xa6u8;
+    assert_eq!(fields!("1010 aabb", x), Some((1, 2)));
+    assert_eq!(fields_nested!("1010 aabb", x), Some((1, 2)));
+    assert_eq!(fields!("aaaa", 0xa60u16, 4), Some(6));
+    assert_eq!(fields_nested!("aaaa", x as u16 | 0x100, 4 + 4), Some(1));
+    assert_eq!(low_nibble!(x), 6);
+    assert_eq!(low_nibble_nested!(x ^ 0xff), 9);
+}
+
+#[test]
+fn test_raw_string() {
+    let x = 0xacu8;
+    assert_eq!(bitpattern!(r"1010 1100", x), Some(()));
+    assert_eq!(bitpattern!(r#"1aaa a100"#, x), Some(5));
+    assert_eq!(bitpattern!(r##"1aa0 _ aa00"##, x), Some((1, 3)));
+    assert_eq!(bitextract!(r#"aaaa_bbbb"#, x), (0xa, 0xc));
+    assert_eq!(bitmask!(r"1??? ???0"), (0x81, 0x80));
+}
+
+#[test]
+fn test_separator() {
+    let x = 0xacu8;
+    assert_eq!(bitpattern!("1010 1100", x), Some(()));
+    assert_eq!(bitpattern!("1aaa a100", x), Some(5));
+    assert_eq!(bitpattern!("1aa0 _ aa00", x), Some((1, 3)));
+}
+
+#[test]
+fn test_pack() {
+    assert_eq!(bitpack!("ooo aabb", o = 0b101, a = 2, b = 1), 0x59u8);
+    assert_eq!(bitpack!("ooo aabb", b = 1, o = 0b101, a = 2), 0x59u8);
+    assert_eq!(bitpack!("ooo aabb", 0b101, 2, 1), 0x59u8);
+    assert_eq!(bitpack!("1010_1100"), 0xacu8);
+    assert_eq!(bitpack!("1?10_1??0",), 0xa8u8);
+    assert_eq!(bitpack!("1a10_1100", a = 1), 0xecu8);
+    assert_eq!(bitpack!("1aab_bccc", 1, 1, 4), 0xacu8);
+
+    let a = 0xfu32;
+    let f = |x: u8| x + 1;
+    assert_eq!(bitpack!("aaaa_bbbb_1111_0000", a = a, b = f(1)), 0xf2f0u16);
+    assert_eq!(
+        bitpack!("aaaa_aaaa_aaaa_aaaa_bbbb_bbbb_bbbb_bbbb", 0x1234, 0x5678),
+        0x1234_5678u32
+    );
+    assert_eq!(
+        bitpack!(
+            "1a??_????_????_????_????_????_????_????_????_????_????_????_????_????_????_????",
+            a = 1
+        ),
+        0xc000_0000_0000_0000u64
+    );
+    assert_eq!(
+        bitpack!(
+            "aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_1111",
+            a = 0xffff_ffff_ffff_fffeu64
+        ),
+        0xf_ffff_ffff_ffff_ffefu128
+    );
+}
+
+#[test]
+fn test_pack_checked() {
+    assert_eq!(bitpack_checked!("1aaa b000", 5, 1), Some(0xd8u8));
+    assert_eq!(bitpack_checked!("1aaa b000", a = 7, b = 1), Some(0xf8u8));
+    assert_eq!(bitpack_checked!("1aaa b000", a = 9, b = 1), None);
+    assert_eq!(bitpack_checked!("1aaa b000", a = 1, b = 2), None);
+    assert_eq!(bitpack_checked!("1aaa b000", a = -1, b = 0), None);
+    assert_eq!(bitpack_checked!("1010_1100"), Some(0xacu8));
+
+    // Values wider than the pattern type are checked before truncation.
+    let a = 0x100u32;
+    assert_eq!(bitpack_checked!("aaaa_aaaa", a), None);
+    assert_eq!(bitpack_checked!("aaaa_aaaa", a - 1), Some(0xffu8));
+
+    let mut count = 0;
+    let mut f = || {
+        count += 1;
+        3
+    };
+    assert_eq!(
+        bitpack_checked!("aa??_????_????_????", a = f()),
+        Some(0xc000u16)
+    );
+    assert_eq!(count, 1);
+
+    assert_eq!(
+        bitpack_checked!("aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa_aaaa", a = !0u128),
+        Some(!0u128)
+    );
+
+    // The expansion names `Option` by its path, so local `Some` and `None`
+    // aren't used.
+    {
+        #[allow(dead_code)]
+        enum Local {
+            Some,
+            None,
+        }
+        #[allow(unused_imports)]
+        use Local::{None, Some};
+        assert_eq!(bitpack_checked!("1aaa b000", 5, 1), Option::Some(0xd8u8));
+        assert_eq!(bitpack_checked!("1aaa b000", 9, 1), Option::None);
+    }
+}
+
+#[test]
+fn test_pack_split() {
+    assert_eq!(bitpack!("aaa0 bbaa", a = 0b10110, b = 2), 0xaau8);
+    assert_eq!(bitpack!("aaa0 bbaa", 0b10110, 2), 0xaau8);
+    assert_eq!(bitpack!("aa0a b1aa", a = 0b11010, b = 1), 0xceu8);
+    assert_eq!(bitpack!("aa0a b1aa", a = 0xffff_ffe0u32, b = 0), 0x04u8);
+    assert_eq!(bitpack!("abab abab", a = 0b1111, b = 0), 0xaau8);
+    assert_eq!(bitpack!("abab abab", a = 0b1010, b = 0b0101), 0x99u8);
+    assert_eq!(
+        bitpack!("aaaa_bbbb_aaaa_bbbb", a = 0x12, b = 0x34),
+        0x1324u16
+    );
+
+    // RISC-V S-type immediate
+    assert_eq!(
+        bitpack!(
+            "iiiiiii sssss aaaaa 010 iiiii 0100011",
+            i = 0x7ff,
+            s = 1,
+            a = 2
+        ),
+        0x7e11_2fa3u32
+    );
+
+    assert_eq!(
+        bitpack_checked!("aa0a b1aa", a = 0b11111, b = 1),
+        Some(0xdfu8)
+    );
+    assert_eq!(bitpack_checked!("aa0a b1aa", a = 0b100000, b = 1), None);
+}
+
+#[test]
+fn test_pack_default() {
+    assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = 0), 0xe4u8);
+    assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = 1), 0xfcu8);
+    assert_eq!(bitpack!("1aa? ?b00", 3, 1, default = 1), 0xfcu8);
+    assert_eq!(bitpack!("0??? ????", default = 1), 0x7fu8);
+
+    // Unchanged bits are copied from the old value.
+    let old = 0x12u8;
+    assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = old), 0xf4u8);
+    assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = !old), 0xecu8);
+    let reg = 0xdead_beefu32;
+    assert_eq!(
+        bitpack!(
+            "???? ???? ???? aaaa ???? ???? ???? ????",
+            a = 0,
+            default = reg
+        ),
+        0xdea0_beef
+    );
+
+    let mut count = 0;
+    let mut f = || {
+        count += 1;
+        old
+    };
+    assert_eq!(bitpack!("1aa? ?b00", a = 3, b = 1, default = f()), 0xf4u8);
+    assert_eq!(count, 1);
+
+    assert_eq!(
+        bitpack_checked!("1aa? ?b00", a = 3, b = 1, default = 1),
+        Some(0xfcu8)
+    );
+    assert_eq!(
+        bitpack_checked!("1aa? ?b00", a = 4, b = 1, default = 1),
+        None
+    );
+}
+
+#[test]
+fn test_bitfmt() {
+    let fmt = bitfmt!("vv_pp_e ???? aaaa", 0x130bu16);
+    assert_eq!(fmt.to_string(), "10_01_1 ---- 1011");
+    assert_eq!(format!("{:#}", fmt), "v=2 p=1 e=1 a=0xB");
+    assert_eq!(format!("{:?}", fmt), "v: 0b10, p: 0b1, e: 0b1, a: 0b1011");
+
+    let x = 0b1011_0011_0011_1100u16;
+    let fmt = bitfmt!("ooo d_dddd aaaaaaaa", x);
+    assert_eq!(fmt.to_string(), "101 1_0011 00111100");
+    assert_eq!(format!("{:?}", fmt), "o: 0b101, d: 0b10011, a: 0x3C");
+    assert_eq!(format!("{:#}", fmt), "o=5 d=0x13 a=0x3C");
+
+    // Mismatched fixed bits are marked.
+    assert_eq!(bitfmt!("10?? aaaa", 0x7bu8).to_string(), "[0][1]-- 1011");
+    assert_eq!(
+        bitfmt!("10?? aaaa", 0x7bu8, any_bits).to_string(),
+        "[0][1]11 1011"
+    );
+    assert_eq!(bitfmt!("10??", 0x8u8).to_string(), "10--");
+    assert_eq!(format!("{:?}", bitfmt!("10??", 0u8)), "");
+}
+
+#[test]
+fn test_bitlet() {
+    bitlet!("ooo ddd ii" = 0b1010_1011u8);
+    assert_eq!((o, d, i), (5, 2, 3));
+
+    // Repeated letters give a single binding.
+    bitlet!("aa?? aaaa" = 0b1011_0110u8);
+    assert_eq!(a, 0b10_0110);
+
+    // Without `else`, the bits above the pattern are dropped.
+    bitlet!("aaaa bbbb" = 0x1234u16);
+    assert_eq!((a, b), (3, 4));
+}
+
+fn decode(x: u8) -> Result<(u8, u8), u8> {
+    bitlet!("10 aabb" = x, else return Err(x));
+    Ok((a, b))
+}
+
+#[test]
+fn test_bitlet_else() {
+    assert_eq!(decode(0b0010_0110), Ok((1, 2)));
+    assert_eq!(decode(0b0000_0110), Err(0b0000_0110));
+
+    let mut found = Vec::new();
+    for x in [0x0au8, 0x2b, 0x13, 0x2f] {
+        bitlet!("1? aa??" = x, else continue);
+        found.push(a);
+    }
+    assert_eq!(found, [2, 3]);
+
+    // Bits set above the pattern don't match either, unless `low` is given.
+    assert_eq!(decode(0b1110_0110), Err(0b1110_0110));
+    let decode_low = |x: u8| {
+        bitlet!(low, "10 aabb" = x, else return None);
+        Some((a, b))
+    };
+    assert_eq!(decode_low(0b1110_0110), Some((1, 2)));
+}
+
+#[test]
+fn test_bitlet_shadowing() {
+    let a = 1u8;
+    {
+        bitlet!("aaaa" = 0xfu8);
+        assert_eq!(a, 0xf);
+    }
+    assert_eq!(a, 1);
+
+    // Earlier bindings can be used by the next destructuring.
+    bitlet!("eeee bbbb" = 0x35u8);
+    bitlet!("aa cc" = b);
+    assert_eq!((e, a, c), (3, 1, 1));
+    let value = 7;
+    let bits = 8;
+    bitlet!("dddd" = 0x2u8);
+    assert_eq!((value, bits, d), (7, 8, 2));
+}
+
+#[test]
+fn test_bitmatches() {
+    let x = 0xf3u8;
+    assert!(bitmatches!("1111 ????", x));
+    assert!(bitmatches!("1111_0011", x));
+    assert!(!bitmatches!("0111 ????", x));
+    // Letters don't take part in the comparison.
+    assert!(bitmatches!("11aa bbbb", x));
+    assert!(bitmatches!("aaaa", 0u8));
+
+    assert!(bitmatches!("1??? ???? ???? ????", 0x8000u16));
+    assert!(bitmatches!(
+        "1 ???????????????????????????????",
+        0x8000_0000u32
+    ));
+    assert!(bitmatches!(
+        "1 ???????????????????????????????????????????????????????????????",
+        1u64 << 63
+    ));
+    assert!(!bitmatches!("0 ???????????????????????????????????????????????????????????????????????????????????????????????????????????????????????????????", 1u128 << 127));
+    // A value doesn't match if it has bits set above the pattern, unless
+    // `low` is given, like `bitpattern!`.
+    assert!(!bitmatches!("0001", 0x31u32));
+    assert!(bitmatches!(low, "0001", 0x31u32));
+    assert!(!bitmatches!("1010 1100", 0xffff_ffacu32));
+    assert!(bitmatches!("1010 1100", 0xacu32));
+    assert!(!bitmatches!("????", 0x10u8));
+    assert!(!bitmatches!("1111 ????", -13i32));
+    assert!(bitmatches!("1111 ????", -13i8));
+    // Narrower signed values are zero-extended.
+    assert!(bitmatches!("0000 0000 1111 111?", -2i8));
+    assert!(!bitmatches!("1111 1111 1111 111?", -2i8));
+}
+
+const HALT: [bool; 4] = [
+    bitmatches!("0111 0110", 0x76u8),
+    bitmatches!("0111 0110", 0x77u8),
+    bitmatches!("0111 0110", 0x176u16),
+    bitmatches!(low, "0111 0110", 0x176u16),
+];
+
+#[test]
+fn test_bitmatches_const_guard() {
+    assert_eq!(HALT, [true, false, false, true]);
+    let kind = |x: u8| match x {
+        x if bitmatches!("0111 0110", x) => "halt",
+        x if bitmatches!("01?? ????", x) => "move",
+        _ => "other",
+    };
+    assert_eq!(kind(0x76), "halt");
+    assert_eq!(kind(0x41), "move");
+    assert_eq!(kind(0x80), "other");
+}
+
+fn panic_message(f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+    let err = std::panic::catch_unwind(f).unwrap_err();
+    match err.downcast::<String>() {
+        Ok(x) => *x,
+        Err(x) => x.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+#[test]
+fn test_bitassert() {
+    bitassert!("0101 aa??", 0x5au8);
+    bitassert!("aaaa", 0u8);
+    debug_bitassert!("0101 aa??", 0x5au8);
+
+    let message = panic_message(|| bitassert!("0101 aa??", 0x78u8));
+    assert_eq!(
+        message,
+        "assertion failed: bit pattern mismatch\n\
+         \x20pattern: 0101 aa??\n\
+         \x20 actual: 0111 1000\n\
+         \x20           ^\n\
+         \x20 fields: a = 0b10"
+    );
+
+    let message = panic_message(|| bitassert!("1111_0000 ????_????", 0x0f00u16));
+    assert_eq!(
+        message,
+        "assertion failed: bit pattern mismatch\n\
+         \x20pattern: 1111_0000 ????_????\n\
+         \x20 actual: 0000_1111 0000_0000\n\
+         \x20         ^^^^_^^^^"
+    );
+
+    let message = panic_message(|| bitassert!("10 aaaa_aaaa bbbb", 0x3fffu32));
+    assert!(
+        message.ends_with("fields: a = 0xFF, b = 0b1111"),
+        "{}",
+        message
+    );
+
+    let message = panic_message(|| bitassert!("1??? aaaa", 0xffff_ffa5u32));
+    assert_eq!(
+        message,
+        "assertion failed: bits set above the pattern\n\
+         \x20pattern: 1??? aaaa"
+    );
+    bitassert!(low, "1??? aaaa", 0xffff_ffa5u32);
+}
+
+#[test]
+#[cfg_attr(debug_assertions, should_panic(expected = "bit pattern mismatch"))]
+fn test_debug_bitassert() {
+    debug_bitassert!("0101 aa??", 0x78u8);
+}
+
+#[test]
+fn test_bitset_fields() {
+    let mut reg = 0xffu8;
+    reg = bitset_fields!("???? aa?b", reg, a = 2, b = 0);
+    assert_eq!(reg, 0b1111_1010);
+    // Only the named letters are written.
+    assert_eq!(bitset_fields!("???? aa?b", reg, b = 1), 0b1111_1011);
+    assert_eq!(bitset_fields!("???? aa?b", reg), reg);
+    // Values are truncated to the field width.
+    assert_eq!(bitset_fields!("???? aa?b", 0u8, a = 0xff), 0b0000_1100);
+
+    // All runs of a split letter are written.
+    assert_eq!(bitset_fields!("aa?? ?aa1", 0x01u8, a = 0b1011), 0b1000_0111);
+
+    let reg = 0x1234_5678u32;
+    assert_eq!(
+        bitset_fields!("???????????????? aaaaaaaa ????????", reg, a = 0xab),
+        0x1234_ab78
+    );
+    let mut regs = [0u16; 2];
+    regs[1] = bitset_fields!("0000 aaaa bbbb cccc", regs[1], c = 3, a = 1);
+    assert_eq!(regs, [0, 0x0103]);
+
+    // A shorter pattern writes the low bits of a wider target, keeping its
+    // type and the bits above.
+    let reg: u32 = bitset_fields!("???? aa?b", 0xdead_beefu32, a = 2, b = 0);
+    assert_eq!(reg, 0xdead_beea);
+    let mode = 1u8;
+    assert_eq!(
+        bitset_fields!("???? aa?b", 0xdead_beefu32, a = u32::from(mode)),
+        0xdead_bee7
+    );
+}
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "fixed bits of the pattern don't match")
+)]
+fn test_bitset_fields_check() {
+    let _ = bitset_fields!("1??? aaaa", 0u8, a = 1);
+}
+
+#[test]
+fn test_bitclear() {
+    assert_eq!(bitclear!("???? aa??", 0xffu8), 0xf3);
+    assert_eq!(bitclear!("aabb", 0xfu8), 0);
+    assert_eq!(bitclear!("aabb", 0xfu8, b), 0xc);
+    assert_eq!(bitclear!("aabbcc", 0x3fu8, a, c), 0x0c);
+    // Fixed bits are never touched.
+    assert_eq!(bitclear!("10aa 01??", 0x00u8), 0x00);
+    assert_eq!(bitclear!("10aa 01??", 0xffu8), 0xcf);
+    // All runs of a split letter are cleared.
+    assert_eq!(bitclear!("aa?? ??aa", 0xffu8), 0x3c);
+
+    assert_eq!(bitclear!("???? aaaa ???? ????", 0xffffu16), 0xf0ff);
+    // A wider target keeps its type and the bits above the pattern.
+    let reg: u32 = bitclear!("???? aa??", 0xdead_beefu32);
+    assert_eq!(reg, 0xdead_bee3);
+    assert_eq!(bitclear!("???? aa??", 0xdead_beefu32, a), 0xdead_bee3);
+    assert_eq!(
+        bitclear!("aaaa???????????????????????????a", 0xffff_ffffu32),
+        0x0fff_fffe
+    );
+    assert_eq!(
+        bitclear!(
+            "a??????????????????????????????? ???????????????????????????????b",
+            !0u64,
+            b
+        ),
+        !1
+    );
+    assert_eq!(
+        bitclear!(
+            "a??????????????????????????????????????????????????????????????? ???????????????????????????????????????????????????????????????b",
+            !0u128
+        ),
+        !0 >> 1 & !1
+    );
+}
+
+#[test]
+fn test_bittoggle() {
+    assert_eq!(bittoggle!("???? ??bb", 0x5au8), 0x59);
+    assert_eq!(bittoggle!("aabb", 0x5u8, a), 0x9);
+    assert_eq!(bittoggle!("10aa 01bb", 0u8), 0x33);
+    assert_eq!(bittoggle!("aa?? ??aa", 0x0fu8), 0xcc);
+
+    assert_eq!(bittoggle!("???? aaaa ???? ????", 0x1234u16), 0x1d34);
+    let reg: u32 = bittoggle!("???? aa?b", 0xdead_beefu32);
+    assert_eq!(reg, 0xdead_bee2);
+    assert_eq!(
+        bittoggle!("aaaa???????????????????????????a", 0x1234_5678u32),
+        0xe234_5679
+    );
+    assert_eq!(
+        bittoggle!(
+            "a??????????????????????????????? ???????????????????????????????b",
+            0u64
+        ),
+        1 << 63 | 1
+    );
+    assert_eq!(
+        bittoggle!(
+            "a??????????????????????????????????????????????????????????????? ???????????????????????????????????????????????????????????????b",
+            0u128,
+            a
+        ),
+        1 << 127
+    );
+}
+
+#[test]
+fn test_bitextract() {
+    let x = 0xacu8;
+    assert_eq!(bitextract!("1aaa_a100", x), 5);
+    assert_eq!(bitextract!("???a_a???", x), 1);
+    assert_eq!(bitextract!("1aa0_aa00", x), (1, 3));
+    assert_eq!(bitextract!("1aab_bccc", x), (1, 1, 4));
+    // Fixed bits are ignored.
+    assert_eq!(bitextract!("0aaa_a000", x), 5);
+    assert_eq!(bitpattern!("0aaa_a000", x), None);
+    let () = bitextract!("0000_0000", x);
+    // The bits above the pattern are ignored.
+    assert_eq!(bitextract!("aaaa", x), 0xc);
+    assert_eq!(bitextract!("aaaa aaaa", 0x1acu32), 0xac);
+    assert_eq!(bitextract!("aaaa aaaa", -0x54i8), 0xac);
+    assert_eq!(bitextract!("aaaa aaaa aaaa aaaa", -0x54i8), 0xac);
+    // Runs of uppercase letters are sign-extended like `bitpattern!`.
+    assert_eq!(bitextract!("SSSS aaaa", 0xf3u8), (-1, 3));
+    assert_eq!(bitextract!("1SSS ????", 0xecu8), -2);
+
+    let x = 0x1234_5678u32;
+    assert_eq!(
+        bitextract!("aaaaaaaa bbbbbbbb ???????? cccccccc", x),
+        (0x12, 0x34, 0x78)
+    );
+    assert_eq!(bitextract!("aaaa ???? ???? ????", 0xf123u16), 0xf);
+}
+
+const ADD: (u32, u32) = bitmask!("0000000 ????? ????? 000 ????? 0110011");
+static SLLI: (u64, u64) = bitmask!("000000 aaaaaa bbbbb 001 ddddd 0010011", u64);
+
+#[test]
+fn test_bitmask() {
+    assert_eq!(ADD, (0xfe00_707f, 0x0000_0033));
+    assert_eq!(SLLI, (0xfc00_707f, 0x0000_1013));
+    assert_eq!(bitmask!("10aa ?1?0"), (0xc5u8, 0x84u8));
+    assert_eq!(bitmask!("aaaa"), (0u8, 0u8));
+    assert_eq!(bitmask!("1 0000 0000"), (0x1ffu16, 0x100u16));
+    assert_eq!(bitmask!("11", u128), (3u128, 3u128));
+
+    let (mask, value) = ADD;
+    assert!(0x0020_81b3 & mask == value);
+    assert!(bitmatches!(
+        "0000000 ????? ????? 000 ????? 0110011",
+        0x0020_81b3u32
+    ));
+}
+
+mod regs {
+    use bitpattern::bitconst;
+
+    bitconst!(pub CTRL, "10ee ?aaa", u8);
+    bitconst!(pub(crate) ADDI, "iiiiiiiiiiii sssss 000 ddddd 0010011");
+}
+
+#[test]
+fn test_bitconst() {
+    use regs::*;
+    assert_eq!((CTRL_MASK, CTRL_VALUE), (0xc0, 0x80));
+    assert_eq!((CTRL_E_SHIFT, CTRL_E_WIDTH, CTRL_E_MASK), (4, 2, 0x3));
+    assert_eq!((CTRL_A_SHIFT, CTRL_A_WIDTH, CTRL_A_MASK), (0, 3, 0x7));
+
+    assert_eq!(
+        (ADDI_MASK, ADDI_VALUE),
+        bitmask!("iiiiiiiiiiii sssss 000 ddddd 0010011")
+    );
+    assert_eq!((ADDI_I_SHIFT, ADDI_I_WIDTH, ADDI_I_MASK), (20, 12, 0xfff));
+    assert_eq!((ADDI_S_SHIFT, ADDI_S_WIDTH, ADDI_S_MASK), (15, 5, 0x1f));
+    assert_eq!((ADDI_D_SHIFT, ADDI_D_WIDTH, ADDI_D_MASK), (7, 5, 0x1f));
+
+    let x = 0xfff0_8113u32;
+    assert_eq!(x & ADDI_MASK, ADDI_VALUE);
+    assert_eq!((x >> ADDI_D_SHIFT) & ADDI_D_MASK, 2);
+
+    bitconst!(WIDE, "1aa", u64);
+    let _: u64 = WIDE_MASK;
+    assert_eq!(WIDE_A_MASK, 3);
+}
+
+#[test]
+fn test_bitgen() {
+    let values: Vec<u8> = bitgen!("10?? aa?1").collect();
+    assert_eq!(values.len(), 32);
+    assert!(values.windows(2).all(|x| x[0] < x[1]));
+    assert!(values.iter().all(|x| x & 0xc1 == 0x81));
+    assert_eq!(values[..4], [0x81, 0x83, 0x85, 0x87]);
+    assert_eq!(values.last(), Some(&0xbf));
+
+    let mut iter = bitgen!("0a0b");
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(0b0000));
+    assert_eq!(iter.next(), Some(0b0001));
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.next(), Some(0b0100));
+    assert_eq!(iter.next(), Some(0b0101));
+    assert_eq!(iter.len(), 0);
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.next(), None);
+}
+
+#[test]
+fn test_bitgen_fixed() {
+    assert_eq!(bitgen!("1010").collect::<Vec<_>>(), [0b1010]);
+    let values: Vec<u16> = bitgen!("???? ???? ?").collect();
+    assert_eq!(values, (0..512).collect::<Vec<u16>>());
+}
+
+#[test]
+fn test_bitgen_large() {
+    let mut iter = bitgen!("a??? ???? ???? ???? ???? ???? ???? ????", allow_large);
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(iter.len(), 1 << 32);
+    assert_eq!(iter.nth(3), Some(3));
+
+    let iter = bitgen!(
+        "???????????????????????????????????????????????????????????????? \
+         ????????????????????????????????????????????????????????????????",
+        allow_large
+    );
+    assert_eq!(iter.size_hint(), (usize::MAX, None));
+}
+
+#[test]
+fn test_bitpermute() {
+    let x = 0b0110_1100u8;
+    assert_eq!(bitpermute!("aabb ccdd" => "ddcc bbaa", x), 0b0011_1001);
+    assert_eq!(bitpermute!("aabb ccdd" => "aabb ccdd", x), x);
+    assert_eq!(bitpermute!("aabb ccdd" => "aa", x), 0b01);
+    assert_eq!(bitpermute!("aabb ccdd" => "aa?? ??bb", x), 0b0100_0010);
+    assert_eq!(bitpermute!("aabb ccdd" => "10aa 01bb", x), 0b1001_0110);
+}
+
+#[test]
+fn test_bitpermute_width() {
+    let x = 0xabcdu16;
+    assert_eq!(bitpermute!("aaaa aaaa bbbb bbbb" => "bbbb bbbb", x), 0xcdu8);
+    assert_eq!(
+        bitpermute!("aaaa aaaa bbbb bbbb" => "bbbb bbbb ???? ???? aaaa aaaa", x),
+        0xcd_00abu32
+    );
+    assert_eq!(
+        bitpermute!("aa?? bbbb" => "bbaa bb", 0b1011_0110u8),
+        0b01_1010
+    );
+}
+
+#[test]
+fn test_bitpermute_split() {
+    let x = 0b1100_1010u8;
+    assert_eq!(bitpermute!("aaaa bbbb" => "aabb aabb", x), 0b1110_0010);
+    assert_eq!(bitpermute!("aabb aabb" => "aaaa bbbb", 0b1110_0010u8), x);
+}
+
+#[test]
+fn test_bitpermute_checked() {
+    assert_eq!(
+        bitpermute!("10aa aaaa" => "1111 aaaa aa00", 0x8bu8),
+        Some(0xf2c)
+    );
+    assert_eq!(bitpermute!("10aa aaaa" => "1111 aaaa aa00", 0x4bu8), None);
+    assert_eq!(bitpermute!("???1 aaaa" => "aaaa", 0x1fu8), Some(0xf));
+    assert_eq!(bitpermute!("???1 aaaa" => "aaaa", 0x0fu8), None);
+
+    // Bits above the source pattern are a mismatch unless `low`.
+    assert_eq!(bitpermute!("1??? aaaa" => "aaaa", 0x1a5u32), None);
+    assert_eq!(bitpermute!(low, "1??? aaaa" => "aaaa", 0x1a5u32), Some(5));
+    // Without fixed bits they're dropped.
+    assert_eq!(bitpermute!("aaaa bbbb" => "bbbb aaaa", 0x1a5u32), 0x5a);
+}
+
+#[test]
+fn test_offset() {
+    let x = 0x0022_ac00u32;
+    assert_eq!(bitpattern!("10aa bbbb cccc", x @ 10), Some((0, 0xa, 0xb)));
+    assert_eq!(bitpattern!("10aa bbbb cccc", x @ 9), None);
+    assert_eq!(bitpattern!("1000 1010 1011", x @ 10), Some(()));
+
+    // The bits above the pattern are ignored, not truncated by a cast.
+    let x = 0xfff0_0000_0000_0003u64;
+    assert_eq!(bitpattern!("aaaa", x @ 60), Some(0xf));
+    assert_eq!(bitpattern!("1a 11", x @ 0), None);
+    assert_eq!(bitpattern!("aaaa_aaaa", x @ 52), Some(0xff));
+
+    const SHIFT: u32 = 2;
+    assert_eq!(bitpattern!("a1", 0b1100u8 @ SHIFT), Some(1));
+    assert_eq!(bitpattern!("a1", 0b1100u8 @ SHIFT - 1), None);
+    assert_eq!(bitpattern!("a1", 0b1100u8 @ (SHIFT + 1)), Some(0));
+}
+
+#[test]
+fn test_bitcount() {
+    assert_eq!(bitcount!("?aa? a?aa", 0xffu8), 5);
+    assert_eq!(bitcount!("?aa? a?aa", 0x90u8), 0);
+    assert_eq!(bitcount!("11aa bbbb", 0x3cu8, a), 2);
+    assert_eq!(bitcount!("11aa bbbb", 0x3cu8, b), 2);
+    assert_eq!(bitcount!("11aa bbbb", 0x3cu8, a, b), 4);
+    assert_eq!(bitcount!("1010 ????", 0xffu8), 0);
+
+    const PARITY: u32 = bitcount!("aaaa ?bbb", 0xf1u8, b) % 2;
+    assert_eq!(PARITY, 1);
+}
+
+#[test]
+fn test_bitcount_width() {
+    assert_eq!(bitcount!("aaaa aaaa a", 0x1ffu16), 9);
+    assert_eq!(bitcount!("aaaa ???? aaaa ????", 0xffffu16), 8);
+    assert_eq!(
+        bitcount!("a??? ???? ???? ???? ???? ???? ???? ???a", 0x8000_0001u32),
+        2
+    );
+    assert_eq!(
+        bitcount!(
+            "aaaaaaaa ???????? ???????? ???????? ???????? ???????? ???????? ????????",
+            !0u64
+        ),
+        8
+    );
+    assert_eq!(
+        bitcount!(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa \
+             bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+            !0u128,
+            b
+        ),
+        64
+    );
+    assert_eq!(
+        bitcount!(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa \
+             aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            0x5555_5555_5555_5555_5555_5555_5555_5555u128
+        ),
+        64
+    );
+}
+
+#[test]
+fn test_bitrange() {
+    let insn = 0x00a1_2423u32;
+    assert_eq!(bitrange!(insn[14:12]), 0b010);
+    assert_eq!(bitrange!(insn[6:0]), 0b010_0011);
+    assert_eq!(bitrange!(insn[24:20], insn[19:15]), (10, 2));
+    assert_eq!(bitrange!(insn[31:31]), 0);
+    assert_eq!(bitrange!(insn[31:0]), insn);
+
+    let regs = [0x12u8, 0x34];
+    assert_eq!(bitrange!(regs[1][7:4], regs[0][3:0],), (0x3, 0x2));
+    assert_eq!(bitrange!((insn >> 4)[3:0]), 2);
+}
+
+#[test]
+fn test_bitrange_concat() {
+    // sw x10, -4(x2)
+    let insn = 0xfea1_2e23u32;
+    let imm = bitrange!(insn[31:25] ++ insn[11:7]);
+    assert_eq!(imm, 0xffc);
+    assert_eq!(bitrange!(insn[11:8] ++ insn[31:31] ++ insn[7:7]), 0b11_1010);
+
+    let x = 0xabcd_u16;
+    assert_eq!(
+        bitrange!(x[3:0] ++ x[15:4], x[7:0] ++ x[15:8]),
+        (0xdabc, 0xcdab)
+    );
+}
+
+#[test]
+fn test_bitrange_ty() {
+    let x = 0x8000_0000_0000_0001u64;
+    assert_eq!(bitrange!(u64; x[63:60]), 0x8);
+    assert_eq!(bitrange!(u64; x[63:63] ++ x[0:0]), 0b11);
+    let y = !0u128;
+    assert_eq!(bitrange!(u128; y[127:0]), !0);
+    assert_eq!(bitrange!(u128; y[127:64], y[63:63]), (u64::MAX as u128, 1));
+    const TOP: u8 = bitrange!(u8; 0xa5u8[7:4]);
+    assert_eq!(TOP, 0xa);
+}
+
+#[test]
+fn test_biteq() {
+    // Differ only in '?' bits.
+    assert!(biteq!("1111 ??aa", 0xf3u8, 0xffu8));
+    assert!(biteq!("1111 ??aa", 0xf3u8, 0xf3u8));
+    // Differ in a letter.
+    assert!(!biteq!("1111 ??aa", 0xf3u8, 0xf2u8));
+    // Differ in a fixed bit, even if neither matches the pattern.
+    assert!(!biteq!("1111 ??aa", 0x03u8, 0x83u8));
+    assert!(biteq!("1111 ??aa", 0x03u8, 0x0fu8));
+
+    assert!(biteq!("???? ????", 0x12u8, 0x34u8));
+    let x = 0x1_2345_6789u64;
+    assert!(biteq!(
+        "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa ?",
+        x,
+        x ^ 1
+    ));
+    assert!(!biteq!(
+        "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa ?",
+        x,
+        x ^ 2
+    ));
+    assert!(!biteq!(
+        "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa ?",
+        x,
+        x ^ (1 << 33)
+    ));
+    assert!(biteq!(
+        low,
+        "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa ?",
+        x,
+        x ^ (1 << 33)
+    ));
+
+    // Either value having bits set above the pattern is a mismatch.
+    assert!(!biteq!("1010 ????", 0x1a6u32, 0xa6u32));
+    assert!(!biteq!("1010 ????", 0xa6u32, 0x1a6u32));
+    assert!(biteq!(low, "1010 ????", 0x1a6u32, 0xa6u32));
+    assert!(biteq!(fixed, low, "1010 ????", 0x1a6u32, 0xa7u32));
+
+    const SAME: [bool; 2] = [
+        biteq!("aaaa ????", 0x12u8, 0x1fu8),
+        biteq!("aaaa ????", 0x12u8, 0x2fu8),
+    ];
+    assert_eq!(SAME, [true, false]);
+}
+
+#[test]
+fn test_biteq_fixed() {
+    assert!(biteq!(fixed, "1111 ??aa", 0xf3u8, 0xf2u8));
+    assert!(biteq!(fixed, "1111 ??aa", 0x03u8, 0x0fu8));
+    assert!(!biteq!(fixed, "1111 ??aa", 0x03u8, 0x83u8));
+    assert!(biteq!(fixed, "aaaa ????", 0x12u8, 0x34u8));
+}
+
+#[test]
+fn test_bitoverlap() {
+    const OVERLAP: [bool; 6] = [
+        bitoverlap!("000 ??aa", "0?0 ??bb"),
+        bitoverlap!("000 ??aa", "??? 1???"),
+        bitoverlap!("aaaa", "bbbb"),
+        // They differ only in one fixed bit.
+        bitoverlap!("000 ??aa", "001 ??bb"),
+        bitoverlap!("1010 1010", "1010 1011"),
+        bitoverlap!(
+            "1??? ???? ???? ???? ???? ???? ???? ???0",
+            "1??? ???? ???? ???? ???? ???? ???? ???1"
+        ),
+    ];
+    assert_eq!(OVERLAP, [true, true, true, false, false, false]);
+}
+
+#[test]
+fn test_bitintersect() {
+    assert_eq!(
+        bitintersect!(
+            "ggggggg bbbbb aaaaa fff ddddd 0110011",
+            "??????? ????? ????? 000 ????? ???????"
+        ),
+        "ggggggg bbbbb aaaaa 000 ddddd 0110011"
+    );
+    // Letters of the first pattern are kept over those of the second.
+    assert_eq!(bitintersect!("aaaa ????", "bbbb cccc"), "aaaa cccc");
+    assert_eq!(bitintersect!("1???_????", "?0?? ?1??"), "10??_?1??");
+    assert_eq!(bitintersect!("10aa", "10??"), "10aa");
+}
+
+#[test]
+fn test_bitfields_iter() {
+    // The letters are in the order they first appear, not alphabetical.
+    let fields = bitfields_iter!("zz ?? aa mm", 0b1101_1011u8);
+    assert_eq!(fields, [('z', 0b11), ('a', 0b10), ('m', 0b11)]);
+
+    // The '0' and '1' bits don't have to match.
+    let fields = bitfields_iter!("1111 aaaa", 0x05u8);
+    assert_eq!(fields, [('a', 5)]);
+
+    // Runs of a split letter are concatenated.
+    let fields = bitfields_iter!("aa bb aa", 0b10_11_01u8);
+    assert_eq!(fields, [('a', 0b1001), ('b', 0b11)]);
+
+    let fields = bitfields_iter!(
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa bbbbbbbb bbbbbbbb bbbbbbbb bbbbbbbb",
+        0x1234_5678_9abc_def0u64
+    );
+    assert_eq!(fields, [('a', 0x1234_5678), ('b', 0x9abc_def0)]);
+
+    let fields: [(char, u32); 0] = bitfields_iter!("10?? ????", 0u8);
+    assert_eq!(fields.len(), 0);
+    let names: Vec<char> = bitfields_iter!("ab cd", 0u8).iter().map(|x| x.0).collect();
+    assert_eq!(names, ['a', 'b', 'c', 'd']);
+}
+
+#[bitcheck("0000000 ????? ????? 000 ????? 0110011")]
+const OPC_ADD: u32 = 0x0000_0033;
+
+#[bitcheck("0100000 ????? ????? 000 ????? 0110011")]
+const OPC_SUB: u32 = OPC_ADD | (1 << 30);
+
+#[bitcheck("1??? aaaa")]
+static FLAGS: u16 = 0x0080 | 0x0005;
+
+#[bitcheck("aaaa bbbb")]
+pub(crate) const ANY: u8 = 0xff;
+
+#[bitcheck(low, "1??? aaaa")]
+const LOW: u32 = 0xffff_ffa5;
+
+#[test]
+fn test_bitcheck() {
+    assert_eq!(OPC_ADD, 0x33);
+    assert_eq!(OPC_SUB, 0x4000_0033);
+    assert_eq!(FLAGS, 0x85);
+    assert_eq!(ANY, 0xff);
+    assert_eq!(LOW, 0xffff_ffa5);
+}
+
+#[test]
+fn test_bitmatch_any() {
+    let class = |x: u8| bitmatch_any!(x, ["000? ????", "001? ????", "01?? ????"]);
+    assert_eq!(class(0x00), Some(0));
+    assert_eq!(class(0x3f), Some(1));
+    assert_eq!(class(0x40), Some(2));
+    assert_eq!(class(0xff), None);
+
+    // The first matching pattern wins, and '?' only patterns match anything.
+    assert_eq!(bitmatch_any!(0x33u8, ["??11 ????", "0011 0011"]), Some(0));
+    assert_eq!(
+        bitmatch_any!(0x33u8, ["1??? ????", "aaaa ????", "0??? ????"]),
+        Some(1)
+    );
+    assert_eq!(bitmatch_any!(0x3u8, ["????"]), Some(0));
+    assert_eq!(bitmatch_any!(0x1a6u32, ["1??? ????", "???? ????"]), None);
+    assert_eq!(
+        bitmatch_any!(low, 0x1a6u32, ["1??? ????", "???? ????"]),
+        Some(0)
+    );
+
+    // The value is evaluated once.
+    let mut count = 0;
+    let found = bitmatch_any!(
+        {
+            count += 1;
+            0x1234_5678u32
+        },
+        [
+            "1??? ???? ???? ???? ???? ???? ???? ????",
+            "0??? ???? ???? ???? ???? ???? ???? 1???",
+            "0??? ???? ???? ???? ???? ???? ???? ?111",
+        ]
+    );
+    assert_eq!(found, Some(1));
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_bitmatch_all() {
+    let x = 0x5au8;
+    assert_eq!(
+        bitmatch_all!(x, ["0??? ????", "1??? ????", "???? 1010"]),
+        0b101
+    );
+    assert_eq!(bitmatch_all!(x, ["aa?? ????", "1111 ????"]), 0b01);
+    assert_eq!(bitmatch_all!(0u16, ["1??? ???? ???? ????"]), 0);
+    let all = bitmatch_all!(
+        0x1u8,
+        [
+            "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1",
+            "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1", "1",
+        ]
+    );
+    assert_eq!(all, u32::MAX);
+
+    assert_eq!(bitmatch_all!(0x1a6u32, ["1??? ????", "???? ????"]), 0);
+    assert_eq!(
+        bitmatch_all!(low, 0x1a6u32, ["1??? ????", "???? ????"]),
+        0b11
+    );
+}
+
+mod device {
+    pub const STATUS: u8 = 0xa7;
+}
+
+struct Device {
+    status: u8,
+    regs: [u16; 4],
+}
+
+impl Device {
+    fn read(&self, addr: usize) -> u16 {
+        self.regs[addr]
+    }
+
+    fn status(&self) -> Option<(u8, u8)> {
+        bitpattern!("1010 aabb", self.status)
+    }
+}
+
+#[test]
+fn test_signed() {
+    let x = -1i8;
+    assert_eq!(bitpattern!("1??? ????", x), Some(()));
+    assert_eq!(bitpattern!("0??? ????", x), None);
+    assert_eq!(bitpattern!("1aaa aaaa", i8::MIN), Some(0));
+    // The bits are zero-extended, not sign-extended like `as`.
+    assert_eq!(bitpattern!("aaaa aaaa aaaa aaaa", x), Some(0x00ff));
+
+    let x = -2i16;
+    assert_eq!(bitpattern!("1??? ???? ???? ???a", x), Some(0));
+    assert_eq!(bitpattern!("aaaa aaaa aaaa aaaa", x), Some(0xfffe));
+    assert_eq!(
+        bitpattern!("aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", x),
+        Some(0xfffe)
+    );
+
+    let x = -1i32;
+    assert_eq!(
+        bitpattern!("1aaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", x),
+        Some(0x7fff_ffff)
+    );
+    assert_eq!(
+        bitpattern!("1??? ???? ???? ???? ???? ???? ???? ????", -0x8000_0000i32),
+        Some(())
+    );
+
+    let x = i64::MIN;
+    assert_eq!(
+        bitpattern!(
+            "1000 0000 0000 0000 0000 0000 0000 0000"
+            "0000 0000 0000 0000 0000 0000 0000 0000",
+            x
+        ),
+        Some(())
+    );
+    assert_eq!(
+        bitpattern!(
+            "aaaa ???? ???? ???? ???? ???? ???? ????"
+            "???? ???? ???? ???? ???? ???? ???? bbbb",
+            -0x7000_0000_0000_0001i64
+        ),
+        Some((0x8, 0xf))
+    );
+
+    // The sign bits of a wider value are above the pattern.
+    assert_eq!(bitpattern!("aaaa aaaa", -2i32), None);
+    assert_eq!(bitpattern!(low, "aaaa aaaa", -2i32), Some(0xfe));
+}
+
+macro_rules! assert_high_bits {
+    ($($bits:literal, $pat:literal: $($ty:ty),*;)*) => {$($(
+        // The top bit of the pattern is set, then the one above it too.
+        let top = (1 as $ty) << ($bits - 1);
+        let found = bitpattern!($pat, top);
+        assert_eq!(found.map(|x| x as $ty), Some(top));
+        assert_eq!(bitpattern!($pat, top | top << 1), None);
+        assert_eq!(bitpattern!(low, $pat, top | top << 1), found);
+        assert!(bitmatches!($pat, top));
+        assert!(!bitmatches!($pat, top | top << 1));
+        assert!(bitmatches!(low, $pat, top | top << 1));
+        assert_eq!(Some(bitextract!($pat, top)), found);
+        assert_eq!(Some(bitextract!($pat, top | top << 1)), found);
+    )*)*};
+}
+
+#[test]
+fn test_high_bits() {
+    let x = 0xffff_ffacu32;
+    assert_eq!(bitpattern!("1010 1100", x), None);
+    assert_eq!(bitpattern!(low, "1010 1100", x), Some(()));
+    assert_eq!(bitpattern!("1010 1100", x & 0xff), Some(()));
+    // A pattern shorter than its type checks the bits above it too.
+    assert_eq!(bitpattern!("aaaa", 0x1au8), None);
+    assert_eq!(bitpattern!("aaaa", 0x1a), None);
+    assert_eq!(bitpattern!(low, "aaaa", 0x1a), Some(0xa));
+    assert_eq!(bitpattern!("aaaa", 0x0au64), Some(0xa));
+    // Narrower values are zero-extended.
+    assert_eq!(bitpattern!("aaaa aaaa aaaa aaaa", 0xffu8), Some(0xff));
+
+    assert_high_bits! {
+        8, "aaaa aaaa": u16, u32, u64, u128, usize;
+        16, "aaaa aaaa aaaa aaaa": u32, u64, u128, usize;
+        32, "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa": u64, u128;
+        64, "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa": u128;
+    }
+}
+
+#[test]
+fn test_usize() {
+    let addr = 0x1234_5678usize;
+    let (dir, table): (usize, usize) =
+        bitpattern!("aaaa aaaa aabb bbbb bbbb ???? ???? ????", addr, usize).unwrap();
+    assert_eq!((dir, table), (0x48, 0x345));
+    assert_eq!(
+        bitpattern!("aaaa aaaa aabb bbbb bbbb ???? ???? ????", addr),
+        Some((0x48, 0x345))
+    );
+    // Without `usize`, the fields have the type of the pattern length.
+    let (dir, table): (u32, u32) =
+        bitpattern!("aaaa aaaa aabb bbbb bbbb ???? ???? ????", addr).unwrap();
+    assert_eq!((dir, table), (0x48, 0x345));
+    // Bits above the pattern aren't dropped.
+    assert_eq!(bitpattern!("aaaa aaaa", 0xacusize), Some(0xac));
+    assert_eq!(bitpattern!("aaaa aaaa", 0x1acusize), None);
+    assert_eq!(bitpattern!("aaaa aaaa", 0x1acusize, usize), None);
+    assert_eq!(bitpattern!("1??? ????", -1isize), None);
+    assert_eq!(bitpattern!("aaaa aaaa", 0xacu64, usize), Some(0xacusize));
+    assert_eq!(bitpattern!("aaaa aaaa", -0x54i8, usize), Some(0xacusize));
+    assert_eq!(bitpattern!("aaaa aaaa", 0x1acu16, usize), None);
+
+    let addr = usize::MAX;
+    #[cfg(target_pointer_width = "64")]
+    {
+        assert_eq!(
+            bitpattern!("aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", addr),
+            None
+        );
+        assert_eq!(
+            bitpattern!(
+                "1aaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa"
+                "???? ???? ???? ???? ???? ???? ???? ????",
+                addr,
+                usize
+            ),
+            Some(0x7fff_ffff)
+        );
+        assert_eq!(
+            bitpattern!(
+                "1aaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa"
+                "???? ???? ???? ???? ???? ???? ???? ????",
+                -1isize
+            ),
+            Some(0x7fff_ffff)
+        );
+        assert_eq!(bitpattern!("1aaa", 0x1_0000_0008u64, usize), None);
+    }
+    #[cfg(target_pointer_width = "32")]
+    {
+        assert_eq!(
+            bitpattern!("aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", addr),
+            Some(0xffff_ffff)
+        );
+        assert_eq!(
+            bitpattern!("1aaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", -1isize, usize),
+            Some(0x7fff_ffff)
+        );
+        assert_eq!(bitpattern!("1aaa", 0x1_0000_0008u64, usize), None);
+    }
+    let _ = addr;
+}
+
+#[test]
+fn test_type() {
+    let insn = 0x0000_0033u32;
+    assert_eq!(bitpattern!("0110011", insn, u32), Some(()));
+    // The pattern is zero-extended.
+    assert_eq!(bitpattern!("0110011", insn | 0x1000, u32), None);
+    assert_eq!(bitpattern!(low, "0110011", insn | 0x1000, u32), Some(()));
+
+    // The fields have the type, which can be wider or narrower than the one
+    // of the pattern length.
+    let fields: Option<(u16, u16)> = bitpattern!("0 aaaa bbbb", 0x5au16, u16);
+    assert_eq!(fields, Some((5, 0xa)));
+    let fields: Option<(u8, u8)> = bitpattern!("aaaa bbbb", 0x5au16, u8);
+    assert_eq!(fields, Some((5, 0xa)));
+    let fields: Option<(u64, u64)> = bitpattern!("aaaa bbbb", 0x5au8, u64);
+    assert_eq!(fields, Some((5, 0xa)));
+    let field: Option<u128> = bitpattern!("aaaa aaaa", -1i8, u128);
+    assert_eq!(field, Some(0xff));
+    let field: Option<u8> = bitpattern!("aaaa aaaa", 0x1ffu16, u8);
+    assert_eq!(field, None);
+    assert_eq!(bitpattern!(low, "aaaa aaaa", 0x1ffu16, u8), Some(0xffu8));
+    assert_eq!(bitpattern!("aaaa", 0x5, u32), Some(5u32));
+}
+
+#[test]
+fn test_float() {
+    // Quiet NaN with a payload.
+    let nan = f32::from_bits(0x7fc0_1234);
+    assert!(nan.is_nan());
+    assert_eq!(
+        bitpattern!(source, "? 11111111 1aaaaaaaaaaaaaaaaaaaaaa", nan),
+        Some(0x1234)
+    );
+    assert_eq!(
+        bitpattern!(source, "? 11111111 0aaaaaaaaaaaaaaaaaaaaaa", nan),
+        None
+    );
+    let nan = f64::from_bits(0xfff8_0000_0000_beef);
+    assert_eq!(
+        bitpattern!(
+            source,
+            "s 11111111111 1aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            nan
+        ),
+        Some((1, 0xbeef))
+    );
+
+    // Negative zero differs from zero only in the sign bit, unlike `as`.
+    assert_eq!(
+        bitpattern!(source, "s 00000000 00000000000000000000000", -0.0f32),
+        Some(1)
+    );
+    assert_eq!(
+        bitpattern!(source, "s 00000000 00000000000000000000000", 0.0f32),
+        Some(0)
+    );
+    assert_eq!(
+        bitpattern!(
+            source,
+            "s 00000000000 0000000000000000000000000000000000000000000000000000",
+            -0.0f64
+        ),
+        Some(1)
+    );
+
+    // Subnormals have a zero exponent.
+    let tiny = f32::from_bits(1);
+    assert_eq!(
+        bitpattern!(source, "0 00000000 mmmmmmmmmmmmmmmmmmmmmmm", tiny),
+        Some(1)
+    );
+    assert_eq!(
+        bitpattern!(source, "0 eeeeeeee ???????????????????????", 1.0f32, u64),
+        Some(0x7fu64)
+    );
+}
+
+/// Decodes the first UTF-8 sequence of `bytes`, with its length.
+fn decode_utf8(bytes: &[u8]) -> Option<(u32, usize)> {
+    let lead = bytes[0];
+    if let Some(a) = bitpattern!("0aaa aaaa", lead) {
+        return Some((a as u32, 1));
+    }
+    let (a, len) = if let Some(a) = bitpattern!("110a aaaa", lead) {
+        (a as u32, 2)
+    } else if let Some(a) = bitpattern!("1110 aaaa", lead) {
+        (a as u32, 3)
+    } else {
+        (bitpattern!("1111 0aaa", lead)? as u32, 4)
+    };
+    let mut x = a;
+    for &byte in bytes.get(1..len)? {
+        x = x << 6 | bitpattern!("10aa aaaa", byte)? as u32;
+    }
+    Some((x, len))
+}
+
+#[test]
+fn test_char() {
+    for c in ['a', 'é', '€', '😀'] {
+        let mut buf = [0; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        assert_eq!(decode_utf8(bytes), Some((c as u32, c.len_utf8())));
+    }
+    assert_eq!(decode_utf8(&[0xe2, 0x82]), None);
+    assert_eq!(decode_utf8(&[0xe2, 0x02, 0xac]), None);
+
+    // A `char` is matched by its scalar value, not truncated to the pattern.
+    assert_eq!(
+        bitpattern!(source, "aaaa bbbbbb cccccc", '€'),
+        Some((0x2, 0x02, 0x2c))
+    );
+    assert_eq!(bitpattern!(source, "0aaa aaaa", 'a'), Some(0x61));
+    assert_eq!(bitpattern!(source, "aaaa aaaa", 'ł'), None);
+    assert_eq!(bitpattern!(source, low, "aaaa aaaa", 'ł'), Some(0x42));
+    let c = '😀';
+    assert_eq!(
+        bitpattern!(source, "aaa bbbbbb cccccc dddddd", c),
+        Some((0, 0x1f, 0x18, 0))
+    );
+    assert_eq!(
+        bitpattern!(source, "aaaa aaaa aaaa aaaa aaaa a", c, u32),
+        Some(0x1f600)
+    );
+
+    // Byte literals are `u8`.
+    assert_eq!(bitpattern!("0100 aaaa", b'A'), Some(1));
+    assert_eq!(bitpattern!("0110 aaaa", b'A'), None);
+}
+
+#[test]
+fn test_std_wrappers() {
+    use std::num::{NonZeroI8, NonZeroU32, NonZeroUsize, Wrapping};
+
+    let reg = NonZeroU32::new(0x8000_00a5).unwrap();
+    assert_eq!(
+        bitpattern!(source, "1??? ???? ???? ???? ???? ???? aaaa bbbb", reg),
+        Some((0xa, 5))
+    );
+    assert_eq!(bitpattern!(source, "aaaa bbbb", reg), None);
+    assert_eq!(bitpattern!(source, low, "aaaa bbbb", reg), Some((0xa, 5)));
+    let fields: Option<(u32, u32)> = bitpattern!(source, "aaaa bbbb", reg, u32);
+    assert_eq!(fields, None);
+    assert_eq!(
+        bitpattern!(source, "aaaa", NonZeroUsize::new(5).unwrap()),
+        Some(5)
+    );
+    assert_eq!(
+        bitpattern!(source, "aaaa aaaa", NonZeroI8::new(-1).unwrap()),
+        Some(0xff)
+    );
+
+    // The niche of `Option<NonZeroU32>` is untouched.
+    const _: () = assert!(std::mem::size_of::<[Option<NonZeroU32>; 3]>() == 12);
+    let regs: [Option<NonZeroU32>; 3] = [NonZeroU32::new(0x12), None, NonZeroU32::new(0x34)];
+    let fields: Vec<_> = regs
+        .iter()
+        .flatten()
+        .map(|&x| bitpattern!(source, "aaaa bbbb", x))
+        .collect();
+    assert_eq!(fields, [Some((1, 2)), Some((3, 4))]);
+
+    // The value is evaluated once.
+    let mut calls = 0;
+    let mut next = || {
+        calls += 1;
+        Wrapping(0x5au8) + Wrapping(0xff)
+    };
+    assert_eq!(bitpattern!(source, "0101 aaaa", next()), Some(9));
+    assert_eq!(calls, 1);
+    let x = Wrapping(0x1234u16);
+    assert_eq!(
+        bitpattern!(source, "aaaa bbbb cccc dddd", x),
+        Some((1, 2, 3, 4))
+    );
+    assert_eq!(bitpattern!(source, "aaaa", Wrapping(-1i32)), None);
+    assert_eq!(bitpattern!(source, low, "aaaa", Wrapping(-1i32)), Some(0xf));
+}
+
+#[test]
+fn test_sign_extend() {
+    assert_eq!(
+        bitpattern!("SSSS SSSS aaaa 0011", 0xff53u16),
+        Some((-1i16, 5u16))
+    );
+    assert_eq!(
+        bitpattern!("SSSS SSSS aaaa 0011", 0x8003u16),
+        Some((-128, 0))
+    );
+    assert_eq!(
+        bitpattern!("SSSS SSSS aaaa 0011", 0x7f03u16),
+        Some((127, 0))
+    );
+    assert_eq!(bitpattern!("SSSS SSSS aaaa 0011", 0x0013u16), Some((0, 1)));
+    assert_eq!(bitpattern!("SSSS SSSS aaaa 0011", 0x0014u16), None);
+
+    // A single bit is 0 or -1, and a field of the whole width is its bits as
+    // the signed type.
+    assert_eq!(bitpattern!("S???", 0x8u8), Some(-1i8));
+    assert_eq!(bitpattern!("S???", 0x7u8), Some(0));
+    assert_eq!(bitpattern!("SSSS SSSS", 0x80u8), Some(i8::MIN));
+    assert_eq!(bitpattern!("SSSS SSSS", 0xffu8), Some(-1));
+    assert_eq!(bitpattern!("SSSS SSSS", 0x7fu8), Some(i8::MAX));
+
+    // The signed type has the width of the comparison.
+    let imm: Option<(i32, u32)> = bitpattern!("IIII IIII IIII rrrrr", 0xfff << 5 | 0xau32, u32);
+    assert_eq!(imm, Some((-1, 0xa)));
+    let imm: Option<isize> = bitpattern!("IIII ????", 0x9cusize, usize);
+    assert_eq!(imm, Some(-7));
+    let x = 0x0022_ac00u32;
+    assert_eq!(
+        bitpattern!("10AA BBBB cccc", x @ 10),
+        Some((0i16, -6i16, 0xbu16))
+    );
+    assert_eq!(
+        bitpattern!("10aa BBBB cccc", x @ 10 => { b: B }).map(|f| f.b),
+        Some(-6)
+    );
+
+    let buf = [0xf0u8, 0x0f, 0x80];
+    assert_eq!(
+        bitpattern!(b"???? DDDD DDDD DDDD EEEE EEEE", &buf[..]),
+        Some((0x00f, -128i32))
+    );
+    let x = bitpattern!(
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????SSSS"
+        "SSSSSSSS ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????",
+        (0xf, 0x80 << 120),
+        (u128, u128)
+    );
+    assert_eq!(x, Some(-128i128));
+}
+
+#[test]
+fn test_narrow() {
+    let x = 0xdead_beef_0123_4567u64;
+    let fields: Option<(u8, u16, u8, u64)> = bitpattern!(
+        narrow,
+        "aa bbbbbbbbbbbbbb cccccccc dddddddddddddddddddddddddddddddddddddddd",
+        x
+    );
+    assert_eq!(fields, Some((3, 0x1ead, 0xbe, 0xef_0123_4567)));
+    let fields: Option<(u8, u16, u8)> = bitpattern!(narrow, "1aab bbbb bbbb bccc", 0xb5a3u16);
+    assert_eq!(fields, Some((1, 0x2b4, 3)));
+    assert_eq!(bitpattern!(narrow, "1aaa_a100", 0xacu8), Some(5u8));
+    let full: Option<u64> = bitpattern!(
+        narrow,
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa",
+        x
+    );
+    assert_eq!(full, Some(x));
+
+    // Signed fields have the signed type of the same width.
+    let fields: Option<(i8, u16, i16)> = bitpattern!(
+        narrow,
+        "SSSS aaaa aaaa aaaa BBBB BBBB BBBB BBBB",
+        0xf123_8000u32
+    );
+    assert_eq!(fields, Some((-1, 0x123, i16::MIN)));
+
+    // The other forms narrow too.
+    let fields: Option<(u8, u8)> = bitpattern!(low, narrow, "aaaa bbbb", 0x1fau32);
+    assert_eq!(fields, Some((0xf, 0xa)));
+    let fields: Option<(u8, u8)> = bitpattern!(narrow, "aaaa bbbb", 0x1fau32 @ 1);
+    assert_eq!(fields, Some((0xf, 0xd)));
+    let fields: Option<(u8, u16)> = bitpattern!(narrow, "aaaa bbbbbbbbbbbb", 0x1234usize, usize);
+    assert_eq!(fields, Some((1, 0x234)));
+    let buf = [0x45u8, 0x00, 0x1c, 0x8f];
+    let fields: Option<(u8, u8, u16)> =
+        bitpattern!(narrow, b"0100 hhhh ssssssss llllllll llllllll", &buf[..]);
+    assert_eq!(fields, Some((5, 0, 0x1c8f)));
+    let field: Option<u8> = bitpattern!(narrow, "aaaa", (0, 5), (u128, u128));
+    assert_eq!(field, Some(5));
+}
+
+#[test]
+fn test_flags() {
+    let status = 0x8a50u16;
+    let (enable, kind, ready, code) = bitpattern!(flags, "e??? tttt r??? cccc", status).unwrap();
+    if enable {
+        assert_eq!(kind, 0xa);
+    } else {
+        panic!("not enabled");
+    }
+    assert!(!ready);
+    let code: u16 = code;
+    assert_eq!(code, 0);
+
+    let fields: Option<(bool, u8, bool)> = bitpattern!(flags, narrow, "a bbbbbb c", 0xffu16);
+    assert_eq!(fields, Some((true, 0x3f, true)));
+    let fields: Option<(bool, u8, bool)> = bitpattern!(flags, narrow, "a bbbbbb c", 0x7eu16);
+    assert_eq!(fields, Some((false, 0x3f, false)));
+
+    // Without `flags`, fields of one bit are integers.
+    assert_eq!(bitpattern!("a bbbbbb c", 0xffu16), Some((1, 0x3f, 1)));
+    assert_eq!(bitpattern!(flags, "S aaa", 0x8u8), Some((true, 0)));
+    assert_eq!(bitpattern!(flags, "0 a ??", 0x4u8 @ 0), Some(true));
+    assert_eq!(
+        bitpattern!(flags, b"1010 aaaa b??????? ????????", &[0xa5, 0x80, 0][..]),
+        Some((5, true))
+    );
+    assert_eq!(bitpattern!(flags, "a", (0, 1), (u128, u128)), Some(true));
+    let found = bitpattern!(flags, "a bbbbbb c", 0x81u16 => { a, c }).unwrap();
+    assert!(found.a && found.c);
+}
+
+macro_rules! first_field {
+    ($pattern:literal, $value:expr) => {
+        match bitpattern!(tuple, $pattern, $value) {
+            Some((a, ..)) => Some(a),
+            None => None,
+        }
+    };
+}
+
+#[test]
+fn test_tuple() {
+    let x = 0xacu8;
+    let Some((a,)) = bitpattern!(tuple, "1aaa_a100", x) else {
+        panic!("no match");
+    };
+    assert_eq!(a, 5);
+    let Some(()) = bitpattern!(tuple, "1010_1100", x) else {
+        panic!("no match");
+    };
+    assert_eq!(bitpattern!(tuple, "1aa0_aa00", x), Some((1, 3)));
+    assert_eq!(bitpattern!(tuple, "0aaa_a100", x), None);
+
+    assert_eq!(first_field!("1aaa_a100", x), Some(5));
+    assert_eq!(first_field!("1aa0_aa00", x), Some(1));
+
+    // Other options and paths keep the shape.
+    assert_eq!(bitpattern!(tuple, flags, "1a10_1100", x), Some((false,)));
+    assert_eq!(
+        bitpattern!(narrow, tuple, "aaaa aaaa ????", 0xabcu16),
+        Some((0xabu8,))
+    );
+    assert_eq!(
+        bitpattern!(tuple, "10aa 1111 0000", 0x0022_ac00u32 @ 10),
+        None
+    );
+    assert_eq!(
+        bitpattern!(tuple, "aa ??????", 0xd4u8, a as AddrMode),
+        Some((AddrMode::Mem,))
+    );
+    assert_eq!(bitpattern!(tuple, "aa ??????", 0x94u8, a as AddrMode), None);
+    assert_eq!(bitpattern!(tuple, "aaaa ????", 0xd4u8, a + 1), Some((14,)));
+    assert_eq!(
+        bitpattern!(tuple, b"aaaa ???? ????????", &[0x40, 0x12][..]),
+        Some((4,))
+    );
+    assert_eq!(bitpattern!(tuple, "a", (0, 1), (u128, u128)), Some((1,)));
+}
+
+#[test]
+fn test_rest() {
+    let x = 0xacu8;
+    assert_eq!(bitpattern!(rest, "1aa? ??00", x), Some((1, 0x0c)));
+    assert_eq!(bitpattern!(rest, "1aa? ??00", 0x2cu8), None);
+    // Letters and fixed bits are cleared, wherever the '?' runs are.
+    assert_eq!(bitpattern!(rest, "??aa ?1?b", 0xffu8), Some((3, 1, 0xca)));
+    assert_eq!(bitpattern!(rest, "??aa ?1?b", 0x5eu8), Some((1, 0, 0x4a)));
+    // A fully specified pattern leaves nothing.
+    assert_eq!(bitpattern!(rest, "1010_1100", x), Some(0));
+    assert_eq!(bitpattern!(rest, "1aab_bccc", x), Some((1, 1, 4, 0)));
+
+    // The residue has the type of the comparison, on every path.
+    let rest: Option<(u16, u16)> = bitpattern!(rest, "aaaa ???? ???? 0000", 0x1230u16);
+    assert_eq!(rest, Some((1, 0x230)));
+    assert_eq!(
+        bitpattern!(rest, "aa?? ????", 0xd4u8, u32),
+        Some((3, 0x14u32))
+    );
+    assert_eq!(
+        bitpattern!(rest, const, "10aa ??00", 0xb4u8),
+        Some((3, 0x04))
+    );
+    assert_eq!(bitpattern!(rest, "10aa ??00", 0xb4), Some((3, 0x04)));
+    assert_eq!(
+        bitpattern!(rest, "10aa ??00", 0x0002_d000u32 @ 10),
+        Some((3, 0x04))
+    );
+    assert_eq!(bitpattern!(rest, low, "aa??", 0xfeu8), Some((3, 0x2)));
+    assert_eq!(bitpattern!(rest, flags, "a???", 0x9u8), Some((true, 0x1)));
+    assert_eq!(bitpattern!(tuple, rest, "????", 0x9u8), Some((0x9,)));
+    assert_eq!(bitpattern!(rest, "1a??", 0x2u8, else (0, 0xff)), (0, 0xff));
+    assert_eq!(bitmatch_result!(rest, "1a??", 0xeu8), Ok((1, 0x2)));
+}
+
+#[test]
+fn test_bool() {
+    let x = 0xacu8;
+    assert!(bitpattern!(bool, "1010_1100", x));
+    assert!(bitpattern!(bool, "1?1? 11??", x));
+    assert!(!bitpattern!(bool, "1010_0000", x));
+    let matched: bool = bitpattern!(bool, "1010_1100", x);
+    assert!(matched);
+
+    // Only '?' bits always match a value of the pattern's width, but bits
+    // above the pattern still don't, unless `low` is given.
+    assert!(bitpattern!(bool, "???? ????", x));
+    assert!(bitpattern!(bool, "????", 0x9u8));
+    assert!(!bitpattern!(bool, "????", 0x19u8));
+    assert!(bitpattern!(bool, low, "????", 0x19u8));
+    assert!(bitpattern!(bool, "????", 0x9u8, u32));
+
+    const MATCHED: bool = bitpattern!(bool, const, "10?? ??00", 0xb4u8);
+    const ANY: bool = bitpattern!(const, bool, "????????", 0xffu8);
+    assert_eq!((MATCHED, ANY), (true, true));
+    assert!(!bitpattern!(bool, "1010 ????", 0x0002_d000u32 @ 10));
+    assert!(bitpattern!(bool, "1011 ????", 0x0002_d000u32 @ 10));
+    assert!(bitpattern!(bool, b"0100 ???? ????????", &[0x40, 0x12][..]));
+    assert!(!bitpattern!(bool, b"0101 ???? ????????", &[0x40, 0x12][..]));
+    assert!(bitpattern!(bool, "1", (0, 1), (u128, u128)));
+    assert!(!bitpattern!(bool, "1", (1, 1), (u128, u128)));
+}
+
+#[test]
+fn test_dropped() {
+    let x = 0xacu8;
+    assert_eq!(bitpattern!("aabb bccc", x), Some((2, 5, 4)));
+    assert_eq!(bitpattern!(~b, "aabb bccc", x), Some((2, 4)));
+    assert_eq!(bitpattern!(~a, ~c, "aabb bccc", x), Some(5));
+    assert_eq!(bitpattern!(~a, ~b, ~c, "aabb bccc", x), Some(()));
+    // Every run of the letter is dropped, and its bits match anything.
+    assert_eq!(bitpattern!(~b, "b0bb aabb", x), Some(3));
+    assert_eq!(bitpattern!(~b, "b1bb aabb", x), None);
+    assert_eq!(bitpattern!(~b, tuple, "aabb bccc", x), Some((2, 4)));
+    assert_eq!(bitpattern!(tuple, ~b, ~c, "aabb bccc", x), Some((2,)));
+    assert!(bitpattern!(bool, ~a, "1aa0 1100", x));
+
+    // The other fields keep their order and options.
+    assert_eq!(bitpattern!(~b, flags, "aabb bccc", x), Some((2, 4)));
+    assert_eq!(bitpattern!(~b, rest, "aabb b??c", x), Some((2, 0, 0x04)));
+    assert_eq!(
+        bitpattern!(~b, "aabb bccc", x => { low: c, high: a }).map(|x| (x.low, x.high)),
+        Some((4, 2))
+    );
+    assert_eq!(
+        bitpattern!(~o, "oo dd iiii", 0x9du8, |i, d| (d, i)),
+        Some((1, 13))
+    );
+    assert_eq!(bitpattern!(~b, "aabb bccc", x, c as u16), Some((2, 4u16)));
+    assert_eq!(bitpattern!(~b, "aabb bccc", x => a + c), Some(6));
+    assert_eq!(
+        bitpattern!(~b, "aabb bccc", 0x0002_b000u32 @ 10),
+        Some((2, 4))
+    );
+    assert_eq!(bitpattern!(~b, b"aaaa bbbb", &[0x4c][..]), Some(4));
+    assert_eq!(bitpattern!(~b, "abbb", (0, 0xc), (u128, u128)), Some(1));
+    assert_eq!(bitmatch_result!(~b, "aabb bccc", x), Ok((2, 4)));
+
+    let (mut a, mut c) = (0, 0);
+    assert!(bitmatch_into!(~b, "aabb bccc", x, a, c));
+    assert_eq!((a, c), (2, 4));
+    assert!(bitmatch_into!(~b, "aabb bccc", 0x47u8, c = c));
+    assert_eq!((a, c), (2, 7));
+}
+
+#[test]
+fn test_bitmatch_result() {
+    let x = 0xacu8;
+    assert_eq!(
+        bitmatch_result!("1aab_bccc", x).ok(),
+        bitpattern!("1aab_bccc", x)
+    );
+    assert_eq!(bitmatch_result!("1010_1100", x).ok(), Some(()));
+
+    let error = bitmatch_result!("0101 aa??", x).unwrap_err();
+    assert_eq!(error.mismatch, 0xf0);
+    assert_eq!((error.mask, error.value), (0xf0, 0xac));
+    let error = bitmatch_result!("1?00 ?1?0", x).unwrap_err();
+    assert_eq!(error.mismatch, 0x20);
+    let error: u16 = bitmatch_result!("1111 0000 aaaa aaaa", 0xf1ffu16)
+        .unwrap_err()
+        .mismatch;
+    assert_eq!(error, 0x0100);
+    assert_eq!(
+        format!("{:?}", bitmatch_result!("01aa", 0xdu8)),
+        "Err(BitMismatch { mismatch: 8, mask: 12, value: 13, high_bits: false })"
+    );
+    assert_eq!(
+        bitmatch_result!("0101 aa??", 0x9bu8)
+            .unwrap_err()
+            .to_string(),
+        "bits 0xc0 of 0x9b don't match the pattern (mask 0xf0)"
+    );
+
+    // A value with bits set above the pattern is truncated to its type, and
+    // `high_bits` tells they're set.
+    let error = bitmatch_result!("1aaa", 0x1au8).unwrap_err();
+    assert_eq!(
+        (error.mismatch, error.value, error.high_bits),
+        (0, 0x1a, true)
+    );
+    assert_eq!(error.to_string(), "bits above the pattern are set");
+    assert_eq!(bitmatch_result!(low, "1aaa", 0x1au8).ok(), Some(2));
+    let error = bitmatch_result!("1010 1100", 0xffff_ffacu32).unwrap_err();
+    assert_eq!(
+        (error.mismatch, error.value, error.high_bits),
+        (0, 0xac, true)
+    );
+    let error = bitmatch_result!("0aaa aaaa", -2i8).unwrap_err();
+    assert_eq!(
+        (error.mismatch, error.value, error.high_bits),
+        (0x80, 0xfe, false)
+    );
+    let error = bitmatch_result!("0aaa aaaa", -2i16).unwrap_err();
+    assert_eq!(
+        (error.mismatch, error.value, error.high_bits),
+        (0x80, 0xfe, true)
+    );
+    assert_eq!(
+        error.to_string(),
+        "bits 0x80 of 0xfe don't match the pattern (mask 0x80), and bits above the pattern are set"
+    );
+    assert_eq!(bitmatch_result!("1aaa aaaa", &-2i8).ok(), Some(0x7e));
+    let error = bitmatch_result!("0000 aaaa", 0x15).unwrap_err();
+    assert_eq!((error.value, error.high_bits), (0x15, false));
+    let error = bitmatch_result!("aaaa", 0x15).unwrap_err();
+    assert_eq!(
+        (error.mismatch, error.value, error.high_bits),
+        (0, 0x15, true)
+    );
+    let error = bitmatch_result!(const, "000 aaaa", 0x95u8).unwrap_err();
+    assert_eq!((error.mismatch, error.high_bits), (0x10, true));
+
+    let y = 0x0022_ac00u32;
+    assert_eq!(
+        bitmatch_result!("10aa bbbb cccc", y @ 10).ok(),
+        Some((0, 0xa, 0xb))
+    );
+    assert_eq!(
+        bitmatch_result!("11aa bbbb cccc", y @ 10)
+            .unwrap_err()
+            .mismatch,
+        0x400
+    );
+    let found = bitmatch_result!(tuple, "1aaa_a100", x).ok();
+    assert_eq!(found, Some((5,)));
+    assert_eq!(
+        bitmatch_result!(tuple, "0aaa_a100", x)
+            .unwrap_err()
+            .mismatch,
+        0x80
+    );
+    let found = bitmatch_result!("1aab_bccc", x => { a, c }).map(|x| (x.a, x.c));
+    assert_eq!(found.ok(), Some((1, 4)));
+    assert!(bitmatch_result!("0aab_bccc", x => { a, c }).is_err());
+    assert_eq!(bitmatch_result!("1aaa_a100", x, a + 1).ok(), Some(6));
+    assert_eq!(
+        bitmatch_result!("0aaa_a100", x, a + 1)
+            .unwrap_err()
+            .mismatch,
+        0x80
+    );
+    let page: Result<usize, _> = bitmatch_result!("aaaa aaaa ????", 0x403usize, usize);
+    assert_eq!(page.ok(), Some(0x40));
+}
+
+#[test]
+fn test_closure() {
+    let regs = [1u32, 2, 3, 4];
+    let x = 0b1001_1100u8;
+    assert_eq!(
+        bitpattern!("aabb cc??", x, |a, b, c| regs[a as usize]
+            + u32::from(b * c)),
+        Some(6)
+    );
+    // Parameters are in any order, and can leave out letters.
+    assert_eq!(bitpattern!("aabb cc??", x, |c, a| (c, a)), Some((3, 2)));
+    assert_eq!(bitpattern!("aabb cc??", x, |b| b), Some(1));
+    assert_eq!(bitpattern!("aabb cc??", x, || "found"), Some("found"));
+    assert_eq!(bitpattern!("11bb cc??", x, |b, c| b + c), None);
+    assert_eq!(bitpattern!("1010 1100", 0xacu8, || 7), Some(7));
+
+    // Parameters can have types, and the closure can capture and move.
+    let mut seen = Vec::new();
+    let found = bitpattern!("aabb cc??", x, |a: u8, c: u8| seen.push(a + c));
+    assert_eq!((found, seen), (Some(()), vec![5]));
+    let name = String::from("r");
+    let reg = bitpattern!("aabb cc??", x, move |a| format!("{}{}", name, a));
+    assert_eq!(reg.as_deref(), Some("r2"));
+
+    // The closure sees the fields after the other arguments.
+    assert_eq!(
+        bitpattern!(flags, "a bbbbbb c", 0x81u16, |c, a| a && c),
+        Some(true)
+    );
+    assert_eq!(
+        bitpattern!("SSSS aaaa", 0xe5u8, a + 1, |S, a| i16::from(S)
+            * i16::from(a)),
+        Some(-12)
+    );
+    assert_eq!(
+        bitpattern!("aaaa aaaa ????", 0xabcu16, u16, |a| a),
+        Some(0xab)
+    );
+    let y = 0x0022_ac00u32;
+    assert_eq!(
+        bitpattern!("10aa bbbb cccc", y @ 10, |b, c| b * c),
+        Some(110)
+    );
+    assert_eq!(
+        bitpattern!(b"0100 hhhh llllllll", &[0x45u8, 0x1c][..], |l, h| l - h),
+        Some(0x17)
+    );
+    assert_eq!(bitmatch_result!("aabb cc??", x, |c| c).ok(), Some(3));
+    assert_eq!(
+        bitmatch_result!("11bb cc??", x, |c| c)
+            .unwrap_err()
+            .mismatch,
+        0x40
+    );
+}
+
+#[test]
+fn test_else() {
+    let x = 0xb4u8;
+    assert_eq!(bitpattern!("10aa bb??", x, else (0, 0)), (3, 1));
+    assert_eq!(bitpattern!("11aa bb??", x, else (0, 0)), (0, 0));
+    assert_eq!(bitpattern!("1aaa aaaa", x, else 0,), 0x34);
+    bitpattern!("1011 0100", x, else panic!("no match"));
+
+    // The value is evaluated once either way.
+    let reads = std::cell::Cell::new(0);
+    let read = || {
+        reads.set(reads.get() + 1);
+        x
+    };
+    assert_eq!(bitpattern!("10aa bb??", read(), else (0, 0)), (3, 1));
+    assert_eq!(bitpattern!("11aa bb??", read(), else (0, 0)), (0, 0));
+    assert_eq!(reads.get(), 2);
+
+    // The fallback can diverge.
+    fn decode(x: u16) -> Result<(u8, u8), u16> {
+        let (a, b) = bitpattern!(narrow, "0000 aaaa bbbb ????", x, else return Err(x));
+        Ok((a, b))
+    }
+    assert_eq!(decode(0x0ab0), Ok((0xa, 0xb)));
+    assert_eq!(decode(0x1ab0), Err(0x1ab0));
+    let words = [0x12u8, 0x84, 0x95, 0x06];
+    let mut fields = Vec::new();
+    for &w in &words {
+        let a = bitpattern!("1aa? ????", w, else continue);
+        fields.push(a);
+    }
+    assert_eq!(fields, [0, 0]);
+    let mut found = None;
+    for &w in &words {
+        found = Some(bitpattern!("000a aaaa", w, else break));
+    }
+    assert_eq!(found, Some(0x12));
+
+    // It applies to what the other arguments return.
+    assert_eq!(bitpattern!(tuple, "1aaa_a100", 0xacu8, else (0,)), (5,));
+    assert_eq!(bitpattern!("aabb cc??", x, |a, c| a + c, else 0), 3);
+    assert_eq!(
+        bitpattern!("aabb cc??", 0xf4u8, a as AddrMode, else (AddrMode::Reg, 0, 0)).1,
+        3
+    );
+    let insn = bitpattern!("oo dd iiii", 0x96u8 => { opcode: o }, else panic!());
+    assert_eq!(insn.opcode, 2);
+    assert_eq!(bitpattern!("aaaa", 0x15u8, else 0), 0);
+    assert_eq!(bitpattern!(b"0100 hhhh", &[0x45u8][..], else 0), 5);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Move { dst: u8, src: u8 },
+    Load(u8, u16),
+    Branch(i32),
+    Nop,
+}
+
+fn reg(x: u8) -> u8 {
+    x + 8
+}
+
+#[test]
+fn test_arm() {
+    let x = 0x06u8;
+    assert_eq!(
+        bitpattern!("0000 aabb", x => Op::Move { dst: a, src: b }),
+        Some(Op::Move { dst: 1, src: 2 })
+    );
+    assert_eq!(
+        bitpattern!("0001 aabb", x => Op::Move { dst: a, src: b }),
+        None
+    );
+    assert_eq!(
+        bitpattern!("0000 aabb", x => Op::Move { dst: reg(b), src: reg(reg(a)) }),
+        Some(Op::Move { dst: 10, src: 17 })
+    );
+    assert_eq!(
+        bitpattern!("01 rr iiii iiii iiii", 0x6abcu16 => Op::Load(r as u8, i)),
+        Some(Op::Load(2, 0xabc))
+    );
+    assert_eq!(bitpattern!("0000 0110", x => Op::Nop), Some(Op::Nop));
+    assert_eq!(
+        bitpattern!("0000 aabb", x => { let c = a * b; c + 1 }),
+        Some(3)
+    );
+    assert_eq!(bitpattern!("0000 aabb", x => (b, a)), Some((2, 1)));
+
+    // The other arguments apply before, and uppercase letters are bound too.
+    assert_eq!(
+        bitpattern!(narrow, "1111 SSSS SSSS", 0xffdu16 => Op::Branch(S.into()), S << 2),
+        Some(Op::Branch(-12))
+    );
+    assert_eq!(
+        bitpattern!("aa bbbb ??", 0xd4u8 => (a, b + 1), a as AddrMode),
+        Some((AddrMode::Mem, 6))
+    );
+    let y = 0x0022_ac00u32;
+    assert_eq!(bitpattern!("10aa bbbb cccc", y @ 10 => b * c), Some(110));
+    assert_eq!(bitpattern!("0000 aabb", 0x16u8 => a + b, else 0), 0);
+    assert_eq!(bitpattern!("aa ?? aa ??", 0xf0u8 => 1), Some(1));
+    assert_eq!(bitmatch_result!("0000 aabb", x => a + b).ok(), Some(3));
+    assert_eq!(
+        bitpattern!(b"0100 hhhh llllllll", &[0x45u8, 0x1c][..] => l - h),
+        Some(0x17)
+    );
+
+    // The value is evaluated before the letters are bound.
+    let a = 0x09u8;
+    assert_eq!(bitpattern!("0000 aabb", a => (a, b)), Some((2, 1)));
+}
+
+#[test]
+fn test_bitmatch_into() {
+    let (mut opcode, mut dest, mut imm) = (0u8, 0u8, 0u8);
+    let mut decoded = Vec::new();
+    for &word in &[0x96u8, 0xff, 0x4a, 0x3c] {
+        if bitmatch_into!("0? dd iiii", word, dest, imm) {
+            decoded.push((dest, imm));
+        }
+    }
+    assert_eq!(decoded, [(0, 0xa), (3, 0xc)]);
+
+    // The places are untouched without a match.
+    assert!(bitmatch_into!("oo dd iiii", 0x96u8, opcode, dest, imm));
+    assert_eq!((opcode, dest, imm), (2, 1, 6));
+    assert!(!bitmatch_into!("1o dd iiii", 0x45u8, opcode, dest, imm));
+    assert_eq!((opcode, dest, imm), (2, 1, 6));
+    assert!(bitmatch_into!("1001 0110", 0x96u8,));
+
+    // Places can be named by letter, in any order and leaving some out.
+    assert!(bitmatch_into!("oo dd iiii", 0x5bu8, imm = i, opcode = o));
+    assert_eq!((opcode, dest, imm), (1, 1, 0xb));
+
+    // Any place can be assigned.
+    struct Regs {
+        r: [u16; 4],
+    }
+    let mut regs = Regs { r: [0; 4] };
+    let word = 0x1234u16;
+    assert!(bitmatch_into!(
+        "aaaa bbbb ???? dddd",
+        word,
+        regs.r[0],
+        regs.r[1],
+        regs.r[3]
+    ));
+    assert_eq!(regs.r, [1, 2, 0, 4]);
+    assert!(!bitmatch_into!("0000 aaaa bbbb ????", word, regs.r[2] = a));
+
+    // Each place has the type of its field, like `bitpattern!`.
+    let (mut enable, mut addr) = (false, 0u16);
+    assert!(bitmatch_into!(
+        flags,
+        "e ??? aaaa aaaa aaaa",
+        0x8abcu16,
+        enable,
+        addr
+    ));
+    assert!(enable);
+    assert_eq!(addr, 0xabc);
+    let (mut small, mut wide) = (0u8, 0u16);
+    assert!(bitmatch_into!(
+        narrow,
+        "aaaa aaaa bbbb bbbb bbbb bbbb",
+        0xab_cdefu32,
+        small,
+        wide
+    ));
+    assert_eq!((small, wide), (0xab, 0xcdef));
+    let mut offset = 0i16;
+    assert!(bitmatch_into!("SSSS SSSS SSSS 0011", 0xffe3u16, offset));
+    assert_eq!(offset, -2);
+    let y = 0x0022_ac00u32;
+    let (mut b, mut c) = (0u16, 0u16);
+    assert!(bitmatch_into!("10aa bbbb cccc", y @ 10, b = b, c = c));
+    assert_eq!((b, c), (0xa, 0xb));
+    let mut len = 0u32;
+    assert!(bitmatch_into!(
+        b"0100 ???? llllllll llllllll ????????",
+        &[0x45u8, 0, 0x1c, 0x8f][..],
+        len = l
+    ));
+    assert_eq!(len, 0x1c);
+}
+
+#[test]
+fn test_names() {
+    let x = 0b1001_0110u8;
+    let insn = bitpattern!("oo dd iiii", x => { opcode: o, dest: d, imm: i }).unwrap();
+    assert_eq!((insn.opcode, insn.dest, insn.imm), (2, 1, 6));
+
+    // The order of the names doesn't matter, and a letter alone is its name.
+    let insn = bitpattern!("oo dd iiii", x => { i, opcode: o }).unwrap();
+    assert_eq!((insn.opcode, insn.i), (2, 6));
+    assert_eq!(bitpattern!("11 dd iiii", x => { dest: d }), None);
+    let fields = bitpattern!("1aab_bccc", 0xacu8 => { a, b, c });
+    assert_eq!(format!("{:?}", fields), "Some(Fields { a: 1, b: 1, c: 4 })");
+
+    let y = 0x0022_ac00u32;
+    let f = bitpattern!("10aa bbbb cccc", y @ 10 => { b, c }).unwrap();
+    assert_eq!((f.b, f.c), (0xa, 0xb));
+    let f = bitpattern!("0110011 aa bb", 0x33du32 => { first: a }, u32).unwrap();
+    let first: u32 = f.first;
+    assert_eq!(first, 3);
+
+    let buf = [0x45u8, 0x00, 0x1c, 0x8f];
+    let header = bitpattern!(
+        b"0100 hhhh ssssssss llllllll llllllll",
+        &buf[..] => { ihl: h, len: l }
+    )
+    .unwrap();
+    assert_eq!((header.ihl, header.len), (5, 0x1c8f));
+    assert!(bitpattern!("1010 ????", 0xa5u8 => {}).is_some());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddrMode {
+    Reg = 0,
+    Imm = 1,
+    // 2 isn't a mode.
+    Mem = 3,
+}
+
+impl std::convert::TryFrom<u8> for AddrMode {
+    type Error = u8;
+
+    fn try_from(x: u8) -> Result<AddrMode, u8> {
+        match x {
+            0 => Ok(AddrMode::Reg),
+            1 => Ok(AddrMode::Imm),
+            3 => Ok(AddrMode::Mem),
+            x => Err(x),
+        }
+    }
+}
+
+impl std::convert::TryFrom<u16> for AddrMode {
+    type Error = u16;
+
+    fn try_from(x: u16) -> Result<AddrMode, u16> {
+        match u8::try_from(x) {
+            Ok(x) => AddrMode::try_from(x).map_err(u16::from),
+            Err(_) => Err(x),
+        }
+    }
+}
+
+mod modes {
+    pub use super::AddrMode as Mode;
+}
+
+#[test]
+fn test_converts() {
+    assert_eq!(
+        bitpattern!("aa bbbb ??", 0x54u8, a as AddrMode),
+        Some((AddrMode::Imm, 5))
+    );
+    assert_eq!(
+        bitpattern!("aa bbbb ??", 0xd4u8, a as AddrMode),
+        Some((AddrMode::Mem, 5))
+    );
+    assert_eq!(bitpattern!("aa bbbb ??", 0x94u8, a as AddrMode), None);
+    assert_eq!(
+        bitpattern!("00 aa ????", 0x0fu8, a as AddrMode),
+        Some(AddrMode::Reg)
+    );
+    assert_eq!(bitpattern!("01 aa ????", 0x0fu8, a as AddrMode), None);
+
+    // Each run of the letter is converted, and a path resolves where the
+    // macro is called.
+    assert_eq!(
+        bitpattern!("aa bb aa ??", 0xc4u8, a as modes::Mode, b as AddrMode,),
+        Some((AddrMode::Mem, AddrMode::Reg, AddrMode::Imm))
+    );
+    assert_eq!(bitpattern!("aa bb aa ??", 0xc8u8, a as AddrMode), None);
+
+    // The conversion is from the type of the field.
+    assert_eq!(
+        bitpattern!("aa ???? bbbb bbbb", 0x30ffu16, a as AddrMode, u16),
+        Some((AddrMode::Mem, 0xff))
+    );
+    assert_eq!(
+        bitpattern!(narrow, "aa ???? bbbb bbbb", 0x10ffu16, a as AddrMode),
+        Some((AddrMode::Imm, 0xffu8))
+    );
+    assert_eq!(
+        bitpattern!("mm ?? dddd", 0b1100_0110u8 => { mode: m, d }, m as AddrMode)
+            .map(|x| (x.mode, x.d)),
+        Some((AddrMode::Mem, 6))
+    );
+    assert_eq!(
+        bitpattern!("mm ?? dddd", 0x86u8 => { mode: m }, m as AddrMode),
+        None
+    );
+    assert_eq!(
+        bitpattern!(b"aa?? ???? bbbbbbbb", &[0x40, 0x12][..], a as AddrMode),
+        Some((AddrMode::Imm, 0x12))
+    );
+}
+
+#[test]
+fn test_maps() {
+    // A branch with a word offset, and a count stored minus one.
+    let insn = 0x5a0c_u16;
+    assert_eq!(
+        bitpattern!("01 aaaaaaaa ??????", insn, a << 2),
+        Some(0x68 << 2)
+    );
+    assert_eq!(bitpattern!("0101 1010 0000 cccc", insn, c + 1), Some(13));
+    assert_eq!(
+        bitpattern!("01 aaaaaaaa bb ????", insn, a => a as usize * 2, b + 1),
+        Some((0xd0, 1))
+    );
+
+    // Sign extension happens before the map.
+    assert_eq!(bitpattern!("1111 SSSS SSSS", 0xffeu16, S << 1), Some(-4i16));
+    assert_eq!(
+        bitpattern!(narrow, "1111 SSSS SSSS", 0xf80u16, S << 1),
+        Some(0i8)
+    );
+    assert_eq!(
+        bitpattern!(narrow, "1111 SSSS SSSS", 0xffdu16, S => S as i32 * 4),
+        Some(-12)
+    );
+
+    // A map without a letter in scope is any expression.
+    let base = 0x1000u32;
+    assert_eq!(
+        bitpattern!("l aaa", 0b1_011u8, a => base + u32::from(a) * 8),
+        Some((1, 0x1018))
+    );
+    assert_eq!(
+        bitpattern!("aa bbbb ??", 0x55u8, a as AddrMode, b => b != 0),
+        Some((AddrMode::Imm, true))
+    );
+    assert_eq!(
+        bitpattern!("aa bbbb ??", 0x94u8, a as AddrMode, b << 1),
+        None
+    );
+    assert_eq!(
+        bitpattern!(flags, "e cccc", 0x1fu8 => { e, count: c }, c + 1).map(|x| (x.e, x.count)),
+        Some((true, 16))
+    );
+    let y = 0x0022_ac00u32;
+    assert_eq!(
+        bitpattern!("10aa bbbb cccc", y @ 10, b << 4, c + 4),
+        Some((0, 0xa0, 0xf))
+    );
+
+    // The maps keep the type of the field: `+` wraps around at the all-ones
+    // boundary, and `<<` drops the bits shifted out.
+    assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a + 1), Some(0));
+    assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a + 0xff), Some(0xfe));
+    assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a << 2), Some(0xfc));
+    assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a << 7), Some(0x80));
+    assert_eq!(bitpattern!("aaaa aaaa", 0xffu8, a << 8), Some(0));
+    assert_eq!(bitpattern!("aaaa", 0xfu8, a + 1), Some(0x10));
+    assert_eq!(bitpattern!("SSSS SSSS", 0x7fu8, S + 1), Some(-0x80));
+    assert_eq!(bitpattern!("SSSS SSSS", 0xffu8, S << 7), Some(-0x80));
+    assert_eq!(
+        bitpattern!(narrow, "??aa aaaa aaaa", 0x3ffu16, a + 1),
+        Some(0x400)
+    );
+    let all = u128::MAX;
+    assert_eq!(bitpattern!(low, "aaaa aaaa", all, a + 1), Some(0));
+    assert_eq!(
+        bitpattern!(const, "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa", all, a + 1),
+        Some(0)
+    );
+    const WRAPPED: Option<(u8, u8)> = bitpattern!(const, "aaaa bbbb", 0xffu8, a << 5, b + 0xf1);
+    assert_eq!(WRAPPED, Some((0xe0, 0)));
+}
+
+#[test]
+fn test_references() {
+    let buf = [0x12u16, 0xa034, 0xa0ff, 0x0056];
+    let fields: Vec<_> = buf
+        .iter()
+        .filter_map(|w| bitpattern!("1010 0000 aaaa bbbb", w))
+        .collect();
+    assert_eq!(fields, [(3, 4), (0xf, 0xf)]);
+
+    let x = 0xacu8;
+    let r = &x;
+    assert_eq!(bitpattern!("1aab_bccc", r), Some((1, 1, 4)));
+    assert_eq!(bitpattern!(source, "1aab_bccc", &r), Some((1, 1, 4)));
+    assert_eq!(bitpattern!("aaaa", r), None);
+    assert_eq!(bitpattern!("aaaa aaaa aaaa", &-2i8), Some(0xfe));
+    assert_eq!(
+        bitpattern!(source, "aaaa", &std::num::Wrapping(5u8)),
+        Some(5)
+    );
+
+    let mut y = 0x1234u16;
+    let m = &mut y;
+    assert_eq!(
+        bitpattern!(source, "aaaa bbbb cccc dddd", m),
+        Some((1, 2, 3, 4))
+    );
+    *m += 1;
+    assert_eq!(bitpattern!(source, "???? ???? ???? aaaa", &mut y), Some(5));
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(u8)]
+enum FrameType {
+    Data = 0x10,
+    Ack = 0x2f,
+    Nack = 0xe5,
+}
+
+impl From<FrameType> for u8 {
+    fn from(x: FrameType) -> u8 {
+        x as u8
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(u32)]
+enum Opcode {
+    Lui = 0x37,
+    Jal = 0x6f,
+    Ecall = 0x0000_0073,
+    Ebreak = 0x0010_0073,
+}
+
+impl From<Opcode> for u32 {
+    fn from(x: Opcode) -> u32 {
+        x as u32
+    }
+}
+
+/// Enum with a `bits` method instead of `Into`.
+#[derive(Clone, Copy)]
+#[repr(u16)]
+enum Mode {
+    Read = 0x0100,
+    Write = 0x0201,
+}
+
+impl Mode {
+    fn bits(&self) -> u16 {
+        *self as u16
+    }
+}
+
+#[test]
+fn test_enum() {
+    assert_eq!(
+        bitpattern!(source, "00 aa bbbb", FrameType::Data),
+        Some((1, 0))
+    );
+    assert_eq!(
+        bitpattern!(source, "00 aa bbbb", FrameType::Ack),
+        Some((2, 0xf))
+    );
+    assert_eq!(bitpattern!(source, "00 aa bbbb", FrameType::Nack), None);
+    assert_eq!(
+        bitpattern!(source, "aa ?? bbbb", FrameType::Nack),
+        Some((3, 5))
+    );
+    let frame = FrameType::Ack;
+    assert_eq!(bitpattern!(source, "??1? aaaa", frame), Some(0xf));
+    assert_eq!(bitpattern!(source, "aa bbbb", frame), Some((2, 0xf)));
+    assert_eq!(bitpattern!(source, "aa bbbb", FrameType::Nack), None);
+    assert_eq!(
+        bitpattern!(source, low, "aa bbbb", FrameType::Nack),
+        Some((2, 5))
+    );
+
+    let ops = [Opcode::Lui, Opcode::Jal, Opcode::Ecall, Opcode::Ebreak];
+    let classes: Vec<_> = ops
+        .iter()
+        .map(|op| bitpattern!(source, low, "aa bbb 11", *op))
+        .collect();
+    assert_eq!(
+        classes,
+        [Some((1, 5)), Some((3, 3)), Some((3, 4)), Some((3, 4))]
+    );
+    for op in ops {
+        let funct = bitpattern!(source, low, "aaaaaaaaaaaa ????? ??? ????? 1110011", op, u32);
+        match op {
+            Opcode::Ecall => assert_eq!(funct, Some(0)),
+            Opcode::Ebreak => assert_eq!(funct, Some(1)),
+            _ => assert_eq!(funct, None),
+        }
+    }
+    assert_eq!(
+        bitpattern!(
+            source,
+            "???? ???? ???? ???? ???? ???? ?aaa aaaa",
+            Opcode::Jal
+        ),
+        Some(0x6f)
+    );
+    assert_eq!(bitpattern!(source, "aaaa aaaa", Opcode::Ebreak), None);
+
+    assert_eq!(
+        bitpattern!(source, "0000 00aa 0000 000b", Mode::Read),
+        Some((1, 0))
+    );
+    assert_eq!(
+        bitpattern!(source, "0000 00aa 0000 000b", Mode::Write),
+        Some((2, 1))
+    );
+}
+
+trait BitSource {
+    type Raw;
+    fn bits(&self) -> Self::Raw;
+}
+
+#[derive(Debug, PartialEq)]
+struct Insn(u32);
+
+impl BitSource for Insn {
+    type Raw = u32;
+    fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Register reader counting its reads, with an inherent `bits`.
+struct Reader<'a> {
+    reads: &'a std::cell::Cell<u32>,
+    value: u16,
+}
+
+impl<'a> Reader<'a> {
+    fn bits(&self) -> u16 {
+        self.reads.set(self.reads.get() + 1);
+        self.value
+    }
+}
+
+#[test]
+fn test_bit_source() {
+    let insn = Insn(0x0000_033d);
+    assert_eq!(
+        bitpattern!(source, "0110011 aa bb", insn, u32),
+        Some((3, 1))
+    );
+    assert_eq!(
+        bitpattern!(source, "0110011 aa bb", &insn, u32),
+        Some((3, 1))
+    );
+    assert_eq!(bitpattern!(source, "0110011 aa bb", insn), Some((3, 1)));
+    assert_eq!(bitpattern!(source, "0110011", insn), None);
+    assert_eq!(bitpattern!(source, low, "0011 1101", insn), Some(()));
+    assert_eq!(bitpattern!(source, "aaaa", Insn(5)), Some(5));
+    // The value isn't moved.
+    assert_eq!(insn, Insn(0x33d));
+
+    let reads = std::cell::Cell::new(0);
+    let reader = Reader {
+        reads: &reads,
+        value: 0x8a50,
+    };
+    assert_eq!(
+        bitpattern!(source, "e??? tttt rrrr ????", reader),
+        Some((1, 0xa, 5))
+    );
+    assert_eq!(reads.get(), 1);
+    assert_eq!(
+        bitpattern!(
+            source,
+            "e??? tttt rrrr ????",
+            Reader {
+                reads: &reads,
+                value: 0x0f00
+            }
+        ),
+        Some((0, 0xf, 0))
+    );
+    assert_eq!(reads.get(), 2);
+    assert_eq!(bitpattern!(source, "0??? ???? ???? ????", &reader), None);
+    assert_eq!(reads.get(), 3);
+}
+
+#[test]
+fn test_expr_kinds() {
+    let dev = Device {
+        status: 0xa6,
+        regs: [0x0000, 0xf00d, 0x1234, 0xffff],
+    };
+    let pc = 2;
+    assert_eq!(dev.status(), Some((1, 2)));
+    assert_eq!(bitpattern!("1010 aabb", dev.status), Some((1, 2)));
+    assert_eq!(bitpattern!("1111 aaaa aaaa aaaa", dev.regs[1]), Some(0x00d));
+    assert_eq!(
+        bitpattern!("0001 aaaa aaaa aaaa", dev.regs[pc]),
+        Some(0x234)
+    );
+    assert_eq!(
+        bitpattern!("aaaa ???? ???? bbbb", dev.read(pc + 1)),
+        Some((0xf, 0xf))
+    );
+    assert_eq!(bitpattern!("1010 aabb", (dev.status | 1)), Some((1, 3)));
+    assert_eq!(bitpattern!("1010 aabb", dev.status | 1), Some((1, 3)));
+    assert_eq!(bitpattern!("1010 aabb", device::STATUS), Some((1, 3)));
+    assert_eq!(
+        bitpattern!("1010 aabb", crate::device::STATUS),
+        Some((1, 3))
+    );
+    assert_eq!(bitpattern!("aaaa aaaa", u8::MAX), Some(0xff));
+
+    // The expression is evaluated once.
+    let mut reads = 0;
+    let mut read = || {
+        reads += 1;
+        0xa5u8
+    };
+    assert_eq!(bitpattern!("1010 aabb", read()), Some((1, 1)));
+    assert_eq!(reads, 1);
+}
+
+#[test]
+fn test_eval_once() {
+    let mut count = 0;
+    let mut next = || {
+        count += 1;
+        0xa5u8
+    };
+    assert_eq!(bitpattern!("1010 0101", next()), Some(()));
+    assert_eq!(
+        bitpattern!("abcd efgh", next()),
+        Some((1, 0, 1, 0, 0, 1, 0, 1))
+    );
+    assert_eq!(bitpattern!("0101 aaaa", next()), None);
+    assert_eq!(bitextract!("abcd efgh", next()), (1, 0, 1, 0, 0, 1, 0, 1));
+    assert!(bitmatches!("1010 ????", next()));
+    assert_eq!(bitcount!("aaaa aaaa", next()), 4);
+    assert_eq!(bitset_fields!("aaaa bbbb", next(), a = 0, b = 1), 0x01);
+    bitassert!("1?1? ?1?1", next());
+    assert_eq!(count, 8);
+
+    let mut count = 0;
+    let mut next = || {
+        count += 1;
+        0xa5u16
+    };
+    assert_eq!(bitpattern!("aaaa bbbb", next() @ 4), Some((0, 0xa)));
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_bitsplice() {
+    assert_eq!(bitsplice!("??aa bb??", 0x00u8, 0xffu8), 0x3c);
+    assert_eq!(bitsplice!("??aa bb??", 0xffu8, 0x00u8), 0xc3);
+    assert_eq!(bitsplice!("??aa bb??", 0x81u8, 0x5au8), 0x99);
+    // The '0' and '1' bits are set whatever `old` and `new` are.
+    assert_eq!(bitsplice!("10?? aaaa", 0x7fu8, 0x00u8), 0xb0);
+    assert_eq!(bitsplice!("10?? aaaa", 0x40u8, 0xffu8), 0x8f);
+    // Bits above a short pattern are kept.
+    assert_eq!(bitsplice!("1a", 0xf0u8, 0x01u8), 0xf3);
+    assert_eq!(bitsplice!("aaaa aaaa", 0x12u8, 0x34u8), 0x34);
+    assert_eq!(bitsplice!("0101 ????", 0x12u8, 0x34u8), 0x52);
+    assert_eq!(bitsplice!("0101 1010", 0x12u8, 0x34u8), 0x5a);
+    assert_eq!(
+        bitsplice!(
+            "aaaa aaaa ???? ???? ???? ???? ???? ????",
+            0x1234_5678u32,
+            !0u32
+        ),
+        0xff34_5678
+    );
+
+    // A wider `old` keeps its type and the bits above the pattern.
+    let reg: u32 = bitsplice!("???? aa??", 0xdead_beefu32, 0u32);
+    assert_eq!(reg, 0xdead_bee3);
+    assert_eq!(bitsplice!("10?? aa??", 0xdead_beefu32, !0u32), 0xdead_beaf);
+}
+
+#[test]
+fn test_bitsplice_named() {
+    assert_eq!(bitsplice!("??aa bb??", 0xffu8, a = 0, b = 1), 0xc7);
+    assert_eq!(bitsplice!("??aa bb??", 0xffu8, b = 0), 0xf3);
+    assert_eq!(bitsplice!("??aa bb??", 0x00u8, a = 0xff,), 0x30);
+    assert_eq!(bitsplice!("10aa bb01", 0x00u8, a = 3), 0xb1);
+    // Split letters take their bits from the value like `bitpack!`.
+    assert_eq!(bitsplice!("aa?? ??aa", 0x00u8, a = 0b1001), 0x81);
+
+    assert_eq!(bitsplice!("???? aa??", 0xdead_beefu32, a = 1), 0xdead_bee7);
+}
+
+#[test]
+fn test_bitexplain() {
+    let explain = bitexplain!("101 aabb");
+    assert_eq!(explain.pattern, "101 aabb");
+    assert_eq!(explain.len, 7);
+    assert_eq!((explain.mask, explain.value), (0x70u8, 0x50));
+    assert_eq!(explain.fields, [('a', 2, 2), ('b', 0, 2)]);
+    assert_eq!(
+        format!("{:?}", explain),
+        "BitExplain { pattern: \"101 aabb\", len: 7, mask: 112, value: 80, \
+         fields: [('a', 2, 2), ('b', 0, 2)] }"
+    );
+
+    let explain = bitexplain!("aa?? 01aa", u32);
+    assert_eq!((explain.mask, explain.value), (0x0cu32, 0x04));
+    assert_eq!(explain.fields, [('a', 6, 2), ('a', 0, 2)]);
+
+    let explain = bitexplain!("0000 ????");
+    assert_eq!(explain.fields, []);
+}
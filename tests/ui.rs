@@ -0,0 +1,13 @@
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+    // Errors for options of features that aren't enabled.
+    if cfg!(not(feature = "arbitrary-int")) {
+        t.compile_fail("tests/ui/features/arbitrary_int.rs");
+    }
+    if cfg!(not(feature = "defmt")) {
+        t.compile_fail("tests/ui/features/defmt.rs");
+    }
+}
@@ -0,0 +1,15 @@
+use bitpattern::bitcheck;
+
+#[bitcheck("0000000 ????? ????? 000 ????? 0110011")]
+const OPC_ADD: u32 = 0x0000_0013;
+
+#[bitcheck("1??? ????")]
+static FLAGS: u8 = 0x7f;
+
+#[bitcheck("1??? aaaa")]
+const HIGH: u32 = 0xffff_ffa5;
+
+#[bitcheck("1??? ????")]
+fn flags() {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use bitpattern::bitconst;
+
+bitconst!(SPLIT, "aa00 aa00");
+bitconst!(NARROW, "1 0000 0000", u8);
+bitconst!(IMM, "aaaa AAAA");
+
+fn main() {}
@@ -0,0 +1,5 @@
+use bitpattern::bitfields_iter;
+
+fn main() {
+    let _ = bitfields_iter!("aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa a", 0u64);
+}
@@ -0,0 +1,5 @@
+use bitpattern::bitfind;
+
+fn main() {
+    let _ = bitfind!("1???????????????????????????????????????????????????????????????????????????????????????????????????????????????????????1", [0u8; 16]);
+}
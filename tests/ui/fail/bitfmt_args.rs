@@ -0,0 +1,7 @@
+use bitpattern::bitfmt;
+
+fn main() {
+    let _ = bitfmt!("1010 aabb", 0u8, any);
+    let _ = bitfmt!("1010 aabb", 0u8, any_bits, any_bits);
+    let _ = bitfmt!("1010 aabb", 0u8, any_bits defmt);
+}
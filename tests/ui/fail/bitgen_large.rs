@@ -0,0 +1,6 @@
+use bitpattern::bitgen;
+
+fn main() {
+    let _ = bitgen!("1??? ???? ???? ???? ???? ???? ??");
+    let _ = bitgen!("1??? ????", large);
+}
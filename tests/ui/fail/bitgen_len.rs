@@ -0,0 +1,10 @@
+use bitpattern::bitgen;
+
+fn main() {
+    // 2^64 values don't fit in `usize`.
+    let iter = bitgen!(
+        "???????????????????????????????? ????????????????????????????????",
+        allow_large
+    );
+    let _ = iter.len();
+}
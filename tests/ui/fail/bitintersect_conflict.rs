@@ -0,0 +1,7 @@
+use bitpattern::bitintersect;
+
+fn main() {
+    let _ = bitintersect!("000 ??aa", "001 ??bb");
+    let _ = bitintersect!("000 ??aa", "000 ??b");
+    let _ = bitintersect!("000 aa??", "000 ??aa");
+}
@@ -0,0 +1,8 @@
+use bitpattern::bitlet;
+
+fn main() {
+    let x = 0u8;
+    bitlet!("10 aabb" = x);
+    let _ = a + b;
+    bitlet!("10 aabb" = x, else ());
+}
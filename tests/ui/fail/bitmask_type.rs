@@ -0,0 +1,6 @@
+use bitpattern::bitmask;
+
+fn main() {
+    let _ = bitmask!("1 0000 0000", u8);
+    let _ = bitmask!("1", i32);
+}
@@ -0,0 +1,35 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn fields(x: u8) -> u8 {
+    match x {
+        "1111 00aa" | "1110 00bb" => a,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn widths(x: u8) -> u8 {
+    match x {
+        "1111 00aa" | "1110 0aaa" => a,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn mixed(x: u8) -> u8 {
+    match x {
+        "1111 00aa" | 0 => 1,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn unreachable(x: u8) -> u8 {
+    match x {
+        "1111 00??" | "1111 0000" => 1,
+        _ => 0,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use bitpattern::{bitmatch_all, bitmatch_any};
+
+fn main() {
+    let x = 0u8;
+    let _ = bitmatch_any!(x, ["000? ????", "001? ???", "01?? ????"]);
+    let _ = bitmatch_all!(x, []);
+}
@@ -0,0 +1,27 @@
+use bitpattern::bitmatch;
+
+#[bitmatch(define(rtype = "ggggggg bbbbb aaaaa fff ddddd 0110011"))]
+mod decode {
+    fn width(insn: u32) -> u32 {
+        match insn {
+            "{rtype: f=000, g=000000}" => a,
+            _ => 0,
+        }
+    }
+
+    fn field(insn: u32) -> u32 {
+        match insn {
+            "{rtype: f=000, x=0000000}" => a,
+            _ => 0,
+        }
+    }
+
+    fn undefined(insn: u32) -> u32 {
+        match insn {
+            "{itype: f=000}" => a,
+            _ => 0,
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn decode(x: u8) -> u8 {
+    match x {
+        "1aaa aaaa" if a > 4 => a,
+        "0??? ????" => 0,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use bitpattern::bitmatch_into;
+
+fn main() {
+    let (mut a, mut b, mut narrow) = (0u8, 0u8, 0u8);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a, b, a);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a = a, b);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a = c);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a = a, b = a);
+    let _ = bitmatch_into!("aa bb aa", 0u8, a = a);
+    let _ = bitmatch_into!("aa bbbb ??", 0u8, a = 1);
+    let _ = bitmatch_into!(tuple, "aa ??????", 0u8, a);
+    let _ = bitmatch_into!("aa bbbb ?? ????????", 0u16, a, narrow);
+    let _ = b;
+}
@@ -0,0 +1,12 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn decode(x: u16) -> u16 {
+    match x {
+        "1aaa aaaa" => a as u16,
+        "1aaa aaaa 0000" => a,
+        _ => 0,
+    }
+}
+
+fn main() {}
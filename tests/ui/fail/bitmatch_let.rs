@@ -0,0 +1,9 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn destructure(x: u8) -> u8 {
+    let "10_aabb" = x;
+    a + b
+}
+
+fn main() {}
@@ -0,0 +1,36 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn decode(x: u8) -> u8 {
+    match x {
+        "1aaa aaaa" => a,
+        "0??? ???0" => 0,
+    }
+}
+
+#[bitmatch]
+fn decode_options(x: u8) -> u8 {
+    #[bitmatch(exhaustive)]
+    match x {
+        "1aaa aaaa" => a,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn decode_non_exhaustive(x: u8) -> u8 {
+    #[bitmatch(non_exhaustive)]
+    match x {
+        "1aaa aaaa" => a,
+    }
+}
+
+#[bitmatch]
+fn decode_high_bits(x: u32) -> u8 {
+    match x {
+        "1aaa aaaa" => a,
+        "0??? ????" => 0,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+#![deny(deprecated)]
+
+use bitpattern::bitmatch;
+
+#[bitmatch(warn_overlap)]
+fn partial(x: u8) -> u8 {
+    match x {
+        "1??? ???0" => 1,
+        "11?? ????" => 2,
+        "?1?? ???1" => 3,
+        _ => 0,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn fixed(#[bitmatch("10 aabb")] x: u8) -> u8 {
+    a + b
+}
+
+#[bitmatch]
+fn unnamed(#[bitmatch("aaaa bbbb")] _: u8) -> u8 {
+    0
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use bitpattern::bitmatch_reg_ptr;
+
+fn main() {
+    let reg = 0u8;
+    let ptr: *const u8 = &reg;
+    let _ = bitmatch_reg_ptr!("00aa aa??", ptr);
+}
@@ -0,0 +1,11 @@
+use bitpattern::bitmatch_result;
+
+fn main() {
+    let x = 0u8;
+    let buf = [0u8; 2];
+    let _ = bitmatch_result!("1010 aabb");
+    let _ = bitmatch_result!(b"1010 aabb", &buf[..]);
+    let _ = bitmatch_result!("1010 aabb", (0, 0), (u128, u128));
+    let _ = bitmatch_result!("1010 aabb", x, a as u8);
+    let _ = bitmatch_result!("1010 aabb", x, else (0, 0));
+}
@@ -0,0 +1,20 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn width(hi: u8, lo: u8) -> u16 {
+    match (hi, lo) {
+        ("1111 aaaa", "aaaa") => a,
+        ("0000 aaaa", "aaa") => a,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn exhaustive(hi: u8, lo: u8) -> u16 {
+    match (hi, lo) {
+        ("1??? ????", "aaaa ????") => a,
+        (_, "0??? ????") => 0,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,40 @@
+use bitpattern::bitmatch;
+
+#[bitmatch]
+fn nested(x: u8) -> u8 {
+    match x {
+        "1??? ????" => 1,
+        "11?? ????" => 2,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn union(x: u8) -> u8 {
+    match x {
+        "1??? ???0" => 1,
+        "1??? ???1" => 2,
+        "1010 aaaa" => a,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn same(x: u8) -> u8 {
+    match x {
+        "0000 aaaa" => a,
+        "0000 bbbb" => b,
+        _ => 0,
+    }
+}
+
+#[bitmatch]
+fn after_catch_all(x: u8) -> u8 {
+    match x {
+        "1??? ????" => 1,
+        y => y,
+        "0??? ????" => 2,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use bitpattern::{bitassert, bitlet, bitmatch_filter, bitmatches, bitswitch};
+
+fn main() {
+    let x = 0u32;
+    let _ = bitmatches!(lo, "1010 ????", x);
+    bitassert!(high, "aaaa ????", x);
+    let _ = bitmatch_filter!(all, "1010 ????", [x]);
+    let _ = bitswitch!(lo, x { "aaaa" => a });
+    bitlet!(lo, "aaaa" = x);
+}
@@ -0,0 +1,5 @@
+use bitpattern::bitnewtype;
+
+bitnewtype!(struct Imm(u8): "Ssss ssss");
+
+fn main() {}
@@ -0,0 +1,5 @@
+use bitpattern::bitoverlap;
+
+fn main() {
+    let _ = bitoverlap!("000 ??aa", "001 ??b");
+}
@@ -0,0 +1,24 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!("1010 aabb" x);
+    let _ = bitpattern!("1010 aabb", x, x);
+    let _ = bitpattern!("1010 aabb", x @ 1 2);
+    let _ = bitpattern!(x, "1010 aabb");
+    let _ = bitpattern!("1010 aabb" 'a', x);
+    let _ = bitpattern!("1010 aabb", x @ 1, usize);
+    let _ = bitpattern!(high, "1010 aabb", x);
+    let _ = bitpattern!(low, "1010 aabb");
+    let _ = bitpattern!("1010 aabb", x, i32);
+    let _ = bitpattern!("1010 aabb c", x, u8);
+    let _ = bitpattern!(
+        "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa"
+        "a",
+        x,
+        usize
+    );
+    let _ = bitpattern!(low, low, "1010 aabb", x);
+    let _ = bitpattern!(source, const, "1010 aabb", x);
+    let _ = bitpattern!(source, "1010 aabb", x @ 1);
+}
@@ -0,0 +1,13 @@
+use bitpattern::{bitmatch_into, bitmatch_result, bitpattern};
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(bool, "1010 aa??", x);
+    let _ = bitpattern!(bool, tuple, "1010 ????", x);
+    let _ = bitpattern!(bool, rest, "1010 ????", x);
+    let _ = bitpattern!(bool, "1010 ????", x => 1);
+    let _ = bitpattern!(bool, "1010 ????", x, else false);
+    let _ = bitpattern!(bool, "1010 ????");
+    let _ = bitmatch_result!(bool, "1010 ????", x);
+    let _ = bitmatch_into!(bool, "1010 ????", x);
+}
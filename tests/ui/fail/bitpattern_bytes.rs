@@ -0,0 +1,15 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let buf = [0u8; 4];
+    let _ = bitpattern!(b"0100 vvv", &buf[..]);
+    let _ = bitpattern!(b"0100 vvvv", &buf[..] @ 4);
+    let _ = bitpattern!(b"0100 vvvv");
+    let _ = bitpattern!(b"0100 vvvv", buf);
+    let _ = bitpattern!(
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "aaaaaaaa",
+        &buf[..]
+    );
+}
@@ -0,0 +1,19 @@
+use bitpattern::bitpattern;
+
+const OPCODE: &str = "1010";
+
+#[allow(unused_macros)]
+macro_rules! opcode {
+    () => {
+        "1010"
+    };
+}
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(OPCODE, x);
+    let _ = bitpattern!(self::OPCODE, x);
+    let _ = bitpattern!(opcode!(), x);
+    let _ = bitpattern!(concat!(OPCODE, " aabb"), x);
+    let _ = bitpattern!(concat!("1010", 1), x);
+}
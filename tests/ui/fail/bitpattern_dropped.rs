@@ -0,0 +1,16 @@
+use bitpattern::{bitmatch_into, bitpattern};
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(~d, "aabb bccc", x);
+    let _ = bitpattern!(~b, ~b, "aabb bccc", x);
+    let _ = bitpattern!(~bb, "aabb bccc", x);
+    let _ = bitpattern!(~b, "aabb bccc");
+    let _ = bitpattern!(~b, "aabb bccc", x => { a, b });
+    let _ = bitpattern!(~b, "aabb bccc", x, b as u16);
+    let _ = bitpattern!(~b, "aabb bccc", x, |a, b| a + b);
+    let _ = bitpattern!(bool, ~b, "aabb bccc", x);
+    let mut b = 0;
+    let _ = bitmatch_into!(~b, "aabb bccc", x, b = b);
+    let _ = b;
+}
@@ -0,0 +1,7 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let _ = bitpattern!(source, "s eeeeeeee mmmm", 1.0f32);
+    let _ = bitpattern!(source, "s eeeeeeeeeee mmmmmmmmmmmmmmmmmmmmmmm", 1.0f64);
+    let _ = bitpattern!("s eeeeeeee mmmmmmmmmmmmmmmmmmmmmmm", 1.0f32);
+}
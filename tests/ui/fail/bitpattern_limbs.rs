@@ -0,0 +1,24 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let x = (0u128, 0u128);
+    let _ = bitpattern!("1010 aabb", x, (u64, u64));
+    let _ = bitpattern!("1010 aabb", x @ 4, (u128, u128));
+    let _ = bitpattern!(b"1010 aabb", x, (u128, u128));
+    let _ = bitpattern!(
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa aaaaaaaa"
+        "a",
+        x,
+        (u128, u128)
+    );
+    let _ = bitpattern!(
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "???????? ???????? ???????? ???????? ???????? ???????? ???????? ????????"
+        "1",
+        x,
+        (u128, u128)
+    );
+}
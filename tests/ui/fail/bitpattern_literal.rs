@@ -0,0 +1,8 @@
+use bitpattern::{bitmask, bitpattern};
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(0b1010_0000, x);
+    let _ = bitpattern!('1', x);
+    let _ = bitmask!(b"1010 ????");
+}
@@ -0,0 +1,24 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!("oo dd iiii", x => { opcode: o, dest: e });
+    let _ = bitpattern!("oo dd iiii", x => { opcode: o, opcode: d });
+    let _ = bitpattern!("aa bb aaaa", x => { a });
+    let _ = bitpattern!("oo dd iiii", x => { opcode o });
+    let _ = bitpattern!("aa bbbb", x, c as u8);
+    let _ = bitpattern!("aa bbbb", x, a as u8, a as u16);
+    let _ = bitpattern!("aa bbbb", x, a as u8, u8, b as u8);
+    let _ = bitpattern!("aa bbbb", x, a << 1, c + 1);
+    let _ = bitpattern!("aa bbbb", x, b => b * 2, b + 1);
+    let _ = bitpattern!(tuple, "aa bbbb", x => { a });
+    let _ = bitpattern!("aa bbbb", x, |a, c| a + c);
+    let _ = bitpattern!("aa bb aa", x, |a, b| a + b);
+    let _ = bitpattern!("aa bbbb", x, |(a, b)| a + b);
+    let _ = bitpattern!("aa bbbb", x => { a }, |a| a);
+    let _ = bitpattern!(tuple, "aa bbbb", x, |a| a);
+    let _ = bitpattern!("aa bbbb", x => a + c);
+    let _ = bitpattern!("aa bb aa", x => a + b);
+    let _ = bitpattern!("aa bbbb", x => a, |b| b);
+    let _ = bitpattern!(tuple, "aa bbbb", x => a);
+}
@@ -0,0 +1,6 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let x = 0u32;
+    let _ = bitpattern!("10aa bbbb cccc", x @ 21);
+}
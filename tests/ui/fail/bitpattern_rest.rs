@@ -0,0 +1,16 @@
+use bitpattern::{bitmatch_into, bitpattern};
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(rest, "aa bb ????", x => { a });
+    let _ = bitpattern!(rest, "aa bb ????", x => a + b);
+    let _ = bitpattern!(rest, "aa bb ????", x, |a| a);
+    let _ = bitpattern!(rest, "aa bb ????", x, a + 1);
+    let _ = bitpattern!(rest, b"aaaa ????", &[0u8][..]);
+    let _ = bitpattern!(rest, "aaaa ????", (0, 0), (u128, u128));
+    let _ = bitpattern!(rest, rest, "aaaa ????", x);
+    let _ = bitpattern!(rest, "aaaa ????");
+    let mut a = 0;
+    let _ = bitmatch_into!(rest, "aaaa ????", x, a);
+    let _ = a;
+}
@@ -0,0 +1,9 @@
+use bitpattern::bitpermute;
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpermute!("aabb ccdd" => "ddcc eeaa", x);
+    let _ = bitpermute!("aabb ccdd" => "dddc ccaa", x);
+    let _ = bitpermute!("aaaa bbbb" => "aaaa bbbb bbbb", x);
+    let _ = bitpermute!("aabb aabb" => "bbaa b", x);
+}
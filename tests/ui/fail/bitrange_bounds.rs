@@ -0,0 +1,11 @@
+use bitpattern::bitrange;
+
+fn main() {
+    let x = 0u32;
+    let _ = bitrange!(x[12:14]);
+    let _ = bitrange!(u32; x[32:30]);
+    let _ = bitrange!(u8; x[7:0] ++ x[7:7]);
+    let _ = bitrange!(i32; x[7:0]);
+    let _ = bitrange!(x);
+    let _ = bitrange!(x[128:0]);
+}
@@ -0,0 +1,9 @@
+use bitpattern::bitset_fields;
+
+fn main() {
+    let reg = 0u8;
+    let _ = bitset_fields!("???? aa?b", reg, a = 1, c = 2);
+    let _ = bitset_fields!("???? aa?b", reg, a = 1, a = 2);
+    // The values must have the type of the target.
+    let _ = bitset_fields!("???? aa?b", 0u32, a = 1u8);
+}
@@ -0,0 +1,11 @@
+use bitpattern::{bitclear, bitset_fields, bitsplice, bittoggle};
+
+fn main() {
+    // The pattern must fit the type of the target.
+    let reg = 0u8;
+    let _ = bitset_fields!("aaaa aaaa aaaa aaaa", reg, a = 1);
+    let _ = bitset_fields!("1??? ???? ???? ????", reg);
+    let _ = bitclear!("aaaa aaaa aaaa aaaa", reg);
+    let _ = bittoggle!("aaaa ???? ???? ????", reg);
+    let _ = bitsplice!("aaaa aaaa aaaa aaaa", reg, reg);
+}
@@ -0,0 +1,22 @@
+bitpattern::bitspec! {
+    CTRL(u32) = "aaaa";
+    Ctrl(u32) = "aaaa";
+}
+
+bitpattern::bitspec! {
+    WIDE(u8) = "1 aaaa aaaa";
+}
+
+bitpattern::bitspec! {
+    SPLIT(u8) = "aa00 aa00";
+}
+
+bitpattern::bitspec! {
+    TYPE(u8) = "aa00 bb00";
+}
+
+bitpattern::bitspec! {
+    CASE(u8) = "aaaa AAAA";
+}
+
+fn main() {}
@@ -0,0 +1,53 @@
+use bitpattern::BitDecode;
+
+#[derive(BitDecode)]
+enum Count {
+    #[bitmatch("000 aa bbb")]
+    Move(u8),
+}
+
+#[derive(BitDecode)]
+enum Missing {
+    #[bitmatch("000 aa bbb")]
+    Move(u8, u8),
+    Nop,
+}
+
+#[derive(BitDecode)]
+enum Narrow {
+    #[bitmatch("aaaa aaaa a")]
+    Wide(u8),
+    #[bitmatch("0 aaaa aaaa")]
+    Flag { a: bool },
+}
+
+#[derive(BitDecode)]
+enum Named {
+    #[bitmatch("aaaa bbbb")]
+    Unknown { a: u8, c: u8 },
+}
+
+#[derive(BitDecode)]
+enum Missing {
+    #[bitmatch("aaaa bbbb")]
+    Missing { a: u8 },
+}
+
+#[derive(BitDecode)]
+enum Renamed {
+    #[bitmatch("aaaa bbbb")]
+    Renamed {
+        #[bits = "ab"]
+        x: u8,
+    },
+}
+
+#[derive(BitDecode)]
+enum Unit {
+    #[bitmatch("1aaa aaaa")]
+    Imm(u8),
+    #[bitmatch(default)]
+    Invalid,
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use bitpattern::BitDecode;
+
+#[derive(BitDecode)]
+enum Unknown {
+    #[bitmatch("0000 dddd", fmt = "li x{d}, {imm}")]
+    Li { d: u8 },
+}
+
+#[derive(BitDecode)]
+enum Positional {
+    #[bitmatch("0000 dddd", fmt = "li x{}")]
+    Li { d: u8 },
+}
+
+fn main() {}
@@ -0,0 +1,27 @@
+use bitpattern::BitFields;
+
+#[derive(BitFields)]
+#[bitmatch("aaaa aaaa a")]
+struct Wide {
+    a: u8,
+}
+
+#[derive(BitFields)]
+#[bitmatch("ee aaaa")]
+struct Flag {
+    e: bool,
+    a: u8,
+}
+
+#[derive(BitFields)]
+#[bitmatch("aaaa bbbb")]
+struct Missing {
+    a: u8,
+}
+
+#[derive(BitFields)]
+enum NotStruct {
+    A,
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+use bitpattern::bitpack;
+
+fn main() {
+    let _ = bitpack!("1aa? ?b00", a = 3, default = 1, b = 1);
+}
@@ -0,0 +1,5 @@
+use bitpattern::bitpack;
+
+fn main() {
+    let _ = bitpack!("ooo aabb", o = 1, a = 2, b = 3, a = 4);
+}
@@ -0,0 +1,5 @@
+use bitpattern::bitpack;
+
+fn main() {
+    let _ = bitpack!("ooo aabb", o = 1, 2, 3);
+}
@@ -0,0 +1,6 @@
+use bitpattern::bitpattern;
+
+fn main() {
+    let x = 0u8;
+    let _ = bitpattern!(exact, "1010 aabb", x);
+}
@@ -0,0 +1,5 @@
+use bitpattern::bitfmt;
+
+fn main() {
+    let _ = bitfmt!("1010 aabb", 0u8, defmt);
+}
@@ -0,0 +1,25 @@
+#![deny(deprecated)]
+
+use bitpattern::bitmatch;
+
+// Disjoint arms and arms followed by a more general arm don't warn.
+#[bitmatch(low, warn_overlap)]
+fn decode(x: u8) -> u8 {
+    match x {
+        "0000 aaaa" => a,
+        "0001 aaaa" => a + 1,
+        "001? ????" => 2,
+        "0??? ????" => 3,
+        "1010 aaaa" => a,
+        "1??? ????" => 4,
+    }
+}
+
+fn main() {
+    assert_eq!(decode(0x05), 5);
+    assert_eq!(decode(0x15), 6);
+    assert_eq!(decode(0x25), 2);
+    assert_eq!(decode(0x45), 3);
+    assert_eq!(decode(0xa5), 5);
+    assert_eq!(decode(0x85), 4);
+}
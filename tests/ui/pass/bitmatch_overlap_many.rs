@@ -0,0 +1,24 @@
+// The warnings themselves are checked by tests/ui/fail/bitmatch_overlap.rs.
+#![allow(deprecated)]
+
+use bitpattern::bitmatch;
+
+// Several partially overlapping arms each raise their own warning.
+#[bitmatch(warn_overlap)]
+fn decode(x: u8) -> u8 {
+    match x {
+        "1??? ???0" => 1,
+        "11?? ????" => 2,
+        "?1?? ???1" => 3,
+        "???? 1???" => 4,
+        _ => 0,
+    }
+}
+
+fn main() {
+    assert_eq!(decode(0x80), 1);
+    assert_eq!(decode(0xc1), 2);
+    assert_eq!(decode(0x41), 3);
+    assert_eq!(decode(0x08), 4);
+    assert_eq!(decode(0x01), 0);
+}
@@ -0,0 +1,128 @@
+use bitpattern::{bitmatch_result, bitpattern};
+
+const fn classify(x: u8) -> Option<(u8, u8)> {
+    bitpattern!(const, "10aa bb??", x)
+}
+
+const fn half(x: u16) -> Option<u16> {
+    bitpattern!(const, "1111 aaaa aaaa 0000", x)
+}
+
+const fn word(x: u32) -> Option<(u32, i32)> {
+    bitpattern!(const, "0110 aaaa SSSS SSSS ???? ???? ???? ????", x)
+}
+
+const fn wide(x: u64) -> Option<u64> {
+    bitpattern!(const, low, "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", x)
+}
+
+const fn huge(x: u128) -> Option<(bool, u8)> {
+    bitpattern!(
+        const,
+        flags,
+        narrow,
+        "f??? ???? ???? ???? ???? ???? ???? ????"
+        "???? ???? ???? ???? ???? ???? ???? ????"
+        "???? ???? ???? ???? ???? ???? ???? ????"
+        "???? ???? ???? ???? ???? ???? ???? ?aaa",
+        x
+    )
+}
+
+const fn page(x: usize) -> Option<usize> {
+    bitpattern!(const, "aaaa aaaa aaaa ???? ???? ???? ????", x, usize)
+}
+
+const fn offset(x: u32) -> Option<(u8, u8)> {
+    bitpattern!(const, "10aa bbbb", x @ 10)
+}
+
+const fn mapped(x: u16) -> Option<(u16, u16)> {
+    bitpattern!(const, tuple, "01 aaaaaaaa bb ????", x, a << 2, b + 1)
+}
+
+const fn branch(x: u16) -> Option<i32> {
+    bitpattern!(const, narrow, "1111 SSSS SSSS", x, S => S as i32 * 4)
+}
+
+// The expansion is `const` without `const` too, for any integer value.
+const fn bare(x: u8) -> Option<(u8, u8)> {
+    bitpattern!("10aabb??", x)
+}
+
+const fn bare_signed(x: i16) -> Option<(u16, i16)> {
+    bitpattern!("aaaa SSSS SSSS SSSS", x)
+}
+
+const fn bare_wide(x: u128) -> Option<u8> {
+    bitpattern!("1010 aaaa", x)
+}
+
+const fn bare_low(x: u64) -> Option<u32> {
+    bitpattern!(low, "aaaa aaaa aaaa aaaa aaaa aaaa aaaa aaaa", x)
+}
+
+const fn bare_page(x: usize) -> Option<usize> {
+    bitpattern!("aaaa aaaa aaaa ???? ???? ???? ????", x, usize)
+}
+
+const fn bare_mismatch(x: u32) -> u8 {
+    match bitmatch_result!("0101 aa??", x) {
+        Ok(_) => 0,
+        Err(error) => error.mismatch,
+    }
+}
+
+struct Insn;
+
+impl Insn {
+    const NOP: Option<()> = bitpattern!(const, "1110 1010", 0xeau8);
+    const MOV: Option<(u8, u8)> = classify(0xb4);
+}
+
+const TABLE: [Option<u8>; 4] = [
+    bitpattern!(const, "00aa", 0x3u8),
+    bitpattern!(const, "01aa", 0x6u8),
+    bitpattern!("10aa", 0xa),
+    bitpattern!(const, "11aa", 0x3u8),
+];
+
+const BYTES: Option<(u32, u32)> = bitpattern!(b"0100 hhhh llllllll llllllll", &[0x45, 0x1c, 0x8f]);
+const LIMBS: Option<u128> = bitpattern!("1aaa", (0, 0xb), (u128, u128));
+const NAMED: Option<u8> = match bitpattern!(const, "oo dd iiii", 0x96u8 => { opcode: o, imm: i }) {
+    Some(insn) => Some(insn.opcode + insn.imm),
+    None => None,
+};
+const MISMATCH: u8 = match bitmatch_result!(const, "0101 aa??", 0x9bu8) {
+    Ok(_) => 0,
+    Err(error) => error.mismatch,
+};
+
+fn main() {
+    assert_eq!(classify(0xb4), Some((3, 1)));
+    assert_eq!(classify(0x34), None);
+    assert_eq!(half(0xfab0), Some(0xab));
+    assert_eq!(word(0x6cfe_0000), Some((0xc, -2)));
+    assert_eq!(wide(0xff_0000_0001), Some(0xf_0000_0001));
+    assert_eq!(huge(1 << 127 | 5), Some((true, 5)));
+    assert_eq!(page(0x0040_3000), Some(0x40));
+    assert_eq!(offset(0x0022_ac00), Some((2, 0xb)));
+    assert_eq!(mapped(0x5a0c), Some((0x68 << 2, 1)));
+    assert_eq!(branch(0xffd), Some(-12));
+    assert_eq!(Insn::NOP, Some(()));
+    assert_eq!(Insn::MOV, Some((3, 1)));
+    assert_eq!(TABLE, [Some(3), Some(2), Some(2), None]);
+    assert_eq!(BYTES, Some((5, 0x1c8f)));
+    assert_eq!(LIMBS, Some(3));
+    assert_eq!(NAMED, Some(8));
+    assert_eq!(MISMATCH, 0xc0);
+    assert_eq!(bare(0xb4), Some((3, 1)));
+    assert_eq!(bare(0x34), None);
+    assert_eq!(bare_signed(-2), Some((0xf, -2)));
+    assert_eq!(bare_wide(0xa5), Some(5));
+    assert_eq!(bare_wide(0x1a5), None);
+    assert_eq!(bare_low(0xff_0000_0001), Some(1));
+    assert_eq!(bare_page(0x0040_3000), Some(0x40));
+    assert_eq!(bare_mismatch(0x9b), 0xc0);
+    assert_eq!(bare_mismatch(0x15b), 0);
+}
@@ -0,0 +1,46 @@
+use bitpattern::{bitpack, bitpack_checked};
+
+const A: u32 = 1;
+const B: u32 = 2;
+const D: u32 = 3;
+
+const ADD: u32 = bitpack!("0000000 bbbbb aaaaa 000 ddddd 0110011", a = A, b = B, d = D);
+static SUB: u32 = bitpack!("0100000 bbbbb aaaaa 000 ddddd 0110011", B, A, D);
+
+const fn r_type(funct7: u32, funct3: u32, a: u32, b: u32, d: u32) -> u32 {
+    bitpack!(
+        "fffffff bbbbb aaaaa ggg ddddd 0110011",
+        f = funct7,
+        g = funct3,
+        a = a,
+        b = b,
+        d = d
+    )
+}
+
+const fn checked(a: u8) -> Option<u8> {
+    bitpack_checked!("1aaa ????", a)
+}
+
+static DISPATCH: [u32; 4] = [
+    r_type(0, 0, 1, 2, 3),
+    r_type(0x20, 0, 1, 2, 3),
+    bitpack!("0000000 bbbbb aaaaa 111 ddddd 0110011", a = 1, b = 2, d = 3),
+    bitpack!("0000000 bbbbb aaaaa 110 ddddd 0110011", a = 1, b = 2, d = 3),
+];
+
+const BUF: [u8; bitpack!("aaaa", a = 3) as usize] = [0; 3];
+
+const CHECKED: Option<u8> = checked(7);
+
+fn main() {
+    assert_eq!(ADD, 0x0020_81b3);
+    assert_eq!(SUB, 0x4020_81b3);
+    assert_eq!(DISPATCH[0], ADD);
+    assert_eq!(DISPATCH[1], SUB);
+    assert_eq!(DISPATCH[2], 0x0020_f1b3);
+    assert_eq!(DISPATCH[3], 0x0020_e1b3);
+    assert_eq!(BUF.len(), 3);
+    assert_eq!(CHECKED, Some(0xf0));
+    assert_eq!(checked(8), None);
+}
@@ -0,0 +1,56 @@
+use bitpattern::{bitmatch_reg, bitmatch_reg_ptr};
+use std::cell::Cell;
+
+/// Register block whose status register counts its reads.
+struct Regs {
+    status: u32,
+    reads: Cell<u32>,
+}
+
+impl Regs {
+    fn status(&self) -> &u32 {
+        self.reads.set(self.reads.get() + 1);
+        &self.status
+    }
+}
+
+#[test]
+fn test_bitmatch_reg() {
+    let regs = Regs {
+        status: 0x8000_a500,
+        reads: Cell::new(0),
+    };
+    let found = bitmatch_reg!("e??? ???? ???? ???? tttt rrrr ???? ????", regs.status());
+    assert_eq!(found, Some((1, 0xa, 5)));
+    assert_eq!(regs.reads.get(), 1);
+
+    let found = bitmatch_reg!("0??? ???? ???? ???? tttt rrrr ???? ????", regs.status());
+    assert_eq!(found, None);
+    assert_eq!(regs.reads.get(), 2);
+
+    let found = bitmatch_reg!("???? ???? ???? ???? ???? ???? ???? ????", regs.status());
+    assert_eq!(found, Some(()));
+    assert_eq!(regs.reads.get(), 3);
+
+    // Uppercase runs are sign-extended like `bitpattern!`.
+    let found = bitmatch_reg!("1??? ???? ???? ???? TTTT RRRR ???? ????", regs.status());
+    assert_eq!(found, Some((-6, 5)));
+}
+
+#[test]
+fn test_bitmatch_reg_ptr() {
+    let reg = 0x3cu8;
+    let mut reads = 0;
+    let ptr: *const u8 = &reg;
+    // SAFETY: `ptr` points to `reg`, which outlives the reads.
+    let found = unsafe {
+        bitmatch_reg_ptr!("00aa aa??", {
+            reads += 1;
+            ptr
+        })
+    };
+    assert_eq!(found, Some(0xf));
+    assert_eq!(reads, 1);
+    // SAFETY: as above.
+    assert_eq!(unsafe { bitmatch_reg_ptr!("1???_????", ptr) }, None);
+}